@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::fs;
 use std::io::{BufRead, BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use crate::config;
 
 pub struct AliasManager {
     aliases: HashMap<String, String>,
     config_path: Option<PathBuf>,
+    file_mtime: Option<SystemTime>,
 }
 
 impl AliasManager {
@@ -15,30 +17,50 @@ impl AliasManager {
         let mut manager = Self {
             aliases: HashMap::new(),
             config_path: config_path.clone(),
+            file_mtime: None,
         };
         if let Some(path) = &config_path {
             let _ = manager.load_from_file(path);
+            manager.file_mtime = file_mtime(path);
         }
         manager
     }
 
+    /// Merge in definitions from the aliases file if it changed on disk since
+    /// we last read or wrote it (dotfile sync, another session, manual edit).
+    pub fn refresh(&mut self) {
+        let Some(path) = self.config_path.clone() else { return; };
+        let mtime = file_mtime(&path);
+        if mtime.is_none() || mtime == self.file_mtime {
+            return;
+        }
+        let _ = self.load_from_file(&path);
+        self.file_mtime = mtime;
+    }
+
     pub fn set(&mut self, name: String, value: String) {
+        self.refresh();
         self.aliases.insert(name, value);
-        if let Some(path) = &self.config_path {
-            let _ = self.save_to_file(path);
-        }
+        self.persist();
     }
 
     pub fn unset(&mut self, name: &str) -> bool {
+        self.refresh();
         let removed = self.aliases.remove(name).is_some();
         if removed {
-            if let Some(path) = &self.config_path {
-                let _ = self.save_to_file(path);
-            }
+            self.persist();
         }
         removed
     }
 
+    fn persist(&mut self) {
+        if let Some(path) = &self.config_path
+            && self.save_to_file(path).is_ok()
+        {
+            self.file_mtime = file_mtime(path);
+        }
+    }
+
     pub fn get(&self, name: &str) -> Option<&String> {
         self.aliases.get(name)
     }
@@ -47,24 +69,64 @@ impl AliasManager {
         &self.aliases
     }
 
+    /// Expand aliases in every command position of `line` (the start, and
+    /// after `|`, `&&`, `;`, `(` ...). Rules, as in other shells:
+    /// - an alias is not expanded again inside its own expansion, so
+    ///   `alias ls='ls --color'` and `l='ll'`/`ll='l'` terminate;
+    /// - a value ending in a space makes the next word eligible too
+    ///   (`alias sudo='sudo '`);
+    /// - `\ls` and `command ls` bypass aliases.
+    ///
+    /// Values may hold whole command lists (`alias up='make && make install'`):
+    /// they are spliced in before the line is parsed, so `&&`, `|` and `;`
+    /// in them work as if typed.
     pub fn expand(&self, line: &str) -> String {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.is_empty() {
-            return line.to_string();
-        }
+        self.expand_with(line, &mut Vec::new()).0
+    }
 
-        // Check if first word is an alias
-        if let Some(alias_value) = self.get(parts[0]) {
-            let mut result = alias_value.clone();
-            // Append remaining arguments
-            if parts.len() > 1 {
-                result.push(' ');
-                result.push_str(&parts[1..].join(" "));
+    /// The expansion, and whether it ends where a command name is expected
+    /// (after `;`, `&&`, ...), so the word following an alias like
+    /// `alias s='make;'` is expanded too.
+    fn expand_with(&self, line: &str, active: &mut Vec<String>) -> (String, bool) {
+        let mut out = String::with_capacity(line.len());
+        let mut command_position = true;
+        let mut rest = line;
+        loop {
+            let word_start = rest.len() - rest.trim_start().len();
+            out.push_str(&rest[..word_start]);
+            rest = &rest[word_start..];
+            let Some(first) = rest.chars().next() else { break; };
+            if "|&;()".contains(first) {
+                out.push(first);
+                rest = &rest[1..];
+                command_position = true;
+                continue;
+            }
+            let len = word_len(rest);
+            let word = &rest[..len];
+            rest = &rest[len..];
+            if !command_position {
+                out.push_str(word);
+                continue;
+            }
+            // Keywords and assignments leave the next word in command position
+            command_position = matches!(word, "!" | "{" | "time") || is_assignment(word);
+            if let Some(bare) = word.strip_prefix('\\') {
+                out.push_str(bare);
+                continue;
+            }
+            match self.aliases.get(word) {
+                Some(value) if !active.iter().any(|a| a == word) => {
+                    active.push(word.to_string());
+                    let (expansion, ends_in_command_position) = self.expand_with(value, active);
+                    active.pop();
+                    out.push_str(&expansion);
+                    command_position = ends_in_command_position || value.ends_with(' ');
+                }
+                _ => out.push_str(word),
             }
-            result
-        } else {
-            line.to_string()
         }
+        (out, command_position)
     }
 
     fn load_from_file(&mut self, path: &PathBuf) -> std::io::Result<()> {
@@ -140,6 +202,8 @@ impl AliasManager {
                 }
                 value.push(c);
             }
+            // Quoted values are kept exactly, trailing space included
+            return Some((name, value));
         } else {
             while let Some(c) = chars.next() {
                 value.push(c);
@@ -150,6 +214,35 @@ impl AliasManager {
     }
 }
 
+/// Length of the word at the start of `s`: up to unquoted whitespace or
+/// one of `|&;()`.
+fn word_len(s: &str) -> usize {
+    let mut quote = None;
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\\') | (Some('"'), '\\') => {
+                chars.next();
+            }
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, _) if c.is_whitespace() || "|&;()".contains(c) => return i,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn file_mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
 impl Default for AliasManager {
     fn default() -> Self {
         Self::new()
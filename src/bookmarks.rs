@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use crate::config;
+
+fn store_path() -> Option<PathBuf> { config::bookmarks_file() }
+
+/// name -> absolute directory
+pub fn load_bookmarks() -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    let Some(path) = store_path() else { return map; };
+    let file = match OpenOptions::new().read(true).open(&path) {
+        Ok(f) => f,
+        Err(_) => return map,
+    };
+    let reader = BufReader::new(file);
+    for line in reader.lines().map_while(Result::ok) {
+        if let Some((name, dir)) = line.split_once('\t') {
+            map.insert(name.to_string(), dir.to_string());
+        }
+    }
+    map
+}
+
+pub fn add_bookmark(name: &str, dir: &str) -> std::io::Result<()> {
+    let mut map = load_bookmarks();
+    map.insert(name.to_string(), dir.to_string());
+    save_bookmarks(&map)
+}
+
+/// Returns false if there was no such bookmark.
+pub fn remove_bookmark(name: &str) -> std::io::Result<bool> {
+    let mut map = load_bookmarks();
+    if map.remove(name).is_none() {
+        return Ok(false);
+    }
+    save_bookmarks(&map)?;
+    Ok(true)
+}
+
+/// Expand `@name` or `@name/rest` to the bookmarked directory.
+pub fn expand(target: &str) -> Option<String> {
+    let spec = target.strip_prefix('@')?;
+    let (name, rest) = spec.split_once('/').unwrap_or((spec, ""));
+    let dir = load_bookmarks().remove(name)?;
+    if rest.is_empty() {
+        Some(dir)
+    } else {
+        Some(format!("{}/{}", dir.trim_end_matches('/'), rest))
+    }
+}
+
+fn save_bookmarks(map: &BTreeMap<String, String>) -> std::io::Result<()> {
+    if let Some(path) = store_path() {
+        let mut tmp = path.clone();
+        tmp.set_extension("tmp");
+        let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(&tmp)?;
+        for (name, dir) in map {
+            writeln!(f, "{}\t{}", name, dir)?;
+        }
+        f.flush()?;
+        fs::rename(tmp, path)?;
+    }
+    Ok(())
+}
@@ -1,12 +1,138 @@
 use std::env;
 use std::path::Path;
 use std::fs;
-use std::io::BufRead;
 use colored::Colorize;
-use chrono::{DateTime, Local};
-use humansize::{format_size, DECIMAL};
-
 use crate::error::ShellError;
+use crate::locale;
+use crate::bookmarks;
+use crate::trash;
+use crate::record;
+use crate::lastout;
+use crate::clipboard;
+use crate::sandbox;
+use crate::filter;
+use crate::plugins;
+use crate::segments;
+use crate::jobs::format_runtime;
+use crate::calc;
+use crate::docs;
+use crate::parser;
+use crate::formatter;
+use crate::lookup::{self, Lookup};
+use crate::shell_config::{option_spec, OPTIONS};
+use crate::theme::{self, Paint, Role, Theme};
+use crate::vars;
+use crate::{errln, outln};
+
+/// Where a builtin is implemented: `try_handle_builtin` below, or
+/// `Shell::execute_simple` for those that need the shell's state (jobs,
+/// aliases, config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handler {
+    Builtins,
+    Shell,
+}
+
+/// What the arguments of a builtin complete to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgCompletion {
+    Files,
+    Directories,
+    Bookmarks,
+    ConfigOptions,
+    ProjectRoots,
+    /// Builtin names (help, type)
+    Builtins,
+    Aliases,
+    /// Environment variable names
+    Variables,
+    /// `NAME=` for existing variables, then a path for the value (export)
+    Assignments,
+    /// `%n` specs of the shell's jobs
+    Jobs,
+    /// Another command line (time, watch, benchmark)
+    Commands,
+    /// Built-in theme names and theme files
+    Themes,
+}
+
+/// One builtin. Its summary and documentation live in `docs::PAGES`.
+pub struct Builtin {
+    pub name: &'static str,
+    pub handler: Handler,
+    pub args: ArgCompletion,
+    /// Acts on the shell's own state (directory, variables, aliases, jobs,
+    /// options, history), or runs a command that may: `&` can't move it
+    /// into a job, where its effect would be lost
+    pub changes_shell: bool,
+}
+
+impl Builtin {
+    pub fn summary(&self) -> &'static str {
+        docs::find(self.name).map(|page| page.summary).unwrap_or_default()
+    }
+
+    const fn changes_shell(self) -> Self {
+        Builtin { changes_shell: true, ..self }
+    }
+}
+
+const fn builtin(name: &'static str, handler: Handler, args: ArgCompletion) -> Builtin {
+    Builtin { name, handler, args, changes_shell: false }
+}
+
+/// Every builtin, for dispatch, completion, highlighting, typo suggestions,
+/// `help` and `type`.
+pub const BUILTINS: &[Builtin] = &[
+    builtin("cd", Handler::Builtins, ArgCompletion::Directories).changes_shell(),
+    builtin("croot", Handler::Shell, ArgCompletion::ProjectRoots).changes_shell(),
+    builtin("cdr", Handler::Shell, ArgCompletion::ProjectRoots).changes_shell(),
+    builtin("bookmark", Handler::Builtins, ArgCompletion::Files),
+    builtin("go", Handler::Builtins, ArgCompletion::Bookmarks).changes_shell(),
+    builtin("ll", Handler::Builtins, ArgCompletion::Directories),
+    builtin("freqs", Handler::Builtins, ArgCompletion::Files),
+    builtin("stats", Handler::Shell, ArgCompletion::Files),
+    builtin("calc", Handler::Builtins, ArgCompletion::Files),
+    builtin("trash", Handler::Builtins, ArgCompletion::Files),
+    builtin("option", Handler::Builtins, ArgCompletion::ConfigOptions),
+    builtin("theme", Handler::Builtins, ArgCompletion::Themes).changes_shell(),
+    builtin("alias", Handler::Shell, ArgCompletion::Files).changes_shell(),
+    builtin("unalias", Handler::Shell, ArgCompletion::Aliases).changes_shell(),
+    builtin("export", Handler::Builtins, ArgCompletion::Assignments).changes_shell(),
+    builtin("unset", Handler::Builtins, ArgCompletion::Variables).changes_shell(),
+    builtin("jobs", Handler::Shell, ArgCompletion::Files).changes_shell(),
+    builtin("fg", Handler::Shell, ArgCompletion::Jobs).changes_shell(),
+    builtin("bg", Handler::Shell, ArgCompletion::Jobs).changes_shell(),
+    builtin("disown", Handler::Shell, ArgCompletion::Jobs).changes_shell(),
+    builtin("wait", Handler::Shell, ArgCompletion::Jobs).changes_shell(),
+    builtin("time", Handler::Shell, ArgCompletion::Commands),
+    builtin("watch", Handler::Shell, ArgCompletion::Commands),
+    builtin("benchmark", Handler::Shell, ArgCompletion::Commands),
+    builtin("help", Handler::Builtins, ArgCompletion::Builtins),
+    builtin("type", Handler::Shell, ArgCompletion::Builtins),
+    builtin("set", Handler::Shell, ArgCompletion::Files).changes_shell(),
+    builtin("quote", Handler::Builtins, ArgCompletion::Files),
+    builtin("record", Handler::Builtins, ArgCompletion::Files).changes_shell(),
+    builtin("lastout", Handler::Builtins, ArgCompletion::Files),
+    builtin("copy", Handler::Builtins, ArgCompletion::Files),
+    builtin("paste", Handler::Builtins, ArgCompletion::Files),
+    builtin("sandbox", Handler::Builtins, ArgCompletion::Commands),
+    builtin("filter", Handler::Builtins, ArgCompletion::Files),
+    builtin("plugins", Handler::Builtins, ArgCompletion::Files),
+    builtin("hash", Handler::Builtins, ArgCompletion::Commands).changes_shell(),
+    builtin("command", Handler::Shell, ArgCompletion::Commands).changes_shell(),
+    builtin("please", Handler::Shell, ArgCompletion::Files).changes_shell(),
+    builtin("limit", Handler::Shell, ArgCompletion::Commands).changes_shell(),
+    builtin("exit", Handler::Builtins, ArgCompletion::Files).changes_shell(),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    lookup(name).is_some()
+}
 
 pub enum BuiltinResult {
     Handled(i32),
@@ -14,16 +140,22 @@ pub enum BuiltinResult {
     NotHandled,
 }
 
-pub fn try_handle_builtin(argv: &[String]) -> Result<BuiltinResult, ShellError> {
+/// Run `argv` if it's a builtin. `input` is `Some` in a pipeline: what the
+/// previous stage wrote, empty for the first one.
+pub fn try_handle_builtin(argv: &[String], input: Option<&[u8]>) -> Result<BuiltinResult, ShellError> {
     if argv.is_empty() {
         return Ok(BuiltinResult::Handled(0));
     }
 
+    if lookup(&argv[0]).is_some_and(|b| b.handler == Handler::Shell) {
+        return Ok(BuiltinResult::NotHandled);
+    }
+
     match argv[0].as_str() {
         "export" => {
             if argv.len() == 1 {
                 for (k, v) in env::vars() {
-                    println!("{}={}", k, v);
+                    outln!("{}={}", k, v);
                 }
                 return Ok(BuiltinResult::Handled(0));
             }
@@ -32,15 +164,23 @@ pub fn try_handle_builtin(argv: &[String]) -> Result<BuiltinResult, ShellError>
                 if let Some((k, v)) = pair.split_once('=') {
                     unsafe { env::set_var(k, v) };
                 } else {
-                    eprintln!("export: invalid assignment: {}", pair);
+                    errln!("export: invalid assignment: {}", pair);
                     status = 1;
                 }
             }
             Ok(BuiltinResult::Handled(status))
         }
+        "quote" => {
+            let quoted = argv[1..].iter().map(|a| parser::quote(a)).collect::<Vec<_>>().join(" ");
+            Ok(BuiltinResult::HandledWithOutput(0, format!("{}\n", quoted).into_bytes()))
+        }
         "unset" => {
-            if argv.len() < 2 { eprintln!("unset: missing name"); return Ok(BuiltinResult::Handled(1)); }
-            for name in &argv[1..] { unsafe { env::remove_var(name) }; }
+            if argv.len() < 2 { errln!("unset: missing name"); return Ok(BuiltinResult::Handled(1)); }
+            for name in &argv[1..] {
+                if !vars::unset(name) {
+                    unsafe { env::remove_var(name) };
+                }
+            }
             Ok(BuiltinResult::Handled(0))
         }
         "cd" => {
@@ -48,26 +188,375 @@ pub fn try_handle_builtin(argv: &[String]) -> Result<BuiltinResult, ShellError>
                 Ok(home) => home,
                 Err(_) => String::from("/"),
             });
-            let target = expand_tilde(&target_raw);
-            match env::set_current_dir(&target) {
+            let target = if target_raw == "-" {
+                // `cd -` goes back, and says where to
+                match env::var("OLDPWD") {
+                    Ok(dir) => {
+                        outln!("{}", dir);
+                        dir
+                    }
+                    Err(_) => {
+                        errln!("cd: OLDPWD not set");
+                        return Ok(BuiltinResult::Handled(1));
+                    }
+                }
+            } else if target_raw.starts_with('@') {
+                match bookmarks::expand(&target_raw) {
+                    Some(dir) => dir,
+                    None => {
+                        errln!("cd: {}: no such bookmark", target_raw);
+                        return Ok(BuiltinResult::Handled(1));
+                    }
+                }
+            } else {
+                expand_tilde(&target_raw)
+            };
+            match vars::change_dir(Path::new(&target)) {
                 Ok(_) => {
-                    record_dir_usage(&target);
+                    // Record the resolved directory; a relative target no longer resolves from here
+                    let cwd = env::current_dir().map(|p| p.to_string_lossy().to_string()).unwrap_or(target);
+                    record_dir_usage(&cwd);
+                    Ok(BuiltinResult::Handled(0))
+                },
+                Err(e) => {
+                    errln!("cd: {}: {}", target, e);
+                    Ok(BuiltinResult::Handled(1))
+                }
+            }
+        }
+        "go" => {
+            let Some(name) = argv.get(1) else {
+                errln!("go: usage: go <bookmark>");
+                return Ok(BuiltinResult::Handled(2));
+            };
+            try_handle_builtin(&[String::from("cd"), format!("@{}", name.trim_start_matches('@'))], None)
+        }
+        "bookmark" => {
+            match argv.get(1).map(String::as_str).unwrap_or("list") {
+                "list" => {
+                    let marks = bookmarks::load_bookmarks();
+                    let width = marks.keys().map(|k| k.len()).max().unwrap_or(0);
+                    for (name, dir) in marks {
+                        outln!("{}{}  {}", format!("@{}", name).paint(Role::Command).bold(), " ".repeat(width - name.len()), collapse_home(&dir).paint(Role::Path));
+                    }
+                    Ok(BuiltinResult::Handled(0))
+                }
+                "add" => {
+                    let Some(name) = argv.get(2).map(|n| n.trim_start_matches('@')).filter(|n| !n.is_empty() && !n.contains(['/', '\t'])) else {
+                        errln!("bookmark: usage: bookmark add <name> [dir]");
+                        return Ok(BuiltinResult::Handled(2));
+                    };
+                    let dir = match argv.get(3) {
+                        Some(d) => Path::new(&expand_tilde(d)).canonicalize(),
+                        None => env::current_dir(),
+                    };
+                    match dir {
+                        Ok(dir) if dir.is_dir() => {
+                            bookmarks::add_bookmark(name, &dir.to_string_lossy())?;
+                            Ok(BuiltinResult::Handled(0))
+                        }
+                        Ok(dir) => {
+                            errln!("bookmark: {}: not a directory", dir.display());
+                            Ok(BuiltinResult::Handled(1))
+                        }
+                        Err(e) => {
+                            errln!("bookmark: {}: {}", argv.get(3).map(String::as_str).unwrap_or("."), e);
+                            Ok(BuiltinResult::Handled(1))
+                        }
+                    }
+                }
+                "rm" | "remove" => {
+                    let mut status = 0;
+                    for name in &argv[2..] {
+                        if !bookmarks::remove_bookmark(name.trim_start_matches('@'))? {
+                            errln!("bookmark: {}: no such bookmark", name);
+                            status = 1;
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(status))
+                }
+                other => {
+                    errln!("bookmark: unknown subcommand '{}' (add, list, rm)", other);
+                    Ok(BuiltinResult::Handled(2))
+                }
+            }
+        }
+        "option" => {
+            let (keys, doc): (Vec<&String>, Vec<&String>) = argv[1..].iter().partition(|a| a.as_str() != "--doc");
+            let doc = !doc.is_empty();
+            match keys.first().map(|k| k.as_str()) {
+                None | Some("list") => {
+                    let width = OPTIONS.iter().map(|o| o.key.len()).max().unwrap_or(0);
+                    let kind_width = OPTIONS.iter().map(|o| o.kind.describe().len()).max().unwrap_or(0);
+                    for option in OPTIONS {
+                        let default = if option.default.is_empty() { "-" } else { option.default };
+                        let kind = option.kind.describe();
+                        outln!("{}{}  {}{}  {}", option.key.paint(Role::Command), " ".repeat(width - option.key.len()), kind.paint(Role::Path), " ".repeat(kind_width - kind.len()), default.dimmed());
+                        if doc {
+                            outln!("{}  {}", " ".repeat(width), option.doc);
+                        }
+                    }
                     Ok(BuiltinResult::Handled(0))
+                }
+                Some(key) => match option_spec(key) {
+                    Some(option) => {
+                        outln!("{} ({}, default: {})", option.key.bold(), option.kind.describe(), if option.default.is_empty() { "-" } else { option.default });
+                        outln!("  {}", option.doc);
+                        Ok(BuiltinResult::Handled(0))
+                    }
+                    None => {
+                        errln!("option: unknown option '{}'", key);
+                        Ok(BuiltinResult::Handled(1))
+                    }
                 },
+            }
+        }
+        "theme" => {
+            match argv.get(1) {
+                None => {
+                    let active = theme::active_name();
+                    for name in theme::available() {
+                        let marker = if name == active { "*" } else { " " };
+                        outln!("{} {}", marker, name.paint(Role::Command));
+                    }
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Some(name) => match Theme::named(name) {
+                    Ok(theme) => {
+                        theme::activate(theme);
+                        Ok(BuiltinResult::Handled(0))
+                    }
+                    Err(e) => {
+                        errln!("theme: {}", e);
+                        Ok(BuiltinResult::Handled(1))
+                    }
+                },
+            }
+        }
+        "hash" => {
+            match argv.get(1).map(String::as_str) {
+                None => {
+                    let hashed = lookup::hashed();
+                    if hashed.is_empty() {
+                        outln!("hash: table empty");
+                    } else {
+                        outln!(" hits  command");
+                        for (_, entry) in &hashed {
+                            outln!("{:>5}  {}", entry.hits, entry.path.display().to_string().paint(Role::Path));
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Some("-r") => {
+                    lookup::forget_all();
+                    segments::forget_system_info();
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Some("-d") => {
+                    let mut status = 0;
+                    for name in &argv[2..] {
+                        if !lookup::forget(name) {
+                            errln!("hash: {}: not found", name);
+                            status = 1;
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(status))
+                }
+                Some(_) => {
+                    let mut status = 0;
+                    for name in argv[1..].iter().filter(|n| !is_builtin(n)) {
+                        match lookup::remember(name) {
+                            Lookup::Found(_) => {}
+                            Lookup::NotExecutable(path) => {
+                                errln!("hash: {}: not executable", path.display());
+                                status = 1;
+                            }
+                            Lookup::NotFound => {
+                                errln!("hash: {}: not found", name);
+                                status = 1;
+                            }
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(status))
+                }
+            }
+        }
+        "calc" => {
+            let expr = argv[1..].join(" ");
+            match calc::evaluate(&expr) {
+                Ok(value) => {
+                    outln!("{}", calc::format_result(&expr, value));
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Err(e) => {
+                    errln!("calc: {}", e);
+                    Ok(BuiltinResult::Handled(1))
+                }
+            }
+        }
+        "lastout" => {
+            let Some(path) = lastout::path() else {
+                errln!("lastout: not enabled; set lastout_kb in the config");
+                return Ok(BuiltinResult::Handled(1));
+            };
+            match fs::read(&path) {
+                Ok(output) => Ok(BuiltinResult::HandledWithOutput(0, output)),
                 Err(e) => {
-                    eprintln!("cd: {}: {}", target, e);
+                    errln!("lastout: {}", e);
                     Ok(BuiltinResult::Handled(1))
                 }
             }
         }
+        "copy" => {
+            let data = if argv.len() > 1 {
+                argv[1..].join(" ").into_bytes()
+            } else if let Some(input) = input {
+                input.to_vec()
+            } else {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(&mut std::io::stdin(), &mut data)?;
+                data
+            };
+            match clipboard::copy(&data) {
+                Ok(()) => Ok(BuiltinResult::Handled(0)),
+                Err(e) => {
+                    errln!("copy: {}", e);
+                    Ok(BuiltinResult::Handled(1))
+                }
+            }
+        }
+        "filter" => {
+            let (status, output) = filter::run(&argv[1..], input);
+            Ok(BuiltinResult::HandledWithOutput(status, output))
+        }
+        "plugins" => {
+            let plugins = plugins::list();
+            if plugins.is_empty() {
+                match crate::config::plugins_dir() {
+                    Some(dir) => outln!("plugins: none in {}", dir.display()),
+                    None => outln!("plugins: none"),
+                }
+            }
+            for plugin in plugins {
+                outln!("{}  {}", plugin.name.bold(), plugin.path.display().to_string().paint(Role::Path));
+                for segment in &plugin.segments {
+                    outln!("  segment  %{{{}}} every {}s", segment.name, segment.interval_secs);
+                }
+                for builtin in &plugin.builtins {
+                    outln!("  builtin  {}  {}", builtin.name.paint(Role::Command), builtin.summary.dimmed());
+                }
+                if !plugin.completes.is_empty() {
+                    outln!("  complete {}", plugin.completes.join(" "));
+                }
+                if !plugin.hooks.is_empty() {
+                    outln!("  hook     {}", plugin.hooks.join(" "));
+                }
+            }
+            Ok(BuiltinResult::Handled(0))
+        }
+        "sandbox" => {
+            let (status, output) = sandbox::run(&argv[1..], input);
+            Ok(match input {
+                Some(_) => BuiltinResult::HandledWithOutput(status, output),
+                None => BuiltinResult::Handled(status),
+            })
+        }
+        // `paste a.txt b.txt` is the coreutils one, merging lines
+        "paste" if argv.len() > 1 => Ok(BuiltinResult::NotHandled),
+        "paste" => match clipboard::paste() {
+            Ok(data) => Ok(BuiltinResult::HandledWithOutput(0, data)),
+            Err(e) => {
+                errln!("paste: {}", e);
+                Ok(BuiltinResult::Handled(1))
+            }
+        },
+        "record" => match (argv.get(1).map(String::as_str), argv.get(2)) {
+            (Some("start"), Some(file)) => match record::start(Path::new(&expand_tilde(file))) {
+                Ok(()) => {
+                    outln!("recording to {}; `record stop` ends it", file);
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Err(e) => {
+                    errln!("record: {}: {}", file, e);
+                    Ok(BuiltinResult::Handled(1))
+                }
+            },
+            (Some("stop"), None) => match record::stop() {
+                Ok((path, elapsed)) => {
+                    outln!("saved {} ({})", path.display(), format_runtime(elapsed));
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Err(e) => {
+                    errln!("record: {}", e);
+                    Ok(BuiltinResult::Handled(1))
+                }
+            },
+            (None, None) => match record::status() {
+                Some((path, elapsed)) => {
+                    outln!("recording to {} ({})", path.display(), format_runtime(elapsed));
+                    Ok(BuiltinResult::Handled(0))
+                }
+                None => {
+                    outln!("not recording");
+                    Ok(BuiltinResult::Handled(1))
+                }
+            },
+            _ => {
+                errln!("record: usage: record start <file.cast> | record stop");
+                Ok(BuiltinResult::Handled(2))
+            }
+        },
+        "trash" => {
+            match argv.get(1).map(String::as_str) {
+                None => {
+                    errln!("trash: usage: trash <path>... | --list | --restore <name|path>... | --empty");
+                    Ok(BuiltinResult::Handled(2))
+                }
+                Some("--list") => {
+                    for entry in trash::list() {
+                        outln!("{}  {}  {}", entry.deleted.replace('T', " ").dimmed(), collapse_home(&entry.original.to_string_lossy()).paint(Role::Path), entry.name.bright_black());
+                    }
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Some("--restore") => {
+                    let mut status = 0;
+                    for spec in &argv[2..] {
+                        match trash::restore(spec) {
+                            Ok(path) => outln!("restored {}", collapse_home(&path.to_string_lossy())),
+                            Err(e) => {
+                                errln!("trash: {}: {}", spec, e);
+                                status = 1;
+                            }
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(status))
+                }
+                Some("--empty") => {
+                    let count = trash::empty()?;
+                    outln!("removed {} item(s) from the trash", count);
+                    Ok(BuiltinResult::Handled(0))
+                }
+                Some(_) => {
+                    let mut status = 0;
+                    for target in argv[1..].iter().filter(|a| a.as_str() != "--") {
+                        if let Err(e) = trash::trash_path(Path::new(target)) {
+                            errln!("trash: {}: {}", target, e);
+                            status = 1;
+                        }
+                    }
+                    Ok(BuiltinResult::Handled(status))
+                }
+            }
+        }
         "ll" => {
-            let target_raw = argv.get(1).cloned().unwrap_or_else(|| String::from("."));
+            let exact = argv.iter().skip(1).any(|a| a == "-e" || a == "--exact");
+            let target_raw = argv.iter().skip(1).find(|a| *a != "-e" && *a != "--exact").cloned().unwrap_or_else(|| String::from("."));
             let target = expand_tilde(&target_raw);
             let path = Path::new(&target);
-            match fancy_list_capture(path) {
+            match fancy_list_capture(path, exact) {
                 Ok((code, output)) => Ok(BuiltinResult::HandledWithOutput(code, output)),
                 Err(e) => {
-                    eprintln!("ll: {}: {}", target, e);
+                    errln!("ll: {}: {}", target, e);
                     Ok(BuiltinResult::Handled(1))
                 }
             }
@@ -76,51 +565,65 @@ pub fn try_handle_builtin(argv: &[String]) -> Result<BuiltinResult, ShellError>
             match fancy_print_dirfreq() {
                 Ok(_) => Ok(BuiltinResult::Handled(0)),
                 Err(e) => {
-                    eprintln!("freqs: {}", e);
+                    errln!("freqs: {}", e);
                     Ok(BuiltinResult::Handled(1))
                 }
             }
         }
         "help" => {
-            let cmd = match argv.get(1) {
+            let cmd = match argv.get(1).map(String::as_str) {
+                Some("-k") => {
+                    let Some(keyword) = argv.get(2) else {
+                        errln!("help: usage: help -k <keyword>");
+                        return Ok(BuiltinResult::Handled(2));
+                    };
+                    let pages = docs::search(keyword);
+                    if pages.is_empty() {
+                        errln!("help: nothing found for '{}'", keyword);
+                        return Ok(BuiltinResult::Handled(1));
+                    }
+                    print_help_index(&pages);
+                    return Ok(BuiltinResult::Handled(0));
+                }
                 Some(s) => s,
                 None => {
-                    println!("Usage: help <command>\nShows a short summary and --help output if available.");
-                    println!("\nBuilt-in commands:");
-                    println!("  alias [name='value']  - Create or list aliases");
-                    println!("  unalias <name>        - Remove an alias");
-                    println!("  cd [dir]              - Change directory");
-                    println!("  ll [dir]              - List directory with details");
-                    println!("  freqs                - Show directory frequency stats");
-                    println!("  export [var=value]    - Set environment variables");
-                    println!("  unset <var>          - Unset environment variable");
-                    println!("  jobs                 - List background jobs");
-                    println!("  fg [job]             - Bring job to foreground");
-                    println!("  bg [job]             - Resume background job");
-                    println!("  time <command>       - Time command execution");
-                    println!("  exit [code]          - Exit shell");
+                    outln!("Usage: help <command> | help -k <keyword>\nShows squish's documentation for builtins, or a short summary and --help output for other commands.");
+                    outln!("\nBuilt-in commands:");
+                    print_help_index(&docs::PAGES.iter().collect::<Vec<_>>());
                     return Ok(BuiltinResult::Handled(0));
                 }
             };
+            if let Some(page) = docs::find(cmd) {
+                formatter::print_help_page(page);
+                return Ok(BuiltinResult::Handled(0));
+            }
+            if let Some((plugin, builtin)) = plugins::builtin(cmd) {
+                outln!("{} - {}
+(from plugin {}; try {} --help)", cmd.bold(), builtin.summary, plugin.name, cmd);
+                return Ok(BuiltinResult::Handled(0));
+            }
             match show_help_for(cmd) {
                 Ok(code) => Ok(BuiltinResult::Handled(code)),
                 Err(e) => {
-                    eprintln!("help: {}", e);
+                    errln!("help: {}", e);
                     Ok(BuiltinResult::Handled(1))
                 }
             }
         }
-        "jobs" => {
-            Ok(BuiltinResult::NotHandled)
-        }
-        "fg" | "bg" => {
-            Ok(BuiltinResult::NotHandled)
-        }
         "exit" => {
             let code = argv.get(1).and_then(|s| s.parse::<i32>().ok()).unwrap_or(0);
-            std::process::exit(code);
+            Err(ShellError::Exit(code))
         }
-        _ => Ok(BuiltinResult::NotHandled),
+        name => match plugins::builtin(name) {
+            Some((plugin, _)) => {
+                let (status, output) = plugins::run_builtin(plugin, name, &argv[1..], input);
+                Ok(match input {
+                    Some(_) => BuiltinResult::HandledWithOutput(status, output),
+                    None => BuiltinResult::Handled(status),
+                })
+            }
+            None => Ok(BuiltinResult::NotHandled),
+        },
     }
 }
 
@@ -148,59 +651,102 @@ fn expand_tilde(input: &str) -> String {
     input.to_string()
 }
 
-fn fancy_list_capture(dir: &Path) -> Result<(i32, Vec<u8>), std::io::Error> {
+/// The `ll` listing of `dir`; `exact` shows timestamps even when
+/// `modified_format=relative`.
+fn fancy_list_capture(dir: &Path, exact: bool) -> Result<(i32, Vec<u8>), std::io::Error> {
     use std::io::Write;
+    use formatter::ListColumn;
     let mut output = Vec::new();
     let mut entries: Vec<_> = fs::read_dir(dir)?.flatten().collect();
     entries.sort_by_key(|e| e.file_name().to_ascii_lowercase());
     entries.sort_by_key(|e| match e.file_type() { Ok(t) if t.is_dir() => 0, _ => 1 });
+    let entries: Vec<_> = entries.into_iter().filter_map(|e| e.metadata().ok().map(|md| (e, md))).collect();
 
-    let header = format!("{:2}  {:>8}  {:<19}  {}", "T", "Size", "Modified", "Name");
+    let columns = formatter::list_columns();
+    let show = |c| columns.contains(&c) && (cfg!(unix) || !matches!(c, ListColumn::Owner | ListColumn::Links));
+    // Owner and group are padded to their widest, so look them up first
+    let owners: Vec<(String, String)> = if show(ListColumn::Owner) {
+        entries.iter().map(|(_, md)| formatter::owner_and_group(md)).collect()
+    } else {
+        Vec::new()
+    };
+    let links: Vec<String> = if show(ListColumn::Links) {
+        entries.iter().map(|(_, md)| formatter::link_count(md).to_string()).collect()
+    } else {
+        Vec::new()
+    };
+    let owner_width = owners.iter().map(|(o, _)| o.chars().count()).max().unwrap_or(0).max(5);
+    let group_width = owners.iter().map(|(_, g)| g.chars().count()).max().unwrap_or(0).max(5);
+    let links_width = links.iter().map(String::len).max().unwrap_or(0).max(1);
+
+    let mut header = format!("{:2}  ", "T");
+    if show(ListColumn::Mode) {
+        header.push_str(&format!("{:<10}  ", "Mode"));
+    }
+    if show(ListColumn::Links) {
+        header.push_str(&format!("{:>links_width$}  ", "#"));
+    }
+    if show(ListColumn::Owner) {
+        header.push_str(&format!("{:<owner_width$}  {:<group_width$}  ", "Owner", "Group"));
+    }
+    header.push_str(&format!("{:>8}  {:<19}  {}", "Size", "Modified", "Name"));
     writeln!(output, "{}", header.bold().underline())?;
 
-    for entry in entries {
+    for (i, (entry, md)) in entries.iter().enumerate() {
         let path = entry.path();
-        let md = match entry.metadata() { Ok(m) => m, Err(_) => continue };
         let file_type = if md.is_dir() { 'd' } else if md.is_symlink() { 'l' } else { '-' };
-        let size = if md.is_dir() { String::from("—") } else { format_size(md.len(), DECIMAL) };
+        let size = if md.is_dir() { String::from("—") } else { locale::format_bytes(md.len()) };
         let modified = md.modified().ok()
-            .and_then(|t| DateTime::<Local>::from(t).format("%Y-%m-%d %H:%M").to_string().into())
+            .map(|t| locale::format_modified(t, exact))
             .unwrap_or_else(|| String::from("—"));
         let name = entry.file_name().to_string_lossy().to_string();
-        let colored_name = colorize_name(&path, &name, &md);
+        let colored_name = colorize_name(&path, &name, md);
 
-        writeln!(output,
-            "{}  {:>8}  {:<19}  {}",
-            style_type(file_type),
-            size.dimmed(),
-            modified.dimmed(),
-            colored_name
-        )?;
+        write!(output, "{}  ", style_type(file_type))?;
+        if show(ListColumn::Mode) {
+            let mode = formatter::mode_string(md);
+            write!(output, "{}{}  ", formatter::color_perms(&mode), " ".repeat(10usize.saturating_sub(mode.len())))?;
+        }
+        if show(ListColumn::Links) {
+            write!(output, "{:>links_width$}  ", links[i].dimmed())?;
+        }
+        if show(ListColumn::Owner) {
+            let (owner, group) = &owners[i];
+            write!(output, "{:<owner_width$}  {:<group_width$}  ", owner.paint(Role::Accent), group.dimmed())?;
+        }
+        write!(output, "{:>8}  {:<19}  {}", size.dimmed(), modified.dimmed(), colored_name)?;
+        if show(ListColumn::Target)
+            && md.is_symlink()
+            && let Ok(target) = fs::read_link(&path)
+        {
+            write!(output, " {}", format!("→ {}", target.display()).dimmed())?;
+        }
+        writeln!(output)?;
     }
     Ok((0, output))
 }
 
 fn style_type(t: char) -> colored::ColoredString {
     match t {
-        'd' => "d".truecolor(140, 180, 255),
-        'l' => "l".truecolor(200, 150, 255),
+        'd' => "d".paint(Role::Path),
+        'l' => "l".paint(Role::Accent),
         _ => "-".dimmed(),
     }
 }
 
 fn colorize_name(path: &Path, name: &str, md: &fs::Metadata) -> colored::ColoredString {
     if md.is_dir() {
-        name.truecolor(140, 180, 255).bold()
+        name.paint(Role::Path).bold()
     } else if md.is_symlink() {
-        name.truecolor(200, 150, 255)
+        name.paint(Role::Accent)
     } else {
         match path.extension().and_then(|e| e.to_str()) {
-            Some("rs") => name.truecolor(255, 150, 180),
-            Some("md") => name.truecolor(240, 160, 255),
-            Some("toml") => name.truecolor(255, 220, 150),
-            Some("png") | Some("jpg") | Some("jpeg") | Some("gif") => name.truecolor(150, 255, 180),
-            Some("zip") | Some("tar") | Some("gz") => name.truecolor(255, 220, 150),
-            Some("sh") => name.truecolor(150, 255, 180),
+            Some("rs") => name.paint(Role::Source),
+            Some("md") => name.paint(Role::Docs),
+            Some("toml") => name.paint(Role::Data),
+            Some("png") | Some("jpg") | Some("jpeg") | Some("gif") => name.paint(Role::Media),
+            Some("zip") | Some("tar") | Some("gz") => name.paint(Role::Data),
+            Some("sh") => name.paint(Role::Source),
             _ => name.normal(),
         }
     }
@@ -219,49 +765,48 @@ fn collapse_home(path: &str) -> String {
 }
 
 fn fancy_print_dirfreq() -> Result<(), std::io::Error> {
-    use crate::config;
-    let Some(file) = config::dirfreq_file() else { return Ok(()); };
-    let f = match fs::File::open(&file) { Ok(f) => f, Err(_) => return Ok(()) };
-    let rd = std::io::BufReader::new(f);
-    let mut rows: Vec<(u64, String)> = Vec::new();
-    for line in rd.lines().flatten() {
-        if let Some((p, c)) = line.rsplit_once('\t') {
-            if let Ok(n) = c.parse::<u64>() {
-                rows.push((n, collapse_home(p)));
-            }
-        }
-    }
+    let mut rows: Vec<(u64, String)> = crate::dirfreq::visible_freqs()
+        .into_iter()
+        .map(|(p, n)| (n, collapse_home(&p)))
+        .collect();
     rows.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
 
     let header = format!("{:>8}  {}", "Count", "Directory");
-    println!("{}", header.bold().underline());
+    outln!("{}", header.bold().underline());
     for (n, p) in rows {
-        println!("{:>8}  {}", n.to_string().truecolor(150, 255, 180), p.truecolor(140, 180, 255));
+        outln!("{:>8}  {}", n.to_string().paint(Role::String), p.paint(Role::Path));
     }
     Ok(())
 }
 
+fn print_help_index(pages: &[&docs::HelpPage]) {
+    let width = pages.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for page in pages {
+        outln!("  {}{}  {}", page.name.paint(Role::Command), " ".repeat(width - page.name.len()), page.summary);
+    }
+}
+
 fn show_help_for(cmd: &str) -> Result<i32, std::io::Error> {
     use std::process::Command;
     if which::which("whatis").is_ok() {
         if let Ok(out) = Command::new("whatis").arg(cmd).output() {
             if out.status.success() {
                 let text = String::from_utf8_lossy(&out.stdout);
-                if !text.trim().is_empty() { println!("{}", text.trim()); }
+                if !text.trim().is_empty() { outln!("{}", text.trim()); }
             }
         }
     }
     if let Ok(out) = Command::new(cmd).arg("--help").output() {
         if out.status.success() {
             let text = String::from_utf8_lossy(&out.stdout);
-            println!("{}", text);
+            outln!("{}", text);
             return Ok(0);
         }
     }
     if let Ok(out) = Command::new(cmd).arg("-h").output() {
         if out.status.success() {
             let text = String::from_utf8_lossy(&out.stdout);
-            println!("{}", text);
+            outln!("{}", text);
             return Ok(0);
         }
     }
@@ -0,0 +1,290 @@
+//! A small expression evaluator for the `calc` builtin and inline math.
+//!
+//! Precedence, lowest first: `|`, `^`, `&`, `<< >>`, `+ -`, `* / %`, unary
+//! `- + ~`, `**` (right associative). Bitwise operators need integer operands.
+
+type MathFn = fn(f64) -> f64;
+
+const FUNCTIONS: &[(&str, MathFn)] = &[
+    ("sqrt", f64::sqrt),
+    ("abs", f64::abs),
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+    ("round", f64::round),
+    ("ln", f64::ln),
+    ("log", f64::log10),
+    ("log2", f64::log2),
+    ("exp", f64::exp),
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/// Evaluate `input`, returning the result or a short error message.
+pub fn evaluate(input: &str) -> Result<f64, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(String::from("empty expression"));
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let value = parser.bitor()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("unexpected '{}'", parser.describe(parser.pos)));
+    }
+    Ok(value)
+}
+
+/// Format a result: integers without a fraction, plus hex when the input
+/// used hex literals.
+pub fn format_result(input: &str, value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 9.007_199_254_740_992e15 {
+        let n = value as i64;
+        if input.contains("0x") || input.contains("0X") {
+            return if n < 0 { format!("{} (-{:#x})", n, n.unsigned_abs()) } else { format!("{} ({:#x})", n, n) };
+        }
+        return n.to_string();
+    }
+    let s = format!("{:.12}", value);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Whether a whole command line should be treated as arithmetic rather than
+/// a command: it starts like a number, contains an operator, uses no
+/// characters a command line would need, and evaluates cleanly.
+pub fn looks_like_math(line: &str) -> bool {
+    let line = line.trim();
+    let starts_ok = line.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '(' | '.' | '-' | '~'));
+    let has_op = line.chars().any(|c| "+-*/%^&|<>~".contains(c)) || line.contains('(');
+    let charset_ok = line.chars().all(|c| c.is_ascii_alphanumeric() || c.is_whitespace() || "+-*/%^&|<>~().".contains(c));
+    starts_ok && has_op && charset_ok && evaluate(line).is_ok_and(f64::is_finite)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                // Exponent sign: 1e-3
+                if matches!(chars[i], 'e' | 'E') && !chars[start..i].iter().any(|c| matches!(c, 'x' | 'X')) && matches!(chars.get(i + 1), Some('-' | '+')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+            tokens.push(Tok::Num(parse_number(&text)?));
+        } else if c.is_ascii_alphabetic() {
+            let start = i;
+            while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                i += 1;
+            }
+            tokens.push(Tok::Ident(chars[start..i].iter().collect()));
+        } else if c == '(' {
+            tokens.push(Tok::LParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(Tok::RParen);
+            i += 1;
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let op = ["**", "<<", ">>"].into_iter().find(|op| *op == two);
+            let op = match op {
+                Some(op) => op,
+                None => ["+", "-", "*", "/", "%", "&", "|", "^", "~"]
+                    .into_iter()
+                    .find(|op| op.starts_with(c))
+                    .ok_or_else(|| format!("unexpected '{}'", c))?,
+            };
+            i += op.len();
+            tokens.push(Tok::Op(op));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<f64, String> {
+    let lower = text.to_ascii_lowercase();
+    let radix = |digits: &str, base: u32| i64::from_str_radix(digits, base).map(|n| n as f64).map_err(|_| format!("invalid number '{}'", text));
+    if let Some(hex) = lower.strip_prefix("0x") {
+        radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        radix(bin, 2)
+    } else if let Some(oct) = lower.strip_prefix("0o") {
+        radix(oct, 8)
+    } else {
+        lower.parse::<f64>().map_err(|_| format!("invalid number '{}'", text))
+    }
+}
+
+struct Parser {
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn describe(&self, pos: usize) -> String {
+        match self.tokens.get(pos) {
+            Some(Tok::Num(n)) => n.to_string(),
+            Some(Tok::Ident(s)) => s.clone(),
+            Some(Tok::Op(op)) => op.to_string(),
+            Some(Tok::LParen) => String::from("("),
+            Some(Tok::RParen) => String::from(")"),
+            None => String::from("end of input"),
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.tokens.get(self.pos), Some(Tok::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn bitor(&mut self) -> Result<f64, String> {
+        let mut left = self.bitxor()?;
+        while self.eat("|") {
+            left = (int(left)? | int(self.bitxor()?)?) as f64;
+        }
+        Ok(left)
+    }
+
+    fn bitxor(&mut self) -> Result<f64, String> {
+        let mut left = self.bitand()?;
+        while self.eat("^") {
+            left = (int(left)? ^ int(self.bitand()?)?) as f64;
+        }
+        Ok(left)
+    }
+
+    fn bitand(&mut self) -> Result<f64, String> {
+        let mut left = self.shift()?;
+        while self.eat("&") {
+            left = (int(left)? & int(self.shift()?)?) as f64;
+        }
+        Ok(left)
+    }
+
+    fn shift(&mut self) -> Result<f64, String> {
+        let mut left = self.additive()?;
+        loop {
+            if self.eat("<<") {
+                let by = int(self.additive()?)?;
+                left = int(left)?.checked_shl(by as u32).ok_or("shift out of range")? as f64;
+            } else if self.eat(">>") {
+                let by = int(self.additive()?)?;
+                left = int(left)?.checked_shr(by as u32).ok_or("shift out of range")? as f64;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn additive(&mut self) -> Result<f64, String> {
+        let mut left = self.multiplicative()?;
+        loop {
+            if self.eat("+") {
+                left += self.multiplicative()?;
+            } else if self.eat("-") {
+                left -= self.multiplicative()?;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<f64, String> {
+        let mut left = self.unary()?;
+        loop {
+            if self.eat("*") {
+                left *= self.unary()?;
+            } else if self.eat("/") {
+                let right = self.unary()?;
+                if right == 0.0 {
+                    return Err(String::from("division by zero"));
+                }
+                left /= right;
+            } else if self.eat("%") {
+                let right = self.unary()?;
+                if right == 0.0 {
+                    return Err(String::from("division by zero"));
+                }
+                left %= right;
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat("-") {
+            Ok(-self.unary()?)
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("~") {
+            Ok(!int(self.unary()?)? as f64)
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.primary()?;
+        if self.eat("**") {
+            // Right associative, and binds tighter than a unary minus on its left
+            let exp = self.unary()?;
+            return Ok(base.powf(exp));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match tok {
+            Some(Tok::Num(n)) => Ok(n),
+            Some(Tok::LParen) => {
+                let value = self.bitor()?;
+                if self.tokens.get(self.pos) != Some(&Tok::RParen) {
+                    return Err(String::from("missing ')'"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(Tok::Ident(name)) => match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "e" => Ok(std::f64::consts::E),
+                _ => {
+                    let f = FUNCTIONS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f).ok_or_else(|| format!("unknown name '{}'", name))?;
+                    if self.tokens.get(self.pos) != Some(&Tok::LParen) {
+                        return Err(format!("{} needs parentheses", name));
+                    }
+                    Ok(f(self.primary()?))
+                }
+            },
+            _ => Err(format!("unexpected {}", self.describe(self.pos - 1))),
+        }
+    }
+}
+
+fn int(value: f64) -> Result<i64, String> {
+    if value.fract() != 0.0 || !value.is_finite() {
+        return Err(format!("bitwise operations need integers, got {}", value));
+    }
+    Ok(value as i64)
+}
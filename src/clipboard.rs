@@ -0,0 +1,229 @@
+//! The system clipboard for `copy` and `paste`. A local clipboard tool is
+//! used when there is one for the running display (wl-copy/wl-paste,
+//! xclip, xsel, pbcopy/pbpaste, clip and PowerShell on Windows); over SSH, or with none available, the
+//! terminal's own clipboard through OSC 52, which reaches the machine the
+//! terminal runs on. Not every terminal allows OSC 52, and fewer allow
+//! reading it back.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::{
+    io::Read,
+    time::{Duration, Instant},
+};
+
+use crate::lookup;
+use crate::spawn::{Io, Spawn};
+
+/// A clipboard tool: program and arguments to copy with, then to paste.
+struct Tool {
+    copy: (&'static str, &'static [&'static str]),
+    paste: (&'static str, &'static [&'static str]),
+}
+
+const WAYLAND: Tool = Tool { copy: ("wl-copy", &[]), paste: ("wl-paste", &["--no-newline"]) };
+const XCLIP: Tool = Tool { copy: ("xclip", &["-selection", "clipboard"]), paste: ("xclip", &["-selection", "clipboard", "-o"]) };
+const XSEL: Tool = Tool { copy: ("xsel", &["--clipboard", "--input"]), paste: ("xsel", &["--clipboard", "--output"]) };
+const MACOS: Tool = Tool { copy: ("pbcopy", &[]), paste: ("pbpaste", &[]) };
+const WINDOWS: Tool = Tool { copy: ("clip", &[]), paste: ("powershell", &["-NoProfile", "-Command", "Get-Clipboard -Raw"]) };
+
+/// The terminal itself, whatever stdout has been redirected to.
+const TERMINAL: &str = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+
+/// The tool for this session's display, if one is installed. None over
+/// SSH: the remote display's clipboard isn't the one the user sees.
+fn local_tool() -> Option<&'static Tool> {
+    let has = |var: &str| std::env::var_os(var).is_some_and(|v| !v.is_empty());
+    if has("SSH_CONNECTION") || has("SSH_TTY") {
+        return None;
+    }
+    let candidates: &[&'static Tool] = if cfg!(target_os = "macos") {
+        &[&MACOS]
+    } else if cfg!(windows) {
+        &[&WINDOWS]
+    } else if has("WAYLAND_DISPLAY") {
+        &[&WAYLAND, &XCLIP, &XSEL]
+    } else if has("DISPLAY") {
+        &[&XCLIP, &XSEL]
+    } else {
+        &[]
+    };
+    candidates.iter().copied().find(|tool| lookup::find(tool.copy.0).is_some())
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|a| a.to_string()).collect()
+}
+
+/// Put `data` on the clipboard.
+pub fn copy(data: &[u8]) -> io::Result<()> {
+    if let Some(tool) = local_tool() {
+        let (program, tool_args) = tool.copy;
+        let tool_args = args(tool_args);
+        // xclip stays behind to serve the selection; it mustn't hold a pipe we wait on
+        let output = Spawn::new(program, &tool_args)
+            .stdout(Io::Null)
+            .stderr(Io::Piped)
+            .output_with_input(data)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        return Err(io::Error::other(format!("{}: {}", program, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    osc52_copy(data)
+}
+
+/// What's on the clipboard.
+pub fn paste() -> io::Result<Vec<u8>> {
+    if let Some(tool) = local_tool() {
+        let (program, tool_args) = tool.paste;
+        let tool_args = args(tool_args);
+        let output = Spawn::new(program, &tool_args)
+            .stdout(Io::Piped)
+            .stderr(Io::Piped)
+            .output()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+        return Err(io::Error::other(format!("{}: {}", program, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    osc52_paste()
+}
+
+/// OSC 52 goes to the terminal itself, not stdout, so `copy` works in the
+/// middle of a pipeline or with stdout redirected. Inside tmux it's
+/// wrapped to pass through to the outer terminal.
+fn osc52(payload: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", payload);
+    if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+fn osc52_copy(data: &[u8]) -> io::Result<()> {
+    let mut tty = OpenOptions::new().write(true).open(TERMINAL)?;
+    tty.write_all(osc52(&base64_encode(data)).as_bytes())?;
+    tty.flush()
+}
+
+/// Ask the terminal for its clipboard and wait briefly for the answer,
+/// `ESC ] 52 ; c ; <base64> BEL` (or ST). Terminals that don't allow it
+/// just don't answer.
+#[cfg(unix)]
+fn osc52_paste() -> io::Result<Vec<u8>> {
+    use std::os::fd::AsRawFd;
+
+    let mut tty = OpenOptions::new().read(true).write(true).open(TERMINAL)?;
+    let fd = tty.as_raw_fd();
+    let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut raw = saved;
+    unsafe { libc::cfmakeraw(&mut raw) };
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) };
+    let _restore = RawMode { fd, saved };
+
+    tty.write_all(osc52("?").as_bytes())?;
+    tty.flush()?;
+    let deadline = Instant::now() + Duration::from_millis(500);
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let mut poll = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        if left.is_zero() || unsafe { libc::poll(&mut poll, 1, left.as_millis() as libc::c_int) } <= 0 {
+            return Err(io::Error::other("the terminal didn't answer an OSC 52 request; it may not allow reading the clipboard"));
+        }
+        let n = tty.read(&mut buf)?;
+        reply.extend_from_slice(&buf[..n]);
+        if reply.ends_with(b"\x07") || reply.ends_with(b"\x1b\\") {
+            break;
+        }
+    }
+    let text = String::from_utf8_lossy(&reply);
+    let payload = text
+        .split_once("52;")
+        .and_then(|(_, rest)| rest.split_once(';'))
+        .map(|(_, data)| data.trim_end_matches(['\x07', '\\']).trim_end_matches('\x1b'))
+        .ok_or_else(|| io::Error::other("unexpected reply to an OSC 52 request"))?;
+    base64_decode(payload).ok_or_else(|| io::Error::other("the terminal's clipboard reply wasn't valid base64"))
+}
+
+/// Puts the terminal back in its usual mode when dropped.
+#[cfg(unix)]
+struct RawMode {
+    fd: libc::c_int,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}
+
+#[cfg(not(unix))]
+fn osc52_paste() -> io::Result<Vec<u8>> {
+    Err(io::Error::other("no clipboard tool found"))
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |acc, (i, &b)| acc | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(any(unix, test))]
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        bits = bits << 6 | BASE64.iter().position(|&b| b == c)? as u32;
+        count += 1;
+        if count == 4 {
+            out.extend_from_slice(&[(bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+            bits = 0;
+            count = 0;
+        }
+    }
+    match count {
+        2 => out.push((bits >> 4) as u8),
+        3 => out.extend_from_slice(&[(bits >> 10) as u8, (bits >> 2) as u8]),
+        _ => {}
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips_every_padding() {
+        for (data, encoded) in [(&b"abc"[..], "YWJj"), (b"ab", "YWI="), (b"a", "YQ=="), (b"", "")] {
+            assert_eq!(base64_encode(data), encoded);
+            assert_eq!(base64_decode(encoded).as_deref(), Some(data));
+        }
+        assert_eq!(base64_decode("YQ"), Some(b"a".to_vec()));
+        assert_eq!(base64_decode("Y!=="), None);
+    }
+}
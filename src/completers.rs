@@ -0,0 +1,96 @@
+//! External completers, registered per command with
+//! `completion.command.<name>=<program>`, so a tool that knows its own
+//! arguments can complete them without anything compiled into squish.
+//!
+//! The program runs with `sh -c` and reads one JSON object on stdin:
+//!
+//! ```text
+//! {"command": "git", "line": "git checkout ma", "cursor": 15,
+//!  "words": ["git", "checkout", "ma"], "current": "ma", "cwd": "/src/app"}
+//! ```
+//!
+//! `cursor` counts characters; `words` are the words up to the cursor, the
+//! last being `current`, the one completed (empty when a new word starts).
+//! It prints the candidates as a JSON array of strings or of
+//! `{"value": ..., "description": ...}` objects, or an object with such an
+//! array under `"candidates"`. Those starting with `current` are offered.
+//! No output, a failure, or nothing matching leaves the word to squish's
+//! own completion.
+
+use std::io::Write;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::spawn::{Io, Spawn};
+use crate::timeout::Watchdog;
+
+/// How long a completer may take before it is stopped
+const LIMIT: Duration = Duration::from_secs(2);
+
+pub struct Candidate {
+    pub value: String,
+    pub description: String,
+}
+
+/// Ask `program` to complete the word before `pos` in `line`. Returns
+/// where the word starts and the matching candidates.
+pub fn complete(program: &str, line: &str, pos: usize) -> Option<(usize, Vec<Candidate>)> {
+    let before = &line[..pos];
+    let mut words: Vec<&str> = before.split_whitespace().collect();
+    if before.ends_with(char::is_whitespace) || words.is_empty() {
+        words.push("");
+    }
+    let current = *words.last()?;
+    let request = json!({
+        "command": words[0],
+        "line": line,
+        "cursor": before.chars().count(),
+        "words": words,
+        "current": current,
+        "cwd": std::env::current_dir().map(|d| d.to_string_lossy().into_owned()).unwrap_or_default(),
+    });
+    let output = run(program, &request.to_string())?;
+    let reply: Value = serde_json::from_slice(&output).ok()?;
+    let list = match &reply {
+        Value::Object(object) => object.get("candidates")?.as_array()?,
+        Value::Array(list) => list,
+        _ => return None,
+    };
+    let candidates: Vec<Candidate> = list
+        .iter()
+        .filter_map(|item| match item {
+            Value::String(value) => Some(Candidate { value: value.clone(), description: String::new() }),
+            Value::Object(object) => Some(Candidate {
+                value: object.get("value")?.as_str()?.to_string(),
+                description: object.get("description").and_then(Value::as_str).unwrap_or_default().to_string(),
+            }),
+            _ => None,
+        })
+        .filter(|candidate| candidate.value.starts_with(current))
+        .collect();
+    (!candidates.is_empty()).then(|| (pos - current.len(), candidates))
+}
+
+/// Run `program` with `request` on stdin; its stdout if it succeeded in
+/// time. Its stderr is dropped: it runs while the user types.
+fn run(program: &str, request: &str) -> Option<Vec<u8>> {
+    let args = [String::from("-c"), program.to_string()];
+    let mut child = Spawn::new("sh", &args)
+        .stdin(Io::Piped)
+        .stdout(Io::Piped)
+        .stderr(Io::Null)
+        .new_process_group()
+        .spawn()
+        .ok()?;
+    let watchdog = Watchdog::start(child.id(), LIMIT);
+    if let Some(mut stdin) = child.stdin.take() {
+        // A completer that doesn't read its request is fine
+        let _ = stdin.write_all(request.as_bytes());
+    }
+    let output = child.wait_with_output();
+    if watchdog.finish().is_some() {
+        return None;
+    }
+    output.ok().filter(|output| output.status.success()).map(|output| output.stdout)
+}
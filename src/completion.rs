@@ -1,125 +1,379 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, OnceLock};
 
 use colored::Colorize;
-use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::completion::{escape, unescape, Completer, FilenameCompleter, Pair, Quote};
 use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
-use rustyline::{Context, Helper};
+use rustyline::{Cmd, ConditionalEventHandler, Context, Event, EventContext, Helper, Movement, RepeatCount};
 use rustyline::history::SearchDirection;
 
+use crate::builtins::{self, ArgCompletion};
+use crate::shell_config::CompletionCase;
+use crate::idle::IdleState;
+use crate::shell::{self, SharedView};
+use crate::suggest::SharedLog;
+use crate::lookup;
+use crate::formatter;
+use crate::preview::{self, ImageProtocol};
+use crate::theme::{Paint, Role};
+
 // Cache for all available commands in PATH
 static COMMAND_CACHE: OnceLock<Arc<Mutex<Option<CommandCache>>>> = OnceLock::new();
 
 struct CommandCache {
-    commands: Vec<String>,
-    path_hash: u64,
+    // Shared so callers get a cheap handle instead of a copy of every name
+    commands: Arc<CommandIndex>,
+    /// The lookup table's PATH scan this index was built from
+    source: Arc<HashMap<String, PathBuf>>,
 }
 
-fn hash_path() -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    if let Ok(path) = env::var("PATH") {
-        path.hash(&mut hasher);
-    }
-    hasher.finish()
+/// Command names sorted for binary-search prefix lookups, plus a lowercase
+/// copy (sorted the same way) for case-insensitive matching.
+struct CommandIndex {
+    names: Vec<String>,
+    /// (lowercased name, index into `names`)
+    lower: Vec<(String, usize)>,
 }
 
-fn get_command_cache() -> Arc<Mutex<Option<CommandCache>>> {
-    COMMAND_CACHE.get_or_init(|| Arc::new(Mutex::new(None))).clone()
-}
+impl CommandIndex {
+    fn new(mut names: Vec<String>) -> Self {
+        names.sort();
+        let mut lower: Vec<(String, usize)> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.to_lowercase(), i))
+            .collect();
+        lower.sort();
+        CommandIndex { names, lower }
+    }
 
-fn build_command_cache() -> CommandCache {
-    let mut commands = HashSet::new();
-    
-    // Collect all commands from PATH directories
-    if let Ok(path_var) = env::var("PATH") {
-        for dir in path_var.split(':') {
-            if let Ok(entries) = fs::read_dir(dir) {
-                for entry in entries.flatten() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        let path = entry.path();
-                        if is_executable(&path) {
-                            commands.insert(name.to_string());
-                        }
-                    }
-                }
-            }
-        }
+    fn contains(&self, name: &str) -> bool {
+        self.names.binary_search_by(|c| c.as_str().cmp(name)).is_ok()
     }
-    
-    let mut command_vec: Vec<String> = commands.into_iter().collect();
-    command_vec.sort();
-    
-    CommandCache {
-        commands: command_vec,
-        path_hash: hash_path(),
+
+    /// All names starting with `prefix`, in sorted order.
+    fn with_prefix(&self, prefix: &str) -> &[String] {
+        let start = self.names.partition_point(|c| c.as_str() < prefix);
+        let len = self.names[start..].partition_point(|c| c.starts_with(prefix));
+        &self.names[start..start + len]
     }
+
+    /// Indices of names whose lowercase form starts with `prefix_lower`.
+    fn with_prefix_ignore_case(&self, prefix_lower: &str) -> impl Iterator<Item = usize> {
+        let start = self.lower.partition_point(|(c, _)| c.as_str() < prefix_lower);
+        self.lower[start..]
+            .iter()
+            .take_while(move |(c, _)| c.starts_with(prefix_lower))
+            .map(|(_, i)| *i)
+    }
+}
+
+fn get_command_cache() -> Arc<Mutex<Option<CommandCache>>> {
+    COMMAND_CACHE.get_or_init(|| Arc::new(Mutex::new(None))).clone()
 }
 
-fn get_all_commands() -> Vec<String> {
+fn get_all_commands() -> Arc<CommandIndex> {
     let cache = get_command_cache();
     let mut cache_guard = cache.lock().unwrap();
     
-    let current_hash = hash_path();
+    // Rebuilt whenever the lookup table rescans PATH, which covers PATH
+    // changes and executables installed mid-session
+    let source = lookup::commands();
     let needs_rebuild = cache_guard.as_ref()
-        .map(|c| c.path_hash != current_hash)
+        .map(|c| !Arc::ptr_eq(&c.source, &source))
         .unwrap_or(true);
     
     if needs_rebuild {
-        *cache_guard = Some(build_command_cache());
+        *cache_guard = Some(CommandCache {
+            commands: Arc::new(CommandIndex::new(source.keys().cloned().collect())),
+            source,
+        });
     }
     
     cache_guard.as_ref()
-        .map(|c| c.commands.clone())
-        .unwrap_or_default()
+        .map(|c| Arc::clone(&c.commands))
+        .unwrap_or_else(|| Arc::new(CommandIndex::new(Vec::new())))
+}
+
+/// Build the PATH command cache on a background thread so the first TAB
+/// doesn't pay for the scan. A completion that arrives first just waits on
+/// the cache lock instead of scanning twice.
+pub fn warm_command_cache() {
+    let _ = std::thread::Builder::new()
+        .name("squish-path-cache".to_string())
+        .spawn(|| {
+            get_all_commands();
+        });
+}
+
+/// Accepts the autosuggestion one word at a time (Alt-Right, Ctrl-Right):
+/// up to the next space, or through the next `/` so paths go a directory at
+/// a time. Without a hint the key keeps its usual forward-word motion.
+pub struct AcceptHintWord;
+
+impl ConditionalEventHandler for AcceptHintWord {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if ctx.pos() != ctx.line().len() {
+            return None;
+        }
+        let hint = ctx.hint_text().filter(|h| !h.is_empty())?;
+        let leading = hint.len() - hint.trim_start().len();
+        let word_end = hint[leading..]
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .map(|i| {
+                let end = leading + i;
+                if hint[end..].starts_with('/') { end + 1 } else { end }
+            })
+            .unwrap_or(hint.len());
+        Some(Cmd::Insert(1, hint[..word_end].to_string()))
+    }
+}
+
+/// The part of a multi-line command already entered, as it was shown: the
+/// main prompt and each finished line. Empty outside a continuation.
+#[derive(Default)]
+pub struct PendingInput {
+    pub shown: String,
+    /// Width of the continuation prompt in front of the line being edited
+    pub prompt_width: usize,
+}
+
+/// Ctrl-L. rustyline's clear-screen redraws only the line being edited, so
+/// in a continuation the earlier lines and the main prompt would vanish;
+/// this prints them again at the top and repaints the edited line below.
+pub struct ClearScreen(pub Arc<Mutex<PendingInput>>);
+
+impl ConditionalEventHandler for ClearScreen {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        use std::io::Write;
+        let pending = self.0.lock().ok()?;
+        if pending.shown.is_empty() {
+            return Some(Cmd::ClearScreen);
+        }
+        // The repaint starts from the row the cursor was on, counted from
+        // the start of the prompt; leave that many rows for it to go back up
+        let columns = formatter::terminal_columns().max(1);
+        let rows = (pending.prompt_width + ctx.line()[..ctx.pos()].chars().count()) / columns;
+        let mut out = std::io::stdout();
+        let _ = write!(out, "\x1b[H\x1b[J{}\r\n{}", pending.shown.replace('\n', "\r\n"), "\r\n".repeat(rows));
+        let _ = out.flush();
+        Some(Cmd::Repaint)
+    }
+}
+
+/// Alt-S: `sudo` in front of the line being edited, or taken off again.
+/// On an empty line, brings back the last command with `sudo` in front,
+/// ready for Enter, as `please` would run it.
+pub struct SudoLine(pub SharedView);
+
+impl ConditionalEventHandler for SudoLine {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let line = ctx.line();
+        let replacement = if line.trim().is_empty() {
+            shell::with_sudo(self.0.read().ok()?.last_command.as_deref()?)
+        } else if let Some(rest) = line.trim_start().strip_prefix("sudo ") {
+            rest.trim_start().to_string()
+        } else {
+            shell::with_sudo(line.trim_start())
+        };
+        Some(Cmd::Replace(Movement::WholeBuffer, Some(replacement)))
+    }
 }
 
 #[derive(Default)]
 pub struct LineHelper {
     filename: FilenameCompleter,
+    idle: Option<Arc<IdleState>>,
+    preview: Option<(ImageProtocol, u16)>,
+    root_markers: Vec<String>,
+    shell: SharedView,
+    command_log: Option<SharedLog>,
+    case: CompletionCase,
+    /// Commands whose arguments complete to directories only
+    dir_commands: Vec<String>,
+    /// `completion.command.<name>` programs, as (name, program)
+    completers: Vec<(String, String)>,
+    /// Longest candidate list shown; 0 shows all
+    max_candidates: usize,
 }
 
 impl LineHelper {
     pub fn new() -> Self {
         Self {
             filename: FilenameCompleter::new(),
+            idle: None,
+            preview: None,
+            root_markers: vec![String::from(".git")],
+            shell: SharedView::default(),
+            command_log: None,
+            case: CompletionCase::default(),
+            dir_commands: Vec::new(),
+            completers: Vec::new(),
+            max_candidates: 100,
+        }
+    }
+
+    /// Let completion and highlighting see the shell's aliases and jobs.
+    pub fn set_shell_view(&mut self, view: SharedView) {
+        self.shell = view;
+    }
+
+    fn has_alias(&self, name: &str) -> bool {
+        self.shell.read().is_ok_and(|view| view.aliases.binary_search_by(|(n, _)| n.as_str().cmp(name)).is_ok())
+    }
+
+    /// Rank history hints by the command log instead of plain recency.
+    pub fn set_command_log(&mut self, log: SharedLog) {
+        self.command_log = Some(log);
+    }
+
+    pub fn set_dir_commands(&mut self, commands: Vec<String>) {
+        self.dir_commands = commands;
+    }
+
+    pub fn set_completers(&mut self, completers: Vec<(String, String)>) {
+        self.completers = completers;
+    }
+
+    /// Whether the command being typed is one of `dir_commands`: its name,
+    /// plus any flags the entry lists (`mkdir -p`) among the arguments
+    /// before the one under the cursor.
+    fn takes_dirs(&self, before: &str) -> bool {
+        let mut words: Vec<&str> = before.split_whitespace().collect();
+        if !before.ends_with(char::is_whitespace) {
+            words.pop();
+        }
+        let Some((command, args)) = words.split_first() else { return false; };
+        self.dir_commands.iter().any(|entry| {
+            let mut parts = entry.split_whitespace();
+            parts.next() == Some(*command) && parts.all(|flag| args.contains(&flag))
+        })
+    }
+
+    pub fn set_max_candidates(&mut self, max: usize) {
+        self.max_candidates = max;
+    }
+
+    pub fn set_case(&mut self, case: CompletionCase) {
+        self.case = case;
+    }
+
+    pub fn set_root_markers(&mut self, markers: Vec<String>) {
+        self.root_markers = markers;
+    }
+
+    pub fn set_idle_state(&mut self, state: Arc<IdleState>) {
+        self.idle = Some(state);
+    }
+
+    /// Enable inline previews of a uniquely completed image/pdf, if the
+    /// terminal speaks an image protocol.
+    pub fn enable_preview(&mut self, rows: u16) {
+        self.preview = preview::detect_protocol().map(|p| (p, rows));
+    }
+
+    fn maybe_preview(&self, candidates: &[Pair]) {
+        let Some((protocol, rows)) = self.preview else { return; };
+        let [only] = candidates else { return; };
+        let mut unescaped = String::new();
+        let mut chars = only.replacement.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(next) = chars.next() { unescaped.push(next); }
+            } else {
+                unescaped.push(c);
+            }
+        }
+        let path = match (unescaped.strip_prefix("~/"), env::var("HOME")) {
+            (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+            _ => Path::new(&unescaped).to_path_buf(),
+        };
+        if preview::is_previewable(&path, protocol) {
+            let _ = preview::show_preview(&path, protocol, rows);
         }
     }
 
-    fn find_commands_in_path(prefix: &str) -> Vec<Pair> {
+    /// Builtins, then PATH commands, for the command name `prefix` that
+    /// starts at `word_start`. An exact builtin match is listed first.
+    fn complete_command(&self, prefix: &str, word_start: usize) -> Option<(usize, Vec<Pair>)> {
+        let builtin_pair = |name: &str| Pair {
+            display: format!("{}", name.paint(Role::Command).bold()),
+            replacement: name.to_string(),
+        };
+        let mut candidates: Vec<Pair> = builtins::lookup(prefix).map(|b| builtin_pair(b.name)).into_iter().collect();
+        candidates.extend(builtins::BUILTINS.iter().filter(|b| b.name != prefix && b.name.starts_with(prefix)).map(|b| builtin_pair(b.name)));
+        candidates.extend(crate::plugins::builtins().filter(|b| b.name.starts_with(prefix)).map(|b| builtin_pair(&b.name)));
+        if let Ok(view) = self.shell.read() {
+            candidates.extend(view.aliases.iter().filter(|(name, _)| name.starts_with(prefix)).map(|(name, value)| Pair {
+                display: format!("{}  {} {}", alias_color(name).bold(), "alias".reversed().dimmed(), format!("→ {}", value).dimmed()),
+                replacement: name.clone(),
+            }));
+        }
+        candidates.extend(self.find_commands_in_path(prefix));
+        if candidates.is_empty() {
+            return None;
+        }
+
+        // If there's only one candidate and it's an exact match, return just that
+        // This helps with fish-like behavior where unique matches complete fully
+        if candidates.len() == 1 && candidates[0].replacement == prefix {
+            // Already exact match, return empty to indicate completion
+            return Some((word_start + prefix.len(), Vec::new()));
+        }
+
+        // If there's only one candidate after filtering exact matches, return just that
+        // This makes it complete fully on next TAB
+        let non_exact: Vec<_> = candidates.iter().filter(|c| c.replacement != prefix).collect();
+        if non_exact.len() == 1 {
+            return Some((word_start, vec![non_exact[0].clone()]));
+        }
+        Some((word_start, candidates))
+    }
+
+    fn find_commands_in_path(&self, prefix: &str) -> Vec<Pair> {
         let all_commands = get_all_commands();
         let prefix_lower = prefix.to_lowercase();
         let mut exact_matches = Vec::new();
         let mut prefix_matches = Vec::new();
         let mut case_insensitive_matches = Vec::new();
         
-        for cmd in all_commands {
+        for cmd in all_commands.with_prefix(prefix) {
             if cmd == prefix {
                 // Exact match - highest priority
                 exact_matches.push(Pair {
-                    display: format!("{}", cmd.truecolor(200, 150, 255).bold()),
-                    replacement: cmd,
+                    display: format!("{}", cmd.paint(Role::Command).bold()),
+                    replacement: cmd.clone(),
                 });
-            } else if cmd.starts_with(prefix) {
+            } else {
                 // Case-sensitive prefix match
                 prefix_matches.push(Pair {
-                    display: format!("{}", cmd.truecolor(180, 150, 255).bold()),
-                    replacement: cmd,
-                });
-            } else if cmd.to_lowercase().starts_with(&prefix_lower) {
-                // Case-insensitive prefix match - lower priority
-                case_insensitive_matches.push(Pair {
-                    display: format!("{}", cmd.truecolor(160, 140, 240)),
-                    replacement: cmd,
+                    display: format!("{}", cmd.paint(Role::Builtin).bold()),
+                    replacement: cmd.clone(),
                 });
             }
         }
+
+        // Case-insensitive prefix matches - lower priority, kept in name order
+        if !self.case.folds(prefix) {
+            return exact_matches.into_iter().chain(prefix_matches).collect();
+        }
+        let mut folded: Vec<usize> = all_commands
+            .with_prefix_ignore_case(&prefix_lower)
+            .filter(|&i| !all_commands.names[i].starts_with(prefix))
+            .collect();
+        folded.sort_unstable();
+        for i in folded {
+            let cmd = &all_commands.names[i];
+            case_insensitive_matches.push(Pair {
+                display: format!("{}", cmd.paint(Role::External)),
+                replacement: cmd.clone(),
+            });
+        }
         
         // Combine: exact matches first, then case-sensitive prefix, then case-insensitive
         let mut result = exact_matches;
@@ -128,6 +382,52 @@ impl LineHelper {
         result
     }
 
+    /// rustyline's filename completer matches case-sensitively; when
+    /// `completion.case` ignores case for this prefix, names that match only
+    /// that way are listed after its own.
+    fn complete_files(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, mut pairs) = self.filename.complete(line, pos, ctx)?;
+        let quote = match line[..start].chars().last() {
+            Some('"') => Quote::Double,
+            Some('\'') => Quote::Single,
+            _ => Quote::None,
+        };
+        let typed = match quote {
+            Quote::Single => line[start..pos].to_string(),
+            _ => unescape(&line[start..pos], Some('\\')).into_owned(),
+        };
+        let (dir_part, name_prefix) = typed.split_at(typed.rfind('/').map_or(0, |i| i + 1));
+        if name_prefix.is_empty() || !self.case.folds(name_prefix) {
+            return Ok((start, pairs));
+        }
+        let dir = match (dir_part.strip_prefix('~'), env::var("HOME")) {
+            (Some(rest), Ok(home)) => format!("{}{}", home, rest),
+            _ if dir_part.is_empty() => String::from("."),
+            _ => dir_part.to_string(),
+        };
+        let break_chars = break_chars(quote);
+        let prefix_lower = name_prefix.to_lowercase();
+        let mut folded: Vec<Pair> = fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                if name.starts_with(name_prefix) || !name.to_lowercase().starts_with(&prefix_lower) {
+                    return None;
+                }
+                let mut path = format!("{}{}", dir_part, name);
+                if entry.path().is_dir() {
+                    path.push('/');
+                }
+                Some(Pair { display: name, replacement: escape(path, Some('\\'), break_chars, quote) })
+            })
+            .collect();
+        folded.sort_by(|a, b| a.display.cmp(&b.display));
+        pairs.extend(folded);
+        Ok((start, pairs))
+    }
+
     fn is_command_position(line: &str, pos: usize) -> bool {
         let before_cursor = &line[..pos];
         let trimmed = before_cursor.trim();
@@ -142,79 +442,13 @@ impl LineHelper {
     }
 }
 
-fn is_executable(path: &Path) -> bool {
-    if let Ok(metadata) = fs::metadata(path) {
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let perms = metadata.permissions();
-            return perms.mode() & 0o111 != 0;
-        }
-        #[cfg(not(unix))]
-        {
-            // On non-Unix, check file extension
-            if let Some(ext) = path.extension() {
-                return matches!(ext.to_str(), Some("exe") | Some("bat") | Some("cmd"));
-            }
-        }
-    }
-    false
-}
-
 impl Helper for LineHelper {}
 
-impl Validator for LineHelper {
-    fn validate(&self, ctx: &mut rustyline::validate::ValidationContext) -> rustyline::Result<rustyline::validate::ValidationResult> {
-        use rustyline::validate::ValidationResult;
-        use rustyline::validate::MatchingBracketValidator;
-        
-        let line = ctx.input();
-        
-        // Check for incomplete commands (unclosed quotes, pipes, etc.)
-        if Self::is_incomplete_command(line) {
-            return Ok(ValidationResult::Incomplete);
-        }
-        
-        // Use default bracket matching
-        let bracket_validator = MatchingBracketValidator::new();
-        bracket_validator.validate(ctx)
-    }
-}
-
-impl LineHelper {
-    pub fn is_incomplete_command(line: &str) -> bool {
-        let mut in_single_quote = false;
-        let mut in_double_quote = false;
-        let mut backslash = false;
-        
-        for ch in line.chars() {
-            if backslash {
-                backslash = false;
-                continue;
-            }
-            
-            match ch {
-                '\\' => backslash = true,
-                '\'' if !in_double_quote => in_single_quote = !in_single_quote,
-                '"' if !in_single_quote => in_double_quote = !in_double_quote,
-                _ => {}
-            }
-        }
-        
-        // Check for trailing backslash (line continuation)
-        if line.trim_end().ends_with('\\') {
-            return true;
-        }
-        
-        // Check for trailing pipe
-        if line.trim_end().ends_with('|') {
-            return true;
-        }
-        
-        // Check for unclosed quotes
-        in_single_quote || in_double_quote
-    }
-}
+// Every line is accepted as typed, even an incomplete one (unclosed quote,
+// trailing pipe, open parenthesis): the REPL reads the rest line by line
+// behind a prompt that says what is open, and a line alone can't be checked
+// for brackets closed on an earlier one.
+impl Validator for LineHelper {}
 
 impl Highlighter for LineHelper {
     fn highlight_hint<'h>(&self, hint: &'h str) -> std::borrow::Cow<'h, str> {
@@ -232,13 +466,13 @@ impl Highlighter for LineHelper {
         }
         
         // Syntax highlighting for commands (only when cursor is at end)
-        let highlighted = Self::highlight_syntax(line);
+        let highlighted = self.highlight_syntax(line);
         Cow::Owned(highlighted)
     }
 }
 
 impl LineHelper {
-    fn highlight_syntax(line: &str) -> String {
+    fn highlight_syntax(&self, line: &str) -> String {
         use colored::Colorize;
         
         let mut result = String::new();
@@ -252,14 +486,14 @@ impl LineHelper {
                 '\'' if !in_double_quote => {
                     if in_single_quote {
                         // End of single-quoted string
-                        result.push_str(&current_word.truecolor(200, 150, 255).to_string());
+                        result.push_str(&current_word.paint(Role::Command).to_string());
                         result.push('\'');
                         current_word.clear();
                         in_single_quote = false;
                     } else {
                         // Start of single-quoted string
                         if !current_word.is_empty() {
-                            result.push_str(&Self::highlight_word(&current_word));
+                            result.push_str(&self.highlight_word(&current_word));
                             current_word.clear();
                         }
                         result.push('\'');
@@ -269,23 +503,24 @@ impl LineHelper {
                 '"' if !in_single_quote => {
                     if in_double_quote {
                         // End of double-quoted string
-                        result.push_str(&current_word.truecolor(200, 150, 255).to_string());
+                        result.push_str(&current_word.paint(Role::Command).to_string());
                         result.push('"');
                         current_word.clear();
                         in_double_quote = false;
                     } else {
                         // Start of double-quoted string
                         if !current_word.is_empty() {
-                            result.push_str(&Self::highlight_word(&current_word));
+                            result.push_str(&self.highlight_word(&current_word));
                             current_word.clear();
                         }
                         result.push('"');
                         in_double_quote = true;
                     }
                 }
-                ' ' | '\t' if !in_single_quote && !in_double_quote => {
+                // A pasted block has several lines; each starts a command
+                ' ' | '\t' | '\n' if !in_single_quote && !in_double_quote => {
                     if !current_word.is_empty() {
-                        result.push_str(&Self::highlight_word(&current_word));
+                        result.push_str(&self.highlight_word(&current_word));
                         current_word.clear();
                     }
                     result.push(ch);
@@ -295,11 +530,11 @@ impl LineHelper {
                 }
                 '|' | '&' | ';' | '<' | '>' if !in_single_quote && !in_double_quote => {
                     if !current_word.is_empty() {
-                        result.push_str(&Self::highlight_word(&current_word));
+                        result.push_str(&self.highlight_word(&current_word));
                         current_word.clear();
                     }
                     // Highlight operators
-                    result.push_str(&ch.to_string().truecolor(255, 200, 150).bold().to_string());
+                    result.push_str(&ch.to_string().paint(Role::Operator).bold().to_string());
                 }
                 _ => {
                     current_word.push(ch);
@@ -310,45 +545,47 @@ impl LineHelper {
         // Handle remaining word
         if !current_word.is_empty() {
             if in_single_quote || in_double_quote {
-                result.push_str(&current_word.truecolor(200, 150, 255).to_string());
+                result.push_str(&current_word.paint(Role::Command).to_string());
             } else {
-                result.push_str(&Self::highlight_word(&current_word));
+                result.push_str(&self.highlight_word(&current_word));
             }
         }
         
         result
     }
     
-    fn highlight_word(word: &str) -> String {
+    fn highlight_word(&self, word: &str) -> String {
         use colored::Colorize;
         
         // Check if it's a path
         if word.contains('/') || word.starts_with('~') || word.starts_with('.') {
-            return word.truecolor(140, 180, 255).to_string();
+            return word.paint(Role::Path).to_string();
         }
         
         // Check if it's a builtin
-        let builtins = ["cd", "ll", "freqs", "help", "export", "unset", "jobs", "fg", "bg", "exit", "alias", "unalias"];
-        if builtins.contains(&word) {
-            return word.truecolor(200, 150, 255).bold().to_string();
+        if builtins::is_builtin(word) || crate::plugins::is_builtin(word) {
+            return word.paint(Role::Command).bold().to_string();
+        }
+        if self.has_alias(word) {
+            return alias_color(word).bold().to_string();
         }
         
         // Check if it's a variable
         if word.starts_with('$') {
-            return word.truecolor(255, 220, 150).to_string();
+            return word.paint(Role::Variable).to_string();
         }
         
         // Check if it's a number
         if word.parse::<f64>().is_ok() {
-            return word.truecolor(150, 255, 180).to_string();
+            return word.paint(Role::String).to_string();
         }
         
         // Default: check if it looks like a command (first word)
         if word.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
             // Could be a command - check if it's in PATH
             let all_commands = get_all_commands();
-            if all_commands.contains(&word.to_string()) {
-                return word.truecolor(180, 150, 255).bold().to_string();
+            if all_commands.contains(word) {
+                return word.paint(Role::Builtin).bold().to_string();
             }
         }
         
@@ -360,6 +597,9 @@ impl Hinter for LineHelper {
     type Hint = String;
 
     fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<Self::Hint> {
+        if let Some(idle) = &self.idle {
+            idle.touch(line.is_empty());
+        }
         if pos != line.len() {
             return None;
         }
@@ -401,6 +641,15 @@ impl Hinter for LineHelper {
             }
         }
         
+        // Ranked suggestions from the command log; plain history until it has entries
+        if let Some(log) = &self.command_log
+            && let Ok(log) = log.read()
+            && !log.is_empty()
+        {
+            let cwd = env::current_dir().map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+            return log.suggest(line, &cwd).map(|entry| entry[line.len()..].to_string());
+        }
+
         // History-based suggestions
         let history = ctx.history();
         // Search most recent first for an entry that starts with the current line
@@ -473,10 +722,9 @@ impl LineHelper {
     }
 }
 
-impl Completer for LineHelper {
-    type Candidate = Pair;
-
-    fn complete(
+impl LineHelper {
+    /// Every candidate for the word before `pos`, before limiting.
+    fn all_candidates(
         &self,
         line: &str,
         pos: usize,
@@ -487,143 +735,424 @@ impl Completer for LineHelper {
             return Ok((pos, Vec::new()));
         }
 
-        // Path-only completion for `cd` arguments
-        if is_cd_context(line, pos) {
-            // If the argument after `cd` is empty, don't suggest anything
-            let trimmed_start = line.trim_start();
-            let after_cd = &trimmed_start[2..];
-            if after_cd.trim().is_empty() {
-                return Ok((pos, Vec::new()));
-            }
-            if let Some((start, pairs)) = complete_cd_only_dirs(line, pos) {
-                return Ok((start, pairs));
+        // Arguments of a builtin complete to whatever the registry says
+        let before = line[..pos].trim_start();
+        if let Some((first, rest)) = before.split_once(char::is_whitespace)
+            && let Some(builtin) = builtins::lookup(first)
+        {
+            let prefix = rest.trim_start();
+            let start = pos - prefix.len();
+            match builtin.args {
+                ArgCompletion::Directories => {
+                    // If the argument is still empty, don't suggest anything
+                    if prefix.is_empty() {
+                        return Ok((pos, Vec::new()));
+                    }
+                    if let Some((start, pairs)) = complete_dirs_only(line, pos, first == "cd", self.case) {
+                        return Ok((start, pairs));
+                    }
+                }
+                ArgCompletion::ConfigOptions => {
+                    let pairs = crate::shell_config::OPTIONS
+                        .iter()
+                        .filter(|o| o.key.starts_with(prefix))
+                        .map(|o| Pair { display: format!("{}  {}", o.key.paint(Role::Command), o.doc.dimmed()), replacement: o.key.to_string() })
+                        .collect();
+                    return Ok((start, pairs));
+                }
+                ArgCompletion::Themes => {
+                    let pairs = crate::theme::available()
+                        .into_iter()
+                        .filter(|name| name.starts_with(prefix))
+                        .map(|name| Pair { display: name.paint(Role::Command).to_string(), replacement: name })
+                        .collect();
+                    return Ok((start, pairs));
+                }
+                ArgCompletion::Bookmarks => {
+                    let pairs = crate::bookmarks::load_bookmarks()
+                        .into_keys()
+                        .filter(|name| name.starts_with(prefix))
+                        .map(|name| Pair { display: name.paint(Role::Command).to_string(), replacement: name })
+                        .collect();
+                    return Ok((start, pairs));
+                }
+                ArgCompletion::Builtins => {
+                    let prefix = prefix.rsplit(char::is_whitespace).next().unwrap_or_default();
+                    let pairs = builtins::BUILTINS
+                        .iter()
+                        .filter(|b| b.name.starts_with(prefix))
+                        .map(|b| Pair { display: format!("{}  {}", b.name.paint(Role::Command), b.summary().dimmed()), replacement: b.name.to_string() })
+                        .collect();
+                    return Ok((pos - prefix.len(), pairs));
+                }
+                ArgCompletion::Aliases => {
+                    let pairs = self.shell.read().map(|view| {
+                        view.aliases
+                            .iter()
+                            .filter(|(name, _)| name.starts_with(prefix))
+                            .map(|(name, value)| Pair { display: format!("{}  {}", alias_color(name), value.dimmed()), replacement: name.clone() })
+                            .collect()
+                    });
+                    return Ok((start, pairs.unwrap_or_default()));
+                }
+                ArgCompletion::Assignments => {
+                    let word = prefix.rsplit(char::is_whitespace).next().unwrap_or_default();
+                    let Some((_, value)) = word.split_once('=') else {
+                        let pairs = complete_variables(word)
+                            .into_iter()
+                            .map(|pair| Pair { replacement: format!("{}=", pair.replacement), ..pair })
+                            .collect();
+                        return Ok((pos - word.len(), pairs));
+                    };
+                    // Only values that look like paths; `=` ends the word for the filename completer
+                    if value.starts_with(['/', '~', '.']) || value.contains('/') {
+                        return self.complete_files(line, pos, ctx);
+                    }
+                    return Ok((pos, Vec::new()));
+                }
+                ArgCompletion::Variables => {
+                    let prefix = prefix.rsplit(char::is_whitespace).next().unwrap_or_default();
+                    return Ok((pos - prefix.len(), complete_variables(prefix)));
+                }
+                ArgCompletion::Jobs => {
+                    let prefix = prefix.rsplit(char::is_whitespace).next().unwrap_or_default();
+                    let pairs = self.shell.read().map(|view| {
+                        view.jobs
+                            .iter()
+                            .map(|(spec, command)| (spec.clone(), command))
+                            .filter(|(spec, _)| spec.starts_with(prefix) || prefix.is_empty())
+                            .map(|(spec, command)| Pair { display: format!("{}  {}", spec.paint(Role::Command), command.dimmed()), replacement: spec })
+                            .collect()
+                    });
+                    return Ok((pos - prefix.len(), pairs.unwrap_or_default()));
+                }
+                ArgCompletion::ProjectRoots => {
+                    if let Some((start, pairs)) = complete_croot(prefix, pos, &self.root_markers) {
+                        return Ok((start, pairs));
+                    }
+                }
+                ArgCompletion::Commands => {
+                    // The wrapped command's name; its own arguments are files
+                    if !prefix.contains(char::is_whitespace)
+                        && let Some(result) = self.complete_command(prefix, start)
+                    {
+                        return Ok(result);
+                    }
+                }
+                ArgCompletion::Files => {}
             }
         }
 
+        // A configured completer (`completion.command.<name>=`) knows best
+        if let Some(command) = before.split_whitespace().next()
+            && before.contains(char::is_whitespace)
+            && let Some((_, program)) = self.completers.iter().find(|(name, _)| name == command)
+            && let Some((start, candidates)) = crate::completers::complete(program, line, pos)
+        {
+            let pairs = candidates
+                .into_iter()
+                .map(|c| Pair {
+                    display: if c.description.is_empty() { c.value.clone() } else { format!("{}  {}", c.value, c.description.dimmed()) },
+                    replacement: c.value,
+                })
+                .collect();
+            return Ok((start, pairs));
+        }
+
+        // Commands configured to take directories (`completion.dirs_only=`)
+        if self.takes_dirs(before)
+            && let Some((start, pairs)) = complete_dirs_only(line, pos, false, self.case)
+        {
+            return Ok((start, pairs));
+        }
+
+        if let Some((start, pairs)) = complete_man(line, pos) {
+            return Ok((start, pairs));
+        }
+
+        if let Some((start, pairs)) = complete_build_targets(line, pos) {
+            return Ok((start, pairs));
+        }
+
+        if let Some((start, pairs)) = complete_from_plugin(line, pos) {
+            return Ok((start, pairs));
+        }
+
+        // `$NAME` anywhere completes environment variables
+        let word = before.rsplit(|c: char| c.is_whitespace() || c == '"').next().unwrap_or_default();
+        if let Some(name) = word.strip_prefix('$')
+            && !name.contains(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        {
+            let pairs = complete_variables(name)
+                .into_iter()
+                .map(|pair| Pair { replacement: format!("${}", pair.replacement), ..pair })
+                .collect();
+            return Ok((pos - word.len(), pairs));
+        }
+
         // If cursor is past the first token (i.e., there's a space before the cursor
         // after leading whitespace), use filename completion for arguments universally
-        let before = &line[..pos];
-        let leading_trim = before.trim_start();
-        let _leading_ws = before.len() - leading_trim.len();
-        let in_args = leading_trim.find(char::is_whitespace).is_some();
+        let in_args = before.find(char::is_whitespace).is_some();
         if in_args {
-            return self.filename.complete(line, pos, ctx);
+            let (start, pairs) = self.complete_files(line, pos, ctx)?;
+            self.maybe_preview(&pairs);
+            return Ok((start, pairs));
         }
 
         if Self::is_command_position(line, pos) {
-            // Try command completion first
             let before_cursor = &line[..pos];
             let word_start = before_cursor
                 .rfind(|c: char| c.is_whitespace() || c == '|' || c == '&' || c == ';')
                 .map(|i| i + 1)
                 .unwrap_or(0);
-            
-            let prefix = &line[word_start..pos];
-            
-            // Builtins first (highest priority)
-            let builtins = ["cd", "ll", "freqs", "help", "export", "unset", "jobs", "fg", "bg", "exit", "time"];
-            let mut builtin_matches = Vec::new();
-            let mut exact_builtin = None;
-            
-            for builtin in builtins {
-                if builtin == prefix {
-                    // Exact builtin match - highest priority
-                    exact_builtin = Some(Pair {
-                        display: format!("{}", builtin.truecolor(200, 150, 255).bold()),
-                        replacement: builtin.to_string(),
-                    });
-                } else if builtin.starts_with(prefix) {
-                    builtin_matches.push(Pair {
-                        display: format!("{}", builtin.truecolor(200, 150, 255).bold()),
-                        replacement: builtin.to_string(),
-                    });
-                }
-            }
-            
-            // Get commands from PATH
-            let path_commands = Self::find_commands_in_path(prefix);
-            
-            // Combine: exact builtin first, then other builtins, then PATH commands
-            let mut candidates = Vec::new();
-            if let Some(exact) = exact_builtin {
-                candidates.push(exact);
-            }
-            candidates.extend(builtin_matches);
-            candidates.extend(path_commands);
-            
-            if !candidates.is_empty() {
-                // If there's only one candidate and it's an exact match, return just that
-                // This helps with fish-like behavior where unique matches complete fully
-                if candidates.len() == 1 && candidates[0].replacement == prefix {
-                    // Already exact match, return empty to indicate completion
-                    return Ok((pos, Vec::new()));
-                }
-                
-                // If there's only one candidate after filtering exact matches, return just that
-                // This makes it complete fully on next TAB
-                let non_exact: Vec<_> = candidates.iter()
-                    .filter(|c| c.replacement != prefix)
-                    .collect();
-                if non_exact.len() == 1 {
-                    // Only one unique completion - return just that one so it completes fully
-                    return Ok((word_start, vec![non_exact[0].clone()]));
-                }
-                
-                return Ok((word_start, candidates));
+            if let Some(result) = self.complete_command(&line[word_start..pos], word_start) {
+                return Ok(result);
             }
         }
         
         // Fall back to filename completion for paths
-        self.filename.complete(line, pos, ctx)
+        self.complete_files(line, pos, ctx)
     }
 }
 
-fn is_cd_context(line: &str, _pos: usize) -> bool {
-    let trimmed_start = line.trim_start();
-    // ensure first token is exactly "cd"
-    if !trimmed_start.starts_with("cd") {
-        return false;
+impl Completer for LineHelper {
+    type Candidate = Pair;
+
+    /// Candidates with duplicates dropped (a builtin that is also on PATH,
+    /// an alias shadowing a command), the first source winning, and cut to
+    /// `completion.max_candidates` with a line saying how many were left
+    /// out. That line's replacement is the typed word, so the common prefix
+    /// of a cut list never extends the word past what all matches share.
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, mut pairs) = self.all_candidates(line, pos, ctx)?;
+        let mut seen = HashSet::new();
+        pairs.retain(|pair| seen.insert(pair.replacement.clone()));
+        if self.max_candidates > 0 && pairs.len() > self.max_candidates {
+            let hidden = pairs.len() - self.max_candidates;
+            pairs.truncate(self.max_candidates);
+            pairs.push(Pair {
+                display: format!("… and {} more (type more to narrow)", hidden).dimmed().to_string(),
+                replacement: line[start..pos].to_string(),
+            });
+        }
+        Ok((start, pairs))
     }
-    // after cd there must be space or end
-    let after_cd = &trimmed_start[2..];
-    if !after_cd.is_empty() && !after_cd.starts_with(char::is_whitespace) {
-        return false;
+}
+
+/// Aliases get their own color, so it's clear the word isn't run as typed.
+fn alias_color(name: &str) -> colored::ColoredString {
+    name.paint(Role::Alias)
+}
+
+/// Environment variable names starting with `prefix`, with their values.
+fn complete_variables(prefix: &str) -> Vec<Pair> {
+    let mut vars: Vec<(String, String)> = env::vars().filter(|(name, _)| name.starts_with(prefix)).collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(name, value)| {
+            let value: String = value.chars().take(60).collect();
+            Pair { display: format!("{}  {}", name.paint(Role::Variable), value.dimmed()), replacement: name }
+        })
+        .collect()
+}
+
+/// `croot <name>` completes the names of project roots seen in dirfreq.
+fn complete_croot(prefix: &str, pos: usize, markers: &[String]) -> Option<(usize, Vec<Pair>)> {
+    if prefix.contains(char::is_whitespace) {
+        return None;
     }
-    // cursor is in or after the argument area
-    true
+    let mut seen = HashSet::new();
+    let pairs: Vec<Pair> = crate::project::known_roots(prefix, markers)
+        .into_iter()
+        .filter_map(|root| {
+            let name = root.file_name()?.to_str()?.to_string();
+            seen.insert(name.clone()).then(|| Pair {
+                display: format!("{}  {}", name.paint(Role::Path), root.display().to_string().dimmed()),
+                replacement: name,
+            })
+        })
+        .collect();
+    Some((pos - prefix.len(), pairs))
 }
 
-fn complete_cd_only_dirs(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
-    // Find the position after "cd " - this is where the path argument starts
-    let cd_pos = line.find("cd")?;
-    let after_cd = &line[cd_pos + 2..];
-    let after_cd_trimmed = after_cd.trim_start();
-    let word_start = cd_pos + 2 + (after_cd.len() - after_cd_trimmed.len());
-    
-    // Get the path argument from word_start to cursor position
-    let token_text = &line[word_start..pos];
-    let arg_at_cursor = token_text.trim();
-    let raw_prefix = if after_cd_trimmed.is_empty() { "" } else { arg_at_cursor };
+/// `man [section] <page>`: pages from the man index, shown with their
+/// sections; a section argument limits the pages offered.
+fn complete_man(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+    let rest = line[..pos].trim_start().strip_prefix("man ")?;
+    let prefix = rest.rsplit(char::is_whitespace).next().unwrap_or_default();
+    let is_section = |w: &str| w.starts_with(|c: char| c.is_ascii_digit()) || w == "n" || w == "l";
+    let section = rest[..rest.len() - prefix.len()].split_whitespace().rev().find(|w| is_section(w));
+    let index = crate::manpages::index();
 
-    // Determine base directory and the last component prefix
-    let (base_dir, base_prefix) = resolve_cd_base_and_prefix(raw_prefix)?;
+    let mut pairs = Vec::new();
+    if section.is_none() && !prefix.is_empty() && is_section(prefix) {
+        for sect in index.sections().into_iter().filter(|s| s.starts_with(prefix)) {
+            pairs.push(Pair { display: format!("{}  {}", sect.paint(Role::String), "section".dimmed()), replacement: sect.to_string() });
+        }
+    }
+    // Group sections per name: printf (1, 3, 3p)
+    let mut grouped: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (name, sect) in index.with_prefix(prefix, section) {
+        match grouped.last_mut() {
+            Some((last, sects)) if *last == name.as_str() => sects.push(sect),
+            _ => grouped.push((name, vec![sect.as_str()])),
+        }
+    }
+    pairs.extend(grouped.into_iter().map(|(name, sects)| Pair {
+        display: format!("{} {}", name.paint(Role::Path), format!("({})", sects.join(", ")).dimmed()),
+        replacement: name.to_string(),
+    }));
+    Some((pos - prefix.len(), pairs))
+}
 
+/// `make`/`just` targets and cargo subcommands, `--bin`, `--example` and
+/// `--features` values, from the build files around the current directory.
+/// Anything else (flags, paths) is left to filename completion.
+fn complete_build_targets(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+    let before = line[..pos].trim_start();
+    let mut words: Vec<&str> = before.split_whitespace().collect();
+    let prefix = if before.ends_with(char::is_whitespace) { "" } else { words.pop().unwrap_or_default() };
+    let (&command, args) = words.split_first()?;
+    if prefix.starts_with('-') {
+        return None;
+    }
+    let cwd = env::current_dir().ok()?;
+    let pair = |name: String, role: Role, note: &str| Pair {
+        display: format!("{}  {}", name.paint(role), note.dimmed()),
+        replacement: name,
+    };
+
+    let (start, pairs): (usize, Vec<Pair>) = match command {
+        "make" => (pos - prefix.len(), crate::targets::make_targets(&cwd).into_iter().map(|t| pair(t, Role::Command, "target")).collect()),
+        "just" => (pos - prefix.len(), crate::targets::just_recipes(&cwd).into_iter().map(|r| pair(r, Role::Command, "recipe")).collect()),
+        "cargo" if args.is_empty() => {
+            (pos - prefix.len(), crate::targets::cargo_subcommands().into_iter().map(|c| pair(c, Role::Command, "subcommand")).collect())
+        }
+        "cargo" => {
+            let targets = crate::targets::cargo_targets(&cwd);
+            match args.last().copied() {
+                Some("--bin") => (pos - prefix.len(), targets.bins.into_iter().map(|b| pair(b, Role::Path, "bin")).collect()),
+                Some("--example") => (pos - prefix.len(), targets.examples.into_iter().map(|e| pair(e, Role::Path, "example")).collect()),
+                // A comma-separated list: complete the last feature
+                Some("--features" | "-F") => {
+                    let feature = prefix.rsplit(',').next().unwrap_or_default();
+                    (pos - feature.len(), targets.features.into_iter().map(|f| pair(f, Role::Variable, "feature")).collect())
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let prefix = &line[start..pos];
+    let pairs: Vec<Pair> = pairs.into_iter().filter(|p| p.replacement.starts_with(prefix)).collect();
+    // No build file, or nothing matching: fall back to filenames
+    if pairs.is_empty() { None } else { Some((start, pairs)) }
+}
+
+/// Arguments of a command a plugin completes (`complete` in its description).
+fn complete_from_plugin(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
+    let before = line[..pos].trim_start();
+    let mut words: Vec<&str> = before.split_whitespace().collect();
+    let prefix = if before.ends_with(char::is_whitespace) { "" } else { words.pop().unwrap_or_default() };
+    let (&command, args) = words.split_first()?;
+    let mut typed = args.to_vec();
+    typed.push(prefix);
+    let pairs: Vec<Pair> = crate::plugins::complete(command, &typed)?
+        .into_iter()
+        .filter(|(candidate, _)| candidate.starts_with(prefix))
+        .map(|(candidate, note)| Pair {
+            display: if note.is_empty() { candidate.clone() } else { format!("{}  {}", candidate, note.dimmed()) },
+            replacement: candidate,
+        })
+        .collect();
+    if pairs.is_empty() { None } else { Some((pos - prefix.len(), pairs)) }
+}
+
+/// The characters escaped in a completed name, as rustyline's filename
+/// completer does: inside double quotes only those still special there.
+fn break_chars(quote: Quote) -> fn(char) -> bool {
+    match quote {
+        Quote::Double => |c| matches!(c, '"' | '$' | '\\' | '`'),
+        _ => |c| matches!(c, ' ' | '\t' | '\n' | '"' | '\\' | '\'' | '`' | '@' | '$' | '>' | '<' | '=' | ';' | '|' | '&' | '{' | '(' | '\0'),
+    }
+}
+
+/// Where the word before `pos` starts, and the quote it is still inside:
+/// `cd My\ Di` and `cd "My Di` both complete `My Di`.
+fn word_start(line: &str, pos: usize) -> (usize, Quote) {
+    let mut start = 0;
+    let mut open: Option<(usize, char)> = None;
+    let mut escaped = false;
+    for (i, c) in line[..pos].char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (open, c) {
+            (Some((_, '\'')), '\'') | (Some((_, '"')), '"') => open = None,
+            (Some((_, '\'')), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => open = Some((i, c)),
+            (None, c) if c.is_whitespace() => start = i + 1,
+            _ => {}
+        }
+    }
+    match open {
+        Some((i, '\'')) => (i + 1, Quote::Single),
+        Some((i, _)) => (i + 1, Quote::Double),
+        None => (start, Quote::None),
+    }
+}
+
+/// Directories completing the argument before `pos`, most visited first.
+/// With `bookmarks` (cd), `@name` bookmarks are offered too.
+fn complete_dirs_only(line: &str, pos: usize, bookmarks: bool, case: CompletionCase) -> Option<(usize, Vec<Pair>)> {
+    let (word_start, quote) = word_start(line, pos);
+    let typed = match quote {
+        Quote::Single => line[word_start..pos].to_string(),
+        _ => unescape(&line[word_start..pos], Some('\\')).into_owned(),
+    };
+    let raw_prefix = typed.as_str();
+    if raw_prefix.starts_with('-') {
+        return None;
+    }
+
+    // Bookmarks: `cd @na<TAB>`, or offered next to directories for a bare name
     let mut scored: Vec<(u64, Pair)> = Vec::new();
+    if bookmarks && !raw_prefix.contains('/') {
+        let wanted = raw_prefix.trim_start_matches('@');
+        for (name, dir) in crate::bookmarks::load_bookmarks() {
+            if name.starts_with(wanted) {
+                let count = crate::dirfreq::get_count(Path::new(&dir));
+                let display = format!("{}  {}", format!("@{}", name).paint(Role::Command).bold(), dir.dimmed());
+                scored.push((count, Pair { display, replacement: format!("@{}/", name) }));
+            }
+        }
+    }
+    if bookmarks && raw_prefix.starts_with('@') {
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.replacement.cmp(&b.1.replacement)));
+        return Some((word_start, scored.into_iter().map(|(_, p)| p).collect()));
+    }
+
+    // Determine base directory and the last component prefix
+    let (base_dir, base_prefix) = resolve_dir_base_and_prefix(raw_prefix)?;
+
     if let Ok(entries) = fs::read_dir(&base_dir) {
         for entry in entries.flatten() {
             let path = entry.path();
             if path.is_dir() {
                 if let Some(name) = entry.file_name().to_str() {
-                    if name.starts_with(&base_prefix) {
+                    if case.matches(name, &base_prefix) {
                         // Append the missing part after the typed base_prefix
-                        let display = format!("{}/", name).truecolor(140, 180, 255).to_string();
+                        let display = format!("{}/", name).paint(Role::Path).to_string();
                         
                         // Build replacement: preserve what user typed up to the base_prefix, then add the directory name
-                        // If base_prefix is empty, we're completing in base_dir, so use token_text as-is (with trailing / if present)
+                        // If base_prefix is empty, we're completing in base_dir, so use raw_prefix as-is (with trailing / if present)
                         // If base_prefix is not empty, we need to replace the partial prefix with the full name
                         let replacement = if base_prefix.is_empty() {
                             // User typed a complete directory path, just append the new directory
-                            let mut result = token_text.to_string();
+                            let mut result = raw_prefix.to_string();
                             // Ensure there's a / separator if needed
                             if !result.is_empty() && !result.ends_with('/') {
                                 result.push('/');
@@ -633,13 +1162,14 @@ fn complete_cd_only_dirs(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
                             result
                         } else {
                             // User typed a partial directory name, replace it
-                            let keep_len = token_text.len().saturating_sub(base_prefix.len());
-                            let kept = &token_text[..keep_len];
+                            let keep_len = raw_prefix.len().saturating_sub(base_prefix.len());
+                            let kept = &raw_prefix[..keep_len];
                             let separator = if kept.is_empty() || kept.ends_with('/') { "" } else { "/" };
                             format!("{}{}{}{}", kept, separator, name, "/")
                         };
                         
                         let count = crate::dirfreq::get_count(&path);
+                        let replacement = escape(replacement, Some('\\'), break_chars(quote), quote);
                         scored.push((count, Pair { display, replacement }));
                     }
                 }
@@ -656,7 +1186,7 @@ fn complete_cd_only_dirs(line: &str, pos: usize) -> Option<(usize, Vec<Pair>)> {
     Some((word_start, pairs))
 }
 
-fn resolve_cd_base_and_prefix(input: &str) -> Option<(std::path::PathBuf, String)> {
+fn resolve_dir_base_and_prefix(input: &str) -> Option<(std::path::PathBuf, String)> {
     use std::path::PathBuf;
 
     if input.is_empty() {
@@ -733,3 +1263,21 @@ fn resolve_cd_base_and_prefix(input: &str) -> Option<(std::path::PathBuf, String
     Some((parent, base))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_start_skips_escaped_and_quoted_spaces() {
+        let start = |line: &str| {
+            let (start, quote) = word_start(line, line.len());
+            (start, matches!(quote, Quote::None), matches!(quote, Quote::Double))
+        };
+        assert_eq!(start("cd My\\ Di"), (3, true, false));
+        assert_eq!(start("cd \"My Di"), (4, false, true));
+        assert_eq!(start("cd 'My Di"), (4, false, false));
+        assert_eq!(start("cd a b"), (5, true, false));
+        assert_eq!(start("cd "), (3, true, false));
+    }
+}
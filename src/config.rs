@@ -43,7 +43,50 @@ pub fn config_file() -> Option<PathBuf> {
     if ensure_dir(&p).is_ok() { Some(p) } else { None }
 }
 
+pub fn corrections_file() -> Option<PathBuf> {
+    let mut p = config_dir()?;
+    p.push("corrections");
+    if ensure_dir(&p).is_ok() { Some(p) } else { None }
+}
 
+pub fn bookmarks_file() -> Option<PathBuf> {
+    let mut p = config_dir()?;
+    p.push("bookmarks");
+    if ensure_dir(&p).is_ok() { Some(p) } else { None }
+}
 
+/// Theme files selected with `theme=<name>`; not created until the user adds one.
+pub fn themes_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("themes"))
+}
 
+/// Plugin executables; not created until the user adds one.
+pub fn plugins_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("plugins"))
+}
 
+/// Every interactive command line with its time, exit status and directory,
+/// for ranking autosuggestions.
+pub fn command_log_file() -> Option<PathBuf> {
+    let mut p = config_dir()?;
+    p.push("command_log");
+    if ensure_dir(&p).is_ok() { Some(p) } else { None }
+}
+
+pub fn stats_file() -> Option<PathBuf> {
+    let mut p = config_dir()?;
+    p.push("stats");
+    if ensure_dir(&p).is_ok() { Some(p) } else { None }
+}
+
+/// Output of `nohup_jobs` and `capture_job_output` background jobs: `$XDG_STATE_HOME/squish/jobs`,
+/// else `~/.local/state/squish/jobs`.
+pub fn job_log_dir() -> Option<PathBuf> {
+    let mut p = match std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("state"),
+    };
+    p.push("squish");
+    p.push("jobs");
+    if std::fs::create_dir_all(&p).is_ok() { Some(p) } else { None }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use crate::config;
+
+fn store_path() -> Option<PathBuf> { config::corrections_file() }
+
+/// typo -> (correction, times accepted)
+pub fn load_corrections() -> HashMap<String, (String, u64)> {
+    let mut map = HashMap::new();
+    let Some(path) = store_path() else { return map; };
+    let file = match OpenOptions::new().read(true).open(&path) {
+        Ok(f) => f,
+        Err(_) => return map,
+    };
+    let reader = BufReader::new(file);
+    for line in reader.lines().map_while(Result::ok) {
+        let mut fields = line.splitn(3, '\t');
+        if let (Some(typo), Some(fixed), Some(count)) = (fields.next(), fields.next(), fields.next())
+            && let Ok(n) = count.parse::<u64>()
+        {
+            map.insert(typo.to_string(), (fixed.to_string(), n));
+        }
+    }
+    map
+}
+
+pub fn learned(typo: &str) -> Option<String> {
+    load_corrections().remove(typo).map(|(fixed, _)| fixed)
+}
+
+pub fn record_correction(typo: &str, fixed: &str) {
+    let mut map = load_corrections();
+    let entry = map.entry(typo.to_string()).or_insert_with(|| (fixed.to_string(), 0));
+    if entry.0 != fixed {
+        *entry = (fixed.to_string(), 0);
+    }
+    entry.1 = entry.1.saturating_add(1);
+    let _ = save_corrections(&map);
+}
+
+fn save_corrections(map: &HashMap<String, (String, u64)>) -> std::io::Result<()> {
+    if let Some(path) = store_path() {
+        let mut tmp = path.clone();
+        tmp.set_extension("tmp");
+        let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(&tmp)?;
+        for (typo, (fixed, n)) in map {
+            writeln!(f, "{}\t{}\t{}", typo, fixed, n)?;
+        }
+        f.flush()?;
+        fs::rename(tmp, path)?;
+    }
+    Ok(())
+}
@@ -1,67 +1,197 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Output;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
 use colored::Colorize;
 
 use crate::error::ShellError;
+use crate::spawn::{Io, Spawn};
+use crate::theme::{Paint, Role};
+use crate::timeout::Watchdog;
+use crate::errln;
+
+/// Longest a package lookup may hold up the next prompt.
+const LOOKUP_LIMIT: Duration = Duration::from_secs(2);
+
+static DNF_PROVIDES: OnceLock<bool> = OnceLock::new();
+
+/// Apply `dnf_provides=`. Only the first call wins.
+pub fn configure(dnf_provides: bool) {
+    let _ = DNF_PROVIDES.set(dnf_provides);
+}
 
 pub fn print_error(err: &ShellError) {
     match err {
         ShellError::CommandNotFound { program } => {
-            eprintln!("{} {}", "error:".truecolor(255, 120, 180).bold(), format!("command not found: {}", program).truecolor(255, 150, 200));
-            let suggestions = top_suggestions(program, 3);
-            if !suggestions.is_empty() {
-                let list = suggestions.join(", ");
-                eprintln!("{} {} {}", "help:".truecolor(180, 160, 255), "did you mean".truecolor(180, 160, 255), list.truecolor(200, 150, 255).bold());
-            }
-            if let Some(hint) = install_hint(program) {
-                eprintln!("{} {}", "help:".truecolor(180, 160, 255), hint.truecolor(180, 160, 255));
-            }
-            if let Some(path_note) = truncated_path_note() {
-                eprintln!("{} {}", "note:".bright_black(), path_note.bright_black());
-            }
+            print_command_not_found(program, std::slice::from_ref(program), &[]);
         }
         ShellError::ExecFailed { program, message } => {
-            eprintln!("{} {}", "error:".truecolor(255, 120, 180).bold(), format!("{}: {}", program, message).truecolor(255, 150, 200));
+            errln!("{} {}", "error:".paint(Role::Error).bold(), format!("{}: {}", program, message).paint(Role::ErrorText));
         }
         ShellError::Io(e) => {
-            eprintln!("{} {}", "error:".truecolor(255, 120, 180).bold(), e.to_string().truecolor(255, 150, 200));
+            errln!("{} {}", "error:".paint(Role::Error).bold(), e.to_string().paint(Role::ErrorText));
         }
         ShellError::LineEditor(e) => {
-            eprintln!("{} {}", "error:".truecolor(255, 120, 180).bold(), e.to_string().truecolor(255, 150, 200));
+            errln!("{} {}", "error:".paint(Role::Error).bold(), e.to_string().paint(Role::ErrorText));
         }
         ShellError::Other(msg) => {
-            eprintln!("{} {}", "error:".truecolor(255, 120, 180).bold(), msg.truecolor(255, 150, 200));
+            errln!("{} {}", "error:".paint(Role::Error).bold(), msg.paint(Role::ErrorText));
         }
+        ShellError::Exit(_) => {}
     }
 }
 
-fn top_suggestions(input: &str, max_n: usize) -> Vec<String> {
-    let mut candidates: Vec<String> = builtins()
-        .into_iter()
-        .map(|s| s.to_string())
-        .collect();
-    if let Ok(path_var) = std::env::var("PATH") {
-        for dir in path_var.split(':') {
-            if let Ok(entries) = std::fs::read_dir(dir) {
-                for entry in entries.flatten() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        candidates.push(name.to_string());
-                    }
+/// Not-found report that can also draw suggestions from previously run
+/// command lines, so `gti status` suggests `git status` rather than `git`.
+pub fn print_command_not_found(program: &str, argv: &[String], history: &[String]) {
+    errln!("{} {}", "error:".paint(Role::Error).bold(), format!("command not found: {}", program).paint(Role::ErrorText));
+    let suggestions = top_suggestions(program, &argv.join(" "), history, 3);
+    if !suggestions.is_empty() {
+        let list = suggestions.join(", ");
+        errln!("{} {} {}", "help:".paint(Role::Hint), "did you mean".paint(Role::Hint), list.paint(Role::Command).bold());
+    }
+    if let Some(hint) = install_hint(program) {
+        errln!("{} {}", "help:".paint(Role::Hint), hint.paint(Role::Hint));
+    }
+    if let Some(path_note) = truncated_path_note() {
+        errln!("{} {}", "note:".bright_black(), path_note.bright_black());
+    }
+}
+
+/// A single unambiguous fix for a mistyped command: a previously accepted
+/// correction, or the only candidate one edit away.
+pub fn correction_for(input: &str) -> Option<String> {
+    let candidates = command_candidates();
+    if let Some(fixed) = crate::corrections::learned(input)
+        && candidates.binary_search(&fixed).is_ok()
+    {
+        return Some(fixed);
+    }
+    let mut close = candidates.into_iter().filter(|c| edit_distance(input, c) == 1);
+    let first = close.next()?;
+    if close.next().is_some() { None } else { Some(first) }
+}
+
+/// cdspell: fix each component of a nonexistent directory path that has a
+/// single case-insensitive or one-transposition match among its siblings.
+/// Returns None unless every component resolves.
+pub fn correct_dir_path(target: &str) -> Option<String> {
+    let path = Path::new(target);
+    if path.is_dir() {
+        return None;
+    }
+    let mut fixed = if path.is_absolute() { std::path::PathBuf::from("/") } else { std::path::PathBuf::new() };
+    let mut changed = false;
+    for component in path.components() {
+        let name = match component {
+            std::path::Component::Normal(name) => name.to_str()?,
+            other => {
+                fixed.push(other.as_os_str());
+                continue;
+            }
+        };
+        let base = if fixed.as_os_str().is_empty() { Path::new(".") } else { fixed.as_path() };
+        if base.join(name).is_dir() {
+            fixed.push(name);
+            continue;
+        }
+        let siblings: Vec<String> = std::fs::read_dir(base)
+            .ok()?
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .collect();
+        let lower = name.to_lowercase();
+        let mut matches = siblings.iter().filter(|s| s.to_lowercase() == lower);
+        let found = match (matches.next(), matches.next()) {
+            (Some(only), None) => only,
+            (Some(_), Some(_)) => return None,
+            _ => {
+                let mut swapped = siblings.iter().filter(|s| is_transposition(&lower, &s.to_lowercase()));
+                match (swapped.next(), swapped.next()) {
+                    (Some(only), None) => only,
+                    _ => return None,
                 }
             }
+        };
+        fixed.push(found);
+        changed = true;
+    }
+    changed.then(|| fixed.to_string_lossy().to_string())
+}
+
+/// Same letters with exactly one pair of neighbours swapped.
+fn is_transposition(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len() != b.len() {
+        return false;
+    }
+    let diffs: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    matches!(diffs[..], [i, j] if j == i + 1 && a[i] == b[j] && a[j] == b[i])
+}
+
+/// How many recent history entries are scanned for suggestions.
+const HISTORY_SCAN: usize = 1000;
+
+fn top_suggestions(input: &str, line: &str, history: &[String], max_n: usize) -> Vec<String> {
+    let recent = &history[history.len().saturating_sub(HISTORY_SCAN)..];
+
+    // Whole command lines the user has run before, most recent first
+    let mut suggestions: Vec<String> = Vec::new();
+    if line != input {
+        let mut lines: Vec<(usize, &String)> = recent
+            .iter()
+            .rev()
+            .filter(|entry| entry.as_str() != line)
+            .map(|entry| (edit_distance(line, entry), entry))
+            .filter(|(d, _)| *d <= 2)
+            .collect();
+        lines.sort_by_key(|(d, _)| *d);
+        for (_, entry) in lines {
+            if !suggestions.contains(entry) {
+                suggestions.push(entry.clone());
+            }
         }
     }
-    candidates.sort();
-    candidates.dedup();
 
-    let mut scored: Vec<(usize, String)> = candidates
+    // Command names, preferring ones that appear often in history
+    let mut usage: HashMap<&str, usize> = HashMap::new();
+    for entry in recent {
+        if let Some(cmd) = entry.split_whitespace().next() {
+            *usage.entry(cmd).or_insert(0) += 1;
+        }
+    }
+    let mut scored: Vec<(usize, usize, String)> = command_candidates()
         .into_iter()
-        .map(|c| (edit_distance(input, &c), c))
-        .filter(|(d, _)| *d <= 2)
+        .map(|c| (edit_distance(input, &c), usage.get(c.as_str()).copied().unwrap_or(0), c))
+        .filter(|(d, _, _)| *d <= 2)
         .collect();
-    scored.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
-    scored.into_iter().take(max_n).map(|(_, s)| s).collect()
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    for (_, _, cmd) in scored {
+        if !suggestions.contains(&cmd) {
+            suggestions.push(cmd);
+        }
+    }
+
+    suggestions.truncate(max_n);
+    suggestions
 }
 
-fn builtins() -> &'static [&'static str] { &["cd", "ll", "freqs", "help", "export", "unset", "jobs", "fg", "bg", "exit"] }
+fn command_candidates() -> Vec<String> {
+    let mut candidates: Vec<String> = crate::builtins::BUILTINS
+        .iter()
+        .map(|b| b.name.to_string())
+        .collect();
+    candidates.extend(crate::lookup::commands().keys().cloned());
+    candidates.extend(crate::plugins::builtins().map(|b| b.name.clone()));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
 
 fn edit_distance(a: &str, b: &str) -> usize {
     let mut dp = vec![vec![0; b.len() + 1]; a.len() + 1];
@@ -77,22 +207,132 @@ fn edit_distance(a: &str, b: &str) -> usize {
 }
 
 fn install_hint(cmd: &str) -> Option<String> {
+    // Prefer a real binary -> package lookup when the distro provides one
+    if let Some((mgr, pkg)) = cached_package_lookup(cmd) {
+        return Some(format!("{} provides it, try: {}", pkg, install_command(&mgr, &pkg)?));
+    }
+
     // Detect common package managers
     let mgrs = detect_pkg_mgrs();
     if mgrs.is_empty() { return None; }
-    let cmd_str = cmd.to_string();
-    let mut hints = Vec::new();
-    for m in mgrs {
-        match m.as_str() {
-            "pacman" => hints.push(format!("try: sudo pacman -S {}", cmd_str)),
-            "apt" => hints.push(format!("try: sudo apt install {}", cmd_str)),
-            "dnf" => hints.push(format!("try: sudo dnf install {}", cmd_str)),
-            "zypper" => hints.push(format!("try: sudo zypper install {}", cmd_str)),
-            "brew" => hints.push(format!("try: brew install {}", cmd_str)),
-            _ => (),
+    let hints: Vec<String> = mgrs
+        .iter()
+        .filter_map(|m| install_command(m, cmd))
+        .map(|c| format!("try: {}", c))
+        .collect();
+    if hints.is_empty() { None } else { Some(hints.join("  |  ")) }
+}
+
+fn install_command(mgr: &str, pkg: &str) -> Option<String> {
+    match mgr {
+        "pacman" => Some(format!("sudo pacman -S {}", pkg)),
+        "apt" => Some(format!("sudo apt install {}", pkg)),
+        "dnf" => Some(format!("sudo dnf install {}", pkg)),
+        "zypper" => Some(format!("sudo zypper install {}", pkg)),
+        "brew" => Some(format!("brew install {}", pkg)),
+        _ => None,
+    }
+}
+
+/// (package manager, package name)
+type PackageMatch = (String, String);
+
+static PACKAGE_CACHE: OnceLock<Mutex<HashMap<String, Option<PackageMatch>>>> = OnceLock::new();
+
+/// Package lookups can take seconds (`dnf provides`, `pacman -F`), so remember
+/// the answer, including "not found", for the rest of the session. Each
+/// helper gets `LOOKUP_LIMIT`; one that runs out gives no hint.
+fn cached_package_lookup(cmd: &str) -> Option<PackageMatch> {
+    let cache = PACKAGE_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(hit) = cache.lock().ok()?.get(cmd) {
+        return hit.clone();
+    }
+    let result = lookup_package(cmd);
+    if let Ok(mut guard) = cache.lock() {
+        guard.insert(cmd.to_string(), result.clone());
+    }
+    result
+}
+
+fn lookup_package(cmd: &str) -> Option<PackageMatch> {
+    if cmd.is_empty() || crate::lookup::is_path(cmd) {
+        return None;
+    }
+
+    // Arch: pkgfile, then pacman's file database
+    if which::which("pkgfile").is_ok()
+        && let Some(pkg) = first_line_of("pkgfile", &["-b", cmd])
+    {
+        return Some(("pacman".to_string(), strip_repo(&pkg)));
+    }
+    if which::which("pacman").is_ok()
+        && let Some(pkg) = first_line_of("pacman", &["-Fq", &format!("/usr/bin/{}", cmd)])
+    {
+        return Some(("pacman".to_string(), strip_repo(&pkg)));
+    }
+
+    // Debian/Ubuntu: command-not-found prints "sudo apt install <pkg>" on stderr
+    let cnf = Path::new("/usr/lib/command-not-found");
+    if cnf.exists()
+        && let Some(out) = run_helper(cnf, &["--no-failure-msg", cmd])
+    {
+        let text = String::from_utf8_lossy(&out.stderr).to_string() + &String::from_utf8_lossy(&out.stdout);
+        for line in text.lines() {
+            if let Some((_, rest)) = line.split_once("apt install ")
+                && let Some(pkg) = rest.split_whitespace().next()
+            {
+                return Some(("apt".to_string(), pkg.to_string()));
+            }
         }
     }
-    if hints.is_empty() { None } else { Some(hints.join("  |  ")) }
+
+    // Fedora: dnf provides reports "name-[epoch:]version-release.arch : summary"
+    if DNF_PROVIDES.get().copied().unwrap_or(false)
+        && which::which("dnf").is_ok()
+        && let Some(line) = first_line_of("dnf", &["provides", "-q", &format!("/usr/bin/{}", cmd)])
+    {
+        let nevra = line.split_whitespace().next().unwrap_or_default();
+        if let Some(name) = nevra.rsplitn(3, '-').nth(2) {
+            return Some(("dnf".to_string(), name.to_string()));
+        }
+    }
+
+    None
+}
+
+/// Run a lookup helper with no input, stopping it after `LOOKUP_LIMIT`.
+/// None if it couldn't start or ran out of time.
+fn run_helper<S: AsRef<OsStr> + ?Sized>(program: &S, args: &[&str]) -> Option<Output> {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let child = Spawn::new(program, &args)
+        .stdin(Io::Null)
+        .stdout(Io::Piped)
+        .stderr(Io::Piped)
+        .new_process_group()
+        .spawn()
+        .ok()?;
+    let watchdog = Watchdog::start(child.id(), LOOKUP_LIMIT);
+    let output = child.wait_with_output();
+    if watchdog.finish().is_some() {
+        return None;
+    }
+    output.ok()
+}
+
+fn first_line_of(program: &str, args: &[&str]) -> Option<String> {
+    let out = run_helper(program, args)?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(str::to_string)
+}
+
+fn strip_repo(pkg: &str) -> String {
+    pkg.rsplit('/').next().unwrap_or(pkg).to_string()
 }
 
 fn detect_pkg_mgrs() -> Vec<String> {
@@ -106,9 +346,10 @@ fn detect_pkg_mgrs() -> Vec<String> {
 
 fn truncated_path_note() -> Option<String> {
     let path = std::env::var("PATH").ok()?;
-    let parts: Vec<&str> = path.split(':').collect();
+    let separator = if cfg!(windows) { ";" } else { ":" };
+    let parts: Vec<&str> = path.split(separator).collect();
     if parts.is_empty() { return None; }
-    let shown = parts.iter().take(3).cloned().collect::<Vec<_>>().join(":");
+    let shown = parts.iter().take(3).cloned().collect::<Vec<_>>().join(separator);
     let rest = parts.len().saturating_sub(3);
     if rest > 0 {
         Some(format!("searched PATH: {} (+{} more)", shown, rest))
@@ -2,10 +2,73 @@ use std::collections::HashMap;
 use std::fs::{self, OpenOptions};
 use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use crate::config;
 
+/// `dirfreq.scope=`: one ranking for everything, or one per git worktree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DirfreqScope {
+    #[default]
+    Global,
+    Repo,
+}
+
+static SCOPE: OnceLock<DirfreqScope> = OnceLock::new();
+
+/// Apply the configured scope. Only the first call wins.
+pub fn configure(scope: DirfreqScope) {
+    let _ = SCOPE.set(scope);
+}
+
 fn store_path() -> Option<PathBuf> { config::dirfreq_file() }
 
+/// Worktree the current directory belongs to when entries are repo-scoped.
+/// Scoped entries are stored as `<worktree>\t<path>\t<count>`.
+fn current_namespace() -> Option<String> {
+    if SCOPE.get().copied().unwrap_or_default() != DirfreqScope::Repo {
+        return None;
+    }
+    let cwd = std::env::current_dir().ok()?;
+    let root = crate::project::find_root(&cwd, &[String::from(".git")])?;
+    Some(root.to_string_lossy().to_string())
+}
+
+fn scoped_key(path: &Path) -> String {
+    let abs = match path.canonicalize() {
+        Ok(p) => p,
+        Err(_) => path.to_path_buf(),
+    };
+    let key = abs.to_string_lossy().to_string();
+    match current_namespace() {
+        Some(ns) => format!("{}\t{}", ns, key),
+        None => key,
+    }
+}
+
+/// Entries ranked in the current scope: this worktree's namespace when
+/// repo-scoped inside a checkout, otherwise the global entries.
+pub fn visible_freqs() -> HashMap<String, u64> {
+    let ns = current_namespace();
+    load_freqs()
+        .into_iter()
+        .filter_map(|(key, count)| match (key.split_once('\t'), &ns) {
+            (Some((entry_ns, path)), Some(ns)) if entry_ns == ns => Some((path.to_string(), count)),
+            (None, None) => Some((key, count)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Every recorded directory regardless of namespace, counts summed.
+pub fn all_dirs() -> HashMap<String, u64> {
+    let mut map = HashMap::new();
+    for (key, count) in load_freqs() {
+        let path = key.split_once('\t').map(|(_, p)| p.to_string()).unwrap_or(key);
+        *map.entry(path).or_insert(0) += count;
+    }
+    map
+}
+
 pub fn load_freqs() -> HashMap<String, u64> {
     let mut map = HashMap::new();
     let Some(path) = store_path() else { return map; };
@@ -25,11 +88,7 @@ pub fn load_freqs() -> HashMap<String, u64> {
 }
 
 pub fn increment_dir_usage(dir: &Path) {
-    let abs = match dir.canonicalize() {
-        Ok(p) => p,
-        Err(_) => dir.to_path_buf(),
-    };
-    let key = abs.to_string_lossy().to_string();
+    let key = scoped_key(dir);
     let mut map = load_freqs();
     let entry = map.entry(key).or_insert(0);
     *entry = entry.saturating_add(1);
@@ -37,11 +96,7 @@ pub fn increment_dir_usage(dir: &Path) {
 }
 
 pub fn get_count(path: &Path) -> u64 {
-    let abs = match path.canonicalize() {
-        Ok(p) => p,
-        Err(_) => path.to_path_buf(),
-    };
-    let key = abs.to_string_lossy().to_string();
+    let key = scoped_key(path);
     let map = load_freqs();
     map.get(&key).copied().unwrap_or(0)
 }
@@ -0,0 +1,442 @@
+//! Documentation pages for squish's builtins, shown by `help <name>` and
+//! searched by `help -k`.
+
+pub struct HelpPage {
+    pub name: &'static str,
+    /// Other names the same builtin answers to
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub description: &'static str,
+    /// (flag or subcommand, explanation)
+    pub options: &'static [(&'static str, &'static str)],
+    pub examples: &'static [&'static str],
+}
+
+impl HelpPage {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    /// Case-insensitive search over every part of the page.
+    pub fn mentions(&self, keyword: &str) -> bool {
+        let keyword = keyword.to_lowercase();
+        let hit = |text: &str| text.to_lowercase().contains(&keyword);
+        hit(self.name)
+            || self.aliases.iter().any(|a| hit(a))
+            || hit(self.summary)
+            || hit(self.description)
+            || self.options.iter().any(|(flag, text)| hit(flag) || hit(text))
+            || self.examples.iter().any(|e| hit(e))
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static HelpPage> {
+    PAGES.iter().find(|p| p.matches(name))
+}
+
+pub fn search(keyword: &str) -> Vec<&'static HelpPage> {
+    PAGES.iter().filter(|p| p.mentions(keyword)).collect()
+}
+
+pub const PAGES: &[HelpPage] = &[
+    HelpPage {
+        name: "alias",
+        aliases: &[],
+        usage: "alias [name='value']",
+        summary: "Create or list aliases",
+        description: "With no arguments, lists every alias. Aliases are expanded wherever a command name is expected and are saved for future sessions. A value may be a whole command list using `&&`, `|` or `;`.",
+        options: &[],
+        examples: &["alias ll='ls -la'", "alias up='sudo pacman -Syu && flatpak update'", "alias"],
+    },
+    HelpPage {
+        name: "unalias",
+        aliases: &[],
+        usage: "unalias <name>",
+        summary: "Remove an alias",
+        description: "Deletes the alias from this session and from the saved aliases.",
+        options: &[],
+        examples: &["unalias ll"],
+    },
+    HelpPage {
+        name: "cd",
+        aliases: &[],
+        usage: "cd [dir | - | @bookmark[/path]]",
+        summary: "Change directory",
+        description: "With no argument, goes home; `cd -` goes back to `$OLDPWD`, and `cd @name` goes to a bookmark. `$PWD` and `$OLDPWD` follow every change. Visited directories are counted for `freqs`; with `cdspell` on, small typos in the path are corrected.",
+        options: &[],
+        examples: &["cd ~/projects", "cd -", "cd @dots/nvim"],
+    },
+    HelpPage {
+        name: "bookmark",
+        aliases: &[],
+        usage: "bookmark [add <name> [dir] | list | rm <name>...]",
+        summary: "Manage directory bookmarks",
+        description: "Bookmarks are named directories you can jump to with `cd @name` or `go name`. Without a subcommand, lists them.",
+        options: &[
+            ("add <name> [dir]", "Bookmark dir (default: the current directory)"),
+            ("list", "Show every bookmark"),
+            ("rm <name>...", "Remove bookmarks"),
+        ],
+        examples: &["bookmark add dots ~/.config", "bookmark rm dots"],
+    },
+    HelpPage {
+        name: "go",
+        aliases: &[],
+        usage: "go <bookmark>",
+        summary: "Jump to a bookmarked directory",
+        description: "Shorthand for `cd @<bookmark>`.",
+        options: &[],
+        examples: &["go dots"],
+    },
+    HelpPage {
+        name: "ll",
+        aliases: &[],
+        usage: "ll [-e] [dir]",
+        summary: "List directory with details",
+        description: "A long listing with permissions, sizes and modification times, colored by file type. Times are relative (`3 min ago`) when `modified_format=relative`.",
+        options: &[("-e, --exact", "Show full timestamps even with relative times configured")],
+        examples: &["ll", "ll /usr/bin", "ll -e"],
+    },
+    HelpPage {
+        name: "freqs",
+        aliases: &[],
+        usage: "freqs",
+        summary: "Show directory frequency stats",
+        description: "Lists the directories you visit most, as counted by cd. With `dirfreq.scope=repo`, only directories of the current repository are shown.",
+        options: &[],
+        examples: &["freqs"],
+    },
+    HelpPage {
+        name: "stats",
+        aliases: &[],
+        usage: "stats [--all] [-n N]",
+        summary: "Most used, slowest and most failing commands",
+        description: "Shows run counts, total and mean time, and failures per program for this session.",
+        options: &[
+            ("-a, --all", "Use statistics from every session"),
+            ("-n N", "Show at most N rows per table (default 10)"),
+        ],
+        examples: &["stats", "stats --all -n 5"],
+    },
+    HelpPage {
+        name: "calc",
+        aliases: &[],
+        usage: "calc <expr>",
+        summary: "Evaluate arithmetic",
+        description: "Supports + - * / % and ** (power), parentheses, hex (0x), binary (0b) and octal (0o) literals, the bitwise operators & | ^ ~ << >>, the constants pi and e, and functions such as sqrt, abs, round, ln and sin. With `inline_math` on, a line that is only an expression is evaluated without `calc`.",
+        options: &[],
+        examples: &["calc '2*(3+4.5)'", "calc '0xff & ~0x0f'", "calc 'sqrt(2)'"],
+    },
+    HelpPage {
+        name: "trash",
+        aliases: &[],
+        usage: "trash <path>... | --list | --restore <name|path>... | --empty",
+        summary: "Move files to the trash",
+        description: "Follows the FreeDesktop trash spec, so files show up in your file manager's trash. With `rm_to_trash` on, interactive `rm` uses it too.",
+        options: &[
+            ("--list", "Show trashed items, newest first"),
+            ("--restore <name|path>", "Put an item back where it came from"),
+            ("--empty", "Permanently delete everything in the trash"),
+        ],
+        examples: &["trash old-notes.txt build/", "trash --restore old-notes.txt"],
+    },
+    HelpPage {
+        name: "option",
+        aliases: &[],
+        usage: "option [list | <key>] [--doc]",
+        summary: "Show config options",
+        description: "Lists every config file option with its type and default, or describes one option.",
+        options: &[("--doc", "Include each option's description in the list")],
+        examples: &["option list --doc", "option prompt"],
+    },
+    HelpPage {
+        name: "theme",
+        aliases: &[],
+        usage: "theme [name]",
+        summary: "List or switch color themes",
+        description: "Without a name, lists the built-in themes and the files in ~/.config/squish/themes, marking the active one. With a name, recolors the prompt, highlighting, listings and messages for the rest of the session; set `theme=` in the config to keep it. A theme file holds `role=color` lines (command, builtin, external, alias, path, string, variable, operator, error, error_text, warning, success, hint, accent, source, docs, data, media), may set `prompt.*` colors, and can start from a built-in theme with `base=<name>`.",
+        options: &[],
+        examples: &["theme", "theme monochrome", "theme solarized"],
+    },
+    HelpPage {
+        name: "croot",
+        aliases: &["cdr"],
+        usage: "croot [name]",
+        summary: "Go to the project root",
+        description: "Without a name, goes to the nearest enclosing directory containing one of `root_markers`. With a name, goes to a previously visited project root of that name.",
+        options: &[],
+        examples: &["croot", "cdr squish"],
+    },
+    HelpPage {
+        name: "export",
+        aliases: &[],
+        usage: "export [var=value]...",
+        summary: "Set environment variables",
+        description: "With no arguments, prints the environment. A `VAR=value` prefix on a command sets it for that command only.",
+        options: &[],
+        examples: &["export EDITOR=nvim", "RUST_LOG=debug cargo run"],
+    },
+    HelpPage {
+        name: "unset",
+        aliases: &[],
+        usage: "unset <var>...",
+        summary: "Unset environment variables",
+        description: "Removes the variables from the environment of the shell and future commands.",
+        options: &[],
+        examples: &["unset RUST_LOG"],
+    },
+    HelpPage {
+        name: "jobs",
+        aliases: &[],
+        usage: "jobs [-l] [-r|-s] [--output %job] [--name name [%job]]",
+        summary: "List background jobs",
+        description: "Commands ending in & run as background jobs, listed with their state and how long they have been running. Jobs still running when the shell exits get SIGHUP unless disowned.",
+        options: &[
+            ("-l", "Also show pid, CPU and memory usage"),
+            ("-r", "Only running jobs"),
+            ("-s", "Only stopped jobs"),
+            ("--name name [%job]", "Label a job (default: the latest) so `fg %name` and friends find it"),
+            ("-o, --output %job", "Print what the job has written so far (with `capture_job_output` or `nohup_jobs`)"),
+        ],
+        examples: &["sleep 60 &", "jobs -l", "jobs --output %2", "jobs --name build %1", "fg %build"],
+    },
+    HelpPage {
+        name: "fg",
+        aliases: &[],
+        usage: "fg [%job]",
+        summary: "Bring a job to the foreground",
+        description: "Gives the job (default: the current one) the terminal, continues it if it was stopped, and waits for it; Ctrl-Z stops it again. If the job's output was captured, it is replayed and then followed. Jobs can be named `%2`, `%+` (current), `%-` (previous), `%name` (command starts with name) or `%?text` (command contains text).",
+        options: &[],
+        examples: &["fg", "fg %2", "fg %vim", "fg %?server"],
+    },
+    HelpPage {
+        name: "bg",
+        aliases: &[],
+        usage: "bg [%job]",
+        summary: "Resume a stopped job in the background",
+        description: "Continues a job stopped with Ctrl-Z (default: the current one) without giving it the terminal.",
+        options: &[],
+        examples: &["bg", "bg %1"],
+    },
+    HelpPage {
+        name: "disown",
+        aliases: &[],
+        usage: "disown [-h] [%job ...]",
+        summary: "Keep jobs running after exit",
+        description: "When the shell exits, running jobs get SIGHUP. A disowned job is removed from the job table and left alone; with `-h` it stays listed but is still kept. With `nohup_jobs` on, every background job starts detached with its output in a log file.",
+        options: &[("-h", "Keep the job in the table, only skip the hangup")],
+        examples: &["disown", "disown -h %2"],
+    },
+    HelpPage {
+        name: "wait",
+        aliases: &[],
+        usage: "wait [%job ...]",
+        summary: "Wait for jobs",
+        description: "Waits for the given jobs, or all of them, and returns the exit status of the last one.",
+        options: &[],
+        examples: &["wait", "wait %1 %3"],
+    },
+    HelpPage {
+        name: "time",
+        aliases: &[],
+        usage: "time <command>",
+        summary: "Time command execution",
+        description: "Reports real, user and system time for the command, including every stage of a pipeline.",
+        options: &[],
+        examples: &["time cargo build", "time sort big.txt | uniq -c"],
+    },
+    HelpPage {
+        name: "watch",
+        aliases: &[],
+        usage: "watch [-n secs] [-d] [-t] <command>",
+        summary: "Re-run a command periodically",
+        description: "Clears the screen and re-runs the command until Ctrl-C.",
+        options: &[
+            ("-n secs", "Interval between runs (default 2)"),
+            ("-d", "Highlight what changed since the previous run"),
+            ("-t", "Hide the header"),
+        ],
+        examples: &["watch -n 1 -d 'ls -l'"],
+    },
+    HelpPage {
+        name: "benchmark",
+        aliases: &[],
+        usage: "benchmark [-n runs] [-w warmups] [--show-output] '<command>'",
+        summary: "Run a command repeatedly and report timings",
+        description: "Prints min, mean, standard deviation and max wall time, and mean user and system time.",
+        options: &[
+            ("-n runs", "Measured runs (default 10)"),
+            ("-w warmups", "Unmeasured runs first (default 1)"),
+            ("--show-output", "Don't silence the command's output"),
+        ],
+        examples: &["benchmark -n 20 'grep -r foo src'"],
+    },
+    HelpPage {
+        name: "help",
+        aliases: &[],
+        usage: "help [command | -k keyword]",
+        summary: "Show help for a command",
+        description: "For builtins, shows this documentation. For other commands, shows `whatis` and the command's own --help output.",
+        options: &[("-k keyword", "Search the builtin documentation")],
+        examples: &["help cd", "help -k bookmark"],
+    },
+    HelpPage {
+        name: "command",
+        aliases: &[],
+        usage: "command [-v] <name> [args...]",
+        summary: "Run a command without alias expansion",
+        description: "Runs the command as typed even if an alias of the same name exists; `\\name` does the same. Aliases are expanded at the start of each command, not inside their own value (so `alias ls='ls --color'` works), and a value ending in a space also expands the next word.",
+        options: &[("-v", "Print the path (or builtin name) the command resolves to")],
+        examples: &["command ls", "\\ls", "command -v git"],
+    },
+    HelpPage {
+        name: "type",
+        aliases: &[],
+        usage: "type <name>...",
+        summary: "Show how a name would be run",
+        description: "Tells whether each name is an alias, a shell builtin, or a program on PATH (and where).",
+        options: &[],
+        examples: &["type ll cd git"],
+    },
+    HelpPage {
+        name: "set",
+        aliases: &[],
+        usage: "set [-o|+o option] [-C|+C]",
+        summary: "Turn shell options on or off",
+        description: "`-o option` turns an option on and `+o option` turns it off for this session; `set -o` lists them. The only option so far is noclobber: `>` then refuses to overwrite an existing file, and `>|` overwrites anyway. `noclobber=true` in the config turns it on at startup.",
+        options: &[
+            ("-o noclobber, -C", "Don't let `>` overwrite existing files"),
+            ("+o noclobber, +C", "Let `>` overwrite files again"),
+        ],
+        examples: &["set -o noclobber", "set +C", "set -o"],
+    },
+    HelpPage {
+        name: "quote",
+        aliases: &[],
+        usage: "quote [arg...]",
+        summary: "Print arguments quoted for reuse as shell input",
+        description: "Prints each argument as a word squish reads back unchanged, separated by spaces. Plain words stay bare; anything with spaces, quotes, `$`, glob characters or operators is single-quoted, with a `'` inside written as `'\"'\"'`. Useful for building commands to paste, alias or eval later.",
+        options: &[],
+        examples: &["quote \"$FILE\"", "quote *.txt", "quote it's \"a b\""],
+    },
+    HelpPage {
+        name: "lastout",
+        aliases: &[],
+        usage: "lastout",
+        summary: "Print the previous command line's output again",
+        description: "With `lastout_kb` set, squish keeps the last part of what each command line writes to stdout, without colors, in the file `$SQUISH_LAST_OUTPUT_FILE`. `lastout` prints it, so a slow command's output can be filtered without running it again. A line starting with `lastout` keeps the previous output in place. Stderr isn't kept.",
+        options: &[],
+        examples: &["lastout | grep error", "wc -l $SQUISH_LAST_OUTPUT_FILE"],
+    },
+    HelpPage {
+        name: "copy",
+        aliases: &[],
+        usage: "copy [text...]",
+        summary: "Put text or piped output on the clipboard",
+        description: "Copies its arguments, or what's piped into it, to the system clipboard. wl-copy, xclip or xsel is used for a local Wayland or X session, and pbcopy on macOS. Otherwise, and always over SSH, the text goes to the terminal as an OSC 52 sequence, which puts it on the clipboard of the machine the terminal runs on, if the terminal allows it. With neither arguments nor a pipe, reads until Ctrl-D.",
+        options: &[],
+        examples: &["ll | copy", "pwd | copy", "copy some text"],
+    },
+    HelpPage {
+        name: "paste",
+        aliases: &[],
+        usage: "paste",
+        summary: "Print the clipboard",
+        description: "Prints what's on the system clipboard, from the same tools `copy` uses. Without one, asks the terminal through OSC 52; many terminals don't allow reading the clipboard that way, and then `paste` gives up after half a second. With arguments, `paste` is the system's command that merges lines of files, as in `paste a.txt b.txt`.",
+        options: &[],
+        examples: &["paste > notes.txt", "paste | wc -l"],
+    },
+    HelpPage {
+        name: "filter",
+        aliases: &[],
+        usage: "filter [-i] [-v] [-F] [-w] [-c] [-n] <pattern> [file...]",
+        summary: "Keep the lines that match a pattern",
+        description: "A grep built into the shell, so filtering works the same everywhere squish runs. Reads the files, or what's piped in, and prints the lines matching the regular expression (Rust regex syntax: `\\d`, `(a|b)`, `[^x]+`, `(?i)` and so on). Flags combine, as in `-iv`. Exits 0 when a line matched, 1 when none did and 2 on errors, like grep.",
+        options: &[
+            ("-i", "Ignore case"),
+            ("-v", "Keep the lines that don't match"),
+            ("-F", "Match the pattern as plain text"),
+            ("-w", "Match whole words only"),
+            ("-c", "Print how many lines matched"),
+            ("-n", "Number the lines"),
+        ],
+        examples: &["ps aux | filter -i firefox", "ll | filter -v '\\.log$'", "filter -n TODO src/main.rs", "git log --oneline | filter -c '^[0-9a-f]+ (fix|feat)'"],
+    },
+    HelpPage {
+        name: "plugins",
+        aliases: &[],
+        usage: "plugins",
+        summary: "List the loaded plugins",
+        description: "Plugins are executables in ~/.config/squish/plugins that add prompt segments, builtins, argument completions and precmd/preexec/chpwd hooks. Each is asked what it provides (`<plugin> describe`) when squish starts; this lists every plugin that answered, with its segments, builtins, completed commands and hooks. A plugin added or changed later is picked up by the next shell. The README describes the protocol; `plugins=false` turns plugins off.",
+        options: &[],
+        examples: &["plugins"],
+    },
+    HelpPage {
+        name: "sandbox",
+        aliases: &[],
+        usage: "sandbox [-i] [-k NAME] [-u NAME] [-e NAME=VALUE] [-C DIR] [-r] command [args...]",
+        summary: "Run a command in a clean or adjusted environment",
+        description: "Runs one program the way a fresh login, cron job or CI runner would see it. Only PATH, HOME, USER, LOGNAME, TERM and LANG are passed on unless `-i` keeps everything; names given to `-k` and `-u` may end in `*` to match a prefix, and several can be separated by commas. Aliases, functions and builtins aren't available inside; use `sh -c` for a pipeline. With `-r`, $HOME is mounted read-only for the command alone, which needs util-linux `unshare` and unprivileged user namespaces.",
+        options: &[
+            ("-i, --inherit", "Keep the whole environment, to change only parts of it"),
+            ("-k, --keep NAME", "Pass on NAME as well (e.g. `-k 'LC_*'`)"),
+            ("-u, --unset NAME", "Leave NAME out"),
+            ("-e, --env NAME=VALUE", "Set NAME for the command"),
+            ("-C, --dir DIR", "Run in DIR"),
+            ("-r, --ro-home", "Make $HOME read-only"),
+        ],
+        examples: &["sandbox ./install.sh", "sandbox -k 'LC_*' -e DEBUG=1 -C /tmp make test", "sandbox -i -u SSH_AUTH_SOCK git fetch", "sandbox -r ./setup.sh"],
+    },
+    HelpPage {
+        name: "record",
+        aliases: &[],
+        usage: "record [start <file.cast> | stop]",
+        summary: "Record the session as an asciinema cast",
+        description: "`record start` saves everything shown from then on, including the prompt, what you type and full-screen programs, to an asciinema v2 file with its timing. `record stop` finishes it, and so does exiting the shell. Play it back with `asciinema play`. With no arguments, says whether a recording is running.",
+        options: &[
+            ("start <file>", "Start recording to the file, replacing it"),
+            ("stop", "Stop recording and save"),
+        ],
+        examples: &["record start demo.cast", "record stop", "record"],
+    },
+    HelpPage {
+        name: "limit",
+        aliases: &[],
+        usage: "limit <duration> command [args...]",
+        summary: "Run a command with a maximum runtime",
+        description: "Runs the command and stops it, along with anything it started, if it's still going after the duration: SIGTERM first, then SIGKILL three seconds later. A stopped command says so and exits with 124, so a timeout can be told apart from the command failing. Durations look like `30`, `1.5s`, `500ms`, `10m`, `2h` or `1h30m`; bare numbers are seconds. Replaces `max_command_runtime` for this command, and `limit 0` lifts it.",
+        options: &[],
+        examples: &["limit 30s make test", "limit 5m ./backup.sh", "limit 0 long-import"],
+    },
+    HelpPage {
+        name: "please",
+        aliases: &[],
+        usage: "please",
+        summary: "Run the previous command again with sudo",
+        description: "Runs the last command line again as `sudo <line>`, like `sudo !!`, printing it first. History keeps the `sudo` line. In the editor, Alt-S does the same in one key: on an empty line it brings back the last command with `sudo` in front, and on a line being typed it adds or removes the `sudo`.",
+        options: &[],
+        examples: &["please"],
+    },
+    HelpPage {
+        name: "hash",
+        aliases: &[],
+        usage: "hash [-r] [-d name...] [name...]",
+        summary: "Show or reset remembered command locations",
+        description: "squish looks commands up on PATH itself and remembers where each one was found. With no arguments, lists the remembered commands and how often each ran; with names, looks them up now. The table is cleared when PATH changes or a PATH directory does. The prompt's hostname and distribution icon are read once too; `hash -r` reads them again.",
+        options: &[
+            ("-r", "Forget every remembered location, and re-read the hostname and distribution"),
+            ("-d name", "Forget the given names"),
+        ],
+        examples: &["hash", "hash -r", "hash git cargo"],
+    },
+    HelpPage {
+        name: "exit",
+        aliases: &[],
+        usage: "exit [code]",
+        summary: "Exit the shell",
+        description: "Exits with the given status (default 0). In a nested squish, returns to the outer one.",
+        options: &[],
+        examples: &["exit 1"],
+    },
+];
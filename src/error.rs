@@ -7,6 +7,9 @@ pub enum ShellError {
     CommandNotFound { program: String },
     ExecFailed { program: String, message: String },
     Other(String),
+    /// `exit N`: not a failure but a request to end the shell, carried up to
+    /// whoever owns the process (the REPL, or a subshell or pipeline stage)
+    Exit(i32),
 }
 
 impl Display for ShellError {
@@ -17,6 +20,7 @@ impl Display for ShellError {
             ShellError::CommandNotFound { program } => write!(f, "command not found: {}", program),
             ShellError::ExecFailed { program, message } => write!(f, "{}: {}", program, message),
             ShellError::Other(msg) => write!(f, "{}", msg),
+            ShellError::Exit(code) => write!(f, "exit {}", code),
         }
     }
 }
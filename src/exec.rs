@@ -1,54 +1,83 @@
-use std::env;
 use std::ffi::OsStr;
-use std::process::{Command, Stdio};
+use std::process::Child;
+use std::time::Duration;
 
 use crate::error::ShellError;
 use crate::formatter;
+use crate::jobs::format_runtime;
+#[cfg(unix)]
+use crate::resources;
+use crate::signals;
+use crate::spawn::{Io, Spawn};
+use crate::stdio;
+use crate::theme::{Paint, Role};
+use crate::timeout::{self, Watchdog};
+use crate::errln;
 
-pub fn run_external_command<S: AsRef<OsStr>>(program: S, args: &[String]) -> Result<i32, ShellError> {
+/// Run a program and wait for it. With a `limit`, it's stopped once it
+/// runs longer, and the status is `timeout::TIMED_OUT`.
+pub fn run_external_command<S: AsRef<OsStr>>(program: S, args: &[String], env_overlay: &[(String, String)], limit: Option<Duration>) -> Result<i32, ShellError> {
     let program_str = program.as_ref().to_string_lossy().to_string();
     
-    // Commands that should be formatted
-    let should_format = matches!(program_str.as_str(), "ls" | "cat" | "cargo");
+    // Commands that should be formatted, and only for the terminal: a
+    // redirect gets what the program printed. cargo isn't: on the terminal
+    // it colors its own diagnostics and draws live progress, both of which
+    // capturing would lose.
+    let should_format = matches!(program_str.as_str(), "ls" | "cat")
+        && formatter::can_format(&program_str, args)
+        && stdio::stdout_is_terminal();
     
-    let mut command = Command::new(&program);
-    command.args(args);
-    command.envs(env::vars());
-    command.stdin(Stdio::inherit());
+    let spawn = Spawn::new(program.as_ref(), args).env(env_overlay);
     
     if should_format {
         // Capture output for formatting
-        command.stdout(Stdio::piped());
-        command.stderr(Stdio::piped());
-        
-        match command.output() {
-            Ok(output) => {
-                let _ = formatter::format_command_output(&program_str, args, &output);
-                Ok(output.status.code().unwrap_or_default())
-            }
-            Err(e) => {
-                use std::io::ErrorKind;
-                match e.kind() {
-                    ErrorKind::NotFound => Err(ShellError::CommandNotFound { program: program_str.clone() }),
-                    _ => Err(ShellError::ExecFailed { program: program_str.clone(), message: e.to_string() }),
-                }
-            },
-        }
+        let output = spawn.stdout(Io::Piped).stderr(Io::Piped).output()?;
+        let _ = formatter::format_command_output(&program_str, args, &output);
+        Ok(output.status.code().unwrap_or_default())
     } else {
-        // Normal execution for other commands
-        command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
-        
-        match command.status() {
-            Ok(status) => Ok(status.code().unwrap_or_default()),
-            Err(e) => {
-                use std::io::ErrorKind;
-                match e.kind() {
-                    ErrorKind::NotFound => Err(ShellError::CommandNotFound { program: program_str.clone() }),
-                    _ => Err(ShellError::ExecFailed { program: program_str.clone(), message: e.to_string() }),
-                }
-            },
+        // Normal execution for other commands. A limited one leads its own
+        // process group, so the watchdog stops whatever it started as well,
+        // and is handed the terminal the shell's group would have had.
+        let spawn = if limit.is_some() { spawn.new_process_group() } else { spawn };
+        let mut child = spawn.spawn()?;
+        let attached = limit.is_some() && signals::holds_terminal() && signals::give_terminal_to(child.id());
+        let watchdog = limit.map(|limit| Watchdog::start(child.id(), limit));
+        let status = wait_foreground(&mut child).map_err(|e| ShellError::ExecFailed { program: program_str.clone(), message: e.to_string() });
+        if attached {
+            signals::reclaim_terminal();
+        }
+        if let (Some(limit), Some(signal)) = (limit, watchdog.and_then(Watchdog::finish)) {
+            use colored::Colorize;
+            errln!("{} {} ran longer than {} and was stopped with {}", "timeout:".paint(Role::Error).bold(), program_str, format_runtime(limit), signal);
+            return Ok(timeout::TIMED_OUT);
         }
+        status
     }
 }
 
+/// Wait for a foreground child with wait4 so its own resource usage can be
+/// reported, rather than the shell-wide RUSAGE_CHILDREN totals.
+fn wait_foreground(child: &mut Child) -> std::io::Result<i32> {
+    #[cfg(unix)]
+    {
+        let pid = child.id() as libc::pid_t;
+        let mut status = 0;
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+            if ret == pid {
+                break;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+        resources::record_last(resources::ResourceUsage::from_rusage(&usage));
+        Ok(if libc::WIFEXITED(status) { libc::WEXITSTATUS(status) } else { 128 + libc::WTERMSIG(status) })
+    }
+    #[cfg(not(unix))]
+    {
+        Ok(child.wait()?.code().unwrap_or_default())
+    }
+}
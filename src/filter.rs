@@ -0,0 +1,177 @@
+//! `filter`: a grep for interactive use that runs in the shell itself, so
+//! `... | filter pattern` behaves the same wherever squish runs, with or
+//! without a grep installed. Patterns are regular expressions (the regex
+//! crate's syntax); lines are matched as bytes, so binary or non-UTF-8
+//! input passes through untouched.
+
+use std::io::Read;
+
+use regex::bytes::{Regex, RegexBuilder};
+use crate::errln;
+
+#[derive(Default)]
+struct Options {
+    ignore_case: bool,
+    invert: bool,
+    fixed: bool,
+    word: bool,
+    count: bool,
+    line_numbers: bool,
+    pattern: String,
+    files: Vec<String>,
+}
+
+const USAGE: &str = "usage: filter [-i] [-v] [-F] [-w] [-c] [-n] <pattern> [file...]";
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    let mut flags_done = false;
+    for arg in args {
+        if flags_done || !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            flags_done = true;
+            continue;
+        }
+        // Single-letter flags, combinable as -iv
+        for flag in arg[1..].chars() {
+            match flag {
+                'i' => options.ignore_case = true,
+                'v' => options.invert = true,
+                'F' => options.fixed = true,
+                'w' => options.word = true,
+                'c' => options.count = true,
+                'n' => options.line_numbers = true,
+                _ => return Err(format!("unknown option -{}\n{}", flag, USAGE)),
+            }
+        }
+    }
+    let mut positional = positional.into_iter();
+    options.pattern = positional.next().ok_or_else(|| USAGE.to_string())?;
+    options.files = positional.collect();
+    Ok(options)
+}
+
+impl Options {
+    fn regex(&self) -> Result<Regex, String> {
+        let mut pattern = if self.fixed { regex::escape(&self.pattern) } else { self.pattern.clone() };
+        // As grep -w: no word character just before or after the match.
+        // Unlike `\b`, that holds for a pattern that starts or ends with
+        // punctuation, as `-F` ones often do
+        if self.word {
+            pattern = format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Append the selected lines of `text` to `out`, prefixed with `label`
+    /// when several files are searched. Returns how many were selected.
+    fn select(&self, regex: &Regex, text: &[u8], label: Option<&str>, out: &mut Vec<u8>) -> usize {
+        let mut selected = 0;
+        for (number, line) in text.split_inclusive(|&b| b == b'\n').enumerate() {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            let content = line.strip_suffix(b"\r").unwrap_or(line);
+            if regex.is_match(content) == self.invert {
+                continue;
+            }
+            selected += 1;
+            if self.count {
+                continue;
+            }
+            if let Some(label) = label {
+                out.extend_from_slice(label.as_bytes());
+                out.push(b':');
+            }
+            if self.line_numbers {
+                out.extend_from_slice(format!("{}:", number + 1).as_bytes());
+            }
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+        if self.count {
+            if let Some(label) = label {
+                out.extend_from_slice(label.as_bytes());
+                out.push(b':');
+            }
+            out.extend_from_slice(format!("{}\n", selected).as_bytes());
+        }
+        selected
+    }
+}
+
+/// Filter the files named in `args`, or else `input` (what a pipeline
+/// feeds in), or else stdin. Exits as grep does: 0 when a line was
+/// selected, 1 when none was, 2 on errors.
+pub fn run(args: &[String], input: Option<&[u8]>) -> (i32, Vec<u8>) {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(message) => {
+            errln!("filter: {}", message);
+            return (2, Vec::new());
+        }
+    };
+    let regex = match options.regex() {
+        Ok(regex) => regex,
+        Err(message) => {
+            errln!("filter: {}", message);
+            return (2, Vec::new());
+        }
+    };
+
+    let mut out = Vec::new();
+    let mut selected = 0;
+    let mut failed = false;
+    if options.files.is_empty() {
+        let text = match input {
+            Some(input) => input.to_vec(),
+            None => {
+                let mut text = Vec::new();
+                if let Err(e) = std::io::stdin().read_to_end(&mut text) {
+                    errln!("filter: stdin: {}", e);
+                    return (2, Vec::new());
+                }
+                text
+            }
+        };
+        selected += options.select(&regex, &text, None, &mut out);
+    } else {
+        let several = options.files.len() > 1;
+        for file in &options.files {
+            match std::fs::read(file) {
+                Ok(text) => selected += options.select(&regex, &text, several.then_some(file.as_str()), &mut out),
+                Err(e) => {
+                    errln!("filter: {}: {}", file, e);
+                    failed = true;
+                }
+            }
+        }
+    }
+    let status = if failed { 2 } else if selected > 0 { 0 } else { 1 };
+    (status, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(args: &[&str], line: &str) -> bool {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args).unwrap().regex().unwrap().is_match(line.as_bytes())
+    }
+
+    #[test]
+    fn whole_words_with_fixed_strings() {
+        assert!(matches(&["-wF", "a.b"], "x a.b y"));
+        assert!(!matches(&["-wF", "a.b"], "x aXb y"));
+        assert!(!matches(&["-wF", "a.b"], "xa.b"));
+        assert!(matches(&["-w", "-F", "--", "-x"], "run -x now"));
+        assert!(!matches(&["-w", "-F", "--", "-x"], "run -xy now"));
+        assert!(matches(&["-wF", "(x)"], "f (x) + 1"));
+    }
+}
@@ -1,86 +1,200 @@
 use std::io::{self, Write};
 use std::path::Path;
 use std::process::Output;
+use std::sync::OnceLock;
 use colored::Colorize;
-use chrono::{DateTime, Local};
-use humansize::{format_size, DECIMAL};
+
+use crate::docs::HelpPage;
+use crate::locale;
+use crate::theme::{Paint, Role};
+use crate::outln;
+use crate::stdio;
+
+/// How `ls` output is laid out (`ls.layout=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LsLayout {
+    /// The table for small listings, the grid past `ls.grid_over` entries
+    /// or when the table is wider than the terminal
+    #[default]
+    Auto,
+    Table,
+    Grid,
+}
+
+impl LsLayout {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "table" => LsLayout::Table,
+            "grid" => LsLayout::Grid,
+            _ => LsLayout::Auto,
+        }
+    }
+}
+
+/// Optional columns of `ll` and the ls table (`ls.columns=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListColumn {
+    /// Mode bits as `drwxr-xr-x`, colored
+    Mode,
+    /// Hard link count
+    Links,
+    /// Owner and group names
+    Owner,
+    /// `name → target` for symlinks
+    Target,
+}
+
+impl ListColumn {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mode" | "perms" => Some(ListColumn::Mode),
+            "links" => Some(ListColumn::Links),
+            "owner" => Some(ListColumn::Owner),
+            "target" => Some(ListColumn::Target),
+            _ => None,
+        }
+    }
+}
+
+struct ListSettings {
+    layout: LsLayout,
+    grid_over: usize,
+    columns: Vec<ListColumn>,
+}
+
+static LIST_SETTINGS: OnceLock<ListSettings> = OnceLock::new();
+
+/// Apply `ls.layout`, `ls.grid_over` and `ls.columns`. Only the first call wins.
+pub fn configure(layout: LsLayout, grid_over: usize, columns: Vec<ListColumn>) {
+    let _ = LIST_SETTINGS.set(ListSettings { layout, grid_over, columns });
+}
+
+/// The optional columns `ll` and the ls table show.
+pub fn list_columns() -> &'static [ListColumn] {
+    LIST_SETTINGS.get().map_or(&[], |s| s.columns.as_slice())
+}
+
+/// Columns of the terminal on stdout, else `$COLUMNS`, else 80.
+pub fn terminal_columns() -> usize {
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            return size.ws_col as usize;
+        }
+    }
+    std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).unwrap_or(80)
+}
 
 pub fn format_command_output(program: &str, args: &[String], output: &Output) -> io::Result<()> {
     match program {
-        "ls" => format_ls_output(&output),
+        "ls" => format_ls_output(args, output),
         "cat" => format_cat_output(args, &output),
-        "cargo" => format_cargo_output(args, &output),
         _ => format_generic_output(&output),
     }
 }
 
-fn format_ls_output(output: &Output) -> io::Result<()> {
+/// ls flags the formatter can render itself; anything else runs ls as is.
+const LS_SHORT_FLAGS: &str = "laAh1";
+const LS_LONG_FLAGS: &[&str] = &["--all", "--almost-all", "--human-readable"];
+
+struct LsOptions {
+    long: bool,
+    /// The directory listed, when it isn't the current one
+    dir: Option<std::path::PathBuf>,
+}
+
+/// Flags and operand of an ls invocation, if all of them are understood:
+/// only the flags above and at most one operand (several print headers).
+fn ls_options(args: &[String]) -> Option<LsOptions> {
+    let mut long = false;
+    let mut operands = Vec::new();
+    for arg in args {
+        if arg.starts_with("--") {
+            if !LS_LONG_FLAGS.contains(&arg.as_str()) {
+                return None;
+            }
+        } else if let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) {
+            if !flags.chars().all(|c| LS_SHORT_FLAGS.contains(c)) {
+                return None;
+            }
+            long |= flags.contains('l');
+        } else {
+            operands.push(arg);
+        }
+    }
+    if operands.len() > 1 {
+        return None;
+    }
+    let dir = operands.first().map(std::path::PathBuf::from).filter(|p| p.is_dir());
+    Some(LsOptions { long, dir })
+}
+
+/// Whether `program args` output is reformatted; otherwise it runs straight
+/// on the terminal.
+pub fn can_format(program: &str, args: &[String]) -> bool {
+    program != "ls" || ls_options(args).is_some()
+}
+
+fn format_ls_output(args: &[String], output: &Output) -> io::Result<()> {
     if !output.status.success() {
-        io::stderr().write_all(&output.stderr)?;
+        stdio::stderr().write_all(&output.stderr)?;
         return Ok(());
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let lines: Vec<&str> = stdout.lines().collect();
-    
+    let Some(options) = ls_options(args) else {
+        return stdio::stdout().write_all(&output.stdout);
+    };
+    let lines: Vec<&str> = stdout.lines().filter(|l| !l.trim().is_empty()).collect();
     if lines.is_empty() {
         return Ok(());
     }
 
-    // Parse ls output - try to detect format
-    // Simple format: just filenames
-    // Long format: permissions, links, user, group, size, date, name
-    
-    let mut entries = Vec::new();
-    
-    for line in lines {
-        if line.trim().is_empty() {
-            continue;
-        }
-        
-        // Check if it's long format (starts with permissions like "drwxr-xr-x")
-        if line.starts_with('-') || line.starts_with('d') || line.starts_with('l') {
-            if let Some(parsed) = parse_ls_long_line(line) {
-                entries.push(parsed);
-            } else {
-                // Fallback: treat as simple filename
-                entries.push(FileEntry {
-                    name: line.to_string(),
-                    is_dir: false,
-                    is_symlink: false,
-                    size: None,
-                    modified: None,
-                });
-            }
-        } else {
-            // Simple format - just filename
-            entries.push(FileEntry {
-                name: line.to_string(),
-                is_dir: false,
-                is_symlink: false,
-                size: None,
-                modified: None,
-            });
+    if options.long {
+        let parsed: Option<Vec<LongEntry>> = lines.iter().filter(|l| !l.starts_with("total ")).map(|l| parse_ls_long_line(l)).collect();
+        // A line we can't read (odd locale, ACL markers we don't know):
+        // show what ls printed rather than a mangled table
+        match parsed {
+            Some(entries) => print_long_table(&entries),
+            None => stdio::stdout().write_all(&output.stdout)?,
         }
+        return Ok(());
     }
 
-    // Try to get actual file metadata for better formatting
-    let cwd = std::env::current_dir().unwrap_or_default();
-    for entry in &mut entries {
-        let path = cwd.join(&entry.name);
-        if let Ok(metadata) = std::fs::metadata(&path) {
-            entry.is_dir = metadata.is_dir();
-            entry.is_symlink = metadata.is_symlink();
-            if entry.size.is_none() {
-                entry.size = Some(metadata.len());
-            }
-            if entry.modified.is_none() {
-                entry.modified = metadata.modified().ok();
+    // Metadata for the table; names are relative to the listed directory
+    let base = options.dir.or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+    let entries: Vec<FileEntry> = lines
+        .iter()
+        .map(|name| {
+            let path = base.join(name);
+            let metadata = std::fs::symlink_metadata(&path).ok();
+            let is_symlink = metadata.as_ref().is_some_and(|m| m.is_symlink());
+            FileEntry {
+                name: name.to_string(),
+                is_dir: !is_symlink && path.is_dir(),
+                is_symlink,
+                size: metadata.as_ref().map(|m| m.len()),
+                modified: metadata.as_ref().and_then(|m| m.modified().ok()),
+                target: if is_symlink { std::fs::read_link(&path).ok().map(|t| t.display().to_string()) } else { None },
+                metadata,
             }
-        }
+        })
+        .collect();
+
+    let (layout, grid_over) = LIST_SETTINGS.get().map_or((LsLayout::Auto, 40), |s| (s.layout, s.grid_over));
+    let table = fancy_table(&entries, list_columns());
+    let columns = terminal_columns();
+    let grid = match layout {
+        LsLayout::Table => false,
+        LsLayout::Grid => true,
+        LsLayout::Auto => entries.len() > grid_over || table.width() > columns,
+    };
+    if grid {
+        print_grid(&entries, columns);
+    } else {
+        table.print();
     }
-
-    print_fancy_list(&entries);
     Ok(())
 }
 
@@ -90,151 +204,404 @@ struct FileEntry {
     is_symlink: bool,
     size: Option<u64>,
     modified: Option<std::time::SystemTime>,
+    /// The entry itself, not what a symlink points to
+    metadata: Option<std::fs::Metadata>,
+    target: Option<String>,
 }
 
-fn parse_ls_long_line(line: &str) -> Option<FileEntry> {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    if parts.len() < 9 {
-        return None;
+/// A table cell: colored text, its visible width, and its alignment.
+struct Cell {
+    text: String,
+    width: usize,
+    right: bool,
+}
+
+impl Cell {
+    fn new(text: impl ToString, plain: &str) -> Self {
+        Cell { text: text.to_string(), width: plain.chars().count(), right: false }
     }
-    
-    let perms = parts[0];
-    let is_dir = perms.starts_with('d');
-    let is_symlink = perms.starts_with('l');
-    
-    let size_str = parts.get(4)?;
-    let size = size_str.parse::<u64>().ok();
-    
-    // Date is usually parts[5], [6], [7] or [6], [7], [8]
-    // Name is the last part (or last few if it contains spaces)
-    let name = parts[8..].join(" ");
-    
-    Some(FileEntry {
-        name,
-        is_dir,
-        is_symlink,
-        size,
-        modified: None, // Would need to parse date
-    })
+
+    fn right(self) -> Self {
+        Cell { right: true, ..self }
+    }
+}
+
+/// A box-drawn table; each column is as wide as its widest cell or its
+/// minimum, whichever is more.
+struct BoxTable {
+    headers: Vec<(&'static str, usize)>,
+    rows: Vec<Vec<Cell>>,
 }
 
-fn print_fancy_list(entries: &[FileEntry]) {
-    if entries.is_empty() {
-        return;
+impl BoxTable {
+    fn widths(&self) -> Vec<usize> {
+        self.headers
+            .iter()
+            .enumerate()
+            .map(|(i, (header, min))| self.rows.iter().map(|r| r[i].width).max().unwrap_or(0).max(header.len()).max(*min))
+            .collect()
     }
 
-    // Calculate column widths
-    let max_size_len = entries.iter()
-        .map(|e| {
-            if e.is_dir {
-                2
-            } else if let Some(s) = e.size {
-                format_size(s, DECIMAL).len()
-            } else {
-                2
-            }
-        })
-        .max()
-        .unwrap_or(8)
-        .max(8);
-    
-    let max_name_len = entries.iter()
-        .map(|e| e.name.len())
-        .max()
-        .unwrap_or(20);
-    
-    // Top border
-    println!("┌{}┬{}┬{}┬{}┐", 
-        "─".repeat(2),
-        "─".repeat(max_size_len.max(8)),
-        "─".repeat(19),
-        "─".repeat(max_name_len.max(20))
-    );
-    
-    // Header (ANSI-aware padding)
-    let t_hdr = "T".bold().to_string();
-    let size_hdr = "Size".bold().to_string();
-    let mod_hdr = "Modified".bold().to_string();
-    let name_hdr = "Name".bold().to_string();
-    let w_size = max_size_len.max(8);
-    let w_name = max_name_len.max(20);
-    let t_pad = 2usize.saturating_sub(visible_width(&t_hdr));
-    let size_pad = w_size.saturating_sub(visible_width(&size_hdr));
-    let mod_pad = 19usize.saturating_sub(visible_width(&mod_hdr));
-    let name_pad = w_name.saturating_sub(visible_width(&name_hdr));
-
-    print!("│{}{}│", t_hdr, " ".repeat(t_pad));
-    print!("{}{}│", size_hdr, " ".repeat(size_pad));
-    print!("{}{}│", mod_hdr, " ".repeat(mod_pad));
-    println!("{}{}│", name_hdr, " ".repeat(name_pad));
-    
-    // Separator
-    println!("├{}┼{}┼{}┼{}┤",
-        "─".repeat(2),
-        "─".repeat(max_size_len.max(8)),
-        "─".repeat(19),
-        "─".repeat(max_name_len.max(20))
-    );
-
-    for entry in entries {
-        let file_type = if entry.is_dir {
-            "d".truecolor(140, 180, 255).bold()
-        } else if entry.is_symlink {
-            "l".truecolor(200, 150, 255).bold()
-        } else {
-            "-".dimmed()
+    fn width(&self) -> usize {
+        self.widths().iter().sum::<usize>() + self.headers.len() + 1
+    }
+
+    fn print(&self) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let widths = self.widths();
+        let rule = |left: &str, mid: &str, right: &str| {
+            let parts: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
+            outln!("{}{}{}", left, parts.join(mid), right);
+        };
+        let line = |cells: &mut dyn Iterator<Item = &Cell>| {
+            let padded: Vec<String> = cells
+                .zip(&widths)
+                .map(|(cell, width)| {
+                    let pad = " ".repeat(width.saturating_sub(cell.width));
+                    if cell.right { format!("{}{}", pad, cell.text) } else { format!("{}{}", cell.text, pad) }
+                })
+                .collect();
+            outln!("│{}│", padded.join("│"));
         };
 
-        // Plain strings for width calculation
-        let size_plain = if entry.is_dir {
-            "-".to_string()
-        } else if let Some(s) = entry.size { format_size(s, DECIMAL) } else { "-".to_string() };
-        let modified_plain = entry
-            .modified
-            .and_then(|t| DateTime::<Local>::from(t).format("%Y-%m-%d %H:%M").to_string().into())
-            .unwrap_or_else(|| String::from("-"));
-        let name_plain = &entry.name;
+        rule("┌", "┬", "┐");
+        let headers: Vec<Cell> = self.headers.iter().map(|(h, _)| Cell::new(h.bold(), h)).collect();
+        line(&mut headers.iter());
+        rule("├", "┼", "┤");
+        for row in &self.rows {
+            line(&mut row.iter());
+        }
+        rule("└", "┴", "┘");
+    }
+}
+
+/// `drwxr-xr-x` for a file's mode, as ls prints it.
+#[cfg(unix)]
+pub fn mode_string(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+    let mode = metadata.permissions().mode();
+    let mut out = String::from(kind);
+    for (shift, special, set_char) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 2 != 0 { 'w' } else { '-' });
+        out.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set_char,
+            (false, true) => set_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    out
+}
+
+/// Without Unix modes, the type and whether the file is read-only.
+#[cfg(not(unix))]
+pub fn mode_string(metadata: &std::fs::Metadata) -> String {
+    let kind = if metadata.is_dir() { 'd' } else if metadata.is_symlink() { 'l' } else { '-' };
+    let perms = if metadata.permissions().readonly() { "r--" } else { "rw-" };
+    format!("{}{}", kind, perms.repeat(3))
+}
 
-        let w_size = max_size_len.max(8);
-        let w_name = max_name_len.max(20);
+/// User or group name for an id, or the number when it has none. Looked
+/// up once per id.
+#[cfg(unix)]
+fn id_name(id: u32, group: bool) -> String {
+    use std::collections::HashMap;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    static NAMES: OnceLock<Mutex<HashMap<(u32, bool), String>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(|| Mutex::new(HashMap::new())).lock().unwrap_or_else(|e| e.into_inner());
+    names
+        .entry((id, group))
+        .or_insert_with(|| {
+            let mut buf = vec![0 as libc::c_char; 4096];
+            let name = unsafe {
+                if group {
+                    let mut entry: libc::group = std::mem::zeroed();
+                    let mut result = std::ptr::null_mut();
+                    libc::getgrgid_r(id, &mut entry, buf.as_mut_ptr(), buf.len(), &mut result);
+                    (!result.is_null()).then(|| CStr::from_ptr(entry.gr_name).to_string_lossy().into_owned())
+                } else {
+                    let mut entry: libc::passwd = std::mem::zeroed();
+                    let mut result = std::ptr::null_mut();
+                    libc::getpwuid_r(id, &mut entry, buf.as_mut_ptr(), buf.len(), &mut result);
+                    (!result.is_null()).then(|| CStr::from_ptr(entry.pw_name).to_string_lossy().into_owned())
+                }
+            };
+            name.unwrap_or_else(|| id.to_string())
+        })
+        .clone()
+}
 
-        let size_pad = w_size.saturating_sub(size_plain.len());
-        let name_pad = w_name.saturating_sub(name_plain.len());
-        let mod_pad = 19usize.saturating_sub(modified_plain.len());
+/// Owner and group names of a file.
+#[cfg(unix)]
+pub fn owner_and_group(metadata: &std::fs::Metadata) -> (String, String) {
+    use std::os::unix::fs::MetadataExt;
+    (id_name(metadata.uid(), false), id_name(metadata.gid(), true))
+}
 
-        let colored_name = colorize_name(name_plain, entry.is_dir, entry.is_symlink);
+/// Hard link count of a file.
+#[cfg(unix)]
+pub fn link_count(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink()
+}
 
-        // Print with manual padding so ANSI codes don't break widths
-        let t_pad = 2usize.saturating_sub(visible_width(&file_type.to_string()));
-        print!("│{}{}│", file_type, " ".repeat(t_pad));
-        print!("{}{}│", size_plain.dimmed(), " ".repeat(size_pad));
-        print!("{}{}│", modified_plain.dimmed(), " ".repeat(mod_pad));
-        println!("{}{}│", colored_name, " ".repeat(name_pad));
+/// std has no owner or link count elsewhere, so `ll` leaves out those
+/// columns; these only keep the callers platform-neutral.
+#[cfg(not(unix))]
+pub fn owner_and_group(_metadata: &std::fs::Metadata) -> (String, String) {
+    (String::new(), String::new())
+}
+
+#[cfg(not(unix))]
+pub fn link_count(_metadata: &std::fs::Metadata) -> u64 {
+    1
+}
+
+/// One line of `ls -l`, fields kept as ls printed them so `-h` sizes and
+/// the locale's date format survive.
+struct LongEntry {
+    perms: String,
+    links: String,
+    owner: String,
+    group: String,
+    size: String,
+    date: String,
+    name: String,
+    /// Where a symlink points
+    target: Option<String>,
+}
+
+impl LongEntry {
+    /// Width of the name cell, `name → target` for symlinks
+    fn name_width(&self) -> usize {
+        self.name.chars().count() + self.target.as_ref().map_or(0, |t| t.chars().count() + 3)
+    }
+}
+
+/// The first `n` whitespace-separated fields of `line` and the text after
+/// them, with the single space ls puts before the name removed.
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line;
+    for _ in 0..n {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace)?;
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    Some((fields, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// perms, links, owner, group, size, three date fields, name. Device files
+/// have `major, minor` in place of the size.
+fn parse_ls_long_line(line: &str) -> Option<LongEntry> {
+    let (probe, _) = split_fields(line, 5)?;
+    let device = probe[4].ends_with(',');
+    let (fields, name) = split_fields(line, if device { 9 } else { 8 })?;
+    if name.is_empty() || !fields[0].starts_with(['-', 'd', 'l', 'c', 'b', 'p', 's']) {
+        return None;
+    }
+    let size = if device { format!("{} {}", fields[4], fields[5]) } else { fields[4].to_string() };
+    let date = fields[fields.len() - 3..].join(" ");
+    let (name, target) = match name.split_once(" -> ") {
+        Some((name, target)) if fields[0].starts_with('l') => (name.to_string(), Some(target.to_string())),
+        _ => (name.to_string(), None),
+    };
+    Some(LongEntry {
+        perms: fields[0].to_string(),
+        links: fields[1].to_string(),
+        owner: fields[2].to_string(),
+        group: fields[3].to_string(),
+        size,
+        date,
+        name,
+        target,
+    })
+}
+
+pub fn color_perms(perms: &str) -> String {
+    perms
+        .chars()
+        .map(|c| match c {
+            'd' => c.to_string().paint(Role::Path).bold().to_string(),
+            'l' => c.to_string().paint(Role::Accent).bold().to_string(),
+            'r' => c.to_string().paint(Role::Warning).to_string(),
+            'w' => c.to_string().paint(Role::Error).to_string(),
+            'x' | 's' | 't' | 'S' | 'T' => c.to_string().paint(Role::Success).to_string(),
+            '-' => c.to_string().dimmed().to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+/// `ls -l` as a table: permissions, links, owner, group, size and date as
+/// ls printed them, names colored like the short listing.
+fn print_long_table(entries: &[LongEntry]) {
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let name = colorize_name(&entry.name, entry.perms.starts_with('d'), entry.perms.starts_with('l')).to_string();
+            let name = match &entry.target {
+                Some(target) => format!("{} {}", name, format!("→ {}", target).dimmed()),
+                None => name,
+            };
+            vec![
+                Cell::new(color_perms(&entry.perms), &entry.perms),
+                Cell::new(entry.links.dimmed(), &entry.links).right(),
+                Cell::new(entry.owner.paint(Role::Accent), &entry.owner),
+                Cell::new(entry.group.dimmed(), &entry.group),
+                Cell::new(&entry.size, &entry.size).right(),
+                Cell::new(entry.date.dimmed(), &entry.date),
+                Cell { text: name, width: entry.name_width(), right: false },
+            ]
+        })
+        .collect();
+    let headers = vec![("Perms", 0), ("#", 0), ("Owner", 0), ("Group", 0), ("Size", 0), ("Modified", 0), ("Name", 0)];
+    BoxTable { headers, rows }.print();
+}
+
+/// Names in as many columns as fit in `width`, filled top to bottom like
+/// `ls` does, colored the same way as the table.
+fn print_grid(entries: &[FileEntry], width: usize) {
+    const GAP: usize = 2;
+    let widths: Vec<usize> = entries.iter().map(|e| e.name.chars().count()).collect();
+    let count = entries.len();
+    // Most columns first; the first layout that fits wins
+    let narrowest = widths.iter().copied().min().unwrap_or(1).max(1);
+    let mut layout = (count.max(1), vec![widths.iter().copied().max().unwrap_or(0)]);
+    for cols in (2..=count.min(width / (narrowest + GAP) + 1)).rev() {
+        let rows = count.div_ceil(cols);
+        let col_widths: Vec<usize> = widths.chunks(rows).map(|c| c.iter().copied().max().unwrap_or(0)).collect();
+        if col_widths.iter().sum::<usize>() + GAP * (col_widths.len() - 1) <= width {
+            layout = (rows, col_widths);
+            break;
+        }
+    }
+    let (rows, col_widths) = layout;
+    for row in 0..rows {
+        let mut line = String::new();
+        for (col, col_width) in col_widths.iter().enumerate() {
+            let Some(entry) = entries.get(col * rows + row) else { break; };
+            if col > 0 {
+                line.push_str(&" ".repeat(GAP));
+            }
+            line.push_str(&colorize_name(&entry.name, entry.is_dir, entry.is_symlink).to_string());
+            // No trailing padding after the last name on the line
+            if entries.get((col + 1) * rows + row).is_some() {
+                line.push_str(&" ".repeat(col_width.saturating_sub(widths[col * rows + row])));
+            }
+        }
+        outln!("{}", line);
     }
-    
-    // Bottom border
-    println!("└{}┴{}┴{}┴{}┘",
-        "─".repeat(2),
-        "─".repeat(max_size_len.max(8)),
-        "─".repeat(19),
-        "─".repeat(max_name_len.max(20))
-    );
+}
+
+/// The short ls listing: type, size, modified and name, plus whichever of
+/// `columns` are configured.
+fn fancy_table(entries: &[FileEntry], columns: &[ListColumn]) -> BoxTable {
+    // No owner or link count outside unix; see `owner_and_group`
+    let show = |c| columns.contains(&c) && (cfg!(unix) || !matches!(c, ListColumn::Owner | ListColumn::Links));
+    let mut headers = vec![("T", 2)];
+    if show(ListColumn::Mode) {
+        headers.push(("Mode", 0));
+    }
+    if show(ListColumn::Links) {
+        headers.push(("#", 0));
+    }
+    if show(ListColumn::Owner) {
+        headers.extend([("Owner", 0), ("Group", 0)]);
+    }
+    headers.extend([("Size", 8), ("Modified", 19), ("Name", 20)]);
+
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let file_type = if entry.is_dir {
+                "d".paint(Role::Path).bold()
+            } else if entry.is_symlink {
+                "l".paint(Role::Accent).bold()
+            } else {
+                "-".dimmed()
+            };
+            let mut row = vec![Cell::new(file_type, "-")];
+            if let Some(md) = &entry.metadata {
+                if show(ListColumn::Mode) {
+                    let mode = mode_string(md);
+                    row.push(Cell::new(color_perms(&mode), &mode));
+                }
+                if show(ListColumn::Links) {
+                    let links = link_count(md).to_string();
+                    row.push(Cell::new(links.dimmed(), &links).right());
+                }
+                if show(ListColumn::Owner) {
+                    let (owner, group) = owner_and_group(md);
+                    row.push(Cell::new(owner.paint(Role::Accent), &owner));
+                    row.push(Cell::new(group.dimmed(), &group));
+                }
+            }
+            // Unreadable entries still fill every column
+            while row.len() < headers.len() - 3 {
+                row.push(Cell::new("-".dimmed(), "-"));
+            }
+
+            let size = match entry.size {
+                Some(s) if !entry.is_dir => locale::format_bytes(s),
+                _ => "-".to_string(),
+            };
+            let modified = entry.modified.map(|t| locale::format_modified(t, false)).unwrap_or_else(|| String::from("-"));
+            row.push(Cell::new(size.dimmed(), &size));
+            row.push(Cell::new(modified.dimmed(), &modified));
+
+            let mut name = Cell::new(colorize_name(&entry.name, entry.is_dir, entry.is_symlink), &entry.name);
+            if columns.contains(&ListColumn::Target)
+                && let Some(target) = &entry.target
+            {
+                let arrow = format!("→ {}", target);
+                name.text = format!("{} {}", name.text, arrow.dimmed());
+                name.width += 1 + arrow.chars().count();
+            }
+            row.push(name);
+            row
+        })
+        .collect();
+    BoxTable { headers, rows }
 }
 
 fn colorize_name(name: &str, is_dir: bool, is_symlink: bool) -> colored::ColoredString {
     if is_dir {
-        name.truecolor(140, 180, 255).bold()
+        name.paint(Role::Path).bold()
     } else if is_symlink {
-        name.truecolor(200, 150, 255)
+        name.paint(Role::Accent)
     } else {
         let path = Path::new(name);
         match path.extension().and_then(|e| e.to_str()) {
-            Some("rs") => name.truecolor(255, 150, 180),
-            Some("md") => name.truecolor(240, 160, 255),
-            Some("toml") => name.truecolor(255, 220, 150),
-            Some("png") | Some("jpg") | Some("jpeg") | Some("gif") => name.truecolor(150, 255, 180),
-            Some("zip") | Some("tar") | Some("gz") => name.truecolor(255, 220, 150),
-            Some("sh") => name.truecolor(150, 255, 180),
+            Some("rs") => name.paint(Role::Source),
+            Some("md") => name.paint(Role::Docs),
+            Some("toml") => name.paint(Role::Data),
+            Some("png") | Some("jpg") | Some("jpeg") | Some("gif") => name.paint(Role::Media),
+            Some("zip") | Some("tar") | Some("gz") => name.paint(Role::Data),
+            Some("sh") => name.paint(Role::Source),
             _ => name.normal(),
         }
     }
@@ -262,7 +629,7 @@ fn strip_ansi_codes(s: &str) -> String {
     out
 }
 
-fn visible_width(s: &str) -> usize {
+pub fn visible_width(s: &str) -> usize {
     strip_ansi_codes(s).chars().count()
 }
 
@@ -294,7 +661,7 @@ fn truncate_visual(s: &str, width: usize) -> String {
 
 fn format_cat_output(args: &[String], output: &Output) -> io::Result<()> {
     if !output.status.success() {
-        io::stderr().write_all(&output.stderr)?;
+        stdio::stderr().write_all(&output.stderr)?;
         return Ok(());
     }
 
@@ -325,7 +692,7 @@ fn print_rust_like(content: &str) {
     let num_width = max_line_num.to_string().len().max(4);
     
     // Top border
-    println!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
+    outln!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
     
     for (i, line) in lines.iter().enumerate() {
         let num = format!("{:width$}", i + 1, width = num_width);
@@ -334,11 +701,11 @@ fn print_rust_like(content: &str) {
         // Truncate long lines for display
         let display_line = truncate_visual(&highlighted, 80);
         let pad = 80usize.saturating_sub(visible_width(&display_line));
-        println!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
+        outln!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
     }
     
     // Bottom border
-    println!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
+    outln!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
 }
 
 fn highlight_rust_line(line: &str) -> String {
@@ -368,7 +735,7 @@ fn highlight_rust_line(line: &str) -> String {
             continue;
         } else if ch == '"' && in_string {
             in_string = false;
-            result.push_str(&format!("{}{}", current_word.truecolor(150, 255, 180), "\"".truecolor(150, 255, 180)));
+            result.push_str(&format!("{}{}", current_word.paint(Role::String), "\"".paint(Role::String)));
             current_word.clear();
             continue;
         }
@@ -397,7 +764,7 @@ fn highlight_rust_line(line: &str) -> String {
     
         if !current_word.is_empty() {
             if in_string {
-                result.push_str(&current_word.truecolor(150, 255, 180).to_string());
+                result.push_str(&current_word.paint(Role::String).to_string());
             } else {
                 result.push_str(&colorize_rust_token(&current_word));
             }
@@ -412,11 +779,11 @@ fn colorize_rust_token(token: &str) -> String {
                      "true", "false", "self", "Self", "async", "await", "const", "static"];
     
     if keywords.contains(&token) {
-        token.truecolor(140, 180, 255).bold().to_string()
+        token.paint(Role::Path).bold().to_string()
     } else if token.parse::<i64>().is_ok() || token.parse::<f64>().is_ok() {
-        token.truecolor(255, 220, 150).to_string()
-    } else if token.starts_with('&') {
-        format!("&{}", token[1..].truecolor(200, 150, 255))
+        token.paint(Role::Variable).to_string()
+    } else if let Some(rest) = token.strip_prefix('&') {
+        format!("&{}", rest.paint(Role::Command))
     } else {
         token.to_string()
     }
@@ -427,21 +794,21 @@ fn print_toml_like(content: &str) {
     let max_line_num = lines.len();
     let num_width = max_line_num.to_string().len().max(4);
     
-    println!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
+    outln!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
     
     for (i, line) in lines.iter().enumerate() {
         let num = format!("{:width$}", i + 1, width = num_width);
         let colored = if line.trim_start().starts_with('[') {
-            line.truecolor(200, 150, 255).bold().to_string()
+            line.paint(Role::Command).bold().to_string()
         } else if line.trim_start().starts_with('#') {
             line.bright_black().to_string()
         } else if line.contains('=') {
             let parts: Vec<&str> = line.splitn(2, '=').collect();
             if parts.len() == 2 {
                 format!("{}{}{}", 
-                    parts[0].trim().truecolor(255, 220, 150).bold(),
+                    parts[0].trim().paint(Role::Variable).bold(),
                     " = ".dimmed(),
-                    parts[1].trim().truecolor(150, 255, 180)
+                    parts[1].trim().paint(Role::String)
                 )
             } else {
                 line.to_string()
@@ -452,16 +819,16 @@ fn print_toml_like(content: &str) {
         
         let display_line = truncate_visual(&colored, 80);
         let pad = 80usize.saturating_sub(visible_width(&display_line));
-        println!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
+        outln!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
     }
     
-    println!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
+    outln!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
 }
 
 fn print_json_like(content: &str) {
     for (i, line) in content.lines().enumerate() {
         let num = format!("{:4}", i + 1);
-        println!("{} {}", num.dimmed(), line);
+        outln!("{} {}", num.dimmed(), line);
     }
 }
 
@@ -473,59 +840,74 @@ fn print_shell_like(content: &str) {
         } else {
             line.to_string()
         };
-        println!("{} {}", num.dimmed(), colored);
+        outln!("{} {}", num.dimmed(), colored);
+    }
+}
+
+/// Render a builtin's documentation page, man-page style.
+pub fn print_help_page(page: &HelpPage) {
+    outln!("{} - {}", page.name.paint(Role::Command).bold(), page.summary);
+    if !page.aliases.is_empty() {
+        outln!("{} {}", "Also:".bright_black(), page.aliases.join(", "));
+    }
+    outln!("\n{}\n  {}", "Usage".bold().underline(), page.usage);
+    outln!("\n{}", "Description".bold().underline());
+    for line in wrap_words(page.description, 76) {
+        outln!("  {}", line);
+    }
+    if !page.options.is_empty() {
+        outln!("\n{}", "Options".bold().underline());
+        let width = page.options.iter().map(|(flag, _)| flag.chars().count()).max().unwrap_or(0);
+        for (flag, text) in page.options {
+            let pad = width - flag.chars().count();
+            outln!("  {}{}  {}", flag.paint(Role::Path), " ".repeat(pad), text);
+        }
+    }
+    if !page.examples.is_empty() {
+        outln!("\n{}", "Examples".bold().underline());
+        for example in page.examples {
+            outln!("  {} {}", "$".bright_black(), example.paint(Role::String));
+        }
     }
 }
 
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
 fn print_with_line_numbers(content: &str) {
     let lines: Vec<&str> = content.lines().collect();
     let max_line_num = lines.len();
     let num_width = max_line_num.to_string().len().max(4);
     
-    println!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
+    outln!("┌{}┬{}┐", "─".repeat(num_width), "─".repeat(80));
     
     for (i, line) in lines.iter().enumerate() {
         let num = format!("{:width$}", i + 1, width = num_width);
         let display_line = truncate_visual(line, 80);
         let pad = 80usize.saturating_sub(visible_width(&display_line));
-        println!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
+        outln!("│{}│{}{}│", num.bright_black().bold(), display_line, " ".repeat(pad));
     }
     
-    println!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
+    outln!("└{}┴{}┘", "─".repeat(num_width), "─".repeat(80));
 }
 
 fn format_generic_output(output: &Output) -> io::Result<()> {
-    io::stdout().write_all(&output.stdout)?;
-    io::stderr().write_all(&output.stderr)?;
+    stdio::stdout().write_all(&output.stdout)?;
+    stdio::stderr().write_all(&output.stderr)?;
     Ok(())
 }
-
-fn format_cargo_output(_args: &[String], output: &Output) -> io::Result<()> {
-    // Colorize cargo/rustc diagnostics: warnings yellow, errors red, help cyan, code lines dimmed
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-
-    for line in stdout.lines() {
-        println!("{}", colorize_diagnostic_line(line));
-    }
-    for line in stderr.lines() {
-        eprintln!("{}", colorize_diagnostic_line(line));
-    }
-    Ok(())
-}
-
-fn colorize_diagnostic_line(line: &str) -> String {
-    let trimmed = line.trim_start();
-    if trimmed.starts_with("error[") || trimmed.starts_with("error:") {
-        line.truecolor(255, 120, 180).bold().to_string()
-    } else if trimmed.starts_with("warning[") || trimmed.starts_with("warning:") {
-        line.truecolor(255, 220, 150).bold().to_string()
-    } else if trimmed.starts_with("help:") || trimmed.starts_with("note:") {
-        line.truecolor(180, 160, 255).to_string()
-    } else if trimmed.starts_with("--> ") || trimmed.starts_with("| ") || trimmed.starts_with("  = ") {
-        line.dimmed().to_string()
-    } else {
-        line.to_string()
-    }
-}
-
@@ -0,0 +1,135 @@
+//! zsh-style glob qualifiers: a parenthesised suffix on a glob that filters
+//! and orders its matches. `*(.)` is plain files, `*(/)` directories,
+//! `*(om[1])` the newest entry, `*.log(OL[1,3])` the three largest logs.
+
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// `.`
+    File,
+    /// `/`
+    Dir,
+    /// `@`
+    Symlink,
+    /// `*`
+    Executable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    /// `n`
+    Name,
+    /// `m`: newest first with `o`
+    Modified,
+    /// `L`
+    Size,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Qualifiers {
+    kinds: Vec<Kind>,
+    /// Key and whether it's reversed (`O` rather than `o`)
+    sort: Option<(SortKey, bool)>,
+    /// `[n]` or `[from,to]`, 1-based and inclusive; negative counts from
+    /// the end
+    range: Option<(i64, i64)>,
+}
+
+/// Split `pattern(qualifiers)` into its parts. `None` when the word doesn't
+/// end in something that parses as qualifiers, so a literal `(1)` in a
+/// file name is left alone.
+pub fn split(word: &str) -> Option<(&str, Qualifiers)> {
+    let body = word.strip_suffix(')')?;
+    let open = body.rfind('(')?;
+    let (pattern, spec) = (&body[..open], &body[open + 1..]);
+    if pattern.is_empty() || !pattern.contains(['*', '?', '[']) {
+        return None;
+    }
+    parse(spec).map(|qualifiers| (pattern, qualifiers))
+}
+
+fn parse(spec: &str) -> Option<Qualifiers> {
+    let mut qualifiers = Qualifiers::default();
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => qualifiers.kinds.push(Kind::File),
+            '/' => qualifiers.kinds.push(Kind::Dir),
+            '@' => qualifiers.kinds.push(Kind::Symlink),
+            '*' => qualifiers.kinds.push(Kind::Executable),
+            'o' | 'O' => {
+                let key = match chars.next()? {
+                    'n' => SortKey::Name,
+                    'm' => SortKey::Modified,
+                    'L' => SortKey::Size,
+                    _ => return None,
+                };
+                qualifiers.sort = Some((key, c == 'O'));
+            }
+            '[' => {
+                let range: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let (from, to) = range.split_once(',').unwrap_or((&range, &range));
+                qualifiers.range = Some((from.trim().parse().ok()?, to.trim().parse().ok()?));
+            }
+            _ => return None,
+        }
+    }
+    (!spec.is_empty()).then_some(qualifiers)
+}
+
+impl Qualifiers {
+    /// Keep the matches of every kind asked for, sort them, then take the
+    /// range.
+    pub fn apply(&self, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+        let mut entries: Vec<(PathBuf, Option<Metadata>)> = paths
+            .into_iter()
+            .map(|path| {
+                let metadata = fs::symlink_metadata(&path).ok();
+                (path, metadata)
+            })
+            .filter(|(path, metadata)| self.kinds.iter().all(|kind| matches_kind(*kind, path, metadata.as_ref())))
+            .collect();
+
+        if let Some((key, reversed)) = self.sort {
+            entries.sort_by(|(a, a_md), (b, b_md)| {
+                let order = match key {
+                    SortKey::Name => a.cmp(b),
+                    // Newest first, as zsh's `om`
+                    SortKey::Modified => modified(b_md).cmp(&modified(a_md)),
+                    SortKey::Size => size(a_md).cmp(&size(b_md)),
+                };
+                let order = order.then_with(|| a.cmp(b));
+                if reversed { order.reverse() } else { order }
+            });
+        }
+
+        let mut paths: Vec<PathBuf> = entries.into_iter().map(|(path, _)| path).collect();
+        if let Some((from, to)) = self.range {
+            let len = paths.len() as i64;
+            let index = |n: i64| if n < 0 { len + n } else { n - 1 };
+            let (from, to) = (index(from).max(0), index(to).min(len - 1));
+            paths = if from > to { Vec::new() } else { paths.drain(from as usize..=to as usize).collect() };
+        }
+        paths
+    }
+}
+
+fn matches_kind(kind: Kind, path: &Path, metadata: Option<&Metadata>) -> bool {
+    let Some(metadata) = metadata else { return false };
+    match kind {
+        Kind::File => metadata.is_file(),
+        Kind::Dir => metadata.is_dir(),
+        Kind::Symlink => metadata.is_symlink(),
+        Kind::Executable => metadata.is_file() && crate::lookup::is_executable(path),
+    }
+}
+
+fn modified(metadata: &Option<Metadata>) -> Option<std::time::SystemTime> {
+    metadata.as_ref().and_then(|m| m.modified().ok())
+}
+
+fn size(metadata: &Option<Metadata>) -> u64 {
+    metadata.as_ref().map_or(0, Metadata::len)
+}
@@ -0,0 +1,138 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use rustyline::ExternalPrinter;
+
+use crate::config;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const LARGE_HISTORY_BYTES: u64 = 5_000_000;
+
+/// Shell state captured when a prompt is drawn, so tips never need to touch
+/// the live `Shell` from the idle thread.
+#[derive(Debug, Clone, Default)]
+pub struct IdleSnapshot {
+    pub running_jobs: usize,
+    pub history_bytes: u64,
+}
+
+impl IdleSnapshot {
+    pub fn capture(running_jobs: usize) -> Self {
+        let history_bytes = config::history_file()
+            .and_then(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        Self { running_jobs, history_bytes }
+    }
+}
+
+/// A source of one-line tips shown after the prompt has sat idle.
+pub trait IdleTip: Send {
+    fn tip(&self, snapshot: &IdleSnapshot) -> Option<String>;
+}
+
+struct RunningJobsTip;
+
+impl IdleTip for RunningJobsTip {
+    fn tip(&self, snapshot: &IdleSnapshot) -> Option<String> {
+        match snapshot.running_jobs {
+            0 => None,
+            1 => Some(String::from("1 background job running (see `jobs`)")),
+            n => Some(format!("{} background jobs running (see `jobs`)", n)),
+        }
+    }
+}
+
+struct HistorySizeTip;
+
+impl IdleTip for HistorySizeTip {
+    fn tip(&self, snapshot: &IdleSnapshot) -> Option<String> {
+        if snapshot.history_bytes < LARGE_HISTORY_BYTES {
+            return None;
+        }
+        let size = crate::locale::format_bytes(snapshot.history_bytes);
+        Some(format!("history file is {}, consider trimming ~/.config/squish/history", size))
+    }
+}
+
+pub fn default_tips() -> Vec<Box<dyn IdleTip>> {
+    vec![Box::new(RunningJobsTip), Box::new(HistorySizeTip)]
+}
+
+#[derive(Default)]
+struct PromptState {
+    active: Option<IdleSnapshot>,
+    last_activity: Option<Instant>,
+    line_empty: bool,
+    shown: bool,
+}
+
+/// Shared between the REPL loop, the line helper (which reports keystrokes),
+/// and the idle thread.
+#[derive(Default)]
+pub struct IdleState {
+    inner: Mutex<PromptState>,
+}
+
+impl IdleState {
+    pub fn prompt_shown(&self, snapshot: IdleSnapshot) {
+        if let Ok(mut s) = self.inner.lock() {
+            *s = PromptState {
+                active: Some(snapshot),
+                last_activity: Some(Instant::now()),
+                line_empty: true,
+                shown: false,
+            };
+        }
+    }
+
+    pub fn prompt_left(&self) {
+        if let Ok(mut s) = self.inner.lock() {
+            s.active = None;
+        }
+    }
+
+    pub fn touch(&self, line_empty: bool) {
+        if let Ok(mut s) = self.inner.lock() {
+            s.last_activity = Some(Instant::now());
+            s.line_empty = line_empty;
+        }
+    }
+}
+
+/// Print at most one tip per prompt once it has been idle for `after`.
+pub fn spawn_idle_hints<P>(mut printer: P, state: Arc<IdleState>, after: Duration, tips: Vec<Box<dyn IdleTip>>)
+where
+    P: ExternalPrinter + Send + 'static,
+{
+    let _ = thread::Builder::new()
+        .name("squish-idle".to_string())
+        .spawn(move || {
+            let mut next = 0usize;
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                let snapshot = {
+                    let Ok(mut s) = state.inner.lock() else { return; };
+                    let idle_for = s.last_activity.map(|t| t.elapsed()).unwrap_or_default();
+                    if s.shown || !s.line_empty || idle_for < after {
+                        continue;
+                    }
+                    let Some(snapshot) = s.active.clone() else { continue; };
+                    s.shown = true;
+                    snapshot
+                };
+                // Rotate through providers so one noisy tip doesn't hide the rest
+                let found = (0..tips.len())
+                    .map(|i| (next + i) % tips.len())
+                    .find_map(|i| tips[i].tip(&snapshot).map(|t| (i, t)));
+                if let Some((i, tip)) = found {
+                    next = i + 1;
+                    if printer.print(format!("{} {}", "tip:".bright_black(), tip.bright_black())).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+}
@@ -1,16 +1,218 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
 use std::process::Child;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::signals;
+
+/// Fallback wakeup for the reaper; normally SIGCHLD wakes it right away.
+const REAP_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a job waits on: a spawned program, which for a whole pipeline is
+/// a `squish -c` running it.
+pub enum JobProcess {
+    Child(Child),
+}
+
+impl JobProcess {
+    fn wait(&mut self) -> i32 {
+        match self {
+            JobProcess::Child(child) => child.wait().ok().and_then(|s| s.code()).unwrap_or(1),
+        }
+    }
+
+    /// Wait until the process exits, or stops (Ctrl-Z, SIGTTIN): `None`.
+    #[cfg(unix)]
+    fn wait_or_stop(&mut self) -> Option<i32> {
+        let pid = match self {
+            JobProcess::Child(child) => child.id(),
+        };
+        wait_untraced(pid).unwrap_or(Some(1))
+    }
+
+    /// Windows has no stopped processes, so `fg` just waits.
+    #[cfg(not(unix))]
+    fn wait_or_stop(&mut self) -> Option<i32> {
+        Some(self.wait())
+    }
+
+    /// Without process groups or SIGHUP, the nearest thing to a hangup:
+    /// TerminateProcess.
+    #[cfg(not(unix))]
+    fn terminate(&mut self) {
+        let JobProcess::Child(child) = self;
+        let _ = child.kill();
+    }
+
+    fn try_wait(&mut self) -> std::io::Result<Option<i32>> {
+        match self {
+            JobProcess::Child(child) => Ok(child.try_wait()?.map(|s| s.code().unwrap_or(1))),
+        }
+    }
+}
+
+/// waitpid with WUNTRACED; `Ok(None)` if it stopped rather than exited.
+#[cfg(unix)]
+fn wait_untraced(pid: u32) -> std::io::Result<Option<i32>> {
+    let mut status = 0;
+    loop {
+        let ret = unsafe { libc::waitpid(pid as libc::pid_t, &mut status, libc::WUNTRACED) };
+        if ret > 0 {
+            if libc::WIFSTOPPED(status) {
+                return Ok(None);
+            }
+            let code = if libc::WIFEXITED(status) { libc::WEXITSTATUS(status) } else { 128 + libc::WTERMSIG(status) };
+            return Ok(Some(code));
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Job {
     pub id: usize,
+    pub pid: u32,
+    /// Process group the job runs in (its own, so Ctrl-C doesn't reach it)
+    pub pgid: u32,
     pub command: String,
-    pub child: Arc<Mutex<Option<Child>>>,
+    /// Label given with `jobs --name`, usable as `%name`
+    pub name: Option<String>,
+    pub started: Instant,
+    /// Left running when the shell exits instead of getting SIGHUP
+    pub nohup: bool,
+    /// File the job's output goes to, instead of the terminal
+    pub output: Option<PathBuf>,
+    pub child: Arc<Mutex<Option<JobProcess>>>,
+    pub exit_status: Arc<Mutex<Option<i32>>>,
+    /// When the exit was noticed, for the runtime of finished jobs
+    pub ended: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Job {
+    pub fn is_running(&self) -> bool {
+        self.child.lock().map(|c| c.is_some()).unwrap_or(false)
+    }
+
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status.lock().ok().and_then(|s| *s)
+    }
+
+    /// Whether the job is stopped (Ctrl-Z, SIGSTOP, or a read from the
+    /// terminal while in the background).
+    pub fn is_stopped(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", self.pid))
+            && let Some(end) = stat.rfind(')')
+        {
+            return stat[end + 1..].trim_start().starts_with('T');
+        }
+        #[cfg(all(unix, not(target_os = "linux")))]
+        if let Some(state) = ps_fields(self.pid, "stat=") {
+            return state.starts_with('T');
+        }
+        false
+    }
+
+    /// `Running`, `Stopped`, `Done`, or `Done(<code>)` for a non-zero exit.
+    pub fn state_label(&self) -> String {
+        if self.is_running() {
+            return String::from(if self.is_stopped() { "Stopped" } else { "Running" });
+        }
+        match self.exit_status() {
+            Some(0) | None => String::from("Done"),
+            Some(code) => format!("Done({})", code),
+        }
+    }
+
+    fn record_exit(&self, code: i32) {
+        if let Ok(mut s) = self.exit_status.lock() {
+            *s = Some(code);
+        }
+        if let Ok(mut ended) = self.ended.lock() {
+            ended.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// How long the job has been running, or ran for.
+    pub fn runtime(&self) -> Duration {
+        let ended = self.ended.lock().ok().and_then(|e| *e);
+        ended.unwrap_or_else(Instant::now).saturating_duration_since(self.started)
+    }
+
+    /// Block until the job exits and return its status.
+    pub fn wait(&self) -> Option<i32> {
+        if let Ok(mut child_opt) = self.child.lock()
+            && let Some(mut child) = child_opt.take()
+        {
+            let code = child.wait();
+            self.record_exit(code);
+        }
+        self.exit_status()
+    }
+
+    /// Send `signal` to the job's whole process group.
+    pub fn signal(&self, signal: i32) {
+        #[cfg(unix)]
+        unsafe {
+            libc::killpg(self.pgid as libc::pid_t, signal);
+        }
+        #[cfg(not(unix))]
+        let _ = signal;
+    }
+
+    /// `fg`: hand the job the terminal, continue it if it was stopped, and
+    /// wait until it exits (`Some(status)`) or is stopped again (`None`).
+    pub fn foreground(&self) -> Option<i32> {
+        let Ok(mut child_opt) = self.child.lock() else { return self.exit_status(); };
+        let Some(process) = child_opt.as_mut() else { return self.exit_status(); };
+        let attached = signals::give_terminal_to(self.pgid);
+        #[cfg(unix)]
+        self.signal(libc::SIGCONT);
+        let result = process.wait_or_stop();
+        if attached {
+            signals::reclaim_terminal();
+        }
+        if let Some(code) = result {
+            *child_opt = None;
+            self.record_exit(code);
+        }
+        result
+    }
+
+    /// Collect the child if it has exited, recording its status. Returns true
+    /// once the job is no longer running. Never blocks on a held lock (e.g.
+    /// while `fg` is waiting on the child).
+    fn reap(&self) -> bool {
+        let Ok(mut child_opt) = self.child.try_lock() else { return false; };
+        if let Some(child) = child_opt.as_mut() {
+            match child.try_wait() {
+                Ok(Some(code)) => {
+                    self.record_exit(code);
+                    *child_opt = None;
+                }
+                Ok(None) => return false,
+                Err(_) => *child_opt = None,
+            }
+        }
+        true
+    }
 }
 
 pub struct JobManager {
     jobs: Vec<Job>,
     next_id: usize,
+    finished: HashMap<usize, i32>,
+    /// Captured output of jobs that were already reported and removed
+    finished_output: HashMap<usize, PathBuf>,
+    watched: Arc<Mutex<Vec<Job>>>,
+    reaper_started: bool,
 }
 
 impl JobManager {
@@ -18,21 +220,139 @@ impl JobManager {
         Self {
             jobs: Vec::new(),
             next_id: 1,
+            finished: HashMap::new(),
+            finished_output: HashMap::new(),
+            watched: Arc::new(Mutex::new(Vec::new())),
+            reaper_started: false,
         }
     }
 
     pub fn add_job(&mut self, command: String, child: Child) -> usize {
+        let pid = child.id();
+        self.track(command, pid, JobProcess::Child(child))
+    }
+
+    fn track(&mut self, command: String, pid: u32, process: JobProcess) -> usize {
         let id = self.next_id;
         self.next_id += 1;
         let job = Job {
             id,
+            pid,
+            pgid: pid,
             command,
-            child: Arc::new(Mutex::new(Some(child))),
+            name: None,
+            started: Instant::now(),
+            nohup: false,
+            output: None,
+            child: Arc::new(Mutex::new(Some(process))),
+            exit_status: Arc::new(Mutex::new(None)),
+            ended: Arc::new(Mutex::new(None)),
         };
+        if let Ok(mut watched) = self.watched.lock() {
+            watched.push(job.clone());
+        }
         self.jobs.push(job);
+        self.start_reaper();
         id
     }
 
+    /// Background children are collected from a helper thread, woken by
+    /// SIGCHLD, so they never linger as zombies between `jobs` invocations.
+    fn start_reaper(&mut self) {
+        if self.reaper_started {
+            return;
+        }
+        let watched = Arc::clone(&self.watched);
+        let spawned = thread::Builder::new()
+            .name("squish-reaper".to_string())
+            .spawn(move || loop {
+                signals::wait_for_child_exit(REAP_INTERVAL);
+                if let Ok(mut jobs) = watched.lock() {
+                    jobs.retain(|job| !job.reap());
+                }
+            });
+        self.reaper_started = spawned.is_ok();
+    }
+
+    /// `jobs --name`: label a job so `%name` finds it. Returns false if
+    /// there is no such job.
+    pub fn set_name(&mut self, id: usize, name: &str) -> bool {
+        self.get_job(id).map(|job| job.name = Some(name.to_string())).is_some()
+    }
+
+    /// `disown -h`: keep the job running when the shell exits.
+    pub fn set_nohup(&mut self, id: usize) -> bool {
+        self.get_job(id).map(|job| job.nohup = true).is_some()
+    }
+
+    pub fn set_output(&mut self, id: usize, path: PathBuf) {
+        if let Some(job) = self.get_job(id) {
+            job.output = Some(path);
+        }
+    }
+
+    /// Where the output of job `id` was captured, while listed or after.
+    pub fn output_file(&self, id: usize) -> Option<&PathBuf> {
+        self.jobs
+            .iter()
+            .find(|job| job.id == id)
+            .and_then(|job| job.output.as_ref())
+            .or_else(|| self.finished_output.get(&id))
+    }
+
+    /// Delete captured output files; `nohup` logs are kept for after exit.
+    pub fn discard_output(&mut self) {
+        let listed = self.jobs.iter().filter(|job| !job.nohup).filter_map(|job| job.output.as_ref());
+        for path in listed.chain(self.finished_output.values()) {
+            let _ = std::fs::remove_file(path);
+        }
+        self.finished_output.clear();
+    }
+
+    /// `disown`: drop the job from the table. It is still reaped, but no
+    /// longer listed, waited for, or hung up on exit.
+    pub fn disown(&mut self, id: usize) -> bool {
+        let before = self.jobs.len();
+        self.jobs.retain(|job| job.id != id);
+        self.jobs.len() != before
+    }
+
+    /// On exit, send SIGHUP to the process group of every running job not
+    /// marked `nohup` (and SIGCONT, in case it was stopped), like a login
+    /// shell's hangup. Returns how many jobs were kept running.
+    pub fn hang_up(&mut self) -> usize {
+        let mut kept = 0;
+        for job in self.jobs.iter().filter(|job| job.is_running()) {
+            if job.nohup {
+                kept += 1;
+                continue;
+            }
+            #[cfg(unix)]
+            unsafe {
+                libc::killpg(job.pgid as libc::pid_t, libc::SIGHUP);
+                libc::killpg(job.pgid as libc::pid_t, libc::SIGCONT);
+            }
+            #[cfg(not(unix))]
+            if let Ok(mut process) = job.child.lock()
+                && let Some(process) = process.as_mut()
+            {
+                process.terminate();
+            }
+        }
+        kept
+    }
+
+    /// A fresh file for the output of the next job, named after the time,
+    /// shell, job number and program.
+    pub fn open_log(&self, command: &str) -> std::io::Result<(PathBuf, File)> {
+        let dir = config::job_log_dir().ok_or_else(|| std::io::Error::other("no state directory"))?;
+        let program = command.split_whitespace().next().and_then(|w| w.rsplit('/').next()).unwrap_or("job");
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let path = dir.join(format!("{}-{}-{}-{}.log", stamp, std::process::id(), self.next_id, program));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok((path, file))
+    }
+
     pub fn list_jobs(&self) -> &[Job] {
         &self.jobs
     }
@@ -41,24 +361,133 @@ impl JobManager {
         self.jobs.iter_mut().find(|j| j.id == id)
     }
 
+    /// Exit status of a job that has already been reported and removed.
+    pub fn finished_status(&self, id: usize) -> Option<i32> {
+        self.finished.get(&id).copied()
+    }
+
     pub fn remove_finished(&mut self) {
-        self.jobs.retain(|job| {
-            if let Ok(mut child_opt) = job.child.lock() {
-                if let Some(ref mut child) = *child_opt {
-                    if let Ok(Some(_)) = child.try_wait() {
-                        *child_opt = None;
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
+        self.take_finished();
+    }
+
+    /// Remove jobs that have exited, remembering their statuses for `wait`.
+    pub fn take_finished(&mut self) -> Vec<Job> {
+        let (done, running): (Vec<Job>, Vec<Job>) = self.jobs.drain(..).partition(|job| job.reap());
+        self.jobs = running;
+        for job in &done {
+            self.finished.insert(job.id, job.exit_status().unwrap_or(0));
+            if let Some(path) = &job.output
+                && !job.nohup
+            {
+                self.finished_output.insert(job.id, path.clone());
+            }
+        }
+        done
+    }
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accept `%1`, `1`, bare `%` / `%%` / `%+` for the most recent job, `%-`
+/// for the one before it, `%name` for a job labelled `name` or else the
+/// latest job whose command starts with `name`, and `%?text` for one whose
+/// command contains `text`.
+pub fn parse_job_spec(spec: &str, jobs: &[Job]) -> Option<usize> {
+    match spec {
+        "%" | "%%" | "%+" => jobs.last().map(|j| j.id),
+        "%-" => jobs.iter().rev().nth(1).or(jobs.last()).map(|j| j.id),
+        _ => {
+            let name = spec.strip_prefix('%').unwrap_or(spec);
+            if let Ok(id) = name.parse() {
+                return Some(id);
+            }
+            if !spec.starts_with('%') {
+                return None;
             }
-            true
-        });
+            let found = match name.strip_prefix('?') {
+                Some(text) => jobs.iter().rev().find(|j| j.command.contains(text)),
+                None => jobs
+                    .iter()
+                    .rev()
+                    .find(|j| j.name.as_deref() == Some(name))
+                    .or_else(|| jobs.iter().rev().find(|j| j.command.starts_with(name))),
+            };
+            found.map(|j| j.id)
+        }
     }
 }
 
+/// `12s`, `3m 05s`, `2h 14m`: a job's runtime at a glance.
+pub fn format_runtime(runtime: Duration) -> String {
+    let secs = runtime.as_secs();
+    match secs {
+        ..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+pub struct JobUsage {
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+}
+
+/// Sample CPU% (averaged over the process lifetime) and resident memory for a pid.
+#[cfg(target_os = "linux")]
+pub fn sample_usage(pid: u32) -> Option<JobUsage> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let comm_end = stat.rfind(')')?;
+    let fields: Vec<&str> = stat[comm_end + 1..].split_whitespace().collect();
+    // Fields after the comm, offset by 3 (pid, comm, state): utime=14, stime=15, starttime=22, rss=24
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    let starttime: u64 = fields.get(19)?.parse().ok()?;
+    let rss_pages: u64 = fields.get(21)?.parse().ok()?;
 
+    let clock_ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+    if clock_ticks <= 0.0 {
+        return None;
+    }
+    let uptime: f64 = std::fs::read_to_string("/proc/uptime").ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let elapsed = uptime - starttime as f64 / clock_ticks;
+    let cpu_secs = (utime + stime) as f64 / clock_ticks;
+    let cpu_percent = if elapsed > 0.0 { cpu_secs / elapsed * 100.0 } else { 0.0 };
 
+    Some(JobUsage {
+        cpu_percent,
+        rss_bytes: rss_pages * page_size,
+    })
+}
 
+/// Without /proc (macOS, the BSDs), ps reports the same: its CPU% is a
+/// recent average rather than a lifetime one, and RSS is in kilobytes.
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn sample_usage(pid: u32) -> Option<JobUsage> {
+    let fields = ps_fields(pid, "%cpu=,rss=")?;
+    let mut fields = fields.split_whitespace();
+    let cpu_percent: f64 = fields.next()?.parse().ok()?;
+    let rss_kb: u64 = fields.next()?.parse().ok()?;
+    Some(JobUsage { cpu_percent, rss_bytes: rss_kb * 1024 })
+}
 
+/// `ps -o <format> -p <pid>`, trimmed; None once the process is gone.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps_fields(pid: u32, format: &str) -> Option<String> {
+    let output = std::process::Command::new("ps").args(["-o", format, "-p", &pid.to_string()]).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+#[cfg(not(unix))]
+pub fn sample_usage(_pid: u32) -> Option<JobUsage> {
+    None
+}
@@ -0,0 +1,234 @@
+//! `lastout`: what the last command line printed, kept so it can be
+//! searched or reused without running the command again. With
+//! `lastout_kb` set, the shell's stdout goes through a pseudo terminal for
+//! the whole session; a thread copies it to the real terminal and keeps
+//! what arrives between the markers the shell writes around each command
+//! line. The markers are private OSC sequences and never reach the
+//! terminal. The text is saved, without colors, to the file named by
+//! `$SQUISH_LAST_OUTPUT_FILE`.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+#[cfg(unix)]
+use std::{fs::File, io::Read};
+
+#[cfg(unix)]
+use crate::{signals, spawn};
+
+const MARK_START: &[u8] = b"\x1b]7770;squish-lastout;";
+const MARK_END: u8 = b'\x07';
+
+struct Tap {
+    path: PathBuf,
+    /// Command lines marked so far; the pump reports each one it saves
+    marked: u64,
+    #[cfg(unix)]
+    restore: spawn::StdioRestore,
+    pump: std::thread::JoinHandle<()>,
+}
+
+static TAP: Mutex<Option<Tap>> = Mutex::new(None);
+/// The last command line whose output the pump has saved
+static SAVED: Mutex<u64> = Mutex::new(0);
+static SAVED_CHANGED: Condvar = Condvar::new();
+static DONE: AtomicBool = AtomicBool::new(false);
+
+/// `$SQUISH_LAST_OUTPUT_FILE`, while output is kept.
+pub fn path() -> Option<PathBuf> {
+    TAP.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|t| t.path.clone())
+}
+
+/// Start keeping up to `max_kb` of each command line's output. Does
+/// nothing when it's 0 or stdout isn't a terminal.
+#[cfg(unix)]
+pub fn start(max_kb: u64) -> io::Result<()> {
+    let mut tap = TAP.lock().unwrap_or_else(|e| e.into_inner());
+    if max_kb == 0 || tap.is_some() {
+        return Ok(());
+    }
+    let (master, slave) = spawn::open_pty_like(libc::STDOUT_FILENO)?;
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("squish-{}-lastout", std::process::id()));
+    write_private(&path, b"")?;
+
+    let terminal = spawn::dup_fd(libc::STDOUT_FILENO)?;
+    let restore = spawn::redirect_stdio(Some(slave), None).map_err(|e| io::Error::other(e.to_string()))?;
+    let pump = {
+        let path = path.clone();
+        std::thread::Builder::new()
+            .name("squish-lastout".to_string())
+            .spawn(move || pump(master, terminal, &path, max_kb as usize * 1024))?
+    };
+    unsafe { std::env::set_var("SQUISH_LAST_OUTPUT_FILE", &path) };
+    *tap = Some(Tap { path, marked: 0, restore, pump });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start(_max_kb: u64) -> io::Result<()> {
+    Ok(())
+}
+
+/// Mark where a command line's output begins.
+pub fn begin() {
+    if TAP.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+        mark("begin");
+    }
+}
+
+/// Mark where it ends, and wait (briefly) for the pump to save it, so the
+/// next line's `lastout` reads this one's output.
+pub fn finish() {
+    let marked = {
+        let mut tap = TAP.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(tap) = tap.as_mut() else { return; };
+        tap.marked += 1;
+        tap.marked
+    };
+    mark(&format!("end;{}", marked));
+    let saved = SAVED.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = SAVED_CHANGED.wait_timeout_while(saved, Duration::from_secs(1), |saved| *saved < marked);
+}
+
+fn mark(what: &str) {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(MARK_START);
+    let _ = write!(stdout, "{}", what);
+    let _ = stdout.write_all(&[MARK_END]);
+    let _ = stdout.flush();
+}
+
+/// Put the real stdout back and remove the file, when the shell exits.
+pub fn stop() {
+    let Some(tap) = TAP.lock().unwrap_or_else(|e| e.into_inner()).take() else { return; };
+    #[cfg(unix)]
+    drop(tap.restore);
+    DONE.store(true, Ordering::SeqCst);
+    let _ = tap.pump.join();
+    let _ = std::fs::remove_file(&tap.path);
+}
+
+/// Copy the pty's output to the terminal, minus markers; between a begin
+/// and an end marker, keep the last `max_bytes` of it and save them.
+#[cfg(unix)]
+fn pump(mut master: File, mut terminal: File, path: &std::path::Path, max_bytes: usize) {
+    let mut buf = [0u8; 8192];
+    // Read but not yet passed on: possibly the start of a marker
+    let mut pending: Vec<u8> = Vec::new();
+    let mut kept: Option<Vec<u8>> = None;
+    let resizes = signals::watch_resizes();
+    loop {
+        let finishing = DONE.load(Ordering::SeqCst);
+        if !spawn::wait_pty_output(&master, &terminal, resizes.as_ref(), if finishing { 0 } else { 100 }) {
+            if finishing {
+                break;
+            }
+            continue;
+        }
+        let n = match master.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        pending.extend_from_slice(&buf[..n]);
+
+        loop {
+            let Some(start) = find(&pending, MARK_START) else {
+                // Hold back a tail that could be the start of a marker
+                let hold = (1..MARK_START.len().min(pending.len() + 1))
+                    .rev()
+                    .find(|&len| pending.ends_with(&MARK_START[..len]))
+                    .unwrap_or(0);
+                let out: Vec<u8> = pending.drain(..pending.len() - hold).collect();
+                pass_on(&out, &mut terminal, &mut kept, max_bytes);
+                break;
+            };
+            let Some(end) = pending[start..].iter().position(|&b| b == MARK_END).map(|i| start + i) else {
+                let out: Vec<u8> = pending.drain(..start).collect();
+                pass_on(&out, &mut terminal, &mut kept, max_bytes);
+                break;
+            };
+            let before: Vec<u8> = pending.drain(..start).collect();
+            pass_on(&before, &mut terminal, &mut kept, max_bytes);
+            let marker = String::from_utf8_lossy(&pending[MARK_START.len()..end - start]).into_owned();
+            pending.drain(..end - start + 1);
+            if marker == "begin" {
+                kept = Some(Vec::new());
+            } else if let Some(number) = marker.strip_prefix("end;").and_then(|n| n.parse::<u64>().ok()) {
+                if let Some(output) = kept.take() {
+                    let _ = write_private(path, plain_text(&output).as_bytes());
+                }
+                *SAVED.lock().unwrap_or_else(|e| e.into_inner()) = number;
+                SAVED_CHANGED.notify_all();
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn pass_on(bytes: &[u8], terminal: &mut File, kept: &mut Option<Vec<u8>>, max_bytes: usize) {
+    if bytes.is_empty() {
+        return;
+    }
+    let _ = terminal.write_all(bytes);
+    if let Some(kept) = kept {
+        kept.extend_from_slice(bytes);
+        if kept.len() > max_bytes {
+            // Keep the end, from the first whole line
+            let cut = kept.len() - max_bytes;
+            let cut = kept[cut..].iter().position(|&b| b == b'\n').map_or(cut, |i| cut + i + 1);
+            kept.drain(..cut);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Output as text to grep: colors and other escapes dropped, and the
+/// terminal's `\r\n` back to `\n`.
+#[cfg(unix)]
+fn plain_text(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final letter
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() || c == '~' {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ST
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\r' if chars.peek() == Some(&'\n') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Write `contents` to `path`, readable only by the user.
+#[cfg(unix)]
+fn write_private(path: &std::path::Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
@@ -0,0 +1,57 @@
+//! squish as a library: the parser, expander, executor and config loading,
+//! usable headlessly by tests and other tools. The `squish` binary is a thin
+//! wrapper around [`repl::run_repl`].
+
+pub mod repl;
+pub mod shell;
+pub mod builtins;
+pub mod exec;
+pub mod error;
+pub mod completion;
+pub mod config;
+pub mod dirfreq;
+pub mod formatter;
+pub mod diagnostics;
+pub mod parser;
+pub mod jobs;
+pub mod aliases;
+pub mod shell_config;
+pub mod corrections;
+pub mod idle;
+pub mod locale;
+pub mod preview;
+pub mod resources;
+pub mod project;
+pub mod watch;
+pub mod notify;
+pub mod pathwatch;
+pub mod wizard;
+pub mod bookmarks;
+pub mod trash;
+pub mod calc;
+pub mod spawn;
+pub mod stdio;
+pub mod stats;
+pub mod docs;
+pub mod manpages;
+pub mod signals;
+pub mod segments;
+pub mod theme;
+pub mod suggest;
+pub mod lookup;
+pub mod targets;
+pub mod vars;
+pub mod glob_qualifiers;
+pub mod record;
+pub mod lastout;
+pub mod clipboard;
+pub mod sandbox;
+pub mod timeout;
+pub mod filter;
+pub mod plugins;
+pub mod completers;
+
+pub use crate::error::ShellError;
+pub use crate::parser::{parse_command_line, CommandPart};
+pub use crate::shell::Shell;
+pub use crate::shell_config::ShellConfig;
@@ -0,0 +1,131 @@
+use std::env;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+use humansize::{format_size, DECIMAL};
+
+const ISO_DATE: &str = "%Y-%m-%d %H:%M";
+
+/// How listings show modification times (`modified_format=`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModifiedFormat {
+    /// A timestamp in `date_format`
+    #[default]
+    Absolute,
+    /// `3 min ago`, `yesterday`, `2 months ago`
+    Relative,
+}
+
+impl ModifiedFormat {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "relative" => ModifiedFormat::Relative,
+            _ => ModifiedFormat::Absolute,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct LocaleOverride {
+    locale: Option<String>,
+    date_format: Option<String>,
+    modified_format: ModifiedFormat,
+}
+
+static OVERRIDE: OnceLock<LocaleOverride> = OnceLock::new();
+
+/// Apply config overrides (`locale=`, `date_format=`, `modified_format=`).
+/// Only the first call wins.
+pub fn configure(locale: Option<String>, date_format: Option<String>, modified_format: ModifiedFormat) {
+    let _ = OVERRIDE.set(LocaleOverride { locale, date_format, modified_format });
+}
+
+/// Resolve a locale category the way libc does: LC_ALL, then the category,
+/// then LANG.
+fn locale_for(category: &str) -> String {
+    if let Some(name) = OVERRIDE.get().and_then(|o| o.locale.clone()) {
+        return name;
+    }
+    for var in ["LC_ALL", category, "LANG"] {
+        if let Ok(v) = env::var(var)
+            && !v.is_empty()
+        {
+            return v;
+        }
+    }
+    String::from("C")
+}
+
+/// "de_DE.UTF-8" -> ("de", "DE")
+fn split_locale(name: &str) -> (String, String) {
+    let base = name.split(['.', '@']).next().unwrap_or_default();
+    let (lang, region) = base.split_once('_').unwrap_or((base, ""));
+    (lang.to_lowercase(), region.to_uppercase())
+}
+
+pub fn decimal_separator() -> char {
+    let (lang, region) = split_locale(&locale_for("LC_NUMERIC"));
+    match lang.as_str() {
+        "de" | "fr" | "es" | "it" | "pt" | "nl" | "ru" | "pl" | "cs" | "sk" | "sv" | "da" | "nb" | "nn" | "no"
+        | "fi" | "tr" | "uk" | "el" | "hu" | "ro" | "bg" | "hr" | "sl" | "sr" | "lt" | "lv" | "et" | "id" => ',',
+        "en" if region == "ZA" => ',',
+        _ => '.',
+    }
+}
+
+pub fn date_format() -> String {
+    if let Some(fmt) = OVERRIDE.get().and_then(|o| o.date_format.clone()) {
+        return fmt;
+    }
+    let (lang, region) = split_locale(&locale_for("LC_TIME"));
+    let fmt = match (lang.as_str(), region.as_str()) {
+        ("en", "US") => "%m/%d/%Y %H:%M",
+        ("en", "GB" | "IE" | "AU" | "NZ" | "IN") => "%d/%m/%Y %H:%M",
+        ("de" | "ru" | "pl" | "cs" | "sk" | "fi" | "nb" | "nn" | "no" | "tr" | "uk" | "ro" | "da", _) => "%d.%m.%Y %H:%M",
+        ("fr" | "es" | "it" | "pt" | "el", _) => "%d/%m/%Y %H:%M",
+        ("nl", _) => "%d-%m-%Y %H:%M",
+        _ => ISO_DATE,
+    };
+    fmt.to_string()
+}
+
+/// Swap the '.' in an already formatted number for the locale's separator.
+pub fn localize_decimal(formatted: &str) -> String {
+    let sep = decimal_separator();
+    if sep == '.' { formatted.to_string() } else { formatted.replace('.', &sep.to_string()) }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    localize_decimal(&format_size(bytes, DECIMAL))
+}
+
+pub fn format_datetime(time: std::time::SystemTime) -> String {
+    DateTime::<Local>::from(time).format(&date_format()).to_string()
+}
+
+/// A modification time for a listing, relative or absolute as configured.
+/// `exact` forces the timestamp.
+pub fn format_modified(time: std::time::SystemTime, exact: bool) -> String {
+    let relative = OVERRIDE.get().is_some_and(|o| o.modified_format == ModifiedFormat::Relative);
+    if relative && !exact { format_relative(time) } else { format_datetime(time) }
+}
+
+/// How long ago `time` was, in the largest unit that fits. Days count by
+/// the calendar, so last night is `yesterday` even if it was 3 hours ago.
+pub fn format_relative(time: std::time::SystemTime) -> String {
+    let then = DateTime::<Local>::from(time);
+    let now = Local::now();
+    let secs = (now - then).num_seconds();
+    let days = (now.date_naive() - then.date_naive()).num_days();
+    let plural = |n: i64, unit: &str| format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" });
+    match secs {
+        ..60 => String::from("just now"),
+        60..3600 => format!("{} min ago", secs / 60),
+        _ if days == 0 => plural(secs / 3600, "hour"),
+        _ if days == 1 => String::from("yesterday"),
+        _ if days < 7 => plural(days, "day"),
+        _ if days < 35 => plural(days / 7, "week"),
+        _ if days < 365 => plural((days / 30).max(1), "month"),
+        _ => plural(days / 365, "year"),
+    }
+}
@@ -0,0 +1,267 @@
+//! Command lookup through `$PATH`. squish searches the path itself instead
+//! of leaving it to the OS, so a name that isn't there can be told apart
+//! from one that is there but can't be run, and errors can name the file
+//! that was found. Resolved commands are remembered (see `hash`), and the
+//! full scan of every executable is the one completion draws from.
+//!
+//! On Windows, `$PATH` is `;`-separated and a file is executable by its
+//! extension: `git` finds `git.exe` through `$PATHEXT`, and completion
+//! lists it as `git`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::pathwatch;
+
+/// What a command name resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    Found(PathBuf),
+    /// A file by that name exists on `$PATH`, but none of them is executable
+    NotExecutable(PathBuf),
+    NotFound,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hashed {
+    pub path: PathBuf,
+    pub hits: u32,
+}
+
+#[derive(Default)]
+struct Table {
+    path_hash: u64,
+    generation: u64,
+    /// Every executable on `$PATH` by name, first directory wins
+    scanned: Option<Arc<HashMap<String, PathBuf>>>,
+    /// Names resolved for running, with how often they ran
+    hashed: BTreeMap<String, Hashed>,
+}
+
+impl Table {
+    /// Drop everything once `$PATH` is reassigned or one of its directories
+    /// changes: a new file may now shadow a remembered one.
+    fn sync(&mut self) {
+        let path_hash = hash_path();
+        let generation = pathwatch::generation();
+        if self.path_hash != path_hash || self.generation != generation {
+            self.path_hash = path_hash;
+            self.generation = generation;
+            self.scanned = None;
+            self.hashed.clear();
+        }
+    }
+}
+
+fn table() -> &'static Mutex<Table> {
+    static TABLE: OnceLock<Mutex<Table>> = OnceLock::new();
+    TABLE.get_or_init(|| Mutex::new(Table::default()))
+}
+
+fn hash_path() -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    if let Ok(path) = env::var("PATH") {
+        path.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH")
+        .map(|path| {
+            env::split_paths(&path)
+                // An empty entry means the current directory
+                .map(|dir| if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    if let Ok(metadata) = fs::metadata(path) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            return metadata.is_file() && metadata.permissions().mode() & 0o111 != 0;
+        }
+        #[cfg(not(unix))]
+        {
+            let Some(ext) = path.extension().and_then(|e| e.to_str()) else { return false; };
+            return metadata.is_file() && executable_extensions().iter().any(|known| known[1..].eq_ignore_ascii_case(ext));
+        }
+    }
+    false
+}
+
+/// `$PATHEXT`: the extensions Windows runs, such as `.EXE` and `.BAT`.
+#[cfg(not(unix))]
+fn executable_extensions() -> Vec<String> {
+    env::var("PATHEXT")
+        .unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"))
+        .split(';')
+        .filter(|ext| ext.starts_with('.') && ext.len() > 1)
+        .map(str::to_string)
+        .collect()
+}
+
+/// The files in `dir` that `name` could mean: just `dir/name` on Unix; on
+/// Windows also with each `$PATHEXT` extension, unless it has one.
+fn candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    #[cfg(not(unix))]
+    if Path::new(name).extension().is_none() {
+        return executable_extensions().iter().map(|ext| dir.join(format!("{}{}", name, ext))).collect();
+    }
+    vec![dir.join(name)]
+}
+
+/// Whether `name` is a path rather than a command name.
+pub fn is_path(name: &str) -> bool {
+    name.contains('/') || (cfg!(windows) && name.contains('\\'))
+}
+
+/// The name a command is typed as: `git` for `git.exe` on Windows.
+fn command_name(file_name: &str) -> &str {
+    #[cfg(not(unix))]
+    if let Some((stem, ext)) = file_name.rsplit_once('.')
+        && executable_extensions().iter().any(|known| known[1..].eq_ignore_ascii_case(ext))
+    {
+        return stem;
+    }
+    file_name
+}
+
+/// Walk `$PATH` for `name`, noting the first non-executable file in case
+/// nothing runnable turns up.
+fn search(name: &str) -> Lookup {
+    let mut blocked = None;
+    for dir in path_dirs() {
+        for candidate in candidates(&dir, name) {
+            if is_executable(&candidate) {
+                return Lookup::Found(candidate);
+            }
+            if blocked.is_none() && candidate.is_file() {
+                blocked = Some(candidate);
+            }
+        }
+    }
+    blocked.map_or(Lookup::NotFound, Lookup::NotExecutable)
+}
+
+fn lookup_in(table: &Table, name: &str) -> Lookup {
+    let known = table
+        .hashed
+        .get(name)
+        .map(|h| &h.path)
+        .or_else(|| table.scanned.as_ref().and_then(|s| s.get(name)));
+    match known {
+        // Still there? It may have been removed behind our back
+        Some(path) if is_executable(path) => Lookup::Found(path.clone()),
+        _ => search(name),
+    }
+}
+
+/// Resolve `name` for running it, remembering the result for next time.
+/// Paths are not looked up.
+pub fn resolve(name: &str) -> Lookup {
+    hash_name(name, 1)
+}
+
+/// Resolve and remember `name` without counting a run (`hash NAME`).
+pub fn remember(name: &str) -> Lookup {
+    hash_name(name, 0)
+}
+
+fn hash_name(name: &str, hits: u32) -> Lookup {
+    if is_path(name) {
+        return Lookup::Found(PathBuf::from(name));
+    }
+    let mut table = table().lock().unwrap();
+    table.sync();
+    let found = lookup_in(&table, name);
+    match &found {
+        Lookup::Found(path) => {
+            let entry = table.hashed.entry(name.to_string()).or_insert_with(|| Hashed { path: path.clone(), hits: 0 });
+            if entry.path != *path {
+                *entry = Hashed { path: path.clone(), hits: 0 };
+            }
+            entry.hits = entry.hits.saturating_add(hits);
+        }
+        _ => {
+            table.hashed.remove(name);
+        }
+    }
+    found
+}
+
+/// The executable `name` would run, without counting it as run.
+pub fn find(name: &str) -> Option<PathBuf> {
+    if is_path(name) {
+        return is_executable(Path::new(name)).then(|| PathBuf::from(name));
+    }
+    let mut table = table().lock().unwrap();
+    table.sync();
+    match lookup_in(&table, name) {
+        Lookup::Found(path) => Some(path),
+        _ => None,
+    }
+}
+
+/// Whether `name` is in the remembered table, for `type`.
+pub fn is_hashed(name: &str) -> bool {
+    let mut table = table().lock().unwrap();
+    table.sync();
+    table.hashed.contains_key(name)
+}
+
+/// Every executable on `$PATH`, scanning the directories on first use and
+/// again after they change. The same map is returned until then, so callers
+/// can tell a rescan apart with `Arc::ptr_eq`.
+pub fn commands() -> Arc<HashMap<String, PathBuf>> {
+    let mut table = table().lock().unwrap();
+    table.sync();
+    if let Some(scanned) = &table.scanned {
+        return Arc::clone(scanned);
+    }
+    let mut commands = HashMap::new();
+    for dir in path_dirs() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue; };
+        for entry in entries.flatten() {
+            if let Some(name) = entry.file_name().to_str().map(command_name)
+                && !commands.contains_key(name)
+            {
+                let path = entry.path();
+                if is_executable(&path) {
+                    commands.insert(name.to_string(), path);
+                }
+            }
+        }
+    }
+    pathwatch::watch_path_dirs();
+    let scanned = Arc::new(commands);
+    table.scanned = Some(Arc::clone(&scanned));
+    scanned
+}
+
+/// Remembered commands in name order, for `hash` with no arguments.
+pub fn hashed() -> Vec<(String, Hashed)> {
+    let mut table = table().lock().unwrap();
+    table.sync();
+    table.hashed.iter().map(|(name, h)| (name.clone(), h.clone())).collect()
+}
+
+/// Forget one remembered command. Returns false if it wasn't remembered.
+pub fn forget(name: &str) -> bool {
+    table().lock().unwrap().hashed.remove(name).is_some()
+}
+
+/// Forget every remembered command and the `$PATH` scan (`hash -r`).
+pub fn forget_all() {
+    let mut table = table().lock().unwrap();
+    table.hashed.clear();
+    table.scanned = None;
+}
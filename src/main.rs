@@ -1,23 +1,18 @@
-mod repl;
-mod shell;
-mod builtins;
-mod exec;
-mod error;
-mod completion;
-mod config;
-mod dirfreq;
-mod formatter;
-mod diagnostics;
-mod parser;
-mod jobs;
-mod aliases;
-mod shell_config;
+use squish::repl::{run_command, run_repl};
 
-use crate::repl::run_repl;
+use squish::ShellError;
 
 fn main() {
-    if let Err(err) = run_repl() {
-        eprintln!("squish: {}", err);
-        std::process::exit(1);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [flag, text] if flag == "-c" => run_command(text),
+        _ => run_repl(),
+    };
+    match result {
+        Ok(code) | Err(ShellError::Exit(code)) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("squish: {}", err);
+            std::process::exit(1);
+        }
     }
 }
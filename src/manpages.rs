@@ -0,0 +1,101 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// (page name, section) pairs from every man directory, sorted by name.
+pub struct ManIndex {
+    pub pages: Vec<(String, String)>,
+    manpath: String,
+}
+
+impl ManIndex {
+    /// Pages starting with `prefix`, optionally limited to sections starting
+    /// with `section` (so `3` also covers `3p`).
+    pub fn with_prefix<'a>(&'a self, prefix: &'a str, section: Option<&'a str>) -> impl Iterator<Item = &'a (String, String)> {
+        let start = self.pages.partition_point(|(name, _)| name.as_str() < prefix);
+        self.pages[start..]
+            .iter()
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .filter(move |(_, sect)| section.is_none_or(|s| sect.starts_with(s)))
+    }
+
+    pub fn sections(&self) -> BTreeSet<&str> {
+        self.pages.iter().map(|(_, s)| s.as_str()).collect()
+    }
+}
+
+/// The man page index, scanned on first use and again only when the
+/// manpath changes.
+pub fn index() -> Arc<ManIndex> {
+    static CACHE: OnceLock<Mutex<Option<Arc<ManIndex>>>> = OnceLock::new();
+    let manpath = manpath();
+    let mut cache = CACHE.get_or_init(|| Mutex::new(None)).lock().unwrap_or_else(|e| e.into_inner());
+    match cache.as_ref() {
+        Some(index) if index.manpath == manpath => Arc::clone(index),
+        _ => {
+            let index = Arc::new(scan(manpath));
+            *cache = Some(Arc::clone(&index));
+            index
+        }
+    }
+}
+
+/// `$MANPATH`, else what `manpath` reports, else the usual locations.
+fn manpath() -> String {
+    if let Ok(path) = std::env::var("MANPATH")
+        && !path.is_empty()
+        && !path.starts_with(':')
+        && !path.ends_with(':')
+    {
+        return path;
+    }
+    static SYSTEM: OnceLock<String> = OnceLock::new();
+    SYSTEM.get_or_init(system_manpath).clone()
+}
+
+/// What `manpath` reports, asked once rather than on every completion.
+fn system_manpath() -> String {
+    if let Ok(out) = Command::new("manpath").arg("-q").output()
+        && out.status.success()
+    {
+        let path = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        if !path.is_empty() {
+            return path;
+        }
+    }
+    String::from("/usr/local/share/man:/usr/share/man")
+}
+
+fn scan(manpath: String) -> ManIndex {
+    let mut pages = BTreeSet::new();
+    for root in manpath.split(':').filter(|d| !d.is_empty()) {
+        let Ok(entries) = fs::read_dir(root) else { continue; };
+        for entry in entries.flatten() {
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            // man1, man3p, ... ; skip cat* and localized trees
+            let Some(section) = dir_name.strip_prefix("man").filter(|s| s.starts_with(|c: char| c.is_ascii_alphanumeric())) else { continue; };
+            scan_section(&entry.path(), section, &mut pages);
+        }
+    }
+    ManIndex { pages: pages.into_iter().collect(), manpath }
+}
+
+fn scan_section(dir: &Path, section: &str, pages: &mut BTreeSet<(String, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else { return; };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let base = [".gz", ".bz2", ".xz", ".zst", ".lzma", ".Z"]
+            .iter()
+            .find_map(|ext| file_name.strip_suffix(ext))
+            .unwrap_or(&file_name);
+        // printf.3p -> ("printf", "3p"); the file's own suffix is the precise section
+        match base.rsplit_once('.') {
+            Some((name, sect)) if !name.is_empty() && sect.starts_with(section.chars().next().unwrap_or('_')) => {
+                pages.insert((name.to_string(), sect.to_string()));
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::locale;
+
+/// Send a desktop notification that `command` finished, unless the terminal
+/// running this shell is the focused window.
+pub fn command_finished(command: &str, elapsed_secs: f64, status: i32) {
+    if terminal_focused() == Some(true) || which::which("notify-send").is_err() {
+        return;
+    }
+    let summary = if status == 0 {
+        format!("✓ {}", command)
+    } else {
+        format!("✗ {} (exit {})", command, status)
+    };
+    let body = format!("finished in {}", format_elapsed(elapsed_secs));
+    let urgency = if status == 0 { "normal" } else { "critical" };
+
+    let child = Command::new("notify-send")
+        .args(["-a", "squish", "-u", urgency, &summary, &body])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    // Reap off-thread so a slow notification daemon never blocks the prompt
+    if let Ok(mut child) = child {
+        let _ = thread::Builder::new().name("squish-notify".to_string()).spawn(move || child.wait());
+    }
+}
+
+fn format_elapsed(secs: f64) -> String {
+    if secs < 60.0 {
+        locale::localize_decimal(&format!("{:.1}s", secs))
+    } else {
+        let secs = secs.round() as u64;
+        format!("{}m {}s", secs / 60, secs % 60)
+    }
+}
+
+/// Whether the focused window belongs to an ancestor of this process (our
+/// terminal). `None` when the window system can't be asked.
+fn terminal_focused() -> Option<bool> {
+    let focused_pid = active_window_pid()?;
+    let mut pid = std::process::id();
+    while pid > 1 {
+        if pid == focused_pid {
+            return Some(true);
+        }
+        pid = parent_pid(pid)?;
+    }
+    Some(false)
+}
+
+fn active_window_pid() -> Option<u32> {
+    if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        let out = Command::new("hyprctl").args(["activewindow", "-j"]).output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let rest = &text[text.find("\"pid\":")? + 6..];
+        let digits: String = rest.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+        return digits.parse().ok();
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        let out = Command::new("xdotool").args(["getactivewindow", "getwindowpid"]).output().ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        return String::from_utf8_lossy(&out.stdout).trim().parse().ok();
+    }
+    None
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces or parens; fields resume after the last ')'
+    let after = &stat[stat.rfind(')')? + 1..];
+    after.split_whitespace().nth(1)?.parse().ok()
+}
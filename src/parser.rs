@@ -1,12 +1,45 @@
 use crate::error::ShellError;
+use crate::glob_qualifiers;
+use crate::spawn;
+use crate::vars;
 
 #[derive(Debug, Clone)]
 pub enum CommandPart {
+    /// A command's words as written; [`expand_argv`] expands them when it
+    /// runs, after everything before it on the line
     Simple { argv: Vec<String>, background: bool },
     Pipe { left: Box<CommandPart>, right: Box<CommandPart> },
-    RedirectOut { cmd: Box<CommandPart>, file: String, append: bool },
-    RedirectIn { cmd: Box<CommandPart>, file: String },
+    /// A command with its `<`, `>`, `>>`, `2>` and `2>>` redirects, in the
+    /// order they were written
+    Redirected { cmd: Box<CommandPart>, redirects: Vec<Redirect> },
     Chain { left: Box<CommandPart>, right: Box<CommandPart>, and: bool },
+    Timed { cmd: Box<CommandPart> },
+    /// `! pipeline`: inverts the pipeline's exit status
+    Not { cmd: Box<CommandPart> },
+    /// `left; right`
+    Sequence { left: Box<CommandPart>, right: Box<CommandPart> },
+    /// `( list )`: runs in a separate squish, as `squish -c text`, so what
+    /// it changes doesn't reach this one
+    Subshell { text: String },
+    /// `{ list; }`: runs in the current shell
+    Group { cmd: Box<CommandPart> },
+    /// `pipeline &` for anything but a simple command: the whole thing runs
+    /// as one job, in a separate squish like a subshell. `text` is also how
+    /// `jobs` shows it.
+    Background { text: String },
+}
+
+#[derive(Debug, Clone)]
+/// Targets are words as written, like a simple command's; see
+/// [`expand_redirect_target`].
+pub enum Redirect {
+    /// `< file`
+    In(String),
+    /// `> file`, `>> file`; `force` for `>|`, which overwrites even with
+    /// noclobber set
+    Out { file: String, append: bool, force: bool },
+    /// `2> file`, `2>> file`, `2>| file`
+    Err { file: String, append: bool, force: bool },
 }
 
 pub fn parse_command_line(input: &str) -> Result<CommandPart, ShellError> {
@@ -14,28 +47,101 @@ pub fn parse_command_line(input: &str) -> Result<CommandPart, ShellError> {
     parse_tokens(&tokens)
 }
 
+// Where quotes were, kept in words as private-use characters: expansion
+// needs to know which parts were quoted, and the quotes themselves go.
+const SINGLE_OPEN: char = '\u{E000}';
+const SINGLE_CLOSE: char = '\u{E001}';
+const DOUBLE_OPEN: char = '\u{E002}';
+const DOUBLE_CLOSE: char = '\u{E003}';
+
+fn is_quote_mark(c: char) -> bool {
+    matches!(c, SINGLE_OPEN | SINGLE_CLOSE | DOUBLE_OPEN | DOUBLE_CLOSE)
+}
+
+/// A word as typed, minus its quotes.
+fn unquoted(word: &str) -> String {
+    word.chars().filter(|c| !is_quote_mark(*c)).collect()
+}
+
+/// A word with its quotes back, for handing to `sh -c`.
+fn requoted(word: &str) -> String {
+    word.chars()
+        .map(|c| match c {
+            SINGLE_OPEN | SINGLE_CLOSE => '\'',
+            DOUBLE_OPEN | DOUBLE_CLOSE => '"',
+            c => c,
+        })
+        .collect()
+}
+
+/// `arg` as a word that reads back as exactly `arg`: bare when the lexer
+/// would keep it as one plain word with nothing to expand, otherwise in
+/// single quotes. There's no backslash escape, so a `'` inside becomes
+/// `'"'"'`.
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && !arg.contains(|c: char| c.is_control() || "'\"\\$`*?[]{}()~#!".contains(c))
+        && matches!(tokenize(arg).as_deref(), Ok([Token::Word(word)]) if word == arg);
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\"'\"'"))
+    }
+}
+
 fn tokenize(input: &str) -> Result<Vec<Token>, ShellError> {
+    Ok(lex(input).0)
+}
+
+/// Tokenizer state left at the end of the input, for telling whether a
+/// line is complete.
+struct LexState {
+    in_single: bool,
+    in_double: bool,
+    subst_depth: usize,
+    trailing_backslash: bool,
+}
+
+fn lex(input: &str) -> (Vec<Token>, LexState) {
     let mut tokens = Vec::new();
     let mut current = String::new();
     let mut chars = input.chars().peekable();
     let mut in_single = false;
     let mut in_double = false;
+    // Parentheses opened inside a word (`$(...)`) keep everything up to the
+    // matching `)` in that word
+    let mut subst_depth = 0usize;
+    // `"$(...)"`: the command inside has quotes of its own, so the outer
+    // double quote is set aside until the matching `)`
+    let mut outer_double = Vec::new();
 
     while let Some(ch) = chars.next() {
+        let literal = in_single || in_double || subst_depth > 0;
         match ch {
+            // Inside `$(...)` quotes are the inner command's to interpret
             '\'' if !in_double => {
+                current.push(match (subst_depth > 0, in_single) {
+                    (true, _) => '\'',
+                    (false, false) => SINGLE_OPEN,
+                    (false, true) => SINGLE_CLOSE,
+                });
                 in_single = !in_single;
             }
             '"' if !in_single => {
+                current.push(match (subst_depth > 0, in_double) {
+                    (true, _) => '"',
+                    (false, false) => DOUBLE_OPEN,
+                    (false, true) => DOUBLE_CLOSE,
+                });
                 in_double = !in_double;
             }
-            ' ' | '\t' if !in_single && !in_double => {
+            ' ' | '\t' if !literal => {
                 if !current.is_empty() {
                     tokens.push(Token::Word(current.clone()));
                     current.clear();
                 }
             }
-            '|' if !in_single && !in_double => {
+            '|' if !literal => {
                 if !current.is_empty() {
                     tokens.push(Token::Word(current.clone()));
                     current.clear();
@@ -47,7 +153,7 @@ fn tokenize(input: &str) -> Result<Vec<Token>, ShellError> {
                     tokens.push(Token::Pipe);
                 }
             }
-            '&' if !in_single && !in_double => {
+            '&' if !literal => {
                 if !current.is_empty() {
                     tokens.push(Token::Word(current.clone()));
                     current.clear();
@@ -59,34 +165,185 @@ fn tokenize(input: &str) -> Result<Vec<Token>, ShellError> {
                     tokens.push(Token::Background);
                 }
             }
-            '>' if !in_single && !in_double => {
+            '>' if !literal => {
+                // `2>` redirects stderr; the 2 isn't a word of its own
+                let stderr = current == "2";
+                if stderr {
+                    current.clear();
+                } else if !current.is_empty() {
+                    tokens.push(Token::Word(current.clone()));
+                    current.clear();
+                }
+                let append = chars.next_if_eq(&'>').is_some();
+                // `>|` overwrites despite noclobber
+                let force = !append && chars.next_if_eq(&'|').is_some();
+                tokens.push(match (stderr, append, force) {
+                    (false, false, false) => Token::RedirectOut,
+                    (false, false, true) => Token::RedirectForce,
+                    (false, true, _) => Token::RedirectAppend,
+                    (true, false, false) => Token::RedirectErr,
+                    (true, false, true) => Token::RedirectErrForce,
+                    (true, true, _) => Token::RedirectErrAppend,
+                });
+            }
+            '<' if !literal => {
                 if !current.is_empty() {
                     tokens.push(Token::Word(current.clone()));
                     current.clear();
                 }
-                if chars.peek() == Some(&'>') {
-                    chars.next();
-                    tokens.push(Token::RedirectAppend);
+                tokens.push(Token::RedirectIn);
+            }
+            '(' if !in_single && (!in_double || current.ends_with('$')) => {
+                if subst_depth == 0 && current.is_empty() {
+                    tokens.push(Token::LParen);
                 } else {
-                    tokens.push(Token::RedirectOut);
+                    current.push('(');
+                    subst_depth += 1;
+                    outer_double.push(std::mem::take(&mut in_double));
+                }
+            }
+            ')' if !in_single && !in_double && subst_depth > 0 => {
+                current.push(')');
+                subst_depth -= 1;
+                in_double = outer_double.pop().unwrap_or(false);
+            }
+            '\n' if !literal => {
+                // `\` at the end of a line continues it
+                if current.ends_with('\\') {
+                    current.pop();
+                } else if !current.is_empty() || matches!(tokens.last(), Some(Token::Word(_) | Token::RParen | Token::Background)) {
+                    // Otherwise a newline ends the command, like `;`
+                    if !current.is_empty() {
+                        tokens.push(Token::Word(current.clone()));
+                        current.clear();
+                    }
+                    tokens.push(Token::Semi);
                 }
             }
-            '<' if !in_single && !in_double => {
+            ')' | ';' if !literal => {
                 if !current.is_empty() {
                     tokens.push(Token::Word(current.clone()));
                     current.clear();
                 }
-                tokens.push(Token::RedirectIn);
+                tokens.push(if ch == ')' { Token::RParen } else { Token::Semi });
             }
             _ => current.push(ch),
         }
     }
 
+    let trailing_backslash = current.ends_with('\\');
     if !current.is_empty() {
         tokens.push(Token::Word(current));
     }
 
-    Ok(tokens)
+    (tokens, LexState { in_single, in_double, subst_depth, trailing_backslash })
+}
+
+/// `input` without its whole-line `#` comments, as a pasted block
+/// often has; a `#` line inside a quote is part of the quoted text.
+pub fn strip_comment_lines(input: &str) -> String {
+    let mut kept = String::new();
+    for line in input.split('\n') {
+        let (_, state) = lex(&kept);
+        let in_word = state.in_single || state.in_double || state.subst_depth > 0;
+        if !in_word && line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !kept.is_empty() {
+            kept.push('\n');
+        }
+        kept.push_str(line);
+    }
+    kept
+}
+
+/// Why a command line needs more input, shown in the continuation prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuation {
+    SingleQuote,
+    DoubleQuote,
+    Backslash,
+    Pipe,
+    And,
+    Or,
+    Subshell,
+    CommandSubstitution,
+    Group,
+}
+
+impl Continuation {
+    /// zsh-style names: `quote>`, `pipe>`, `subsh>`, ...
+    pub fn label(self) -> &'static str {
+        match self {
+            Continuation::SingleQuote => "quote",
+            Continuation::DoubleQuote => "dquote",
+            Continuation::Backslash => "",
+            Continuation::Pipe => "pipe",
+            Continuation::And => "and",
+            Continuation::Or => "or",
+            Continuation::Subshell => "subsh",
+            Continuation::CommandSubstitution => "cmdsubst",
+            Continuation::Group => "group",
+        }
+    }
+}
+
+/// What `input` leaves open, judged by the tokenizer that runs it so the
+/// REPL only executes lines the parser can finish: a quote or `$(`, a
+/// trailing `\`, `|`, `&&` or `||`, or an unclosed `( )` or `{ }`.
+pub fn incomplete_reason(input: &str) -> Option<Continuation> {
+    let (tokens, state) = lex(input);
+    if state.in_single {
+        return Some(Continuation::SingleQuote);
+    }
+    if state.in_double {
+        return Some(Continuation::DoubleQuote);
+    }
+    if state.subst_depth > 0 {
+        return Some(Continuation::CommandSubstitution);
+    }
+    if state.trailing_backslash {
+        return Some(Continuation::Backslash);
+    }
+    match tokens.last() {
+        Some(Token::Pipe) => return Some(Continuation::Pipe),
+        Some(Token::And) => return Some(Continuation::And),
+        Some(Token::Or) => return Some(Continuation::Or),
+        _ => {}
+    }
+
+    // `{` and `}` only open and close groups where a command starts
+    let mut open = Vec::new();
+    let mut command_start = true;
+    for token in &tokens {
+        match token {
+            Token::LParen => open.push(Closer::Paren),
+            Token::RParen if open.last() == Some(&Closer::Paren) => {
+                open.pop();
+            }
+            Token::Word(w) if command_start && w == "{" => open.push(Closer::Brace),
+            Token::Word(w) if command_start && w == "}" && open.last() == Some(&Closer::Brace) => {
+                open.pop();
+            }
+            _ => {}
+        }
+        command_start = match token {
+            Token::Word(w) => command_start && w == "{",
+            Token::RParen
+            | Token::RedirectOut
+            | Token::RedirectForce
+            | Token::RedirectAppend
+            | Token::RedirectIn
+            | Token::RedirectErr
+            | Token::RedirectErrForce
+            | Token::RedirectErrAppend => false,
+            _ => true,
+        };
+    }
+    open.pop().map(|closer| match closer {
+        Closer::Paren => Continuation::Subshell,
+        Closer::Brace => Continuation::Group,
+    })
 }
 
 #[derive(Debug, Clone)]
@@ -94,11 +351,18 @@ enum Token {
     Word(String),
     Pipe,
     RedirectOut,
+    RedirectForce,
     RedirectAppend,
     RedirectIn,
+    RedirectErr,
+    RedirectErrForce,
+    RedirectErrAppend,
     And,
     Or,
     Background,
+    Semi,
+    LParen,
+    RParen,
 }
 
 fn parse_tokens(tokens: &[Token]) -> Result<CommandPart, ShellError> {
@@ -106,10 +370,86 @@ fn parse_tokens(tokens: &[Token]) -> Result<CommandPart, ShellError> {
         return Err(ShellError::Other("empty command".to_string()));
     }
 
+    let (cmd, used) = parse_list(tokens, None)?;
+    match tokens.get(used) {
+        None => Ok(cmd),
+        Some(token) => Err(ShellError::Other(format!("syntax error near unexpected {}", describe(token)))),
+    }
+}
+
+/// Where a nested list ends: `)` for a subshell, a `}` word for a group.
+#[derive(Clone, Copy, PartialEq)]
+enum Closer {
+    Paren,
+    Brace,
+}
+
+fn closes(token: &Token, closer: Option<Closer>) -> bool {
+    match (token, closer) {
+        (Token::RParen, Some(Closer::Paren)) => true,
+        (Token::Word(w), Some(Closer::Brace)) => w == "}",
+        _ => false,
+    }
+}
+
+/// Chains separated by `;`, stopping before `closer` or anything else that
+/// can't start a command.
+fn parse_list(tokens: &[Token], closer: Option<Closer>) -> Result<(CommandPart, usize), ShellError> {
+    let mut parts = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() && !closes(&tokens[i], closer) {
+        // Blank lines inside a group
+        if matches!(tokens[i], Token::Semi) {
+            i += 1;
+            continue;
+        }
+        let (part, used) = parse_timed(&tokens[i..])?;
+        let text = render(&tokens[i..i + used]);
+        i += used;
+        match tokens.get(i) {
+            Some(Token::Semi) => {
+                parts.push(part);
+                i += 1;
+            }
+            // `&` backgrounds everything since the previous separator
+            Some(Token::Background) => {
+                parts.push(match part {
+                    CommandPart::Simple { argv, .. } => CommandPart::Simple { argv, background: true },
+                    _ => CommandPart::Background { text },
+                });
+                i += 1;
+            }
+            _ => {
+                parts.push(part);
+                break;
+            }
+        }
+    }
+
+    let mut parts = parts.into_iter();
+    let Some(mut result) = parts.next() else {
+        return Err(ShellError::Other("empty command".to_string()));
+    };
+    for right in parts {
+        result = CommandPart::Sequence { left: Box::new(result), right: Box::new(right) };
+    }
+    Ok((result, i))
+}
+
+fn parse_timed(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
+    // `time` is a keyword: it wraps the whole pipeline/chain after it
+    if let [Token::Word(word), rest @ ..] = tokens
+        && word == "time"
+        && matches!(rest.first(), Some(Token::Word(_) | Token::LParen))
+    {
+        let (cmd, used) = parse_chain(rest)?;
+        return Ok((CommandPart::Timed { cmd: Box::new(cmd) }, used + 1));
+    }
     parse_chain(tokens)
 }
 
-fn parse_chain(tokens: &[Token]) -> Result<CommandPart, ShellError> {
+fn parse_chain(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
     let mut parts = Vec::new();
     let mut ops = Vec::new();
     let mut i = 0;
@@ -117,7 +457,7 @@ fn parse_chain(tokens: &[Token]) -> Result<CommandPart, ShellError> {
     while i < tokens.len() {
         let (part, next_i) = parse_pipe(&tokens[i..])?;
         parts.push(part);
-        i = next_i;
+        i += next_i;
 
         if i < tokens.len() {
             match &tokens[i] {
@@ -147,10 +487,57 @@ fn parse_chain(tokens: &[Token]) -> Result<CommandPart, ShellError> {
         };
     }
 
-    Ok(result)
+    Ok((result, i))
+}
+
+/// Tokens back as command line text.
+fn render(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Word(w) => requoted(w),
+            other => describe(other).trim_matches('`').to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(w) => format!("`{}`", unquoted(w)),
+        Token::Pipe => String::from("`|`"),
+        Token::RedirectOut => String::from("`>`"),
+        Token::RedirectForce => String::from("`>|`"),
+        Token::RedirectAppend => String::from("`>>`"),
+        Token::RedirectIn => String::from("`<`"),
+        Token::RedirectErr => String::from("`2>`"),
+        Token::RedirectErrForce => String::from("`2>|`"),
+        Token::RedirectErrAppend => String::from("`2>>`"),
+        Token::And => String::from("`&&`"),
+        Token::Or => String::from("`||`"),
+        Token::Background => String::from("`&`"),
+        Token::Semi => String::from("`;`"),
+        Token::LParen => String::from("`(`"),
+        Token::RParen => String::from("`)`"),
+    }
 }
 
 fn parse_pipe(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
+    // A leading `!` word negates the whole pipeline
+    if let [Token::Word(word), rest @ ..] = tokens
+        && word == "!"
+        && matches!(rest.first(), Some(Token::Word(_)))
+    {
+        let (cmd, used) = parse_pipe(rest)?;
+        return Ok((CommandPart::Not { cmd: Box::new(cmd) }, used + 1));
+    }
+    if let [Token::Word(word), ..] = tokens
+        && word == "!"
+        && !matches!(tokens.get(1), Some(Token::Word(_)))
+    {
+        return Err(ShellError::Other("`!` needs a command to negate".to_string()));
+    }
+
     let mut parts = Vec::new();
     let mut i = 0;
 
@@ -181,76 +568,89 @@ fn parse_pipe(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
     Ok((result, i))
 }
 
+/// A command with any number of redirects in any order, before or after
+/// it: `cmd > out 2> err < in`, `< in cmd`. Words after a redirect are
+/// more arguments for a simple command (`echo > out hi`).
 fn parse_redirect(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
-    if tokens.is_empty() {
+    let mut redirects = Vec::new();
+    let mut i = 0;
+    while let Some(used) = parse_one_redirect(&tokens[i..], &mut redirects)? {
+        i += used;
+    }
+    if tokens.len() <= i {
         return Err(ShellError::Other("empty command".to_string()));
     }
 
-    let (cmd, mut i) = parse_simple(&tokens)?;
+    let (mut cmd, used) = parse_compound(&tokens[i..])?;
+    i += used;
 
-    while i < tokens.len() {
-        match &tokens[i] {
-            Token::RedirectOut => {
-                i += 1;
-                if i >= tokens.len() {
-                    return Err(ShellError::Other("redirect output: missing filename".to_string()));
-                }
-                if let Token::Word(file) = &tokens[i] {
-                    let file = expand_tilde(file);
-                    return Ok((
-                        CommandPart::RedirectOut {
-                            cmd: Box::new(cmd),
-                            file,
-                            append: false,
-                        },
-                        i + 1,
-                    ));
-                } else {
-                    return Err(ShellError::Other("redirect output: expected filename".to_string()));
-                }
-            }
-            Token::RedirectAppend => {
-                i += 1;
-                if i >= tokens.len() {
-                    return Err(ShellError::Other("redirect append: missing filename".to_string()));
-                }
-                if let Token::Word(file) = &tokens[i] {
-                    let file = expand_tilde(file);
-                    return Ok((
-                        CommandPart::RedirectOut {
-                            cmd: Box::new(cmd),
-                            file,
-                            append: true,
-                        },
-                        i + 1,
-                    ));
-                } else {
-                    return Err(ShellError::Other("redirect append: expected filename".to_string()));
-                }
-            }
-            Token::RedirectIn => {
-                i += 1;
-                if i >= tokens.len() {
-                    return Err(ShellError::Other("redirect input: missing filename".to_string()));
-                }
-                if let Token::Word(file) = &tokens[i] {
-                    let file = expand_tilde(file);
-                    return Ok((
-                        CommandPart::RedirectIn {
-                            cmd: Box::new(cmd),
-                            file,
-                        },
-                        i + 1,
-                    ));
-                } else {
-                    return Err(ShellError::Other("redirect input: expected filename".to_string()));
+    loop {
+        if let Some(used) = parse_one_redirect(&tokens[i..], &mut redirects)? {
+            i += used;
+            continue;
+        }
+        match (&mut cmd, tokens.get(i)) {
+            (CommandPart::Simple { argv, .. }, Some(Token::Word(_))) if !redirects.is_empty() => {
+                let (more, used) = parse_simple(&tokens[i..])?;
+                if let CommandPart::Simple { argv: more, .. } = more {
+                    argv.extend(more);
                 }
+                i += used;
             }
             _ => break,
         }
     }
 
-    Ok((cmd, i))
+    if redirects.is_empty() {
+        return Ok((cmd, i));
+    }
+    Ok((CommandPart::Redirected { cmd: Box::new(cmd), redirects }, i))
+}
+
+/// A redirect and its target at the start of `tokens`, if there is one.
+/// Returns how many tokens it took.
+fn parse_one_redirect(tokens: &[Token], redirects: &mut Vec<Redirect>) -> Result<Option<usize>, ShellError> {
+    let what = match tokens.first() {
+        Some(Token::RedirectOut | Token::RedirectForce | Token::RedirectErr | Token::RedirectErrForce) => "redirect output",
+        Some(Token::RedirectAppend | Token::RedirectErrAppend) => "redirect append",
+        Some(Token::RedirectIn) => "redirect input",
+        _ => return Ok(None),
+    };
+    let file = match tokens.get(1) {
+        Some(Token::Word(file)) => file.clone(),
+        Some(_) => return Err(ShellError::Other(format!("{}: expected filename", what))),
+        None => return Err(ShellError::Other(format!("{}: missing filename", what))),
+    };
+    redirects.push(match tokens[0] {
+        Token::RedirectOut => Redirect::Out { file, append: false, force: false },
+        Token::RedirectForce => Redirect::Out { file, append: false, force: true },
+        Token::RedirectAppend => Redirect::Out { file, append: true, force: false },
+        Token::RedirectErr => Redirect::Err { file, append: false, force: false },
+        Token::RedirectErrForce => Redirect::Err { file, append: false, force: true },
+        Token::RedirectErrAppend => Redirect::Err { file, append: true, force: false },
+        _ => Redirect::In(file),
+    });
+    Ok(Some(2))
+}
+
+/// `( list )`, `{ list; }` or a simple command.
+fn parse_compound(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
+    let (closer, name) = match tokens.first() {
+        Some(Token::LParen) => (Closer::Paren, "`)`"),
+        Some(Token::Word(w)) if w == "{" => (Closer::Brace, "`}`"),
+        _ => return parse_simple(tokens),
+    };
+    let (cmd, used) = parse_list(&tokens[1..], Some(closer))?;
+    match tokens.get(used + 1) {
+        Some(token) if closes(token, Some(closer)) => {}
+        Some(token) => return Err(ShellError::Other(format!("syntax error near unexpected {}", describe(token)))),
+        None => return Err(ShellError::Other(format!("syntax error: missing {}", name))),
+    }
+    let part = match closer {
+        Closer::Paren => CommandPart::Subshell { text: render(&tokens[1..used + 1]) },
+        Closer::Brace => CommandPart::Group { cmd: Box::new(cmd) },
+    };
+    Ok((part, used + 2))
 }
 
 fn parse_simple(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
@@ -260,34 +660,38 @@ fn parse_simple(tokens: &[Token]) -> Result<(CommandPart, usize), ShellError> {
 
     let mut argv = Vec::new();
     let mut i = 0;
-    let mut background = false;
 
-    while i < tokens.len() {
-        match &tokens[i] {
-            Token::Word(word) => {
-                let expanded = expand_word_with_subst(word)?;
-                let globbed = expand_glob(&expanded);
-                if globbed.is_empty() {
-                    argv.push(expanded);
-                } else {
-                    argv.extend(globbed);
-                }
-                i += 1;
-            }
-            Token::Background => {
-                background = true;
-                i += 1;
-                break;
-            }
-            _ => break,
-        }
+    while let Some(Token::Word(word)) = tokens.get(i) {
+        argv.push(word.clone());
+        i += 1;
     }
 
     if argv.is_empty() {
         return Err(ShellError::Other("empty command".to_string()));
     }
 
-    Ok((CommandPart::Simple { argv, background }, i))
+    Ok((CommandPart::Simple { argv, background: false }, i))
+}
+
+/// Expand a simple command's words just before it runs, so it sees what
+/// the commands before it did: `cd src && ls $PWD`, `false | true; echo
+/// ${PIPESTATUS[0]}`.
+pub fn expand_argv(words: &[String]) -> Result<Vec<String>, ShellError> {
+    let mut argv = Vec::new();
+    for word in words {
+        // `NAME=$value` before the command is one word, split or not
+        let assignment = argv.iter().all(|a: &String| spawn::is_assignment(a)) && spawn::is_assignment(word);
+        for field in expand_word(word, !assignment)? {
+            match field.pattern.map(|pattern| expand_glob(&pattern)) {
+                Some(globbed) if !globbed.is_empty() => argv.extend(globbed),
+                _ => argv.push(field.text),
+            }
+        }
+    }
+    if argv.is_empty() {
+        return Err(ShellError::Other("empty command".to_string()));
+    }
+    Ok(argv)
 }
 
 fn expand_tilde(input: &str) -> String {
@@ -307,27 +711,61 @@ fn expand_tilde(input: &str) -> String {
     }
 }
 
-fn expand_word_with_subst(word: &str) -> Result<String, ShellError> {
+/// A redirect target gets the same expansion as an argument, but has to
+/// stay one word: a glob must match a single file, and an empty expansion
+/// is an error rather than a redirect to nowhere. A glob matching nothing
+/// is taken literally.
+pub fn expand_redirect_target(word: &str) -> Result<String, ShellError> {
+    let ambiguous = || ShellError::Other(format!("{}: ambiguous redirect", requoted(word)));
+    let [expanded] = <[Field; 1]>::try_from(expand_word(word, true)?).map_err(|_| ambiguous())?;
+    let mut globbed = expanded.pattern.map(|pattern| expand_glob(&pattern)).unwrap_or_default();
+    if expanded.text.is_empty() || globbed.len() > 1 {
+        return Err(ambiguous());
+    }
+    Ok(globbed.pop().unwrap_or(expanded.text))
+}
+
+/// Expand `~`, `$VAR`, `${VAR}`, `$(cmd)` and backticks in a word, except
+/// inside single quotes, and drop its quotes. What unquoted expansions
+/// produce is split into fields on `$IFS` (with `split`, unless
+/// `word_split=false`), so a word can become several arguments, or none
+/// when it expands to nothing.
+fn expand_word(word: &str, split: bool) -> Result<Vec<Field>, ShellError> {
     let s = expand_tilde(word);
-    let mut out = String::new();
+    let mut fields = Fields::new(if split && vars::word_split() { vars::ifs() } else { String::new() });
     let mut chars = s.chars().peekable();
-    
+    let mut quoted = false;
+    let mut single = false;
+
     while let Some(ch) = chars.next() {
-        if ch == '$' {
+        if is_quote_mark(ch) {
+            quoted = matches!(ch, SINGLE_OPEN | DOUBLE_OPEN);
+            single = ch == SINGLE_OPEN;
+            // `""` is an empty argument, not nothing
+            fields.started = true;
+        } else if single {
+            fields.push_literal(ch, true);
+        } else if ch == '$' {
             if let Some('(') = chars.peek().copied() {
                 chars.next();
                 let mut cmd_str = String::new();
                 let mut depth = 1;
+                let mut quote = None;
                 while let Some(c) = chars.next() {
-                    if c == '(' { depth += 1; }
-                    if c == ')' { 
-                        depth -= 1;
-                        if depth == 0 { break; }
+                    match (quote, c) {
+                        (None, '\'' | '"') => quote = Some(c),
+                        (Some(q), c) if c == q => quote = None,
+                        (None, '(') => depth += 1,
+                        (None, ')') => {
+                            depth -= 1;
+                            if depth == 0 { break; }
+                        }
+                        _ => {}
                     }
                     cmd_str.push(c);
                 }
-                let subst_output = execute_command_subst(&cmd_str)?;
-                out.push_str(&subst_output);
+                let subst_output = execute_command_subst(&requoted(&cmd_str))?;
+                fields.push_expansion(&subst_output, quoted);
             } else if let Some('{') = chars.peek().copied() {
                 chars.next();
                 let mut name = String::new();
@@ -335,16 +773,16 @@ fn expand_word_with_subst(word: &str) -> Result<String, ShellError> {
                     if c == '}' { break; }
                     name.push(c);
                 }
-                if let Ok(val) = std::env::var(&name) { out.push_str(&val); }
+                if let Some(val) = variable(&name) { fields.push_expansion(&val, quoted); }
             } else {
                 let mut name = String::new();
                 while let Some(&c) = chars.peek() {
                     if c.is_alphanumeric() || c == '_' { name.push(c); chars.next(); } else { break; }
                 }
                 if !name.is_empty() {
-                    if let Ok(val) = std::env::var(&name) { out.push_str(&val); }
+                    if let Some(val) = variable(&name) { fields.push_expansion(&val, quoted); }
                 } else {
-                    out.push('$');
+                    fields.push_literal('$', quoted);
                 }
             }
         } else if ch == '`' {
@@ -353,13 +791,95 @@ fn expand_word_with_subst(word: &str) -> Result<String, ShellError> {
                 if c == '`' { break; }
                 cmd_str.push(c);
             }
-            let subst_output = execute_command_subst(&cmd_str)?;
-            out.push_str(&subst_output);
+            let subst_output = execute_command_subst(&requoted(&cmd_str))?;
+            fields.push_expansion(&subst_output, quoted);
         } else {
-            out.push(ch);
+            fields.push_literal(ch, quoted);
         }
     }
-    Ok(out)
+    Ok(fields.finish())
+}
+
+/// One argument a word expands to.
+struct Field {
+    text: String,
+    /// The glob to expand it with, when it has an unquoted `*`, `?` or `[`;
+    /// quoted ones are escaped so `'*'.rs` only matches a file named `*.rs`
+    pattern: Option<String>,
+}
+
+/// The fields a word expands into, built up left to right.
+struct Fields {
+    ifs: String,
+    done: Vec<Field>,
+    current: String,
+    pattern: String,
+    glob: bool,
+    /// Whether `current` is a field even if empty
+    started: bool,
+}
+
+impl Fields {
+    fn new(ifs: String) -> Self {
+        Fields { ifs, done: Vec::new(), current: String::new(), pattern: String::new(), glob: false, started: false }
+    }
+
+    fn push_literal(&mut self, c: char, quoted: bool) {
+        self.current.push(c);
+        if quoted && matches!(c, '*' | '?' | '[' | ']') {
+            self.pattern.push_str(&glob::Pattern::escape(&c.to_string()));
+        } else {
+            self.pattern.push(c);
+            self.glob |= matches!(c, '*' | '?' | '[');
+        }
+        self.started = true;
+    }
+
+    fn end_field(&mut self) {
+        let pattern = std::mem::take(&mut self.pattern);
+        self.done.push(Field {
+            text: std::mem::take(&mut self.current),
+            pattern: std::mem::take(&mut self.glob).then_some(pattern),
+        });
+        self.started = false;
+    }
+
+    /// Runs of IFS whitespace separate fields and are dropped at the ends;
+    /// every other IFS character ends a field, so `a::b` on `:` is three.
+    fn push_expansion(&mut self, value: &str, quoted: bool) {
+        if quoted || self.ifs.is_empty() {
+            value.chars().for_each(|c| self.push_literal(c, quoted));
+            self.started = true;
+            return;
+        }
+        // IFS whitespace next to another separator is part of it: `a : b`
+        let mut after_space = false;
+        for c in value.chars() {
+            if !self.ifs.contains(c) {
+                self.push_literal(c, false);
+                after_space = false;
+            } else if c.is_whitespace() {
+                if self.started {
+                    self.end_field();
+                    after_space = true;
+                }
+            } else if !std::mem::take(&mut after_space) {
+                self.end_field();
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Field> {
+        if self.started {
+            self.end_field();
+        }
+        self.done
+    }
+}
+
+/// A shell variable, else an environment variable.
+fn variable(name: &str) -> Option<String> {
+    crate::vars::get(name).or_else(|| std::env::var(name).ok())
 }
 
 fn execute_command_subst(cmd: &str) -> Result<String, ShellError> {
@@ -374,13 +894,62 @@ fn execute_command_subst(cmd: &str) -> Result<String, ShellError> {
 }
 
 fn expand_glob(word: &str) -> Vec<String> {
-    if !(word.contains('*') || word.contains('?') || word.contains('[')) { return Vec::new(); }
-    let mut out = Vec::new();
-    if let Ok(paths) = glob::glob(word) {
-        for entry in paths.flatten() {
-            if let Some(s) = entry.to_str() { out.push(s.to_string()); }
-        }
-    }
-    out
+    let (pattern, qualifiers) = match glob_qualifiers::split(word) {
+        Some((pattern, qualifiers)) => (pattern, Some(qualifiers)),
+        None => (word, None),
+    };
+    if !(pattern.contains('*') || pattern.contains('?') || pattern.contains('[')) { return Vec::new(); }
+    let mut paths: Vec<_> = match glob::glob(pattern) {
+        Ok(paths) => paths.flatten().collect(),
+        Err(_) => Vec::new(),
+    };
+    if let Some(qualifiers) = qualifiers { paths = qualifiers.apply(paths); }
+    paths.into_iter().filter_map(|p| p.to_str().map(str::to_string)).collect()
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_reads_back() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("*.rs"), "'*.rs'");
+        assert_eq!(quote("it's"), "'it'\"'\"'s'");
+    }
+
+    #[test]
+    fn quoted_glob_characters_match_literally() {
+        let mut fields = Fields::new(String::new());
+        fields.push_literal('*', true);
+        ".rs".chars().for_each(|c| fields.push_literal(c, false));
+        let [field] = <[Field; 1]>::try_from(fields.finish()).ok().unwrap();
+        assert_eq!(field.text, "*.rs");
+        assert_eq!(field.pattern, None);
+
+        let mut fields = Fields::new(String::new());
+        "[a]".chars().for_each(|c| fields.push_literal(c, true));
+        "*".chars().for_each(|c| fields.push_literal(c, false));
+        let [field] = <[Field; 1]>::try_from(fields.finish()).ok().unwrap();
+        assert_eq!(field.text, "[a]*");
+        assert_eq!(field.pattern.as_deref(), Some("[[]a[]]*"));
+    }
+
+    #[test]
+    fn unquoted_expansions_split_and_glob() {
+        let mut fields = Fields::new(" \t\n".to_string());
+        fields.push_expansion(" a  *.rs ", false);
+        let fields = fields.finish();
+        assert_eq!(fields.iter().map(|f| f.text.as_str()).collect::<Vec<_>>(), ["a", "*.rs"]);
+        assert_eq!(fields[0].pattern, None);
+        assert_eq!(fields[1].pattern.as_deref(), Some("*.rs"));
+
+        let mut fields = Fields::new(" \t\n".to_string());
+        fields.push_expansion(" a  *.rs ", true);
+        let [field] = <[Field; 1]>::try_from(fields.finish()).ok().unwrap();
+        assert_eq!(field.text, " a  *.rs ");
+        assert_eq!(field.pattern, None);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+/// Bumped by the watcher thread whenever something in a PATH directory changes.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Changes whenever a PATH directory changes; compare against a value seen
+/// earlier to know whether anything built from the directories is stale.
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Watch every directory in `$PATH` for new or newly executable files. Safe
+/// to call repeatedly; directories already watched are not added twice.
+/// Missing directories (a fresh `~/.local/bin`) are covered by watching their
+/// nearest existing parent, so their creation also counts as a change.
+#[cfg(target_os = "linux")]
+pub fn watch_path_dirs() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let Some(fd) = inotify_fd() else { return; };
+    let Ok(path_var) = std::env::var("PATH") else { return; };
+    let mask = libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_ATTRIB | libc::IN_DELETE | libc::IN_MOVED_FROM;
+    for dir in path_var.split(':').filter(|d| !d.is_empty()) {
+        let Some(existing) = Path::new(dir).ancestors().find(|p| p.is_dir()) else { continue; };
+        let Ok(c_path) = CString::new(existing.as_os_str().as_bytes()) else { continue; };
+        unsafe { libc::inotify_add_watch(fd, c_path.as_ptr(), mask) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch_path_dirs() {}
+
+/// One inotify instance for the whole session, drained by a background thread.
+#[cfg(target_os = "linux")]
+fn inotify_fd() -> Option<i32> {
+    static FD: OnceLock<Option<i32>> = OnceLock::new();
+    *FD.get_or_init(|| {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let spawned = std::thread::Builder::new().name("squish-pathwatch".to_string()).spawn(move || {
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+                if n < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+                    return;
+                }
+                if n > 0 {
+                    GENERATION.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+        if spawned.is_err() {
+            unsafe { libc::close(fd) };
+            return None;
+        }
+        Some(fd)
+    })
+}
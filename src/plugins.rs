@@ -0,0 +1,227 @@
+//! Plugins: executables in `~/.config/squish/plugins/` that add prompt
+//! segments, builtins, completions and hooks without changes to squish.
+//! A plugin is started once per request, with the request as arguments,
+//! so it can be written in any language:
+//!
+//! - `describe`: print what the plugin provides, one declaration a line:
+//!   `segment NAME [SECONDS]`, `builtin NAME [SUMMARY]`,
+//!   `complete COMMAND...` and `hook precmd|preexec|chpwd`. Unknown lines
+//!   are ignored, so newer declarations don't break older shells.
+//! - `segment NAME`: print the segment's text.
+//! - `builtin NAME ARGS...`: run the builtin, on the terminal or in a pipeline.
+//! - `complete COMMAND WORDS...`: print candidates for the last word (empty
+//!   when a new word is started), one a line, optionally `candidate<TAB>note`.
+//! - `hook EVENT ARG`: precmd gets the last exit status, preexec the command
+//!   line, chpwd the new directory. What a hook prints is run in the shell,
+//!   so it can `export` or `cd`.
+//!
+//! Plugins see `SQUISH_PLUGIN_API=1`. They are described once, in the
+//! background at startup; a new or changed plugin is picked up by the
+//! next shell. Until that finishes, the prompt, highlighting, completion
+//! and hooks go without them; running a command waits for it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::builtins;
+use crate::config;
+use crate::error::ShellError;
+use crate::lookup;
+use crate::parser;
+use crate::shell_config::PromptSegment;
+use crate::spawn::{self, Io, Spawn};
+use crate::timeout::Watchdog;
+use crate::errln;
+
+/// The protocol version, in `SQUISH_PLUGIN_API`
+const API_VERSION: &str = "1";
+
+/// How long `describe` and `complete` may take before they are stopped
+const QUERY_LIMIT: Duration = Duration::from_secs(2);
+
+pub const HOOK_EVENTS: &[&str] = &["precmd", "preexec", "chpwd"];
+
+pub struct PluginBuiltin {
+    pub name: String,
+    pub summary: String,
+}
+
+pub struct Plugin {
+    pub name: String,
+    pub path: PathBuf,
+    pub segments: Vec<PromptSegment>,
+    pub builtins: Vec<PluginBuiltin>,
+    /// Commands whose arguments it completes
+    pub completes: Vec<String>,
+    pub hooks: Vec<String>,
+}
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+static PLUGINS: OnceLock<Vec<Plugin>> = OnceLock::new();
+
+/// Apply `plugins=`, and start describing the plugins in the background.
+/// Only the first call wins.
+pub fn configure(enabled: bool) {
+    if ENABLED.set(enabled).is_ok() && enabled {
+        let _ = std::thread::Builder::new().name("squish-plugins".to_string()).spawn(|| {
+            list();
+        });
+    }
+}
+
+/// Every plugin that described itself, by name; waits for the startup scan.
+pub fn list() -> &'static [Plugin] {
+    PLUGINS.get_or_init(|| if ENABLED.get() == Some(&true) { scan() } else { Vec::new() })
+}
+
+/// The plugins if the startup scan is done, else none: for what runs while
+/// the user types, which mustn't wait on a slow `describe`.
+fn loaded() -> &'static [Plugin] {
+    PLUGINS.get().map_or(&[], Vec::as_slice)
+}
+
+fn scan() -> Vec<Plugin> {
+    let Some(dir) = config::plugins_dir() else { return Vec::new(); };
+    let Ok(entries) = fs::read_dir(&dir) else { return Vec::new(); };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| lookup::is_executable(p)).collect();
+    paths.sort();
+    paths.iter().filter_map(|path| describe(path)).collect()
+}
+
+fn describe(path: &Path) -> Option<Plugin> {
+    let name = path.file_stem()?.to_string_lossy().into_owned();
+    let text = match query(path, &[String::from("describe")]) {
+        Ok(text) => text,
+        Err(e) => {
+            errln!("squish: plugin {}: {}", name, e);
+            return None;
+        }
+    };
+    let mut plugin = Plugin { name, path: path.to_path_buf(), segments: Vec::new(), builtins: Vec::new(), completes: Vec::new(), hooks: Vec::new() };
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("segment"), Some(segment)) => plugin.segments.push(PromptSegment {
+                name: segment.to_string(),
+                command: [path.to_string_lossy().as_ref(), "segment", segment].map(parser::quote).join(" "),
+                interval_secs: words.next().and_then(|s| s.parse().ok()).unwrap_or(60),
+            }),
+            // squish's own builtins can't be replaced
+            (Some("builtin"), Some(builtin)) if !builtins::is_builtin(builtin) => plugin.builtins.push(PluginBuiltin {
+                name: builtin.to_string(),
+                summary: words.collect::<Vec<_>>().join(" "),
+            }),
+            (Some("complete"), Some(command)) => {
+                plugin.completes.push(command.to_string());
+                plugin.completes.extend(words.map(str::to_string));
+            }
+            (Some("hook"), Some(event)) if HOOK_EVENTS.contains(&event) => plugin.hooks.push(event.to_string()),
+            _ => {}
+        }
+    }
+    Some(plugin)
+}
+
+/// Run `path` with `args` and collect its output, stopping it after
+/// `QUERY_LIMIT`. Its stderr is dropped: these run while the user types.
+fn query(path: &Path, args: &[String]) -> Result<String, ShellError> {
+    let overlay = api_env();
+    let child = Spawn::new(path, args)
+        .env(&overlay)
+        .stdin(Io::Null)
+        .stdout(Io::Piped)
+        .stderr(Io::Null)
+        .new_process_group()
+        .spawn()?;
+    let watchdog = Watchdog::start(child.id(), QUERY_LIMIT);
+    let output = child.wait_with_output();
+    if watchdog.finish().is_some() {
+        return Err(ShellError::Other(format!("{} {} took longer than {}s", path.display(), args[0], QUERY_LIMIT.as_secs())));
+    }
+    let output = output?;
+    if !output.status.success() {
+        return Err(ShellError::Other(format!("{} {} failed with status {}", path.display(), args[0], spawn::exit_code(output.status))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn api_env() -> [(String, String); 1] {
+    [(String::from("SQUISH_PLUGIN_API"), String::from(API_VERSION))]
+}
+
+/// Segments from all plugins, for the prompt, which looks at configured
+/// `prompt.segment.<name>` entries first: those win a clash of names.
+pub fn segments<'a>() -> impl Iterator<Item = &'a PromptSegment> {
+    loaded().iter().flat_map(|plugin| &plugin.segments)
+}
+
+/// The plugin builtin called `name`, and the plugin it comes from.
+pub fn builtin(name: &str) -> Option<(&'static Plugin, &'static PluginBuiltin)> {
+    list().iter().find_map(|plugin| plugin.builtins.iter().find(|b| b.name == name).map(|b| (plugin, b)))
+}
+
+/// Whether `name` is a plugin builtin, for highlighting; doesn't wait.
+pub fn is_builtin(name: &str) -> bool {
+    builtins().any(|b| b.name == name)
+}
+
+/// The plugin builtins described so far, for completion and suggestions.
+pub fn builtins() -> impl Iterator<Item = &'static PluginBuiltin> {
+    loaded().iter().flat_map(|plugin| &plugin.builtins)
+}
+
+/// Run the plugin builtin `name`, on the terminal, or with `input` on stdin
+/// and its output collected when it's in a pipeline.
+pub fn run_builtin(plugin: &Plugin, name: &str, args: &[String], input: Option<&[u8]>) -> (i32, Vec<u8>) {
+    let mut argv = vec![String::from("builtin"), name.to_string()];
+    argv.extend(args.iter().cloned());
+    let overlay = api_env();
+    let spawn = Spawn::new(&plugin.path, &argv).env(&overlay);
+    let result = match input {
+        Some(input) => spawn.stdout(Io::Piped).output_with_input(input).map(|output| (spawn::exit_code(output.status), output.stdout)),
+        None => spawn.spawn().and_then(|mut child| Ok((spawn::exit_code(child.wait()?), Vec::new()))),
+    };
+    result.unwrap_or_else(|e| {
+        errln!("{}: plugin {}: {}", name, plugin.name, e);
+        (126, Vec::new())
+    })
+}
+
+/// Candidates, with their notes, for the last of `words` (the arguments
+/// typed after `command`), from the first plugin that completes `command`.
+pub fn complete(command: &str, words: &[&str]) -> Option<Vec<(String, String)>> {
+    let plugin = loaded().iter().find(|plugin| plugin.completes.iter().any(|c| c == command))?;
+    let mut args = vec![String::from("complete"), command.to_string()];
+    args.extend(words.iter().map(|w| w.to_string()));
+    let text = query(&plugin.path, &args).ok()?;
+    Some(
+        text.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once('\t') {
+                Some((candidate, note)) => (candidate.to_string(), note.to_string()),
+                None => (line.to_string(), String::new()),
+            })
+            .collect(),
+    )
+}
+
+/// Run every plugin's hook for `event`, returning what each printed, for
+/// the shell to run. Hooks share the terminal, so Ctrl-C stops a stuck one.
+pub fn hook(event: &str, arg: &str) -> Vec<String> {
+    let overlay = api_env();
+    let args = [String::from("hook"), event.to_string(), arg.to_string()];
+    loaded()
+        .iter()
+        .filter(|plugin| plugin.hooks.iter().any(|h| h == event))
+        .filter_map(|plugin| match Spawn::new(&plugin.path, &args).env(&overlay).stdin(Io::Null).stdout(Io::Piped).output() {
+            Ok(output) => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+            Err(e) => {
+                errln!("squish: plugin {}: {} hook: {}", plugin.name, event, e);
+                None
+            }
+        })
+        .filter(|script| !script.is_empty())
+        .collect()
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::io::Write;
+use std::path::Path;
+
+/// Files larger than this are never previewed; reading and encoding them on
+/// every Tab would stall the line editor.
+const MAX_PREVIEW_BYTES: u64 = 4_000_000;
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageProtocol {
+    Kitty,
+    ITerm,
+}
+
+pub fn detect_protocol() -> Option<ImageProtocol> {
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || program == "ghostty" {
+        return Some(ImageProtocol::Kitty);
+    }
+    if program == "iTerm.app" || program == "WezTerm" || env::var("LC_TERMINAL").is_ok_and(|t| t == "iTerm2") {
+        return Some(ImageProtocol::ITerm);
+    }
+    None
+}
+
+pub fn is_previewable(path: &Path, protocol: ImageProtocol) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    match (protocol, ext.as_deref()) {
+        // kitty's direct transmission only understands PNG without a converter
+        (ImageProtocol::Kitty, Some("png")) => true,
+        (ImageProtocol::ITerm, Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "pdf")) => true,
+        _ => false,
+    }
+}
+
+/// Draw a small preview below the current line. Leaves a blank line after the
+/// image so the editor's redraw lands underneath it.
+pub fn show_preview(path: &Path, protocol: ImageProtocol, rows: u16) -> std::io::Result<()> {
+    let md = std::fs::metadata(path)?;
+    if !md.is_file() || md.len() > MAX_PREVIEW_BYTES {
+        return Ok(());
+    }
+    let data = base64_encode(&std::fs::read(path)?);
+    let cols = rows.saturating_mul(2);
+
+    let mut out = std::io::stdout().lock();
+    write!(out, "\r\n")?;
+    match protocol {
+        ImageProtocol::Kitty => {
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = if i + 1 < chunks.len() { 1 } else { 0 };
+                if i == 0 {
+                    write!(out, "\x1b_Gf=100,a=T,c={},r={},m={};", cols, rows, more)?;
+                } else {
+                    write!(out, "\x1b_Gm={};", more)?;
+                }
+                out.write_all(chunk)?;
+                write!(out, "\x1b\\")?;
+            }
+        }
+        ImageProtocol::ITerm => {
+            write!(out, "\x1b]1337;File=inline=1;width={};height={};preserveAspectRatio=1:{}\x07", cols, rows, data)?;
+        }
+    }
+    write!(out, "\r\n\r\n")?;
+    out.flush()
+}
+
+fn base64_encode(input: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        out.push(TABLE[(n >> 18) as usize & 63] as char);
+        out.push(TABLE[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { TABLE[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { TABLE[n as usize & 63] as char } else { '=' });
+    }
+    out
+}
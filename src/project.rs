@@ -0,0 +1,126 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Project-state checks that can be shown as prompt warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectCheck {
+    /// Cargo.lock modified after the last cargo build
+    StaleLockfile,
+    /// package.json present but node_modules missing
+    MissingNodeModules,
+    /// .env present but its variables are not in the environment
+    UnloadedEnv,
+}
+
+impl ProjectCheck {
+    pub const ALL: [ProjectCheck; 3] = [ProjectCheck::StaleLockfile, ProjectCheck::MissingNodeModules, ProjectCheck::UnloadedEnv];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lockfile" | "cargo_lock" => Some(ProjectCheck::StaleLockfile),
+            "node_modules" => Some(ProjectCheck::MissingNodeModules),
+            "dotenv" | "env" => Some(ProjectCheck::UnloadedEnv),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ProjectCheck::StaleLockfile => "Cargo.lock newer than build",
+            ProjectCheck::MissingNodeModules => "no node_modules",
+            ProjectCheck::UnloadedEnv => ".env not loaded",
+        }
+    }
+
+    fn triggered(self, cwd: &Path) -> bool {
+        match self {
+            ProjectCheck::StaleLockfile => find_upwards(cwd, "Cargo.lock").is_some_and(|lock| lockfile_is_stale(&lock)),
+            ProjectCheck::MissingNodeModules => {
+                find_upwards(cwd, "package.json").is_some_and(|pkg| pkg.parent().is_some_and(|dir| !dir.join("node_modules").is_dir()))
+            }
+            ProjectCheck::UnloadedEnv => find_upwards(cwd, ".env").is_some_and(|file| dotenv_unloaded(&file)),
+        }
+    }
+}
+
+/// Labels for every enabled check that fires in the current directory.
+pub fn warnings(checks: &[ProjectCheck]) -> Vec<&'static str> {
+    if checks.is_empty() {
+        return Vec::new();
+    }
+    let Ok(cwd) = env::current_dir() else { return Vec::new(); };
+    checks.iter().filter(|c| c.triggered(&cwd)).map(|c| c.label()).collect()
+}
+
+/// Nearest ancestor (including `start`) containing `name`. Stops at $HOME so a
+/// stray ~/.env doesn't flag every directory below it.
+pub fn find_upwards(start: &Path, name: &str) -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    for dir in start.ancestors() {
+        if home.as_deref() == Some(dir) {
+            break;
+        }
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Nearest enclosing directory (including `start`) that holds one of
+/// `markers`. `.git` may be a directory or, in worktrees, a file.
+pub fn find_root(start: &Path, markers: &[String]) -> Option<PathBuf> {
+    start.ancestors().find(|dir| is_root(dir, markers)).map(Path::to_path_buf)
+}
+
+pub fn is_root(dir: &Path, markers: &[String]) -> bool {
+    markers.iter().any(|m| dir.join(m).exists())
+}
+
+/// Roots previously visited (as recorded in dirfreq) whose name matches
+/// `name`, most frequently used first. Exact names sort before prefixes.
+pub fn known_roots(name: &str, markers: &[String]) -> Vec<PathBuf> {
+    let mut found: Vec<(bool, u64, PathBuf)> = crate::dirfreq::all_dirs()
+        .into_iter()
+        .map(|(path, count)| (PathBuf::from(path), count))
+        .filter_map(|(path, count)| {
+            let base = path.file_name()?.to_str()?;
+            if !base.starts_with(name) || !path.is_dir() || !is_root(&path, markers) {
+                return None;
+            }
+            Some((base == name, count, path))
+        })
+        .collect();
+    found.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then_with(|| a.2.cmp(&b.2)));
+    found.into_iter().map(|(_, _, path)| path).collect()
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Cargo touches `.cargo-lock` in the profile directory on every build. No
+/// build at all is not reported; there's nothing stale yet.
+fn lockfile_is_stale(lock: &Path) -> bool {
+    let Some(root) = lock.parent() else { return false; };
+    let target = env::var_os("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|| root.join("target"));
+    let built = ["debug", "release"].iter().filter_map(|profile| mtime(&target.join(profile).join(".cargo-lock"))).max();
+    match (mtime(lock), built) {
+        (Some(lock_time), Some(build_time)) => lock_time > build_time,
+        _ => false,
+    }
+}
+
+/// True if any variable defined in the file is missing from the environment.
+fn dotenv_unloaded(path: &Path) -> bool {
+    let Ok(content) = fs::read_to_string(path) else { return false; };
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.trim_start_matches("export ").split_once('='))
+        .any(|(key, _)| env::var_os(key.trim()).is_none())
+}
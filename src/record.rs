@@ -0,0 +1,166 @@
+//! `record`: capture the session as an asciinema v2 cast. While recording,
+//! the shell's stdout and stderr (and so every child's) are a pseudo
+//! terminal; a thread copies what arrives on it to the real terminal and
+//! appends it to the cast with its timing. Programs still see a terminal,
+//! so the prompt, the line being typed and full-screen programs are all in
+//! the recording.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+#[cfg(unix)]
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    thread::JoinHandle,
+};
+
+#[cfg(unix)]
+use crate::{signals, spawn};
+
+struct Recording {
+    path: PathBuf,
+    started: Instant,
+    /// Puts the real stdout and stderr back when dropped
+    #[cfg(unix)]
+    restore: spawn::StdioRestore,
+    #[cfg(unix)]
+    done: Arc<AtomicBool>,
+    #[cfg(unix)]
+    pump: JoinHandle<()>,
+}
+
+static RECORDING: Mutex<Option<Recording>> = Mutex::new(None);
+
+/// The cast being written and how long ago it started, if recording.
+pub fn status() -> Option<(PathBuf, std::time::Duration)> {
+    let recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    recording.as_ref().map(|r| (r.path.clone(), r.started.elapsed()))
+}
+
+#[cfg(unix)]
+pub fn start(path: &Path) -> io::Result<()> {
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(current) = recording.as_ref() {
+        return Err(io::Error::other(format!("already recording to {}", current.path.display())));
+    }
+    let (master, slave) = spawn::open_pty_like(libc::STDOUT_FILENO).map_err(|_| io::Error::other("stdout is not a terminal"))?;
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    let mut cast = BufWriter::new(File::create(path)?);
+    let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let env_var = |name| json_string(&std::env::var(name).unwrap_or_default());
+    writeln!(
+        cast,
+        "{{\"version\": 2, \"width\": {}, \"height\": {}, \"timestamp\": {}, \"env\": {{\"SHELL\": {}, \"TERM\": {}}}}}",
+        // A terminal that doesn't report its size gets the usual default
+        if size.ws_col == 0 { 80 } else { size.ws_col },
+        if size.ws_row == 0 { 24 } else { size.ws_row },
+        timestamp,
+        env_var("SHELL"),
+        env_var("TERM")
+    )?;
+    cast.flush()?;
+
+    let terminal = spawn::dup_fd(libc::STDOUT_FILENO)?;
+    let restore = spawn::redirect_stdio(Some(slave.try_clone()?), Some(slave)).map_err(|e| io::Error::other(e.to_string()))?;
+
+    let started = Instant::now();
+    let done = Arc::new(AtomicBool::new(false));
+    let pump = {
+        let done = Arc::clone(&done);
+        std::thread::Builder::new()
+            .name("squish-record".to_string())
+            .spawn(move || pump(master, terminal, cast, started, &done))?
+    };
+    *recording = Some(Recording { path: path.to_path_buf(), started, restore, done, pump });
+    Ok(())
+}
+
+/// Put the real terminal back and finish the cast. Returns where it was
+/// saved and how long it ran.
+#[cfg(unix)]
+pub fn stop() -> io::Result<(PathBuf, std::time::Duration)> {
+    let Some(recording) = RECORDING.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return Err(io::Error::other("not recording"));
+    };
+    drop(recording.restore);
+    let elapsed = recording.started.elapsed();
+    recording.done.store(true, Ordering::SeqCst);
+    let _ = recording.pump.join();
+    Ok((recording.path, elapsed))
+}
+
+#[cfg(not(unix))]
+pub fn start(_path: &Path) -> io::Result<()> {
+    Err(io::Error::other("recording is only supported on Unix"))
+}
+
+#[cfg(not(unix))]
+pub fn stop() -> io::Result<(PathBuf, std::time::Duration)> {
+    Err(io::Error::other("not recording"))
+}
+
+/// Copy the pseudo terminal's output to the real one and into the cast,
+/// until `done` and nothing more is waiting. The pty follows the real
+/// terminal's size, so programs started later lay out for it.
+#[cfg(unix)]
+fn pump(mut master: File, mut terminal: File, mut cast: BufWriter<File>, started: Instant, done: &AtomicBool) {
+    let mut buf = [0u8; 8192];
+    // Bytes of a UTF-8 sequence split across reads; an event holds whole characters
+    let mut partial = Vec::new();
+    // After `done`, a job that keeps writing mustn't hold `record stop` up
+    let mut drains = 0;
+    let resizes = signals::watch_resizes();
+    loop {
+        let finishing = done.load(Ordering::SeqCst);
+        if finishing {
+            drains += 1;
+        }
+        let ready = spawn::wait_pty_output(&master, &terminal, resizes.as_ref(), if finishing { 0 } else { 100 });
+        if !ready || drains > 50 {
+            if finishing {
+                break;
+            }
+            continue;
+        }
+        let n = match master.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let _ = terminal.write_all(&buf[..n]);
+        partial.extend_from_slice(&buf[..n]);
+        let complete = match std::str::from_utf8(&partial) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => partial.len(),
+        };
+        let text = String::from_utf8_lossy(&partial[..complete]).into_owned();
+        partial.drain(..complete);
+        let _ = writeln!(cast, "[{:.6}, \"o\", {}]", started.elapsed().as_secs_f64(), json_string(&text));
+        let _ = cast.flush();
+    }
+}
+
+/// `text` as a JSON string literal.
+#[cfg(unix)]
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
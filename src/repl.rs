@@ -1,31 +1,113 @@
 use std::env;
+use std::io::IsTerminal;
 use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use colored::Colorize;
 use rustyline::error::ReadlineError;
 use rustyline::history::{DefaultHistory, History};
 use rustyline::CompletionType;
 use rustyline::config::Configurer;
-use rustyline::Editor;
+use rustyline::{Editor, EventHandler, KeyCode, KeyEvent, Modifiers};
 
-use crate::completion::LineHelper;
+use crate::completion::{self, LineHelper, PendingInput};
+use crate::formatter;
 use crate::config;
+use crate::diagnostics;
 use crate::error::ShellError;
+use crate::idle::{default_tips, spawn_idle_hints, IdleSnapshot, IdleState};
+use crate::parser::{self, Continuation};
+use crate::lastout;
+use crate::plugins;
+use crate::project;
+use crate::record;
+use crate::segments;
 use crate::shell::Shell;
-use crate::shell_config::ShellConfig;
+use crate::shell_config::{option_spec, EditMode, ShellConfig};
+use crate::signals;
+use crate::stdio;
+use crate::suggest;
+use crate::theme::{self, apply_color, parse_rgb, Paint, Role};
+use crate::vars;
+use crate::wizard;
 
-pub fn run_repl() -> Result<(), ShellError> {
+/// Runs the interactive shell; returns the status to exit with.
+pub fn run_repl() -> Result<i32, ShellError> {
+    #[cfg(windows)]
+    prepare_windows();
+    completion::warm_command_cache();
     let mut rl = Editor::<LineHelper, DefaultHistory>::new().map_err(|e| ShellError::LineEditor(e.to_string()))?;
     rl.set_helper(Some(LineHelper::new()));
     
     rl.set_completion_type(CompletionType::List);
+    // Lists are capped by completion.max_candidates and page with --More--
+    // rather than asking "Display all N possibilities?" first
+    rl.set_completion_prompt_limit(usize::MAX);
+    for modifier in [Modifiers::ALT, Modifiers::CTRL] {
+        rl.bind_sequence(
+            KeyEvent(KeyCode::Right, modifier),
+            EventHandler::Conditional(Box::new(completion::AcceptHintWord)),
+        );
+    }
+    // A paste lands in the buffer, newlines and all, and runs only on Enter
+    rl.enable_bracketed_paste(true);
+    let pending = Arc::new(Mutex::new(PendingInput::default()));
+    rl.bind_sequence(
+        KeyEvent(KeyCode::Char('L'), Modifiers::CTRL),
+        EventHandler::Conditional(Box::new(completion::ClearScreen(Arc::clone(&pending)))),
+    );
     rl.set_history_ignore_space(true);
     let _ = rl.set_history_ignore_dups(true);
     
+    if wizard::should_run()
+        && let Err(e) = wizard::run()
+    {
+        eprintln!("squish: setup: {}", e);
+    }
+
+    signals::install();
     let mut shell = Shell::new();
+    rl.bind_sequence(
+        KeyEvent(KeyCode::Char('s'), Modifiers::ALT),
+        EventHandler::Conditional(Box::new(completion::SudoLine(Arc::clone(&shell.view)))),
+    );
     let shell_config = shell.config.clone();
+    if let Err(e) = lastout::start(shell_config.lastout_kb) {
+        eprintln!("squish: lastout: {}", e);
+    }
+    show_greeting(&mut shell, &shell_config)?;
     load_startup_config(&mut shell)?;
 
+    rl.set_edit_mode(match shell_config.edit_mode {
+        EditMode::Vi => rustyline::EditMode::Vi,
+        EditMode::Emacs => rustyline::EditMode::Emacs,
+    });
+
+    let command_log = suggest::load_shared(shell_config.suggest_failed);
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_root_markers(shell_config.root_markers.clone());
+        helper.set_case(shell_config.completion_case);
+        helper.set_max_candidates(shell_config.completion_max_candidates);
+        helper.set_dir_commands(shell_config.completion_dirs_only.clone());
+        helper.set_completers(shell_config.completers.clone());
+        helper.set_command_log(Arc::clone(&command_log));
+        helper.set_shell_view(Arc::clone(&shell.view));
+    }
+
+    if shell_config.completion_preview
+        && let Some(helper) = rl.helper_mut()
+    {
+        helper.enable_preview(shell_config.completion_preview_rows);
+    }
+
+    let idle_state = if shell_config.idle_hints {
+        start_idle_hints(&mut rl, &shell_config)
+    } else {
+        None
+    };
+
     let history_path = config::history_file();
     if let Some(path) = &history_path {
         let _ = rl.load_history(path);
@@ -33,26 +115,62 @@ pub fn run_repl() -> Result<(), ShellError> {
 
 
     let mut current_line = String::new();
+    let mut continuation = None;
+    let mut exit_code = 0;
+    // For $LINENO: lines read this session, and where the current command began
+    let mut lines_read = 0;
+    let mut first_line = 0;
+    // What's on screen of a command still being continued, for Ctrl-L
+    let mut shown = String::new();
     
     loop {
+        if current_line.is_empty() {
+            shell.report_finished_jobs();
+            if let Err(ShellError::Exit(code)) = shell.run_precmd() {
+                exit_code = code;
+                break;
+            }
+            shell.refresh_view();
+        }
         let prompt_text = if current_line.is_empty() {
-            generate_prompt(&shell_config, shell.last_status)
+            generate_prompt(&shell_config, shell.last_status, shell.session_level, shell.jobs.list_jobs().len())
         } else {
-            "  ".truecolor(200, 180, 255).dimmed().to_string() + "> "
+            let label = continuation.map(Continuation::label).unwrap_or_default();
+            format!("{}{}> ", " ".repeat(2usize.saturating_sub(label.len())), label.paint(Role::Hint).dimmed())
         };
         
-        match rl.readline(&prompt_text) {
+        if let Some(idle) = &idle_state {
+            let running = shell.jobs.list_jobs().iter().filter(|j| j.is_running()).count();
+            idle.prompt_shown(IdleSnapshot::capture(running));
+        }
+        if let Ok(mut pending) = pending.lock() {
+            pending.shown = shown.trim_end_matches('\n').to_string();
+            pending.prompt_width = formatter::visible_width(prompt_text.rsplit('\n').next().unwrap_or_default());
+        }
+        let read = rl.readline(&prompt_text);
+        if let Some(idle) = &idle_state {
+            idle.prompt_left();
+        }
+
+        match read {
             Ok(line) => {
+                lines_read += 1;
+                shown.push_str(&prompt_text);
+                shown.push_str(&line);
+                shown.push('\n');
                 if current_line.is_empty() {
+                    first_line = lines_read;
                     current_line = line;
                 } else {
                     current_line.push('\n');
                     current_line.push_str(&line);
                 }
                 
-                if !LineHelper::is_incomplete_command(&current_line.trim()) {
+                continuation = parser::incomplete_reason(current_line.trim());
+                if continuation.is_none() {
                     let full_line = current_line.trim().to_string();
                     current_line.clear();
+                    shown.clear();
                     
                     if !full_line.is_empty() {
                         let history = rl.history();
@@ -69,14 +187,30 @@ pub fn run_repl() -> Result<(), ShellError> {
                             rl.add_history_entry(&full_line).ok();
                         }
                     }
-                    if let Err(e) = shell.run_line(&full_line) {
+                    let cwd = env::current_dir().map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+                    vars::set_lineno(first_line);
+                    let result = shell.run_line(&full_line);
+                    if let Some(line) = shell.history_rewrite.take() {
+                        replace_last_history(rl.history_mut(), line);
+                    }
+                    if let Err(ShellError::Exit(code)) = result {
+                        exit_code = code;
+                        break;
+                    }
+                    if let Err(e) = result {
                         eprintln!("squish: {}", e);
                     }
+                    if !full_line.is_empty()
+                        && let Ok(mut log) = command_log.write()
+                    {
+                        log.record(&full_line, &cwd, shell.last_status);
+                    }
                 }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("");
                 current_line.clear();
+                shown.clear();
                 continue;
             }
             Err(ReadlineError::Eof) => {
@@ -90,21 +224,95 @@ pub fn run_repl() -> Result<(), ShellError> {
     if let Some(path) = &history_path {
         let _ = rl.save_history(path);
     }
+    // Finish a cast left running, so the file is whole
+    let _ = record::stop();
+    lastout::stop();
+    shell.hang_up_jobs();
+    let level = env::var("SQUISH_LEVEL").ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+    if level > 1 {
+        let remaining = level - 1;
+        eprintln!("{}", format!("leaving nested squish, {} level{} remaining", remaining, if remaining == 1 { "" } else { "s" }).bright_black());
+    }
+
+    Ok(exit_code)
+}
+
+/// Runs `squish -c TEXT`, which is how subshells and background pipelines
+/// run; returns the status to exit with.
+pub fn run_command(text: &str) -> Result<i32, ShellError> {
+    let mut shell = Shell::for_command();
+    match shell.run_command_text(text) {
+        Ok(status) | Err(ShellError::Exit(status)) => Ok(status),
+        Err(e) => {
+            diagnostics::print_error(&e);
+            Ok(1)
+        }
+    }
+}
+
+/// Put `line` in place of the `please` that ran it. rustyline can only add
+/// entries, so the history is rebuilt; saving writes the whole file anyway.
+fn replace_last_history(history: &mut DefaultHistory, line: String) {
+    let mut entries: Vec<String> = history.iter().cloned().collect();
+    // A line typed with a leading space never went in
+    if entries.last().and_then(|last| last.split_whitespace().next()) != Some("please") {
+        return;
+    }
+    entries.pop();
+    let _ = history.clear();
+    for entry in entries.into_iter().chain([line]) {
+        let _ = history.add_owned(entry);
+    }
+}
+
+fn start_idle_hints(rl: &mut Editor<LineHelper, DefaultHistory>, config: &ShellConfig) -> Option<Arc<IdleState>> {
+    let printer = rl.create_external_printer().ok()?;
+    let state = Arc::new(IdleState::default());
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_idle_state(Arc::clone(&state));
+    }
+    spawn_idle_hints(printer, Arc::clone(&state), Duration::from_secs(config.idle_hint_seconds), default_tips());
+    Some(state)
+}
 
+/// `greeting=` and `greeting.command=`, only when started on a terminal so
+/// scripts and piped input stay quiet.
+fn show_greeting(shell: &mut Shell, config: &ShellConfig) -> Result<(), ShellError> {
+    if !std::io::stdin().is_terminal() || config.greeting.as_deref() == Some("none") {
+        return Ok(());
+    }
+    if let Some(template) = &config.greeting {
+        println!("{}", expand_prompt_format(template, config, 0, 0));
+    }
+    if let Some(command) = &config.greeting_command {
+        match shell.run_line(command) {
+            Err(ShellError::Exit(code)) => return Err(ShellError::Exit(code)),
+            Err(e) => eprintln!("squish: greeting: {}", e),
+            Ok(()) => {}
+        }
+    }
     Ok(())
 }
 
+/// `autostart=` and the command lines in the config file. A silent start
+/// (`greeting=none`, or stdin not a terminal) drops what they print to
+/// stdout; errors still show.
 fn load_startup_config(shell: &mut Shell) -> Result<(), ShellError> {
     let shell_config = shell.config.clone();
+    let quiet = !std::io::stdin().is_terminal() || shell_config.greeting.as_deref() == Some("none");
+    let _silenced = quiet.then(|| stdio::redirect(stdio::null().ok(), None));
     for cmd in &shell_config.autostart {
-        if let Err(e) = shell.run_line(cmd) {
-            eprintln!("squish: autostart error: {}", e);
+        match shell.run_line(cmd) {
+            Err(ShellError::Exit(code)) => return Err(ShellError::Exit(code)),
+            Err(e) => eprintln!("squish: autostart error: {}", e),
+            Ok(()) => {}
         }
     }
     
     if let Some(config_path) = config::config_file() {
         if let Ok(content) = std::fs::read_to_string(&config_path) {
-            for line in content.lines() {
+            for (number, line) in content.lines().enumerate() {
+                vars::set_lineno(number + 1);
                 let line = line.trim();
                 if line.is_empty() || line.starts_with('#') {
                     continue;
@@ -112,11 +320,14 @@ fn load_startup_config(shell: &mut Shell) -> Result<(), ShellError> {
                 if line.contains('=') && !line.contains(' ') {
                     continue;
                 }
-                if line.starts_with("autostart ") || line.starts_with("autostart=") {
+                // Options were read by ShellConfig::load; their values may have spaces
+                if line.starts_with("autostart ") || line.split_once('=').is_some_and(|(key, _)| option_spec(key.trim()).is_some()) {
                     continue;
                 }
-                if let Err(e) = shell.run_line(line) {
-                    eprintln!("squish: config error: {}", e);
+                match shell.run_line(line) {
+                    Err(ShellError::Exit(code)) => return Err(ShellError::Exit(code)),
+                    Err(e) => eprintln!("squish: config error: {}", e),
+                    Ok(()) => {}
                 }
             }
         }
@@ -125,16 +336,98 @@ fn load_startup_config(shell: &mut Shell) -> Result<(), ShellError> {
 }
 
 
-fn generate_prompt(config: &ShellConfig, last_status: i32) -> String {
+fn generate_prompt(config: &ShellConfig, last_status: i32, session_level: usize, jobs: usize) -> String {
     if let Some(ref format) = config.prompt_format {
-        let mut result = format.clone();
-        result = result.replace("%u", &env::var("USER").unwrap_or_else(|_| "user".to_string()));
-        result = result.replace("%h", &hostname().unwrap_or_else(|| "host".to_string()));
-        result = result.replace("%d", &current_dir_path().unwrap_or_else(|| "?".to_string()));
-        result = result.replace("%s", &if last_status == 0 { "✓" } else { "✗" });
-        result
+        expand_prompt_format(format, config, last_status, jobs)
     } else {
-        prompt(config, last_status)
+        prompt(config, last_status, session_level)
+    }
+}
+
+/// Expands the custom `prompt` format in a single pass, so text substituted
+/// for one token (a directory named `%u`, say) is never expanded again.
+fn expand_prompt_format(format: &str, config: &ShellConfig, last_status: i32, jobs: usize) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&'n') {
+            chars.next();
+            result.push('\n');
+            continue;
+        }
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => result.push_str(&env::var("USER").unwrap_or_else(|_| "user".to_string())),
+            Some('h') => result.push_str(&segments::hostname().unwrap_or_else(|| "host".to_string())),
+            Some('d') => result.push_str(&current_dir_path(config).unwrap_or_else(|| "?".to_string())),
+            Some('s') => result.push_str(if last_status == 0 { "✓" } else { "✗" }),
+            Some('e') => result.push_str(&last_status.to_string()),
+            Some('w') => result.push_str(&project::warnings(&config.project_warnings).join(", ")),
+            Some('t') => result.push_str(&segments::clock(&config.prompt_clock_format)),
+            Some('v') => result.push_str(env!("CARGO_PKG_VERSION")),
+            Some('b') => {
+                if let Some(battery) = segments::battery() {
+                    result.push_str(&battery_text(&battery).to_string());
+                }
+            }
+            Some('g') => result.push_str(&git_segment().unwrap_or_default()),
+            Some('j') => {
+                if jobs > 0 {
+                    result.push_str(&jobs.to_string());
+                }
+            }
+            Some('D') if chars.peek() == Some(&'{') => {
+                chars.next();
+                let date_format: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                result.push_str(&segments::clock(&date_format));
+            }
+            Some('{') => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                result.push_str(&prompt_brace_token(&name, config));
+            }
+            Some('%') => result.push('%'),
+            Some(other) => {
+                result.push('%');
+                result.push(other);
+            }
+            None => result.push('%'),
+        }
+    }
+    result
+}
+
+/// `%{name}`: a configured segment's output, `reset`, or a color such as
+/// `red`, `#ff8800` or `cyan+bold` that applies until the next `%{reset}`.
+fn prompt_brace_token(name: &str, config: &ShellConfig) -> String {
+    if let Some(segment) = config.prompt_segments.iter().chain(plugins::segments()).find(|s| s.name == name) {
+        return segments::output(segment).unwrap_or_default();
+    }
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return String::new();
+    }
+    if name == "reset" {
+        return "\x1b[0m".to_string();
+    }
+    // Only the opening escape is wanted: color a marker and cut before it
+    let painted = apply_color("\u{0}", name, false).to_string();
+    match painted.split_once('\u{0}') {
+        Some((start, _)) => start.to_string(),
+        None => String::new(),
+    }
+}
+
+/// `⚡ 80%` while charging, `🔋 15%` otherwise: green above 50%, yellow
+/// above 20%, red below.
+fn battery_text(battery: &segments::Battery) -> colored::ColoredString {
+    let icon = if battery.charging { "⚡" } else { "🔋" };
+    let text = format!("{} {}%", icon, battery.percent);
+    match battery.percent {
+        51.. => text.green(),
+        21..=50 => text.yellow(),
+        _ => text.red().bold(),
     }
 }
 
@@ -148,8 +441,9 @@ fn apply_text_color(text: &str, color: Option<&String>) -> colored::ColoredStrin
 
 fn apply_bg_color(text: colored::ColoredString, color: Option<&String>) -> colored::ColoredString {
     if let Some(color_str) = color {
-        if let Some((r, g, b)) = parse_rgb(color_str) {
-            text.on_truecolor(r, g, b)
+        let color_str = color_str.split('+').next().unwrap_or_default().trim();
+        if let Some(rgb_color) = parse_rgb(color_str) {
+            theme::rgb(text, rgb_color, true)
         } else {
             match color_str.to_lowercase().as_str() {
                 "black" => text.on_black(),
@@ -176,122 +470,25 @@ fn apply_bg_color(text: colored::ColoredString, color: Option<&String>) -> color
     }
 }
 
-fn apply_color(text: &str, color_str: &str, is_bg: bool) -> colored::ColoredString {
-    if let Some((r, g, b)) = parse_rgb(color_str) {
-        if is_bg {
-            text.normal().on_truecolor(r, g, b)
-        } else {
-            text.truecolor(r, g, b)
-        }
-    } else {
-        let colored = match color_str.to_lowercase().as_str() {
-            "black" => text.black(),
-            "red" => text.red(),
-            "green" => text.green(),
-            "yellow" => text.yellow(),
-            "blue" => text.blue(),
-            "magenta" => text.magenta(),
-            "cyan" => text.cyan(),
-            "white" => text.white(),
-            "bright_black" | "brightblack" => text.bright_black(),
-            "bright_red" | "brightred" => text.bright_red(),
-            "bright_green" | "brightgreen" => text.bright_green(),
-            "bright_yellow" | "brightyellow" => text.bright_yellow(),
-            "bright_blue" | "brightblue" => text.bright_blue(),
-            "bright_magenta" | "brightmagenta" => text.bright_magenta(),
-            "bright_cyan" | "brightcyan" => text.bright_cyan(),
-            "bright_white" | "brightwhite" => text.bright_white(),
-            _ => text.normal(),
-        };
-        if is_bg {
-            match color_str.to_lowercase().as_str() {
-                "black" => colored.on_black(),
-                "red" => colored.on_red(),
-                "green" => colored.on_green(),
-                "yellow" => colored.on_yellow(),
-                "blue" => colored.on_blue(),
-                "magenta" => colored.on_magenta(),
-                "cyan" => colored.on_cyan(),
-                "white" => colored.on_white(),
-                "bright_black" | "brightblack" => colored.on_bright_black(),
-                "bright_red" | "brightred" => colored.on_bright_red(),
-                "bright_green" | "brightgreen" => colored.on_bright_green(),
-                "bright_yellow" | "brightyellow" => colored.on_bright_yellow(),
-                "bright_blue" | "brightblue" => colored.on_bright_blue(),
-                "bright_magenta" | "brightmagenta" => colored.on_bright_magenta(),
-                "bright_cyan" | "brightcyan" => colored.on_bright_cyan(),
-                "bright_white" | "brightwhite" => colored.on_bright_white(),
-                _ => colored,
-            }
-        } else {
-            colored
-        }
-    }
-}
-
-fn parse_rgb(color_str: &str) -> Option<(u8, u8, u8)> {
-    let parts: Vec<&str> = color_str.split(|c: char| c == ',' || c == ' ').collect();
-    if parts.len() == 3 {
-        if let (Ok(r), Ok(g), Ok(b)) = (
-            parts[0].trim().parse::<u8>(),
-            parts[1].trim().parse::<u8>(),
-            parts[2].trim().parse::<u8>(),
-        ) {
-            return Some((r, g, b));
-        }
-    }
-    None
-}
-
-fn get_distro_icon() -> &'static str {
-    if let Ok(content) = std::fs::read_to_string("/etc/os-release") {
-        for line in content.lines() {
-            if line.starts_with("ID=") {
-                let distro = line.trim_start_matches("ID=").trim_matches('"').to_lowercase();
-                return match distro.as_str() {
-                    "arch" | "archlinux" => "\u{f303}",
-                    "ubuntu" => "\u{f31b}",
-                    "debian" => "\u{e77d}",
-                    "fedora" => "\u{f31a}",
-                    "centos" => "\u{f304}",
-                    "gentoo" => "\u{f30d}",
-                    "nixos" => "\u{f313}",
-                    "alpine" => "\u{f300}",
-                    "manjaro" => "\u{f312}",
-                    "opensuse" | "suse" => "\u{f314}",
-                    "mint" | "linuxmint" => "\u{f30e}",
-                    "pop" => "\u{f32a}",
-                    "kali" => "\u{f327}",
-                    "elementary" => "\u{f309}",
-                    "void" => "\u{f32e}",
-                    "raspbian" => "\u{f315}",
-                    "redhat" | "rhel" => "\u{f316}",
-                    "slackware" => "\u{f318}",
-                    _ => "\u{f17c}",
-                };
-            }
-        }
-    }
-    "\u{f17c}"
-}
-
-fn prompt(config: &ShellConfig, last_status: i32) -> String {
+fn prompt(config: &ShellConfig, last_status: i32, session_level: usize) -> String {
     let user = env::var("USER").unwrap_or_else(|_| String::from("user"));
-    let host = hostname().unwrap_or_else(|| String::from("host"));
-    let cwd_path = current_dir_path().unwrap_or_else(|| String::from("?"));
+    let host = segments::hostname().unwrap_or_else(|| String::from("host"));
+    let cwd_path = current_dir_path(config).unwrap_or_else(|| String::from("?"));
     let git = git_segment();
-    let distro_icon = get_distro_icon();
+    let distro_icon = segments::distro_icon();
     let sep = "\u{e0b0}";
     let top_left = "╭─".bright_black();
-    let distro_text_color = config.prompt_colors.distro_text.as_ref();
-    let distro_bg_color = config.prompt_colors.distro_bg.as_ref();
+    let mut colors = config.prompt_colors.clone();
+    colors.fill_from(theme::prompt_colors());
+    let distro_text_color = colors.distro_text.as_ref();
+    let distro_bg_color = colors.distro_bg.as_ref();
     let distro_text = apply_text_color(&format!(" {} ", distro_icon), distro_text_color);
     let distro_bg = if let Some(bg) = distro_bg_color {
         apply_bg_color(distro_text, Some(bg))
     } else {
         distro_text.black().on_bright_yellow()
     };
-    let user_host_bg_color = config.prompt_colors.user_host_bg.as_ref();
+    let user_host_bg_color = colors.user_host_bg.as_ref();
     let default_distro_sep_color = "bright_yellow".to_string();
     let distro_sep_color = distro_bg_color.unwrap_or(&default_distro_sep_color);
     let distro_sep = apply_color(sep, distro_sep_color, false);
@@ -300,14 +497,14 @@ fn prompt(config: &ShellConfig, last_status: i32) -> String {
     } else {
         distro_sep.on_white()
     };
-    let user_host_text_color = config.prompt_colors.user_host_text.as_ref();
+    let user_host_text_color = colors.user_host_text.as_ref();
     let user_host_text = apply_text_color(&format!(" {}@{} ", user, host), user_host_text_color);
     let user_host_bg = if let Some(bg) = user_host_bg_color {
         apply_bg_color(user_host_text, Some(bg))
     } else {
         user_host_text.black().on_white()
     };
-    let dir_bg_color = config.prompt_colors.dir_bg.as_ref();
+    let dir_bg_color = colors.dir_bg.as_ref();
     let default_user_sep_color = "white".to_string();
     let user_sep_color = user_host_bg_color.unwrap_or(&default_user_sep_color);
     let user_sep_colored = apply_color(sep, user_sep_color, false);
@@ -316,7 +513,7 @@ fn prompt(config: &ShellConfig, last_status: i32) -> String {
     } else {
         user_sep_colored.on_bright_cyan()
     };
-    let dir_text_color = config.prompt_colors.dir_text.as_ref();
+    let dir_text_color = colors.dir_text.as_ref();
     let dir_text = apply_text_color(&format!(" {} ", cwd_path), dir_text_color);
     let dir_bg = if let Some(bg) = dir_bg_color {
         apply_bg_color(dir_text, Some(bg))
@@ -324,11 +521,18 @@ fn prompt(config: &ShellConfig, last_status: i32) -> String {
         dir_text.black().on_bright_cyan()
     };
     
-    let mut first_line = format!("{} {}{}{}{}", 
-        top_left, distro_bg, distro_sep, user_host_bg, user_sep);
+    // Nested squish sessions get a subtle depth marker, e.g. "↳2"
+    let nesting = if session_level > 1 {
+        format!("{} ", format!("↳{}", session_level).bright_black())
+    } else {
+        String::new()
+    };
+
+    let mut first_line = format!("{} {}{}{}{}{}", 
+        top_left, nesting, distro_bg, distro_sep, user_host_bg, user_sep);
     
     if let Some(g) = git {
-        let git_bg_color = config.prompt_colors.git_bg.as_ref();
+        let git_bg_color = colors.git_bg.as_ref();
         let default_dir_sep_color = "bright_cyan".to_string();
         let dir_sep_color = dir_bg_color.unwrap_or(&default_dir_sep_color);
         let dir_sep_colored = apply_color(sep, dir_sep_color, false);
@@ -338,7 +542,7 @@ fn prompt(config: &ShellConfig, last_status: i32) -> String {
             dir_sep_colored.on_bright_magenta()
         };
         
-        let git_text_color = config.prompt_colors.git_text.as_ref();
+        let git_text_color = colors.git_text.as_ref();
         let git_text = apply_text_color(&format!(" {} ", g), git_text_color);
         let git_bg = if let Some(bg) = git_bg_color {
             apply_bg_color(git_text, Some(bg))
@@ -360,87 +564,147 @@ fn prompt(config: &ShellConfig, last_status: i32) -> String {
         };
         first_line.push_str(&format!("{}{}", dir_bg, dir_sep));
     }
+    for text in config.prompt_segments.iter().chain(plugins::segments()).filter_map(segments::output) {
+        first_line.push_str(&format!(" {}", text.bright_black()));
+    }
+    if config.prompt_battery
+        && let Some(battery) = segments::battery()
+    {
+        first_line.push_str(&format!(" {}", battery_text(&battery)));
+    }
+    if config.prompt_clock {
+        first_line.push_str(&format!(" {}", segments::clock(&config.prompt_clock_format).bright_black()));
+    }
+    let warnings = project::warnings(&config.project_warnings);
+    if !warnings.is_empty() {
+        let default_warning_color = "yellow".to_string();
+        let warning_color = colors.warning.as_ref().unwrap_or(&default_warning_color);
+        first_line.push_str(&format!(" {}", apply_color(&format!("⚠ {}", warnings.join(" · ")), warning_color, false)));
+    }
     let bottom_left = "╰─".bright_black();
     let default_success_color = "bright_green".to_string();
     let default_error_color = "bright_red".to_string();
     let arrow_color = if last_status == 0 {
-        config.prompt_colors.arrow_success.as_ref().unwrap_or(&default_success_color)
+        colors.arrow_success.as_ref().unwrap_or(&default_success_color)
     } else {
-        config.prompt_colors.arrow_error.as_ref().unwrap_or(&default_error_color)
+        colors.arrow_error.as_ref().unwrap_or(&default_error_color)
     };
     let prompt_arrow = apply_color("❯", arrow_color, false);
+    // Failure is also marked with a glyph so it doesn't rely on color alone
+    let status_glyph = if last_status == 0 {
+        String::new()
+    } else {
+        format!("{} ", apply_color("✗", arrow_color, false))
+    };
     
-    format!("{}\n{}{} ", first_line, bottom_left, prompt_arrow)
+    format!("{}\n{}{}{} ", first_line, bottom_left, status_glyph, prompt_arrow)
 }
 
-fn current_dir_path() -> Option<String> {
+/// The directory for the prompt: `~`-collapsed or relative to the git repo,
+/// then shortened per `prompt.dir_depth` and `prompt.dir_abbrev`.
+fn current_dir_path(config: &ShellConfig) -> Option<String> {
     let cwd = env::current_dir().ok()?;
-    let path = cwd.to_string_lossy().to_string();
-    let home = env::var("HOME").ok();
-    if let Some(home_dir) = home {
-        if path == home_dir {
-            return Some(String::from("~"));
-        }
-        if path.starts_with(&home_dir) {
-            let mut collapsed = String::from("~");
-            collapsed.push_str(&path[home_dir.len()..]);
-            return Some(collapsed);
+    let names = |path: &std::path::Path| -> Vec<String> {
+        path.components()
+            .filter_map(|c| match c {
+                std::path::Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect()
+    };
+    let repo_root = config
+        .prompt_dir_repo_relative
+        .then(|| project::find_root(&cwd, &[String::from(".git")]))
+        .flatten();
+    let home = env::var_os("HOME").map(std::path::PathBuf::from).filter(|h| !h.as_os_str().is_empty());
+
+    // The first part is "~", the repo's name, or "" for the filesystem root
+    let mut parts = if let Some(root) = repo_root
+        && let Ok(rel) = cwd.strip_prefix(&root)
+    {
+        let name = root.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| String::from("/"));
+        std::iter::once(name).chain(names(rel)).collect()
+    } else if let Some(home) = home
+        && let Ok(rel) = cwd.strip_prefix(&home)
+    {
+        std::iter::once(String::from("~")).chain(names(rel)).collect()
+    } else {
+        std::iter::once(String::new()).chain(names(&cwd)).collect::<Vec<_>>()
+    };
+    if parts.len() == 1 && parts[0].is_empty() {
+        return Some(String::from("/"));
+    }
+
+    let depth = config.prompt_dir_depth;
+    let truncated = depth > 0 && parts.len() > depth;
+    if truncated {
+        parts.drain(..parts.len() - depth);
+    }
+    if config.prompt_dir_abbrev {
+        // fish-style: every component but the last shortened to one letter
+        // (two for dotfiles: .config -> .c)
+        let last = parts.len().saturating_sub(1);
+        for part in &mut parts[..last] {
+            if part != "~" {
+                let keep = if part.starts_with('.') { 2 } else { 1 };
+                *part = part.chars().take(keep).collect();
+            }
         }
     }
-    Some(path)
+    let joined = parts.join("/");
+    Some(if truncated { format!("…/{}", joined) } else { joined })
 }
 
-fn hostname() -> Option<String> {
-    if let Ok(h) = env::var("HOSTNAME") {
-        if !h.is_empty() {
-            return Some(h);
-        }
+/// Give the Windows console what the rest of the shell expects: escape
+/// sequences interpreted (conpty), and `HOME` and `USER` taken from
+/// `USERPROFILE` and `USERNAME` when unset, as MSYS shells do.
+#[cfg(windows)]
+fn prepare_windows() {
+    if colored::control::set_virtual_terminal(true).is_err() {
+        colored::control::set_override(false);
     }
-    match std::fs::read_to_string("/proc/sys/kernel/hostname") {
-        Ok(s) => Some(s.trim().to_string()),
-        Err(_) => None,
+    for (name, windows_name) in [("HOME", "USERPROFILE"), ("USER", "USERNAME")] {
+        if env::var_os(name).is_none()
+            && let Some(value) = env::var_os(windows_name)
+        {
+            unsafe { env::set_var(name, value) };
+        }
     }
 }
 
+/// The branch (or short commit) and `*` when the worktree has changes.
+/// The branch is read from HEAD, so outside a repository and for the name
+/// no git runs; only the dirty check needs it.
 fn git_segment() -> Option<String> {
-    let inside = Command::new("git")
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()
-        .ok()?;
-    if !inside.status.success() { return None; }
-    let ok = String::from_utf8_lossy(&inside.stdout).trim() == "true";
-    if !ok { return None; }
-
-    let branch_out = Command::new("git")
-        .args(["symbolic-ref", "--short", "HEAD"])
-        .output()
-        .ok();
-
-    let branch = if let Some(out) = branch_out {
-        if out.status.success() {
-            Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
-        } else {
-            None
-        }
-    } else { None };
-
-    let name = if let Some(b) = branch { b } else {
-        let rev = Command::new("git")
-            .args(["rev-parse", "--short", "HEAD"]) 
-            .output()
-            .ok()?;
-        if !rev.status.success() { return None; }
-        String::from_utf8_lossy(&rev.stdout).trim().to_string()
+    let cwd = env::current_dir().ok()?;
+    let worktree = project::find_root(&cwd, &[String::from(".git")])?;
+    let head = std::fs::read_to_string(git_dir(&worktree.join(".git"))?.join("HEAD")).ok()?;
+    let head = head.trim();
+    let name = match head.strip_prefix("ref: ") {
+        Some(reference) => reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string(),
+        None => head.chars().take(7).collect(),
     };
 
+    // Fails inside .git itself, which isn't a worktree
     let status = Command::new("git")
         .args(["status", "--porcelain"]) 
         .output()
-        .ok()?;
+        .ok()
+        .filter(|out| out.status.success())?;
     let dirty = !String::from_utf8_lossy(&status.stdout).trim().is_empty();
     let branch_icon = "\u{e725}";
     let dirty_marker = if dirty { "*" } else { "" };
     
     Some(format!("{} {}{}", branch_icon, name, dirty_marker))
+}
+
+/// A `.git` directory, or the one a `.git` file points to (`gitdir: ...`)
+/// in linked worktrees and submodules.
+fn git_dir(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+    let text = std::fs::read_to_string(dot_git).ok()?;
+    let target = text.trim().strip_prefix("gitdir: ")?;
+    Some(dot_git.parent()?.join(target))
 }
\ No newline at end of file
@@ -0,0 +1,62 @@
+use std::sync::Mutex;
+
+/// CPU time, peak memory and page faults, as reported by getrusage/wait4.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResourceUsage {
+    pub user: f64,
+    pub system: f64,
+    pub max_rss_kb: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl ResourceUsage {
+    #[cfg(unix)]
+    pub fn from_rusage(usage: &libc::rusage) -> Self {
+        Self {
+            user: usage.ru_utime.tv_sec as f64 + usage.ru_utime.tv_usec as f64 / 1_000_000.0,
+            system: usage.ru_stime.tv_sec as f64 + usage.ru_stime.tv_usec as f64 / 1_000_000.0,
+            // Linux reports kilobytes, macOS bytes
+            max_rss_kb: if cfg!(target_os = "macos") { usage.ru_maxrss as u64 / 1024 } else { usage.ru_maxrss as u64 },
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+        }
+    }
+
+    /// Counters accumulated between `earlier` and `self`. Peak RSS is not a
+    /// counter, so it is kept from `self`.
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            user: (self.user - earlier.user).max(0.0),
+            system: (self.system - earlier.system).max(0.0),
+            max_rss_kb: self.max_rss_kb,
+            minor_faults: self.minor_faults.saturating_sub(earlier.minor_faults),
+            major_faults: self.major_faults.saturating_sub(earlier.major_faults),
+        }
+    }
+}
+
+/// Totals for all children the shell has waited on so far.
+pub fn children_usage() -> ResourceUsage {
+    #[cfg(unix)]
+    {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } == 0 {
+            return ResourceUsage::from_rusage(&usage);
+        }
+    }
+    ResourceUsage::default()
+}
+
+static LAST_WAITED: Mutex<Option<ResourceUsage>> = Mutex::new(None);
+
+/// Remember the exact usage of the foreground process that was just reaped.
+pub fn record_last(usage: ResourceUsage) {
+    if let Ok(mut last) = LAST_WAITED.lock() {
+        *last = Some(usage);
+    }
+}
+
+pub fn take_last() -> Option<ResourceUsage> {
+    LAST_WAITED.lock().ok().and_then(|mut last| last.take())
+}
@@ -0,0 +1,156 @@
+//! `sandbox`: run one command in a clean or adjusted environment, to try a
+//! script the way a fresh login, cron or CI would run it. By default only a
+//! few basic variables are passed on; `-k`, `-u` and `-e` adjust the list,
+//! `-C` picks the directory, and `-r` makes `$HOME` read-only for the
+//! command through a private mount namespace, using util-linux `unshare`
+//! with an unprivileged user namespace.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::error::ShellError;
+use crate::lookup;
+use crate::spawn::{self, Io, Spawn};
+use crate::errln;
+
+/// Passed on when the environment isn't kept whole with `-i`.
+const BASIC_VARS: &[&str] = &["PATH", "HOME", "USER", "LOGNAME", "TERM", "LANG"];
+
+#[derive(Default)]
+struct Options {
+    inherit: bool,
+    /// Names (or `PREFIX*` patterns) to pass on as well
+    keep: Vec<String>,
+    unset: Vec<String>,
+    set: Vec<(String, String)>,
+    dir: Option<PathBuf>,
+    read_only_home: bool,
+    command: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().cloned().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-i" | "--inherit" => options.inherit = true,
+            "-k" | "--keep" => options.keep.extend(value(arg)?.split(',').map(str::to_string)),
+            "-u" | "--unset" => options.unset.extend(value(arg)?.split(',').map(str::to_string)),
+            "-e" | "--env" => {
+                let assignment = value(arg)?;
+                let Some((name, val)) = assignment.split_once('=') else {
+                    return Err(format!("-e: expected NAME=VALUE, got '{}'", assignment));
+                };
+                options.set.push((name.to_string(), val.to_string()));
+            }
+            "-C" | "--dir" => options.dir = Some(PathBuf::from(value(arg)?)),
+            "-r" | "--ro-home" => options.read_only_home = true,
+            "--" => {
+                options.command = args.cloned().collect();
+                break;
+            }
+            flag if flag.starts_with('-') && flag.len() > 1 => return Err(format!("unknown option {}", flag)),
+            _ => {
+                options.command = std::iter::once(arg.clone()).chain(args.cloned()).collect();
+                break;
+            }
+        }
+    }
+    if options.command.is_empty() {
+        return Err("usage: sandbox [-i] [-k NAME] [-u NAME] [-e NAME=VALUE] [-C DIR] [-r] command [args...]".to_string());
+    }
+    Ok(options)
+}
+
+impl Options {
+    /// Everything the command will see, in the shell's order.
+    fn environment(&self) -> Vec<(String, String)> {
+        let matches = |name: &str, pattern: &String| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        };
+        let mut vars: Vec<(String, String)> = env::vars()
+            .filter(|(name, _)| self.inherit || BASIC_VARS.contains(&name.as_str()) || self.keep.iter().any(|k| matches(name, k)))
+            .filter(|(name, _)| !self.unset.iter().any(|u| matches(name, u)))
+            .collect();
+        for (name, value) in &self.set {
+            vars.retain(|(n, _)| n != name);
+            vars.push((name.clone(), value.clone()));
+        }
+        vars
+    }
+
+    /// The program and arguments to start: the command itself, or with
+    /// `-r` a wrapper that mounts `$HOME` read-only and then drops back to
+    /// the user's own ids before running it.
+    fn argv(&self, dir: &Path) -> Result<Vec<String>, String> {
+        if !self.read_only_home {
+            return Ok(self.command.clone());
+        }
+        if !cfg!(target_os = "linux") {
+            return Err("-r needs Linux user namespaces".to_string());
+        }
+        let find = |name: &str| lookup::find(name).map(|p| p.to_string_lossy().into_owned()).ok_or_else(|| format!("-r needs {} (util-linux)", name));
+        let (unshare, mount, sh) = (find("unshare")?, find("mount")?, find("sh")?);
+        let home = env::var("HOME").map_err(|_| "-r: HOME is not set".to_string())?;
+        #[cfg(unix)]
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        #[cfg(not(unix))]
+        let (uid, gid) = (0, 0);
+        // Entering the directory again after the mount puts the command on
+        // the read-only copy, even when it starts inside $HOME
+        let script = r#"home=$1 dir=$2 uid=$3 gid=$4 unshare=$5 mount=$6; shift 6
+"$mount" --bind "$home" "$home" && "$mount" -o remount,bind,ro "$home" && cd "$dir" &&
+exec "$unshare" --user --map-user="$uid" --map-group="$gid" -- "$@""#;
+        let mut argv: Vec<String> = [unshare.as_str(), "--user", "--map-root-user", "--mount", "--", &sh, "-c", script, "sandbox", &home]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        argv.extend([dir.to_string_lossy().into_owned(), uid.to_string(), gid.to_string(), unshare, mount]);
+        argv.extend(self.command.iter().cloned());
+        Ok(argv)
+    }
+}
+
+/// Run the command described by `args`, on the terminal, or with `input`
+/// on stdin and its output collected when it's in a pipeline.
+pub fn run(args: &[String], input: Option<&[u8]>) -> (i32, Vec<u8>) {
+    match try_run(args, input) {
+        Ok(result) => result,
+        Err(e) => {
+            errln!("sandbox: {}", e);
+            let status = match e {
+                ShellError::CommandNotFound { .. } => 127,
+                ShellError::ExecFailed { .. } => 126,
+                _ => 2,
+            };
+            (status, Vec::new())
+        }
+    }
+}
+
+fn try_run(args: &[String], input: Option<&[u8]>) -> Result<(i32, Vec<u8>), ShellError> {
+    let options = parse_args(args).map_err(ShellError::Other)?;
+    let dir = match &options.dir {
+        Some(dir) if !dir.is_dir() => return Err(ShellError::Other(format!("{}: not a directory", dir.display()))),
+        Some(dir) => std::path::absolute(dir)?,
+        None => env::current_dir()?,
+    };
+    let argv = options.argv(&dir).map_err(ShellError::Other)?;
+    // `./script` is relative to the directory the command runs in
+    let program = if lookup::is_path(&argv[0]) { dir.join(&argv[0]).to_string_lossy().into_owned() } else { argv[0].clone() };
+    let environment = options.environment();
+    let spawn = Spawn::new(&program, &argv[1..]).env(&environment).clear_env().current_dir(&dir);
+
+    match input {
+        Some(input) => {
+            let output = spawn.stdout(Io::Piped).output_with_input(input)?;
+            Ok((spawn::exit_code(output.status), output.stdout))
+        }
+        None => {
+            let status = spawn.spawn()?.wait().map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
+            Ok((spawn::exit_code(status), Vec::new()))
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Optional prompt segments: the clock, the battery, and user-defined ones
+//! (`prompt.segment.<name>=<command>`). Each user command runs in the
+//! background and its output is cached, so a slow command never delays the
+//! prompt: until the first run finishes the segment is simply left out, and
+//! stale output is shown while refreshing.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::shell_config::PromptSegment;
+use crate::spawn::{Io, Spawn};
+
+/// Longest segment text shown, in characters
+const MAX_WIDTH: usize = 60;
+
+#[derive(Default)]
+struct Cached {
+    output: String,
+    refreshed: Option<Instant>,
+    running: bool,
+}
+
+fn cache() -> &'static Mutex<HashMap<String, Cached>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Cached>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The segment's last output (None if empty or not produced yet), starting
+/// a background refresh when it is older than the segment's interval.
+pub fn output(segment: &PromptSegment) -> Option<String> {
+    if segment.command.is_empty() {
+        return None;
+    }
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    let entry = cache.entry(segment.name.clone()).or_default();
+    let stale = entry.refreshed.is_none_or(|at| at.elapsed() >= Duration::from_secs(segment.interval_secs));
+    if stale && !entry.running {
+        entry.running = true;
+        let segment = segment.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("squish-segment-{}", segment.name))
+            .spawn(move || refresh(&segment));
+        entry.running = spawned.is_ok();
+    }
+    (!entry.output.is_empty()).then(|| entry.output.clone())
+}
+
+fn refresh(segment: &PromptSegment) {
+    let args = [String::from("-c"), segment.command.clone()];
+    // Own process group, so Ctrl-C at the prompt doesn't reach it
+    let output = Spawn::new("sh", &args)
+        .stdin(Io::Null)
+        .stdout(Io::Piped)
+        .stderr(Io::Null)
+        .new_process_group()
+        .output();
+    let text = match output {
+        Ok(out) if out.status.success() => {
+            let text = String::from_utf8_lossy(&out.stdout);
+            text.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(MAX_WIDTH).collect()
+        }
+        _ => String::new(),
+    };
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    let entry = cache.entry(segment.name.clone()).or_default();
+    entry.output = text;
+    entry.refreshed = Some(Instant::now());
+    entry.running = false;
+}
+
+/// The current time in strftime `format`; empty if the format is invalid
+/// (chrono's `to_string` would panic on it).
+pub fn clock(format: &str) -> String {
+    let mut text = String::new();
+    match write!(text, "{}", chrono::Local::now().format(format)) {
+        Ok(()) => text,
+        Err(_) => String::new(),
+    }
+}
+
+pub struct Battery {
+    pub percent: u8,
+    pub charging: bool,
+}
+
+/// The first battery under /sys/class/power_supply; None on desktops.
+pub fn battery() -> Option<Battery> {
+    let mut supplies: Vec<_> = fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| fs::read_to_string(path.join("type")).is_ok_and(|t| t.trim() == "Battery"))
+        .collect();
+    supplies.sort();
+    let path = supplies.first()?;
+    let percent = fs::read_to_string(path.join("capacity")).ok()?.trim().parse::<u8>().ok()?;
+    let status = fs::read_to_string(path.join("status")).unwrap_or_default();
+    Some(Battery {
+        percent: percent.min(100),
+        charging: matches!(status.trim(), "Charging" | "Full"),
+    })
+}
+
+/// The hostname and distribution icon: both only change on a reboot or an
+/// upgrade, so they are read once rather than for every prompt.
+struct SystemInfo {
+    hostname: Option<String>,
+    distro_icon: &'static str,
+}
+
+static SYSTEM: Mutex<Option<SystemInfo>> = Mutex::new(None);
+
+fn system<T>(read: impl FnOnce(&SystemInfo) -> T) -> T {
+    let mut system = SYSTEM.lock().unwrap_or_else(|e| e.into_inner());
+    read(system.get_or_insert_with(|| SystemInfo { hostname: read_hostname(), distro_icon: read_distro_icon() }))
+}
+
+/// `$HOSTNAME` (or `%COMPUTERNAME%`) if set, else the system's hostname.
+pub fn hostname() -> Option<String> {
+    for name in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(h) = std::env::var(name)
+            && !h.is_empty()
+        {
+            return Some(h);
+        }
+    }
+    system(|info| info.hostname.clone())
+}
+
+/// The distribution's icon, from /etc/os-release.
+pub fn distro_icon() -> &'static str {
+    system(|info| info.distro_icon)
+}
+
+/// Read the hostname and distribution again on the next prompt (`hash -r`).
+pub fn forget_system_info() {
+    *SYSTEM.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn read_hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            let name = String::from_utf8_lossy(&buf[..end]);
+            // macOS reports its Bonjour name, `name.local`
+            let name = name.strip_suffix(".local").unwrap_or(&name);
+            return (!name.is_empty()).then(|| name.to_string());
+        }
+    }
+    None
+}
+
+fn read_distro_icon() -> &'static str {
+    if let Ok(content) = fs::read_to_string("/etc/os-release") {
+        for line in content.lines() {
+            if line.starts_with("ID=") {
+                let distro = line.trim_start_matches("ID=").trim_matches('"').to_lowercase();
+                return match distro.as_str() {
+                    "arch" | "archlinux" => "\u{f303}",
+                    "ubuntu" => "\u{f31b}",
+                    "debian" => "\u{e77d}",
+                    "fedora" => "\u{f31a}",
+                    "centos" => "\u{f304}",
+                    "gentoo" => "\u{f30d}",
+                    "nixos" => "\u{f313}",
+                    "alpine" => "\u{f300}",
+                    "manjaro" => "\u{f312}",
+                    "opensuse" | "suse" => "\u{f314}",
+                    "mint" | "linuxmint" => "\u{f30e}",
+                    "pop" => "\u{f32a}",
+                    "kali" => "\u{f327}",
+                    "elementary" => "\u{f309}",
+                    "void" => "\u{f32e}",
+                    "raspbian" => "\u{f315}",
+                    "redhat" | "rhel" => "\u{f316}",
+                    "slackware" => "\u{f318}",
+                    _ => "\u{f17c}",
+                };
+            }
+        }
+    }
+    "\u{f17c}"
+}
@@ -1,15 +1,37 @@
-use crate::builtins::{try_handle_builtin, BuiltinResult};
+use crate::builtins::{self, try_handle_builtin, BuiltinResult};
 use crate::error::ShellError;
 use crate::exec::run_external_command;
+use crate::spawn::{self, Io, Spawn};
+use crate::stdio;
 use crate::diagnostics;
-use crate::parser::{parse_command_line, CommandPart};
-use crate::jobs::JobManager;
+use crate::parser::{self, parse_command_line, CommandPart, Redirect};
+use crate::jobs::{parse_job_spec, Job, JobManager};
 use crate::aliases::AliasManager;
-use crate::shell_config::ShellConfig;
+use crate::shell_config::{AutoCorrect, ShellConfig};
+use crate::corrections;
+use crate::locale;
+use crate::calc;
+use crate::dirfreq;
+use crate::formatter;
+use crate::notify;
+use crate::project;
+use crate::watch;
+use crate::resources;
+use crate::lastout;
+use crate::signals;
+use crate::timeout;
+use crate::lookup;
+use crate::plugins;
+use crate::stats::{self, StatsTable};
+use crate::theme::{self, Paint, Role, Theme};
+use crate::vars;
+use crate::{err, errln, outln};
 use std::fs::OpenOptions;
-use std::io::{Read, Write};
-use std::process::{Command, Stdio};
-use std::time::Instant;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
 struct TimingInfo {
@@ -18,38 +40,143 @@ struct TimingInfo {
     system: f64,
 }
 
+/// The parts of the shell's state the line editor can see, for completion
+/// and highlighting. Refreshed before each prompt.
+#[derive(Debug, Default)]
+pub struct ShellView {
+    /// (name, value), sorted by name
+    pub aliases: Vec<(String, String)>,
+    /// (spec, command) of the jobs still listed; the spec is `%name` for a
+    /// labelled job and `%id` otherwise
+    pub jobs: Vec<(String, String)>,
+    /// The command line `please` and Alt-S would run again
+    pub last_command: Option<String>,
+}
+
+pub type SharedView = Arc<RwLock<ShellView>>;
+
 pub struct Shell {
     pub last_status: i32,
     pub jobs: JobManager,
     pub aliases: AliasManager,
     pub config: ShellConfig,
     pub last_command_time: Option<f64>,
+    pub history: Vec<String>,
+    /// The line `please` ran in its own place, for the line editor to
+    /// put in its history too
+    pub history_rewrite: Option<String>,
+    pub session_level: usize,
+    /// Per-program counts and timings for this session
+    pub session_stats: StatsTable,
+    pub view: SharedView,
+    /// `limit`'s runtime limit while its command runs; otherwise
+    /// `max_command_runtime` applies
+    command_limit: Option<Duration>,
+    /// The directory plugins' chpwd hooks last saw
+    hook_dir: Option<PathBuf>,
 }
 
 impl Shell {
     pub fn new() -> Self {
+        Self::build(load_history(), enter_session_level())
+    }
+
+    /// The shell behind `squish -c`, which subshells and jobs run in: it
+    /// reads no history and doesn't count as another session level.
+    pub fn for_command() -> Self {
+        let level = std::env::var("SQUISH_LEVEL").ok().and_then(|v| v.parse().ok()).unwrap_or(1);
+        Self::build(Vec::new(), level)
+    }
+
+    fn build(history: Vec<String>, session_level: usize) -> Self {
+        let config = ShellConfig::load();
+        locale::configure(config.locale.clone(), config.date_format.clone(), config.modified_format);
+        dirfreq::configure(config.dirfreq_scope);
+        vars::configure(config.word_split);
+        diagnostics::configure(config.dnf_provides);
+        plugins::configure(config.plugins);
+        formatter::configure(config.ls_layout, config.ls_grid_over, config.ls_columns.clone());
+        theme::configure_depth(config.color_depth);
+        if let Some(name) = &config.theme {
+            match Theme::named(name) {
+                Ok(theme) => theme::activate(theme),
+                Err(e) => errln!("squish: config: theme: {}", e),
+            }
+        }
         Self { 
             last_status: 0,
             jobs: JobManager::new(),
             aliases: AliasManager::new(),
-            config: ShellConfig::load(),
+            config,
             last_command_time: None,
+            history,
+            history_rewrite: None,
+            session_level,
+            session_stats: StatsTable::new(),
+            view: SharedView::default(),
+            command_limit: None,
+            hook_dir: std::env::current_dir().ok(),
         }
     }
 
+    /// Copy aliases and jobs into `view` for the line editor.
+    pub fn refresh_view(&self) {
+        let mut aliases: Vec<(String, String)> = self.aliases.list().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        aliases.sort();
+        let jobs = self
+            .jobs
+            .list_jobs()
+            .iter()
+            .map(|job| (format!("%{}", job.name.clone().unwrap_or_else(|| job.id.to_string())), job.command.clone()))
+            .collect();
+        let last_command = self.previous_command().map(str::to_string);
+        if let Ok(mut view) = self.view.write() {
+            *view = ShellView { aliases, jobs, last_command };
+        }
+    }
+
+    /// The last command line from history, passing over `please` itself.
+    fn previous_command(&self) -> Option<&str> {
+        self.history.iter().rev().map(String::as_str).find(|line| line.split_whitespace().next() != Some("please"))
+    }
+
     pub fn run_line(&mut self, line: &str) -> Result<(), ShellError> {
+        let line = parser::strip_comment_lines(line);
         let trimmed = line.trim();
-        if trimmed.is_empty() || trimmed.starts_with('#') {
+        if trimmed.is_empty() {
+            return Ok(());
+        }
+
+        self.history.push(trimmed.to_string());
+        self.run_plugin_hooks("preexec", trimmed)?;
+
+        // inline_math: a line that is only an expression prints its value
+        if self.config.inline_math && calc::looks_like_math(trimmed) {
+            if let Ok(value) = calc::evaluate(trimmed) {
+                outln!("{}", calc::format_result(trimmed, value));
+            }
+            self.last_status = 0;
             return Ok(());
         }
 
+        self.aliases.refresh();
         let expanded = self.aliases.expand(trimmed);
+        let usage_before = resources::children_usage();
+        resources::take_last();
+        signals::take_interrupted();
+        let terminal = signals::TerminalState::save();
         let start = Instant::now();
 
+        // `lastout` reads the previous line's output, so it doesn't replace it
+        let keep_output = expanded.split_whitespace().next() != Some("lastout");
+        if keep_output {
+            lastout::begin();
+        }
+        let mut parsed = false;
         let result = match parse_command_line(&expanded) {
             Ok(cmd) => {
-                self.last_status = self.execute_command(&cmd)?;
-                Ok(())
+                parsed = true;
+                self.execute_command(&cmd).map(|status| self.last_status = status)
             }
             Err(e) => {
                 diagnostics::print_error(&e);
@@ -57,96 +184,293 @@ impl Shell {
                 Ok(())
             }
         };
+        if keep_output {
+            lastout::finish();
+        }
+        result?;
+
+        // A command killed by a signal may have left the terminal raw
+        if signals::take_interrupted() || self.last_status > 128 {
+            terminal.restore();
+        }
 
         let elapsed = start.elapsed();
         let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
         self.last_command_time = Some(elapsed_ms);
+        if parsed && let Some(program) = stats::program_of(&expanded) {
+            stats::record(&mut self.session_stats, program, elapsed_ms, self.last_status);
+        }
 
         if self.config.show_timing && (self.config.timing_threshold_ms == 0 || elapsed_ms >= self.config.timing_threshold_ms as f64) {
             self.display_timing(elapsed_ms);
         }
 
+        let notify_over = self.config.notify_over_ms;
+        if notify_over > 0 && elapsed_ms >= notify_over as f64 {
+            let program = expanded.split_whitespace().find(|w| *w != "time").unwrap_or_default();
+            if !self.config.notify_ignore.iter().any(|c| c == program) {
+                notify::command_finished(trimmed, elapsed.as_secs_f64(), self.last_status);
+            }
+        }
+
+        let report_over = self.config.report_resources_over_ms;
+        if report_over > 0 && elapsed_ms >= report_over as f64 {
+            self.display_resources(elapsed.as_secs_f64(), &usage_before);
+        }
+
+        Ok(())
+    }
+
+    /// `precmd=`: runs before each prompt, in this shell so its exports and
+    /// `cd`s reach the prompt. It isn't history, isn't timed, and leaves
+    /// `$?` and `PIPESTATUS` as the user's last command set them.
+    pub fn run_precmd(&mut self) -> Result<(), ShellError> {
+        if let Ok(cwd) = std::env::current_dir()
+            && self.hook_dir.as_ref() != Some(&cwd)
+        {
+            self.run_plugin_hooks("chpwd", &cwd.to_string_lossy())?;
+            self.hook_dir = Some(cwd);
+        }
+        self.run_plugin_hooks("precmd", &self.last_status.to_string())?;
+        let Some(precmd) = self.config.precmd.clone() else { return Ok(()); };
+        let line = self.aliases.expand(&precmd);
+        match self.run_hook_line(&line) {
+            Err(ShellError::Exit(code)) => Err(ShellError::Exit(code)),
+            Err(e) => {
+                errln!("squish: precmd: {}", e);
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Plugins' hooks for `event`. What each prints runs here like precmd,
+    /// leaving `$?` and `PIPESTATUS` alone.
+    fn run_plugin_hooks(&mut self, event: &str, arg: &str) -> Result<(), ShellError> {
+        for script in plugins::hook(event, arg) {
+            match self.run_hook_line(&script) {
+                Err(ShellError::Exit(code)) => return Err(ShellError::Exit(code)),
+                Err(e) => errln!("squish: {} hook: {}", event, e),
+                Ok(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `line` for precmd or a hook, then put back the statuses the
+    /// user's last command left.
+    fn run_hook_line(&mut self, line: &str) -> Result<i32, ShellError> {
+        let status = self.last_status;
+        let pipestatus = vars::pipestatus();
+        let result = parse_command_line(line).and_then(|cmd| self.execute_command(&cmd));
+        self.last_status = status;
+        vars::set_pipestatus(pipestatus);
         result
     }
 
+    /// Peak memory, CPU% and page faults of the line that just ran. Peak RSS
+    /// comes from wait4 on the foreground process when there was one; for
+    /// pipelines and builtins only a session-wide high-water mark exists, so it
+    /// is shown only if this line raised it.
+    fn display_resources(&self, real: f64, before: &resources::ResourceUsage) {
+        use colored::Colorize;
+        let after = resources::children_usage();
+        let delta = after.since(before);
+        let max_rss_kb = match resources::take_last() {
+            Some(usage) => Some(usage.max_rss_kb),
+            None if after.max_rss_kb > before.max_rss_kb => Some(after.max_rss_kb),
+            None => None,
+        };
+
+        let mut parts = Vec::new();
+        if let Some(kb) = max_rss_kb {
+            parts.push(format!("max RSS {}", locale::format_bytes(kb * 1024)));
+        }
+        if real > 0.0 {
+            let cpu_percent = (delta.user + delta.system) / real * 100.0;
+            parts.push(locale::localize_decimal(&format!("CPU {:.1}%", cpu_percent)));
+        }
+        parts.push(format!("faults {} minor / {} major", delta.minor_faults, delta.major_faults));
+
+        errln!("{} {}", "⚙".paint(Role::Accent), parts.join("  ").dimmed());
+    }
+
+    /// Run the `autols` command after changing directory, if enabled.
+    fn run_autols(&mut self) {
+        if !self.config.autols {
+            return;
+        }
+        let line = self.config.autols_command.clone();
+        match parse_command_line(&line) {
+            Ok(cmd) => {
+                if let Err(e) = self.execute_command(&cmd) {
+                    diagnostics::print_error(&e);
+                }
+            }
+            Err(e) => diagnostics::print_error(&e),
+        }
+    }
+
+    /// Print `[id] Done(code) cmd` for background jobs that finished since the
+    /// last prompt.
+    pub fn report_finished_jobs(&mut self) {
+        for job in self.jobs.take_finished() {
+            println!("[{}] {} {}", job.id, job.state_label(), job.command);
+        }
+    }
+
     fn display_timing(&self, elapsed_ms: f64) {
         use colored::Colorize;
         
         let time_str = if elapsed_ms < 1000.0 {
             format!("{:.0}ms", elapsed_ms)
         } else {
-            format!("{:.2}s", elapsed_ms / 1000.0)
+            locale::localize_decimal(&format!("{:.2}s", elapsed_ms / 1000.0))
         };
         
         let color = if elapsed_ms > 5000.0 {
-            time_str.truecolor(255, 120, 120).bold()
+            time_str.paint(Role::Error).bold()
         } else if elapsed_ms > 1000.0 {
-            time_str.truecolor(255, 200, 120)
+            time_str.paint(Role::Warning)
         } else {
-            time_str.truecolor(150, 255, 180)
+            time_str.paint(Role::Success)
         };
-        eprintln!("⏱ {}", color);
+        errln!("⏱ {}", color);
     }
 
     fn execute_command(&mut self, cmd: &CommandPart) -> Result<i32, ShellError> {
         match cmd {
-            CommandPart::Simple { argv, background } => self.execute_simple(argv, *background),
+            CommandPart::Simple { argv, background } => {
+                let status = self.execute_simple(&parser::expand_argv(argv)?, *background)?;
+                vars::set_pipestatus(vec![status]);
+                Ok(status)
+            }
             CommandPart::Pipe { left, right } => self.execute_pipe(left, right),
-            CommandPart::RedirectOut { cmd, file, append } => self.execute_redirect_out(cmd, file, *append),
-            CommandPart::RedirectIn { cmd, file } => self.execute_redirect_in(cmd, file),
+            CommandPart::Redirected { cmd, redirects } => Ok(self.execute_redirected(cmd, redirects, None, false)?.0),
             CommandPart::Chain { left, right, and } => self.execute_chain(left, right, *and),
+            CommandPart::Timed { cmd } => self.execute_timed(cmd),
+            CommandPart::Not { cmd } => Ok(negate(self.execute_command(cmd)?)),
+            CommandPart::Sequence { left, right } => {
+                self.last_status = self.execute_command(left)?;
+                // Ctrl-C abandons the rest of the line
+                if signals::interrupted() {
+                    return Ok(self.last_status);
+                }
+                self.execute_command(right)
+            }
+            CommandPart::Group { cmd } => self.execute_command(cmd),
+            CommandPart::Subshell { text } => {
+                let status = self.run_subshell(text, None, false)?.0;
+                vars::set_pipestatus(vec![status]);
+                Ok(status)
+            }
+            CommandPart::Background { text } => self.start_background(text),
         }
     }
 
     fn execute_simple(&mut self, argv: &[String], background: bool) -> Result<i32, ShellError> {
+        let (env_overlay, argv) = spawn::split_env_assignments(argv);
         if argv.is_empty() {
+            // Bare `NAME=value` with no command sets it for the shell itself
+            for (name, value) in env_overlay {
+                if !vars::assign(&name, &value) {
+                    unsafe { std::env::set_var(name, value) };
+                }
+            }
             return Ok(0);
         }
 
+        // `time <cmd>` is parsed into CommandPart::Timed; only a bare `time` lands here
         if argv[0] == "time" {
-            if argv.len() < 2 {
-                eprintln!("time: missing command");
-                return Ok(1);
-            }
-            let cmd_argv = &argv[1..];
-            let (status, timing) = self.execute_with_timing(cmd_argv, background)?;
-            self.display_detailed_timing(&timing);
-            return Ok(status);
+            errln!("time: missing command");
+            return Ok(1);
+        }
+
+        // `ll /big/dir &` runs as a job like any program would; builtins that
+        // change the shell itself stay in the foreground, since in a
+        // separate squish their effect would be lost
+        if background && (builtins::is_builtin(&argv[0]) || plugins::builtin(&argv[0]).is_some()) && !builtins::lookup(&argv[0]).is_some_and(|b| b.changes_shell) {
+            let script = argv.iter().map(|word| parser::quote(word)).collect::<Vec<_>>().join(" ");
+            let (squish, args) = self.subshell_args(&script)?;
+            return self.start_job(&argv.join(" "), &squish, &args, &[]);
         }
 
         match argv[0].as_str() {
             "alias" => {
                 if argv.len() == 1 {
                     for (name, value) in self.aliases.list() {
-                        println!("alias {}='{}'", name, value);
+                        outln!("alias {}='{}'", name, value);
                     }
                     return Ok(0);
                 }
                 let alias_def = argv[1..].join(" ");
                 if let Some((name, value)) = alias_def.split_once('=') {
-                    let value = value.trim();
-                    let value = if (value.starts_with('\'') && value.ends_with('\'')) ||
-                                   (value.starts_with('"') && value.ends_with('"')) {
-                        &value[1..value.len()-1]
+                    // Keep a trailing space: it makes the next word expand too
+                    let value = value.trim_start();
+                    let quoted = value.trim_end();
+                    let value = if quoted.len() >= 2 && ((quoted.starts_with('\'') && quoted.ends_with('\'')) ||
+                                   (quoted.starts_with('"') && quoted.ends_with('"'))) {
+                        &quoted[1..quoted.len()-1]
                     } else {
                         value
                     };
                     self.aliases.set(name.trim().to_string(), value.to_string());
                     return Ok(0);
                 } else {
-                    eprintln!("alias: invalid format: {}", alias_def);
+                    errln!("alias: invalid format: {}", alias_def);
                     return Ok(1);
                 }
             }
             "unalias" => {
                 if argv.len() < 2 {
-                    eprintln!("unalias: missing alias name");
+                    errln!("unalias: missing alias name");
                     return Ok(1);
                 }
                 let mut status = 0;
                 for name in &argv[1..] {
                     if !self.aliases.unset(name) {
-                        eprintln!("unalias: {}: not found", name);
+                        errln!("unalias: {}: not found", name);
+                        status = 1;
+                    }
+                }
+                return Ok(status);
+            }
+            "command" => {
+                // Aliases were already left alone by `AliasManager::expand`
+                if argv.get(1).is_some_and(|a| a == "-v") {
+                    let mut status = 0;
+                    for name in &argv[2..] {
+                        match lookup::find(name) {
+                            _ if builtins::is_builtin(name) || plugins::builtin(name).is_some() => outln!("{}", name),
+                            Some(path) => outln!("{}", path.display()),
+                            None => status = 1,
+                        }
+                    }
+                    return Ok(status);
+                }
+                return self.execute_simple(&argv[1..], background);
+            }
+            "type" => {
+                if argv.len() < 2 {
+                    errln!("type: usage: type <name>...");
+                    return Ok(2);
+                }
+                let mut status = 0;
+                for name in &argv[1..] {
+                    if let Some(value) = self.aliases.get(name) {
+                        outln!("{} is aliased to `{}'", name, value);
+                    } else if let Some(builtin) = builtins::lookup(name) {
+                        outln!("{} is a shell builtin ({})", name, builtin.summary().to_lowercase());
+                    } else if let Some((plugin, _)) = plugins::builtin(name) {
+                        outln!("{} is a builtin from plugin {} ({})", name, plugin.name, plugin.path.display());
+                    } else if let Some(path) = lookup::find(name) {
+                        if lookup::is_hashed(name) {
+                            outln!("{} is hashed ({})", name, path.display());
+                        } else {
+                            outln!("{} is {}", name, path.display());
+                        }
+                    } else {
+                        errln!("type: {}: not found", name);
                         status = 1;
                     }
                 }
@@ -156,58 +480,322 @@ impl Shell {
         }
 
         match argv[0].as_str() {
+            "benchmark" => {
+                return Ok(self.run_benchmark(&argv[1..]));
+            }
+            "watch" => {
+                return Ok(watch::run(&argv[1..], |line| match parse_command_line(line).and_then(|cmd| self.capture(&cmd, None)) {
+                    Ok((_, output)) => String::from_utf8_lossy(&output).into_owned(),
+                    Err(e) => format!("watch: {}\n", e),
+                }));
+            }
+            "stats" => {
+                let all = argv.iter().any(|a| a == "--all" || a == "-a");
+                let limit = match argv.iter().position(|a| a == "-n").map(|i| argv.get(i + 1).and_then(|n| n.parse().ok())) {
+                    Some(Some(n)) => n,
+                    Some(None) => {
+                        errln!("stats: -n needs a number");
+                        return Ok(1);
+                    }
+                    None => 10,
+                };
+                if all {
+                    stats::print_report("All-time command statistics", &stats::load_stats(), limit);
+                } else {
+                    stats::print_report("Command statistics for this session", &self.session_stats, limit);
+                }
+                return Ok(0);
+            }
+            "limit" => {
+                let Some(limit) = argv.get(1).and_then(|d| timeout::parse_duration(d)).filter(|_| argv.len() > 2) else {
+                    errln!("limit: usage: limit <duration> command [args...]");
+                    return Ok(2);
+                };
+                let outer = self.command_limit.replace(limit);
+                let status = self.execute_simple(&argv[2..], background);
+                self.command_limit = outer;
+                return status;
+            }
+            "please" => {
+                if argv.len() > 1 {
+                    errln!("please: usage: please");
+                    return Ok(2);
+                }
+                let Some(line) = self.previous_command().map(with_sudo) else {
+                    errln!("please: no previous command");
+                    return Ok(1);
+                };
+                {
+                    use colored::Colorize;
+                    errln!("{}", line.bright_black());
+                }
+                // History keeps what ran, as `sudo !!` would leave it
+                if let Some(last) = self.history.last_mut() {
+                    *last = line.clone();
+                }
+                self.history_rewrite = Some(line.clone());
+                return parse_command_line(&self.aliases.expand(&line)).and_then(|cmd| self.execute_command(&cmd));
+            }
+            "croot" | "cdr" => {
+                let markers = &self.config.root_markers;
+                let target = match argv.get(1) {
+                    Some(name) => project::known_roots(name, markers).into_iter().next(),
+                    None => std::env::current_dir().ok().and_then(|cwd| project::find_root(&cwd, markers)),
+                };
+                let Some(target) = target else {
+                    match argv.get(1) {
+                        Some(name) => errln!("{}: no known project root named '{}'", argv[0], name),
+                        None => errln!("{}: not inside a project (markers: {})", argv[0], markers.join(", ")),
+                    }
+                    return Ok(1);
+                };
+                if let Err(e) = vars::change_dir(&target) {
+                    errln!("{}: {}: {}", argv[0], target.display(), e);
+                    return Ok(1);
+                }
+                dirfreq::increment_dir_usage(&target);
+                self.run_autols();
+                return Ok(0);
+            }
             "jobs" => {
-                self.jobs.remove_finished();
+                if let Some(i) = argv.iter().position(|a| a == "--output" || a == "-o") {
+                    let spec = argv.get(i + 1).map(String::as_str).unwrap_or("%+");
+                    let Some(id) = parse_job_spec(spec, self.jobs.list_jobs()) else {
+                        errln!("jobs: {}: invalid job spec", spec);
+                        return Ok(1);
+                    };
+                    return self.replay_output(id);
+                }
+                if let Some(i) = argv.iter().position(|a| a == "--name") {
+                    let Some(name) = argv.get(i + 1).filter(|n| !n.starts_with('%')) else {
+                        errln!("jobs: --name needs a name");
+                        return Ok(2);
+                    };
+                    let spec = argv.get(i + 2).map(String::as_str).unwrap_or("%+");
+                    let id = parse_job_spec(spec, self.jobs.list_jobs());
+                    if !id.is_some_and(|id| self.jobs.set_name(id, name)) {
+                        errln!("jobs: {}: no such job", spec);
+                        return Ok(1);
+                    }
+                    return Ok(0);
+                }
+                let flags: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+                let long = flags.contains(&"-l");
+                let only_running = flags.contains(&"-r");
+                let only_stopped = flags.contains(&"-s");
+                if let Some(flag) = flags.iter().find(|f| !matches!(**f, "-l" | "-r" | "-s")) {
+                    errln!("jobs: {}: invalid option (see help jobs)", flag);
+                    return Ok(2);
+                }
                 for job in self.jobs.list_jobs() {
-                    let status = if let Ok(child_opt) = job.child.lock() {
-                        if child_opt.is_some() { "Running" } else { "Done" }
+                    let stopped = job.is_running() && job.is_stopped();
+                    if (only_running && (!job.is_running() || stopped)) || (only_stopped && !stopped) {
+                        continue;
+                    }
+                    let status = job.state_label();
+                    let runtime = crate::jobs::format_runtime(job.runtime());
+                    use colored::Colorize;
+                    let name = job.name.as_ref().map(|n| format!("  %{}", n).dimmed().to_string()).unwrap_or_default();
+                    if long {
+                        let (cpu, mem) = match crate::jobs::sample_usage(job.pid) {
+                            Some(u) => (locale::localize_decimal(&format!("{:.1}%", u.cpu_percent)), locale::format_bytes(u.rss_bytes)),
+                            None => (String::from("—"), String::from("—")),
+                        };
+                        outln!("[{}] {:>7} {:<8} {:>7} {:>6} {:>9}  {}{}", job.id, job.pid, status, runtime, cpu, mem, job.command, name);
                     } else {
-                        "Unknown"
-                    };
-                    println!("[{}] {} {}", job.id, status, job.command);
+                        outln!("[{}] {:<8} {:>7}  {}{}", job.id, status, runtime, job.command, name);
+                    }
                 }
+                self.jobs.remove_finished();
                 return Ok(0);
             }
             "fg" => {
-                let id = argv.get(1).and_then(|s| s.parse::<usize>().ok()).unwrap_or(1);
-                if let Some(job) = self.jobs.get_job(id) {
-                    if let Ok(mut child_opt) = job.child.lock() {
-                        if let Some(mut child) = child_opt.take() {
-                            let status = child.wait()?;
-                            return Ok(status.code().unwrap_or(1));
-                        }
+                let spec = argv.get(1).map(String::as_str).unwrap_or("%+");
+                let Some(job) = self.running_job(spec) else {
+                    errln!("fg: {}: no such job", spec);
+                    return Ok(1);
+                };
+                outln!("{}", job.command);
+                // Captured output is shown while the job has the terminal
+                let done = AtomicBool::new(false);
+                let result = std::thread::scope(|scope| {
+                    if let Some(path) = &job.output {
+                        scope.spawn(|| follow_output(path, &done));
+                    }
+                    let result = job.foreground();
+                    done.store(true, Ordering::SeqCst);
+                    result
+                });
+                return Ok(match result {
+                    Some(status) => {
+                        self.jobs.remove_finished();
+                        status
                     }
+                    None => {
+                        outln!("\n[{}] Stopped {}", job.id, job.command);
+                        148
+                    }
+                });
+            }
+            "wait" => {
+                if argv.len() == 1 {
+                    let mut status = 0;
+                    for job in self.jobs.list_jobs() {
+                        status = job.wait().unwrap_or(status);
+                    }
+                    self.report_finished_jobs();
+                    return Ok(status);
+                }
+                let mut status = 0;
+                for spec in &argv[1..] {
+                    let Some(id) = parse_job_spec(spec, self.jobs.list_jobs()) else {
+                        errln!("wait: {}: invalid job spec", spec);
+                        status = 1;
+                        continue;
+                    };
+                    status = if let Some(job) = self.jobs.get_job(id) {
+                        job.wait().unwrap_or(1)
+                    } else if let Some(code) = self.jobs.finished_status(id) {
+                        code
+                    } else {
+                        errln!("wait: {}: no such job", spec);
+                        127
+                    };
                 }
-                eprintln!("fg: job {} not found", id);
-                return Ok(1);
+                self.jobs.remove_finished();
+                return Ok(status);
             }
             "bg" => {
+                let spec = argv.get(1).map(String::as_str).unwrap_or("%+");
+                let Some(job) = self.running_job(spec) else {
+                    errln!("bg: {}: no such job", spec);
+                    return Ok(1);
+                };
+                #[cfg(unix)]
+                job.signal(libc::SIGCONT);
+                outln!("[{}] {} &", job.id, job.command);
+                return Ok(0);
+            }
+            "set" => {
+                if argv.len() == 1 || argv[1..] == ["-o"] || argv[1..] == ["+o"] {
+                    outln!("noclobber\t{}", if self.config.noclobber { "on" } else { "off" });
+                    return Ok(0);
+                }
+                let mut args = argv[1..].iter();
+                while let Some(arg) = args.next() {
+                    let (on, name) = match arg.as_str() {
+                        "-C" => (true, "noclobber"),
+                        "+C" => (false, "noclobber"),
+                        "-o" | "+o" => match args.next() {
+                            Some(name) => (arg == "-o", name.as_str()),
+                            None => (arg == "-o", ""),
+                        },
+                        _ => {
+                            errln!("set: {}: invalid option (see help set)", arg);
+                            return Ok(2);
+                        }
+                    };
+                    match name {
+                        "noclobber" => self.config.noclobber = on,
+                        _ => {
+                            errln!("set: {}: invalid option name", name);
+                            return Ok(2);
+                        }
+                    }
+                }
                 return Ok(0);
             }
+            "disown" => {
+                let keep_listed = argv.get(1).is_some_and(|a| a == "-h");
+                let specs = &argv[1 + usize::from(keep_listed)..];
+                let ids: Vec<Option<usize>> = if specs.is_empty() {
+                    vec![self.jobs.list_jobs().last().map(|j| j.id)]
+                } else {
+                    specs.iter().map(|spec| parse_job_spec(spec, self.jobs.list_jobs())).collect()
+                };
+                let mut status = 0;
+                for (i, id) in ids.into_iter().enumerate() {
+                    let found = match id {
+                        Some(id) if keep_listed => self.jobs.set_nohup(id),
+                        Some(id) => self.jobs.disown(id),
+                        None => false,
+                    };
+                    if !found {
+                        errln!("disown: {}: no such job", specs.get(i).map(String::as_str).unwrap_or("current"));
+                        status = 1;
+                    }
+                }
+                return Ok(status);
+            }
             _ => {}
         }
 
-        match try_handle_builtin(argv)? {
-            BuiltinResult::Handled(status) => Ok(status),
-            BuiltinResult::HandledWithOutput(status, _) => Ok(status),
+        // rm_to_trash: interactive `rm` moves files to the trash instead
+        if argv[0] == "rm" && self.config.rm_to_trash && std::io::stdin().is_terminal() {
+            let targets: Vec<String> = argv[1..].iter().filter(|a| !a.starts_with('-')).cloned().collect();
+            if !targets.is_empty() {
+                use colored::Colorize;
+                errln!("{} moving to the trash (rm_to_trash); run {} to delete permanently", "rm:".paint(Role::Warning).bold(), "/bin/rm".bold());
+                let mut trash_argv = vec![String::from("trash"), String::from("--")];
+                trash_argv.extend(targets);
+                return self.execute_simple(&trash_argv, background);
+            }
+        }
+
+        if argv[0] == "cd"
+            && self.config.cdspell
+            && let Some(target) = argv.get(1)
+            && !target.starts_with('@')
+            && let Some(fixed) = diagnostics::correct_dir_path(target)
+        {
+            use colored::Colorize;
+            errln!("{} {} → {}", "cd:".bright_black(), target, fixed.bold());
+            return self.execute_simple(&[String::from("cd"), fixed], background);
+        }
+
+        match try_handle_builtin(argv, None)? {
+            BuiltinResult::Handled(status) => {
+                if argv[0] == "cd" && status == 0 {
+                    self.run_autols();
+                }
+                Ok(status)
+            }
+            BuiltinResult::HandledWithOutput(status, output) => {
+                let mut stdout = stdio::stdout();
+                stdout.write_all(&output)?;
+                stdout.flush()?;
+                Ok(status)
+            }
             BuiltinResult::NotHandled => {
                 let program = &argv[0];
                 let args = &argv[1..];
+                // autocd: a lone directory name that isn't also a command
+                if self.config.autocd
+                    && !background
+                    && args.is_empty()
+                    && std::path::Path::new(program).is_dir()
+                    && lookup::find(program).is_none()
+                {
+                    return self.execute_simple(&[String::from("cd"), program.clone()], false);
+                }
                 if background {
-                    let mut command = Command::new(program);
-                    command.args(args);
-                    command.envs(std::env::vars());
-                    let child = command.spawn()
-                        .map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
                     let cmd_str = format!("{} {}", program, args.join(" "));
-                    let job_id = self.jobs.add_job(cmd_str, child);
-                    println!("[{}] {}", job_id, self.jobs.list_jobs().last().unwrap().command);
-                    Ok(0)
+                    self.start_job(&cmd_str, program, args, &env_overlay)
                 } else {
-                    match run_external_command(program, args) {
+                    let limit = self.command_limit.or(self.config.max_command_runtime).filter(|d| !d.is_zero());
+                    match run_external_command(program, args, &env_overlay, limit) {
                         Ok(code) => Ok(code),
                         Err(e) => {
-                            diagnostics::print_error(&e);
+                            if let ShellError::CommandNotFound { .. } = e
+                                && let Some(status) = self.try_autocorrect(argv)?
+                            {
+                                return Ok(status);
+                            }
+                            if let ShellError::CommandNotFound { program } = &e {
+                                diagnostics::print_command_not_found(program, argv, &self.history);
+                            } else {
+                                diagnostics::print_error(&e);
+                            }
                             match e {
                                 crate::error::ShellError::CommandNotFound { .. } => Ok(127),
                                 crate::error::ShellError::ExecFailed { .. } => Ok(126),
@@ -220,36 +808,152 @@ impl Shell {
         }
     }
 
+    /// Offer (or apply, in `auto` mode) a one-edit fix for a command that
+    /// wasn't found. Returns `None` when nothing was run.
+    fn try_autocorrect(&mut self, argv: &[String]) -> Result<Option<i32>, ShellError> {
+        use colored::Colorize;
+
+        if self.config.autocorrect == AutoCorrect::Off {
+            return Ok(None);
+        }
+        let typo = &argv[0];
+        let Some(fixed) = diagnostics::correction_for(typo) else { return Ok(None); };
+        let mut corrected = argv.to_vec();
+        corrected[0] = fixed.clone();
+
+        if self.config.autocorrect == AutoCorrect::Auto {
+            errln!("{} {}", "squish:".paint(Role::Hint), format!("correcting '{}' to '{}'", typo, fixed).paint(Role::Hint));
+        } else {
+            if !std::io::stdin().is_terminal() {
+                return Ok(None);
+            }
+            let seen_before = corrections::learned(typo).as_deref() == Some(fixed.as_str());
+            let choices = if seen_before { "[Y/n/e]" } else { "[y/N/e]" };
+            err!("{} {} ", format!("run '{}' as '{}'?", typo, fixed).paint(Role::Hint), choices.bright_black());
+            let _ = stdio::stderr().flush();
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            match answer.trim().to_lowercase().as_str() {
+                "y" | "yes" => {}
+                "" if seen_before => {}
+                "e" | "edit" => {
+                    let initial = corrected.iter().map(|a| parser::quote(a)).collect::<Vec<_>>().join(" ");
+                    let mut editor = rustyline::DefaultEditor::new().map_err(|e| ShellError::LineEditor(e.to_string()))?;
+                    let line = match editor.readline_with_initial("> ", (&initial, "")) {
+                        Ok(line) => line,
+                        Err(_) => return Ok(Some(130)),
+                    };
+                    if line.trim().is_empty() {
+                        return Ok(Some(0));
+                    }
+                    return match parse_command_line(line.trim()) {
+                        Ok(cmd) => Ok(Some(self.execute_command(&cmd)?)),
+                        Err(e) => {
+                            diagnostics::print_error(&e);
+                            Ok(Some(1))
+                        }
+                    };
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        corrections::record_correction(typo, &fixed);
+        Ok(Some(self.execute_simple(&corrected, false)?))
+    }
+
+    /// Run each stage with the previous one's output, recording every
+    /// stage's status for `$PIPESTATUS`.
     fn execute_pipe(&mut self, left: &CommandPart, right: &CommandPart) -> Result<i32, ShellError> {
-        let left_output = self.capture_output(left)?;
-        self.execute_with_input(right, &left_output)
-    }
-
-    fn execute_redirect_out(&mut self, cmd: &CommandPart, file: &str, append: bool) -> Result<i32, ShellError> {
-        let output = self.capture_output(cmd)?;
-        let mut file_handle = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(append)
-            .truncate(!append)
-            .open(file)
-            .map_err(|e| ShellError::Other(format!("cannot open {}: {}", file, e)))?;
-        file_handle.write_all(&output)
-            .map_err(|e| ShellError::Other(format!("cannot write to {}: {}", file, e)))?;
-        Ok(0)
+        let mut stages = vec![right];
+        let mut first = left;
+        while let CommandPart::Pipe { left, right } = first {
+            stages.push(right);
+            first = left;
+        }
+        stages.reverse();
+
+        let (status, mut output) = self.capture(first, None)?;
+        let mut statuses = vec![status];
+        for (i, stage) in stages.iter().enumerate() {
+            if i + 1 == stages.len() {
+                statuses.push(self.execute_with_input(stage, &output)?);
+            } else {
+                let (status, next) = self.capture(stage, Some(&output))?;
+                statuses.push(status);
+                output = next;
+            }
+        }
+        let status = statuses.last().copied().unwrap_or(0);
+        vars::set_pipestatus(statuses);
+        Ok(status)
     }
 
-    fn execute_redirect_in(&mut self, cmd: &CommandPart, file: &str) -> Result<i32, ShellError> {
-        let mut file_handle = std::fs::File::open(file)
-            .map_err(|e| ShellError::Other(format!("cannot open {}: {}", file, e)))?;
-        let mut input = Vec::new();
-        file_handle.read_to_end(&mut input)
-            .map_err(|e| ShellError::Other(format!("cannot read from {}: {}", file, e)))?;
-        self.execute_with_input(cmd, &input)
+    /// Run `cmd` with its redirects. Every file is opened in order, so
+    /// `> a > b` creates both as sh does, and the last one for each stream
+    /// wins. With noclobber, `>` won't truncate an existing regular file
+    /// unless written `>|`. A `<` file replaces piped `input`. This
+    /// thread's stdout and stderr point at their files while `cmd` runs
+    /// (see `stdio`), so builtins, children and whole pipelines write
+    /// there; with stdout redirected, there is nothing left for `capture`
+    /// to collect.
+    fn execute_redirected(&mut self, cmd: &CommandPart, redirects: &[Redirect], input: Option<&[u8]>, capture: bool) -> Result<(i32, Vec<u8>), ShellError> {
+        let noclobber = self.config.noclobber;
+        let open = |file: &str, append: bool, force: bool| {
+            let cannot_open = |e: std::io::Error| ShellError::Other(format!("cannot open {}: {}", file, e));
+            if noclobber && !append && !force {
+                // Create it exclusively, so nothing can appear between a
+                // check and the open; what exists is only written to if
+                // the opened file is no regular file (`/dev/null`, a FIFO)
+                return match OpenOptions::new().write(true).create_new(true).open(file) {
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                        let existing = OpenOptions::new().write(true).open(file).map_err(cannot_open)?;
+                        if existing.metadata().map_err(cannot_open)?.is_file() {
+                            return Err(ShellError::Other(format!("{}: cannot overwrite existing file (noclobber; use >| to force)", file)));
+                        }
+                        Ok(existing)
+                    }
+                    result => result.map_err(cannot_open),
+                };
+            }
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(file)
+                .map_err(cannot_open)
+        };
+        let mut stdin = None;
+        let mut stdout = None;
+        let mut stderr = None;
+        for redirect in redirects {
+            match redirect {
+                Redirect::In(file) => {
+                    let file = &parser::expand_redirect_target(file)?;
+                    let data = std::fs::read(file).map_err(|e| ShellError::Other(format!("cannot open {}: {}", file, e)))?;
+                    stdin = Some(data);
+                }
+                Redirect::Out { file, append, force } => stdout = Some(open(&parser::expand_redirect_target(file)?, *append, *force)?),
+                Redirect::Err { file, append, force } => stderr = Some(open(&parser::expand_redirect_target(file)?, *append, *force)?),
+            }
+        }
+        let input = stdin.as_deref().or(input);
+        let capture = capture && stdout.is_none();
+
+        let _redirected = stdio::redirect(stdout, stderr);
+        match (capture, input) {
+            (true, _) => self.capture(cmd, input),
+            (false, Some(input)) => Ok((self.execute_with_input(cmd, input)?, Vec::new())),
+            (false, None) => Ok((self.execute_command(cmd)?, Vec::new())),
+        }
     }
 
     fn execute_chain(&mut self, left: &CommandPart, right: &CommandPart, and: bool) -> Result<i32, ShellError> {
         let left_status = self.execute_command(left)?;
+        if signals::interrupted() {
+            return Ok(left_status);
+        }
         let should_run_right = if and {
             left_status == 0  // &&: run if left succeeded
         } else {
@@ -262,89 +966,110 @@ impl Shell {
         }
     }
 
-    fn capture_output(&mut self, cmd: &CommandPart) -> Result<Vec<u8>, ShellError> {
+    /// Run `cmd` with `input` on its stdin (the terminal's if `None`) and
+    /// collect its stdout. The status is what the same command would have
+    /// returned uncaptured: the last stage for a pipe, the last command run
+    /// for a chain or sequence.
+    fn capture(&mut self, cmd: &CommandPart, input: Option<&[u8]>) -> Result<(i32, Vec<u8>), ShellError> {
         match cmd {
-            CommandPart::Simple { argv, background: _ } => {
+            // `{ slow & fast; } | less`: the job gets started, not waited for
+            CommandPart::Simple { argv, background: true } => Ok((self.execute_simple(&parser::expand_argv(argv)?, true)?, Vec::new())),
+            CommandPart::Simple { argv, background: false } => {
+                let argv = parser::expand_argv(argv)?;
+                let (env_overlay, argv) = spawn::split_env_assignments(&argv);
                 if argv.is_empty() {
-                    return Ok(Vec::new());
+                    return Ok((0, Vec::new()));
                 }
-                match try_handle_builtin(argv)? {
-                    BuiltinResult::Handled(_) => Ok(Vec::new()),
-                    BuiltinResult::HandledWithOutput(_, output) => Ok(output),
+                // A pipeline stage is its own subshell: `exit` only ends it.
+                // What the builtin prints is the stage's output too.
+                let captured = stdio::capture();
+                let result = try_handle_builtin(argv, Some(input.unwrap_or_default())).or_else(exit_status);
+                let printed = captured.finish();
+                match result? {
+                    BuiltinResult::Handled(status) => Ok((status, printed)),
+                    BuiltinResult::HandledWithOutput(status, output) => Ok((status, [printed, output].concat())),
                     BuiltinResult::NotHandled => {
-                        let program = &argv[0];
-                        let args = &argv[1..];
-                        let mut command = Command::new(program);
-                        command.args(args);
-                        command.envs(std::env::vars());
-                        command.stdout(Stdio::piped());
-                        command.stderr(Stdio::inherit());
-                        let output = command.output()
-                            .map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
-                        if !output.status.success() {
-                            return Err(ShellError::Other(format!("command failed with status {}", output.status.code().unwrap_or(-1))));
-                        }
-                        Ok(output.stdout)
+                        let spawn = Spawn::new(&argv[0], &argv[1..]).env(&env_overlay).stdout(Io::Piped);
+                        let output = match input {
+                            Some(input) => spawn.output_with_input(input)?,
+                            None => spawn.output()?,
+                        };
+                        Ok((spawn::exit_code(output.status), output.stdout))
                     }
                 }
             }
             CommandPart::Pipe { left, right } => {
-                let left_out = self.capture_output(left)?;
-                let mut command = self.build_command_for_pipe(right, &left_out)?;
-                command.stdout(Stdio::piped());
-                command.stderr(Stdio::inherit());
-                let output = command.output()?;
-                if !output.status.success() {
-                    return Err(ShellError::Other(format!("command failed with status {}", output.status.code().unwrap_or(-1))));
+                let (_, left_out) = self.capture(left, input)?;
+                self.capture(right, Some(&left_out))
+            }
+            CommandPart::Redirected { cmd, redirects } => self.execute_redirected(cmd, redirects, input, true),
+            CommandPart::Chain { left, right, and } => {
+                let (status, mut output) = self.capture(left, input)?;
+                if signals::interrupted() || (status == 0) != *and {
+                    return Ok((status, output));
                 }
-                Ok(output.stdout)
+                let (status, right_out) = self.capture(right, input)?;
+                output.extend(right_out);
+                Ok((status, output))
+            }
+            CommandPart::Timed { cmd } => {
+                let (user_before, sys_before) = children_cpu_times();
+                let start = Instant::now();
+                let output = self.capture(cmd, input);
+                let (user_after, sys_after) = children_cpu_times();
+                self.display_detailed_timing(&TimingInfo {
+                    real: start.elapsed().as_secs_f64(),
+                    user: user_after - user_before,
+                    system: sys_after - sys_before,
+                });
+                output
             }
-            CommandPart::RedirectOut { cmd, .. } | CommandPart::RedirectIn { cmd, .. } => {
-                self.capture_output(cmd)
+            CommandPart::Not { cmd } => {
+                let (status, output) = self.capture(cmd, input)?;
+                Ok((negate(status), output))
             }
-            CommandPart::Chain { left, .. } => {
-                self.capture_output(left)
+            CommandPart::Sequence { left, right } => {
+                let (_, mut output) = self.capture(left, input)?;
+                let (status, right_out) = self.capture(right, input.map(|_| &[][..]))?;
+                output.extend(right_out);
+                Ok((status, output))
             }
+            CommandPart::Group { cmd } => self.capture(cmd, input),
+            CommandPart::Background { text } => Ok((self.start_background(text)?, Vec::new())),
+            CommandPart::Subshell { text } => self.run_subshell(text, input, true),
         }
     }
 
     fn execute_with_input(&mut self, cmd: &CommandPart, input: &[u8]) -> Result<i32, ShellError> {
         match cmd {
-            CommandPart::Simple { argv, background: _ } => {
+            CommandPart::Simple { argv, background: true } => self.execute_simple(&parser::expand_argv(argv)?, true),
+            CommandPart::Simple { argv, background: false } => {
+                let argv = parser::expand_argv(argv)?;
+                let (env_overlay, argv) = spawn::split_env_assignments(&argv);
                 if argv.is_empty() {
                     return Ok(0);
                 }
-                match try_handle_builtin(argv)? {
+                match try_handle_builtin(argv, Some(input)).or_else(exit_status)? {
                     BuiltinResult::Handled(status) => Ok(status),
-                    BuiltinResult::HandledWithOutput(status, _) => Ok(status),
+                    BuiltinResult::HandledWithOutput(status, output) => {
+                        let mut stdout = stdio::stdout();
+                        stdout.write_all(&output)?;
+                        stdout.flush()?;
+                        Ok(status)
+                    }
                     BuiltinResult::NotHandled => {
                         let program = &argv[0];
                         let args = &argv[1..];
-                        let mut command = Command::new(program);
-                        command.args(args);
-                        command.envs(std::env::vars());
-                        command.stdin(Stdio::piped());
-                        command.stdout(Stdio::inherit());
-                        command.stderr(Stdio::inherit());
-                        let mut child = command.spawn()
-                            .map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
-                        if let Some(mut stdin) = child.stdin.take() {
-                            stdin.write_all(input)
-                                .map_err(|e| ShellError::Other(format!("pipe write error: {}", e)))?;
-                        }
-                        let status = child.wait()
-                            .map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
-                        Ok(status.code().unwrap_or(1))
+                        let output = Spawn::new(program, args).env(&env_overlay).output_with_input(input)?;
+                        Ok(spawn::exit_code(output.status))
                     }
                 }
             }
             CommandPart::Pipe { left, right } => {
-                let left_out = self.capture_output(left)?;
+                let (_, left_out) = self.capture(left, Some(input))?;
                 self.execute_with_input(right, &left_out)
             }
-            CommandPart::RedirectOut { cmd, .. } | CommandPart::RedirectIn { cmd, .. } => {
-                self.execute_with_input(cmd, input)
-            }
+            CommandPart::Redirected { cmd, redirects } => Ok(self.execute_redirected(cmd, redirects, Some(input), false)?.0),
             CommandPart::Chain { left, right, and } => {
                 let left_status = self.execute_with_input(left, input)?;
                 let should_run = if *and { left_status == 0 } else { left_status != 0 };
@@ -354,196 +1079,369 @@ impl Shell {
                     Ok(left_status)
                 }
             }
+            CommandPart::Timed { cmd } => {
+                let (user_before, sys_before) = children_cpu_times();
+                let start = Instant::now();
+                let status = self.execute_with_input(cmd, input);
+                let (user_after, sys_after) = children_cpu_times();
+                self.display_detailed_timing(&TimingInfo {
+                    real: start.elapsed().as_secs_f64(),
+                    user: user_after - user_before,
+                    system: sys_after - sys_before,
+                });
+                status
+            }
+            CommandPart::Not { cmd } => Ok(negate(self.execute_with_input(cmd, input)?)),
+            CommandPart::Sequence { left, right } => {
+                self.execute_with_input(left, input)?;
+                self.execute_with_input(right, &[])
+            }
+            CommandPart::Group { cmd } => self.execute_with_input(cmd, input),
+            CommandPart::Subshell { text } => Ok(self.run_subshell(text, Some(input), false)?.0),
+            CommandPart::Background { text } => self.start_background(text),
         }
     }
 
-    fn build_command_for_pipe(&self, cmd: &CommandPart, _input: &[u8]) -> Result<Command, ShellError> {
-        match cmd {
-            CommandPart::Simple { argv, background: _ } => {
-                if argv.is_empty() {
-                    return Err(ShellError::Other("empty command in pipe".to_string()));
+    /// `pipeline &`: run it in a separate squish tracked as a single job.
+    fn start_background(&mut self, text: &str) -> Result<i32, ShellError> {
+        let (squish, args) = self.subshell_args(text)?;
+        self.start_job(text, &squish, &args, &[])
+    }
+
+    /// Start a job running `program`, in its own process group and not
+    /// waited for. With `nohup_jobs` on, it is detached into its own session
+    /// with output going to a log file, and kept on exit; with
+    /// `capture_job_output`, only its output goes to a file.
+    fn start_job<S: AsRef<std::ffi::OsStr> + ?Sized>(&mut self, text: &str, program: &S, args: &[String], env_overlay: &[(String, String)]) -> Result<i32, ShellError> {
+        let detach = self.config.nohup_jobs;
+        let log = if detach || self.config.capture_job_output {
+            Some(self.jobs.open_log(text).map_err(|e| ShellError::Other(format!("job output: {}", e)))?)
+        } else {
+            None
+        };
+        let (log_path, log_file) = log.unzip();
+        let child = {
+            let _redirected = match log_file {
+                Some(file) => {
+                    let copy = file.try_clone().map_err(|e| ShellError::Other(format!("job output: {}", e)))?;
+                    Some(stdio::redirect(Some(file), Some(copy)))
                 }
-                let program = &argv[0];
-                let args = &argv[1..];
-                let mut command = Command::new(program);
-                command.args(args);
-                command.envs(std::env::vars());
-                command.stdin(Stdio::piped());
-                Ok(command)
+                None => None,
+            };
+            let spawn = Spawn::new(program, args).env(env_overlay);
+            if detach {
+                spawn.stdin(Io::Null).new_session().spawn()?
+            } else {
+                spawn.new_process_group().spawn()?
+            }
+        };
+        let job_id = self.jobs.add_job(text.to_string(), child);
+        // The shell's notice, not the job's output: it stays on the
+        // terminal under a redirect
+        match log_path {
+            Some(path) if detach => {
+                self.jobs.set_nohup(job_id);
+                println!("[{}] {} (output: {})", job_id, text, path.display());
+                self.jobs.set_output(job_id, path);
+            }
+            Some(path) => {
+                self.jobs.set_output(job_id, path);
+                println!("[{}] {} (output: jobs --output {})", job_id, text, job_id);
             }
-            _ => Err(ShellError::Other("complex commands in pipes not fully supported".to_string())),
+            None => println!("[{}] {}", job_id, text),
         }
+        Ok(0)
     }
 
-    fn execute_with_timing(&mut self, argv: &[String], background: bool) -> Result<(i32, TimingInfo), ShellError> {
-        if argv.is_empty() {
-            return Ok((0, TimingInfo { real: 0.0, user: 0.0, system: 0.0 }));
+    /// Print what job `id` has written to its output file so far.
+    fn replay_output(&mut self, id: usize) -> Result<i32, ShellError> {
+        let Some(path) = self.jobs.output_file(id) else {
+            errln!("jobs: {}: output isn't captured (see capture_job_output)", id);
+            return Ok(1);
+        };
+        let mut file = std::fs::File::open(path).map_err(|e| ShellError::Other(format!("{}: {}", path.display(), e)))?;
+        let mut stdout = stdio::stdout();
+        std::io::copy(&mut file, &mut stdout)?;
+        stdout.flush()?;
+        Ok(0)
+    }
+
+    /// The still-running (or stopped) job a `%spec` names, for fg and bg.
+    fn running_job(&mut self, spec: &str) -> Option<Job> {
+        let id = parse_job_spec(spec, self.jobs.list_jobs())?;
+        self.jobs.get_job(id).filter(|job| job.is_running()).cloned()
+    }
+
+    /// Before exiting: hang up running jobs, except `disown -h`/nohup ones,
+    /// and delete captured output.
+    pub fn hang_up_jobs(&mut self) {
+        self.jobs.discard_output();
+        let kept = self.jobs.hang_up();
+        if kept > 0 {
+            errln!("squish: leaving {} job{} running", kept, if kept == 1 { "" } else { "s" });
         }
+    }
 
-        let start = Instant::now();
-        
-        let is_external = match try_handle_builtin(argv)? {
-            BuiltinResult::NotHandled => true,
-            _ => false,
+    /// Run `( text )` in a separate squish, with `input` on its stdin and,
+    /// with `capture`, its stdout collected.
+    fn run_subshell(&mut self, text: &str, input: Option<&[u8]>, capture: bool) -> Result<(i32, Vec<u8>), ShellError> {
+        let (squish, args) = self.subshell_args(text)?;
+        let spawn = Spawn::new(&squish, &args).stdout(if capture { Io::Piped } else { Io::Inherit });
+        let output = match input {
+            Some(input) => spawn.output_with_input(input)?,
+            None => spawn.output()?,
         };
+        Ok((spawn::exit_code(output.status), output.stdout))
+    }
 
-        let (status, user_time, system_time) = if is_external && !background {
-            self.execute_external_with_timing(argv)?
-        } else {
-            let status = if background {
-                self.execute_simple(argv, background)?
-            } else {
-                match try_handle_builtin(argv)? {
-                    BuiltinResult::Handled(s) => s,
-                    BuiltinResult::HandledWithOutput(s, _) => s,
-                    BuiltinResult::NotHandled => {
-                        run_external_command(&argv[0], &argv[1..])
-                            .map_err(|e| {
-                                diagnostics::print_error(&e);
-                                match e {
-                                    crate::error::ShellError::CommandNotFound { .. } => ShellError::CommandNotFound { program: argv[0].clone() },
-                                    _ => e,
-                                }
-                            })
-                            .unwrap_or(1)
-                    }
-                }
-            };
-            (status, 0.0, 0.0)
-        };
+    /// `squish -c` arguments that run `text` the way this shell would: the
+    /// environment and directory are inherited, and noclobber and `IFS`
+    /// are set again in front of `text`. Aliases were expanded already.
+    fn subshell_args(&self, text: &str) -> Result<(PathBuf, [String; 2]), ShellError> {
+        let squish = std::env::current_exe().map_err(|e| ShellError::Other(format!("cannot start a subshell: {}", e)))?;
+        let mut script = String::new();
+        if self.config.noclobber {
+            script.push_str("set -C\n");
+        }
+        if let Some(ifs) = vars::get("IFS") {
+            script.push_str(&format!("IFS={}\n", parser::quote(&ifs)));
+        }
+        script.push_str(text);
+        Ok((squish, [String::from("-c"), script]))
+    }
 
-        let elapsed = start.elapsed();
-        let real_time = elapsed.as_secs_f64();
+    /// Run `text` as `squish -c` does: no history, hooks or timing.
+    pub fn run_command_text(&mut self, text: &str) -> Result<i32, ShellError> {
+        parse_command_line(text).and_then(|cmd| self.execute_command(&cmd))
+    }
 
-        Ok((status, TimingInfo {
-            real: real_time,
-            user: user_time,
-            system: system_time,
-        }))
+    /// `time <pipeline>`: real time plus user/sys summed over every child
+    /// process the construct waited on.
+    fn execute_timed(&mut self, cmd: &CommandPart) -> Result<i32, ShellError> {
+        let (user_before, sys_before) = children_cpu_times();
+        let start = Instant::now();
+        let result = self.execute_command(cmd);
+        let real = start.elapsed().as_secs_f64();
+        let (user_after, sys_after) = children_cpu_times();
+        self.display_detailed_timing(&TimingInfo {
+            real,
+            user: user_after - user_before,
+            system: sys_after - sys_before,
+        });
+        result
     }
 
-    fn execute_external_with_timing(&mut self, argv: &[String]) -> Result<(i32, f64, f64), ShellError> {
-        let program = &argv[0];
-        let args = &argv[1..];
-        
-        let mut command = Command::new(program);
-        command.args(args);
-        command.envs(std::env::vars());
-        command.stdin(Stdio::inherit());
-        command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
-
-        let mut child = command.spawn()
-            .map_err(|e| {
-                use std::io::ErrorKind;
-                match e.kind() {
-                    ErrorKind::NotFound => ShellError::CommandNotFound { program: program.clone() },
-                    _ => ShellError::ExecFailed { program: program.clone(), message: e.to_string() },
-                }
-            })?;
-        
-        let pid = child.id() as i32;
-        
-        #[cfg(target_os = "linux")]
-        {
-            use libc::{wait4, rusage};
-            let mut rusage: rusage = unsafe { std::mem::zeroed() };
-            let mut status: i32 = 0;
-            
-            let result = unsafe { wait4(pid, &mut status, 0, &mut rusage) };
-            
-            if result == pid {
-                let user_time = rusage.ru_utime.tv_sec as f64 + rusage.ru_utime.tv_usec as f64 / 1_000_000.0;
-                let system_time = rusage.ru_stime.tv_sec as f64 + rusage.ru_stime.tv_usec as f64 / 1_000_000.0;
-                let exit_code = if libc::WIFEXITED(status) {
-                    libc::WEXITSTATUS(status)
-                } else {
-                    1
-                };
-                return Ok((exit_code, user_time, system_time));
+    /// `benchmark [-n runs] [-w warmups] [--show-output] 'cmd'`: run a command
+    /// repeatedly and summarize the timings in a table.
+    fn run_benchmark(&mut self, args: &[String]) -> i32 {
+        use colored::Colorize;
+
+        let mut runs = 10usize;
+        let mut warmups = 1usize;
+        let mut show_output = false;
+        let mut words = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-n" | "--runs" | "-w" | "--warmup" => {
+                    let Some(n) = iter.next().and_then(|v| v.parse::<usize>().ok()) else {
+                        errln!("benchmark: {} expects a number", arg);
+                        return 2;
+                    };
+                    if matches!(arg.as_str(), "-n" | "--runs") { runs = n.max(1) } else { warmups = n }
+                }
+                "--show-output" => show_output = true,
+                _ => words.push(arg.as_str()),
             }
         }
-        
-        let status = child.wait()
-            .map_err(|e| ShellError::ExecFailed { program: program.clone(), message: e.to_string() })?;
-        
-        let exit_code = status.code().unwrap_or(1);
-        
-        let mut user_time = 0.0;
-        let mut system_time = 0.0;
-        for _ in 0..3 {
-            if let Some((u, s)) = get_child_process_times(pid as u32) {
-                user_time = u;
-                system_time = s;
-                break;
+        let line = words.join(" ");
+        if line.trim().is_empty() {
+            errln!("usage: benchmark [-n runs] [-w warmups] [--show-output] '<command>'");
+            return 2;
+        }
+        let cmd = match parse_command_line(&line) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                diagnostics::print_error(&e);
+                return 2;
+            }
+        };
+
+        let mut samples: Vec<TimingInfo> = Vec::with_capacity(runs);
+        let mut failures = 0usize;
+        for i in 0..warmups + runs {
+            let silenced = (!show_output).then(silence);
+            let (user_before, sys_before) = children_cpu_times();
+            let start = Instant::now();
+            let status = self.execute_command(&cmd);
+            let real = start.elapsed().as_secs_f64();
+            let (user_after, sys_after) = children_cpu_times();
+            drop(silenced);
+            match status {
+                Ok(0) => {}
+                Ok(_) => failures += 1,
+                Err(e) => {
+                    diagnostics::print_error(&e);
+                    return 1;
+                }
+            }
+            if i >= warmups {
+                samples.push(TimingInfo { real, user: user_after - user_before, system: sys_after - sys_before });
+            }
+            if signals::interrupted() {
+                errln!("benchmark: interrupted");
+                return 130;
             }
-            std::thread::sleep(std::time::Duration::from_millis(1));
         }
-        
-        Ok((exit_code, user_time, system_time))
+
+        let n = samples.len() as f64;
+        let reals: Vec<f64> = samples.iter().map(|t| t.real).collect();
+        let mean = reals.iter().sum::<f64>() / n;
+        let stddev = if samples.len() > 1 {
+            (reals.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        let min = reals.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = reals.iter().cloned().fold(0.0, f64::max);
+        let user = samples.iter().map(|t| t.user).sum::<f64>() / n;
+        let system = samples.iter().map(|t| t.system).sum::<f64>() / n;
+
+        let rows = [
+            ("Runs", format!("{} (+{} warmup)", samples.len(), warmups)),
+            ("Min", format_seconds(min)),
+            ("Mean", format_seconds(mean)),
+            ("Std dev", format!("± {}", format_seconds(stddev))),
+            ("Max", format_seconds(max)),
+            ("User", format_seconds(user)),
+            ("Sys", format_seconds(system)),
+        ];
+        let label_w = 8;
+        let value_w = rows.iter().map(|(_, v)| v.chars().count()).max().unwrap_or(0).max(12);
+
+        outln!("{} {}", "Benchmark:".bold(), line);
+        outln!("┌{}┬{}┐", "─".repeat(label_w), "─".repeat(value_w));
+        for (label, value) in &rows {
+            let label_pad = label_w.saturating_sub(label.len());
+            let value_pad = value_w.saturating_sub(value.chars().count());
+            let value = if *label == "Mean" { value.paint(Role::Success).bold() } else { value.normal() };
+            outln!("│{}{}│{}{}│", label.bold(), " ".repeat(label_pad), value, " ".repeat(value_pad));
+        }
+        outln!("└{}┴{}┘", "─".repeat(label_w), "─".repeat(value_w));
+        if failures > 0 {
+            errln!("{} {} of {} runs exited with a non-zero status", "warning:".paint(Role::Warning).bold(), failures, warmups + runs);
+        }
+        if failures > 0 { 1 } else { 0 }
     }
 
     fn display_detailed_timing(&self, timing: &TimingInfo) {
         use colored::Colorize;
         
-        let format_time = |t: f64| {
-            if t < 0.001 {
-                format!("{:.3}m", t * 1000.0)
-            } else if t < 1.0 {
-                format!("{:.3}s", t)
-            } else {
-                format!("{:.2}s", t)
-            }
-        };
-
-        eprintln!("\n{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
-        eprintln!("{}", "  Timing Information".bold());
-        eprintln!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
-        eprintln!("  {}  {}", "Real:".truecolor(150, 255, 180).bold(), format_time(timing.real));
+        errln!("\n{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
+        errln!("{}", "  Timing Information".bold());
+        errln!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
+        errln!("  {}  {}", "Real:".paint(Role::Success).bold(), format_seconds(timing.real));
         
         if timing.user > 0.0 || timing.system > 0.0 {
-            eprintln!("  {}  {}", "User:".truecolor(140, 180, 255).bold(), format_time(timing.user));
-            eprintln!("  {}  {}", "Sys: ".truecolor(255, 200, 120).bold(), format_time(timing.system));
+            errln!("  {}  {}", "User:".paint(Role::Path).bold(), format_seconds(timing.user));
+            errln!("  {}  {}", "Sys: ".paint(Role::Warning).bold(), format_seconds(timing.system));
             
             let total_cpu = timing.user + timing.system;
             if total_cpu > 0.0 {
                 let cpu_percent = (total_cpu / timing.real * 100.0).min(100.0);
-                eprintln!("  {}  {:.1}%", "CPU: ".truecolor(200, 150, 255).bold(), cpu_percent);
+                errln!("  {}  {}", "CPU: ".paint(Role::Command).bold(), locale::localize_decimal(&format!("{:.1}%", cpu_percent)));
             }
         }
-        eprintln!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
+        errln!("{}", "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".dimmed());
     }
 }
 
-fn get_child_process_times(pid: u32) -> Option<(f64, f64)> {
-    #[cfg(target_os = "linux")]
-    {
-        use std::fs;
-        let stat_path = format!("/proc/{}/stat", pid);
-        if let Ok(stat_content) = fs::read_to_string(&stat_path) {
-            let comm_end = stat_content.find(')')?;
-            let rest = &stat_content[comm_end + 1..];
-            let fields: Vec<&str> = rest.trim_start().split_whitespace().collect();
-            
-            if fields.len() >= 14 {
-                if let (Ok(utime_ticks), Ok(stime_ticks)) = (
-                    fields[12].parse::<u64>(),
-                    fields[13].parse::<u64>(),
-                ) {
-                    unsafe extern "C" {
-                        fn sysconf(name: i32) -> i64;
-                    }
-                    const _SC_CLK_TCK: i32 = 2;
-                    let clock_ticks = unsafe { sysconf(_SC_CLK_TCK) } as f64;
-                    if clock_ticks > 0.0 {
-                        let user_time = utime_ticks as f64 / clock_ticks;
-                        let system_time = stime_ticks as f64 / clock_ticks;
-                        return Some((user_time, system_time));
-                    }
-                }
-            }
-        }
+/// Exit status of `! pipeline`.
+fn negate(status: i32) -> i32 {
+    if status == 0 { 1 } else { 0 }
+}
+
+pub(crate) fn format_seconds(t: f64) -> String {
+    let formatted = if t < 0.001 {
+        format!("{:.3}m", t * 1000.0)
+    } else if t < 1.0 {
+        format!("{:.3}s", t)
+    } else {
+        format!("{:.2}s", t)
+    };
+    locale::localize_decimal(&formatted)
+}
+
+/// Point stdout and stderr at the null device until the guard is dropped,
+/// so benchmarked commands and everything they spawn run quietly.
+fn silence() -> stdio::Redirected {
+    stdio::redirect(stdio::null().ok(), stdio::null().ok())
+}
+
+/// Accumulated user and system CPU seconds of all waited-for children.
+fn children_cpu_times() -> (f64, f64) {
+    let usage = resources::children_usage();
+    (usage.user, usage.system)
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bump SHLVL and our own SQUISH_LEVEL so nested squish sessions can be told
+/// apart from the outermost one.
+fn enter_session_level() -> usize {
+    let bump = |name: &str| {
+        let level = std::env::var(name).ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(0) + 1;
+        unsafe { std::env::set_var(name, level.to_string()) };
+        level
+    };
+    bump("SHLVL");
+    bump("SQUISH_LEVEL")
+}
+
+/// `line` run as root: `sudo` in front, unless it's there already.
+pub fn with_sudo(line: &str) -> String {
+    if line.split_whitespace().next() == Some("sudo") {
+        line.to_string()
+    } else {
+        format!("sudo {}", line)
+    }
+}
+
+/// Previously run command lines from the history file (rustyline's format).
+fn load_history() -> Vec<String> {
+    let Some(path) = crate::config::history_file() else { return Vec::new(); };
+    let Ok(content) = std::fs::read_to_string(path) else { return Vec::new(); };
+    content
+        .lines()
+        .filter(|l| !l.is_empty() && *l != "#V2")
+        .map(crate::suggest::unescape_line)
+        .collect()
+}
+
+/// `exit` inside a pipeline stage ends just that stage, with its status.
+fn exit_status(e: ShellError) -> Result<BuiltinResult, ShellError> {
+    match e {
+        ShellError::Exit(status) => Ok(BuiltinResult::Handled(status)),
+        e => Err(e),
     }
-    None
 }
 
+/// Copy a captured job's output to the terminal as it is written, until
+/// `done` is set.
+fn follow_output(path: &Path, done: &AtomicBool) {
+    let Ok(mut file) = std::fs::File::open(path) else { return; };
+    let mut stdout = std::io::stdout();
+    loop {
+        let finished = done.load(Ordering::SeqCst);
+        let _ = std::io::copy(&mut file, &mut stdout);
+        let _ = stdout.flush();
+        if finished {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
@@ -1,6 +1,13 @@
 use std::fs;
 use std::io::BufRead;
+use std::time::Duration;
 use crate::config;
+use crate::dirfreq::DirfreqScope;
+use crate::formatter::{ListColumn, LsLayout};
+use crate::locale::ModifiedFormat;
+use crate::project::ProjectCheck;
+use crate::theme::ColorDepth;
+use crate::timeout;
 
 #[derive(Debug, Clone)]
 pub struct PromptColors {
@@ -14,6 +21,85 @@ pub struct PromptColors {
     pub git_bg: Option<String>,
     pub arrow_success: Option<String>,
     pub arrow_error: Option<String>,
+    pub warning: Option<String>,
+}
+
+impl PromptColors {
+    /// Prompt colors of the built-in themes. Explicit `prompt.*` keys still
+    /// win over the preset.
+    pub fn preset(name: &str) -> Option<Self> {
+        let c = |s: &str| Some(s.to_string());
+        match name {
+            // Okabe-Ito colors: distinguishable with red-green color blindness
+            "deuteranopia" | "colorblind" => Some(Self {
+                distro_text: c("black"),
+                distro_bg: c("240,228,66"),
+                user_host_text: c("black"),
+                user_host_bg: c("86,180,233"),
+                dir_text: c("255,255,255+bold"),
+                dir_bg: c("0,114,178"),
+                git_text: c("black"),
+                git_bg: c("204,121,167"),
+                arrow_success: c("86,180,233+bold"),
+                arrow_error: c("230,159,0+bold"),
+                warning: c("230,159,0"),
+            }),
+            // No hues at all: state is carried by bold/underline/reverse and glyphs
+            "monochrome" | "mono" => Some(Self {
+                distro_text: c("black+bold"),
+                distro_bg: c("white"),
+                user_host_text: c("white"),
+                user_host_bg: c("bright_black"),
+                dir_text: c("black+bold"),
+                dir_bg: c("white"),
+                git_text: c("white+underline"),
+                git_bg: c("bright_black"),
+                arrow_success: c("white+bold"),
+                arrow_error: c("white+bold+reversed"),
+                warning: c("white+underline"),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Sets one color by its key without the `prompt.` prefix, e.g. `dir_bg`.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let slot = match key {
+            "distro_text" => &mut self.distro_text,
+            "distro_bg" => &mut self.distro_bg,
+            "user_host_text" => &mut self.user_host_text,
+            "user_host_bg" => &mut self.user_host_bg,
+            "dir_text" => &mut self.dir_text,
+            "dir_bg" => &mut self.dir_bg,
+            "git_text" => &mut self.git_text,
+            "git_bg" => &mut self.git_bg,
+            "arrow_success" => &mut self.arrow_success,
+            "arrow_error" => &mut self.arrow_error,
+            "warning" => &mut self.warning,
+            _ => return false,
+        };
+        *slot = Some(value.to_string());
+        true
+    }
+
+    pub fn fill_from(&mut self, preset: Self) {
+        let fill = |slot: &mut Option<String>, value: Option<String>| {
+            if slot.is_none() {
+                *slot = value;
+            }
+        };
+        fill(&mut self.distro_text, preset.distro_text);
+        fill(&mut self.distro_bg, preset.distro_bg);
+        fill(&mut self.user_host_text, preset.user_host_text);
+        fill(&mut self.user_host_bg, preset.user_host_bg);
+        fill(&mut self.dir_text, preset.dir_text);
+        fill(&mut self.dir_bg, preset.dir_bg);
+        fill(&mut self.git_text, preset.git_text);
+        fill(&mut self.git_bg, preset.git_bg);
+        fill(&mut self.arrow_success, preset.arrow_success);
+        fill(&mut self.arrow_error, preset.arrow_error);
+        fill(&mut self.warning, preset.warning);
+    }
 }
 
 impl Default for PromptColors {
@@ -29,29 +115,339 @@ impl Default for PromptColors {
             git_bg: None,
             arrow_success: None,
             arrow_error: None,
+            warning: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    Emacs,
+    Vi,
+}
+
+/// The value type of a config option, for docs and validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    Bool,
+    Number,
+    Text,
+    Color,
+    /// Comma-separated values
+    List,
+    Choice(&'static [&'static str]),
+}
+
+impl OptionKind {
+    pub fn describe(&self) -> String {
+        match self {
+            OptionKind::Bool => String::from("bool"),
+            OptionKind::Number => String::from("number"),
+            OptionKind::Text => String::from("text"),
+            OptionKind::Color => String::from("color"),
+            OptionKind::List => String::from("list"),
+            OptionKind::Choice(values) => values.join("|"),
+        }
+    }
+
+    /// Whether `value` is acceptable; text and colors take anything.
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            OptionKind::Bool => value.parse::<bool>().is_ok(),
+            OptionKind::Number => value.parse::<u64>().is_ok(),
+            OptionKind::Choice(values) => values.contains(&value),
+            OptionKind::Text | OptionKind::Color | OptionKind::List => true,
+        }
+    }
+}
+
+/// One documented config key. `ShellConfig::set` is the parser; this table
+/// is what `option list`, the setup wizard and key completion read.
+pub struct OptionSpec {
+    pub key: &'static str,
+    pub kind: OptionKind,
+    pub default: &'static str,
+    pub doc: &'static str,
+}
+
+const fn opt(key: &'static str, kind: OptionKind, default: &'static str, doc: &'static str) -> OptionSpec {
+    OptionSpec { key, kind, default, doc }
+}
+
+pub const OPTIONS: &[OptionSpec] = &[
+    opt("prompt", OptionKind::Text, "", "Custom prompt (%u user, %h host, %d directory, %s status, %e exit code, %g git, %j jobs, %w project warnings, %t time, %v version, %D{fmt} date, %b battery, %{name} segment, %{color}/%{reset}, \\n newline); quote to keep trailing spaces"),
+    opt("show_timing", OptionKind::Bool, "true", "Show how long each command took"),
+    opt("timing_threshold_ms", OptionKind::Number, "50", "Only show timing for commands slower than this"),
+    opt("max_command_runtime", OptionKind::Text, "0", "Stop a foreground command that runs longer than this (e.g. 30s, 10m, 1h30m; 0 = off): SIGTERM, then SIGKILL after 3 seconds; it exits with 124"),
+    opt("report_resources_over_ms", OptionKind::Number, "0", "Print max RSS, CPU% and page faults after commands slower than this (0 = off)"),
+    opt("notify_over_ms", OptionKind::Number, "0", "Desktop notification when a command takes longer than this and the terminal isn't focused (0 = off)"),
+    opt("notify_ignore", OptionKind::List, "vim,nvim,vi,nano,less,more,man,ssh,top,htop,watch", "Programs that never trigger a notification"),
+    opt("fancy_mode", OptionKind::Bool, "true", "Formatted output for ls and cat"),
+    opt("suggest_failed", OptionKind::Bool, "false", "Autosuggest command lines whose last run failed"),
+    opt("edit_mode", OptionKind::Choice(&["emacs", "vi"]), "emacs", "Line editing key bindings"),
+    opt("color_depth", OptionKind::Choice(&["auto", "truecolor", "256", "16"]), "auto", "Colors the terminal supports; RGB colors are reduced to fit (auto checks COLORTERM, TERM and terminfo)"),
+    opt("theme", OptionKind::Text, "default", "UI color theme: default, deuteranopia, monochrome or a file in ~/.config/squish/themes; prompt.* keys override it"),
+    opt("prompt.dir_depth", OptionKind::Number, "0", "Show at most this many trailing directory components, as …/src/parser (0 shows all)"),
+    opt("prompt.dir_abbrev", OptionKind::Bool, "false", "Shorten all but the last directory component fish-style: ~/c/s/squish"),
+    opt("prompt.dir_repo_relative", OptionKind::Bool, "false", "Inside a git repository, show the path from the repository root"),
+    opt("prompt.clock", OptionKind::Bool, "false", "Show the time in the prompt"),
+    opt("prompt.clock_format", OptionKind::Text, "%H:%M", "strftime format for the prompt clock (and %t)"),
+    opt("prompt.battery", OptionKind::Bool, "false", "Show laptop battery charge in the prompt, colored by level"),
+    opt("prompt.segment.<name>", OptionKind::Text, "", "Command whose output is shown in the prompt; %{name} places it in a custom prompt"),
+    opt("prompt.segment.<name>.interval", OptionKind::Number, "60", "Seconds a segment's output is reused before the command runs again"),
+    opt("prompt.distro_text", OptionKind::Color, "black", "Distro icon text color (append +bold, +underline, ... for styles)"),
+    opt("prompt.distro_bg", OptionKind::Color, "bright_yellow", "Distro icon background"),
+    opt("prompt.user_host_text", OptionKind::Color, "black", "user@host text color"),
+    opt("prompt.user_host_bg", OptionKind::Color, "white", "user@host background"),
+    opt("prompt.dir_text", OptionKind::Color, "black", "Directory text color"),
+    opt("prompt.dir_bg", OptionKind::Color, "bright_cyan", "Directory background"),
+    opt("prompt.git_text", OptionKind::Color, "black", "Git branch text color"),
+    opt("prompt.git_bg", OptionKind::Color, "bright_magenta", "Git branch background"),
+    opt("prompt.arrow_success", OptionKind::Color, "bright_green", "Prompt arrow after a successful command"),
+    opt("prompt.arrow_error", OptionKind::Color, "bright_red", "Prompt arrow and ✗ glyph after a failed command"),
+    opt("prompt.warning", OptionKind::Color, "yellow", "Project warning text"),
+    opt("project_warnings", OptionKind::List, "", "Prompt warnings: lockfile, node_modules, dotenv, or all"),
+    opt("locale", OptionKind::Text, "auto", "Locale for numbers and dates, e.g. de_DE (auto follows LC_* variables)"),
+    opt("date_format", OptionKind::Text, "", "strftime format for dates in listings, e.g. %d.%m.%Y %H:%M"),
+    opt("modified_format", OptionKind::Choice(&["absolute", "relative"]), "absolute", "Modification times in ll and the ls table as timestamps or as `3 min ago`; ll -e shows timestamps either way"),
+    opt("autocorrect", OptionKind::Choice(&["off", "prompt", "auto"]), "prompt", "Typo correction for unknown commands"),
+    opt("dnf_provides", OptionKind::Bool, "false", "Ask `dnf provides` which package has an unknown command; off by default as dnf may refresh its metadata first"),
+    opt("idle_hints", OptionKind::Bool, "false", "Show tips after sitting at an empty prompt"),
+    opt("idle_hint_seconds", OptionKind::Number, "30", "Seconds at an empty prompt before a tip"),
+    opt("completion_preview", OptionKind::Bool, "false", "Inline preview of a uniquely completed image/pdf (kitty or iTerm2 protocol)"),
+    opt("completion_preview_rows", OptionKind::Number, "8", "Height of completion previews in rows"),
+    opt("completion.case", OptionKind::Choice(&["sensitive", "insensitive", "smart"]), "smart", "Case matching for command and filename completion; smart ignores case unless the prefix has a capital"),
+    opt("ls.layout", OptionKind::Choice(&["auto", "table", "grid"]), "auto", "Layout of ls output: a table, a colored multi-column grid, or auto (grid for long listings or when the table doesn't fit)"),
+    opt("ls.grid_over", OptionKind::Number, "40", "Entries past which auto switches ls to the grid"),
+    opt("ls.columns", OptionKind::List, "", "Extra columns for ll and the ls table: mode, links, owner, target"),
+    opt("completion.max_candidates", OptionKind::Number, "100", "Most completion candidates listed at once, with a line counting the rest; 0 lists all"),
+    opt("completion.dirs_only", OptionKind::List, "rmdir,pushd,z,mkdir -p", "Commands whose arguments complete to directories only, most visited first; an entry with flags applies once they are typed"),
+    opt("completion.command.<name>", OptionKind::Text, "", "Program that completes <name>'s arguments: it reads the line as JSON on stdin and prints the candidates as JSON"),
+    opt("autols", OptionKind::Bool, "false", "Run autols_command after every successful cd"),
+    opt("autols_command", OptionKind::Text, "ll", "Command run by autols"),
+    opt("autocd", OptionKind::Bool, "false", "Change into a directory by typing just its name"),
+    opt("cdspell", OptionKind::Bool, "false", "Fix case and swapped-letter typos in cd paths"),
+    opt("inline_math", OptionKind::Bool, "false", "Evaluate a line that is only a math expression (2*(3+4)) and print the result"),
+    opt("word_split", OptionKind::Bool, "true", "Split unquoted $VAR and $(cmd) results into words on $IFS; false keeps each expansion one word, as fish does"),
+    opt("noclobber", OptionKind::Bool, "false", "`>` refuses to overwrite an existing file; `>|` still does (also `set -o noclobber`)"),
+    opt("rm_to_trash", OptionKind::Bool, "false", "Interactive rm moves files to the trash (~/.local/share/Trash) with a warning"),
+    opt("nohup_jobs", OptionKind::Bool, "false", "Start background jobs detached (own session, output to a log file) so they outlive the shell"),
+    opt("lastout_kb", OptionKind::Number, "0", "Keep up to this many KB of the last command line's output for lastout and $SQUISH_LAST_OUTPUT_FILE (0 = off); stdout then passes through a pseudo terminal"),
+    opt("capture_job_output", OptionKind::Bool, "false", "Write background job output to a file instead of the terminal; see it with jobs --output or fg"),
+    opt("root_markers", OptionKind::List, ".git", "Files/directories that mark a project root for croot/cdr"),
+    opt("dirfreq.scope", OptionKind::Choice(&["global", "repo"]), "global", "Directory frequency ranking: one list, or one per git worktree"),
+    opt("greeting", OptionKind::Text, "", "Shown when squish starts on a terminal: text with the prompt's %u, %h, %v version, %d, %t, %D{fmt} and %{color} escapes, or none for a silent start that also drops autostart output"),
+    opt("greeting.command", OptionKind::Text, "", "Command run at startup after the greeting, such as fastfetch; skipped with greeting=none"),
+    opt("autostart", OptionKind::Text, "", "Command to run at startup; repeat the key for several. Its output is dropped with greeting=none or when stdin isn't a terminal"),
+    opt("precmd", OptionKind::Text, "", "Command run in the shell before each prompt, so what it exports or changes shows in the prompt (mise/asdf/direnv hooks)"),
+    opt("plugins", OptionKind::Bool, "true", "Load plugins (prompt segments, builtins, completions, hooks) from ~/.config/squish/plugins"),
+];
+
+pub fn option_spec(key: &str) -> Option<&'static OptionSpec> {
+    let key = match key.strip_prefix("prompt.segment.") {
+        Some(rest) if rest.ends_with(".interval") => "prompt.segment.<name>.interval",
+        Some(_) => "prompt.segment.<name>",
+        None if key.starts_with("completion.command.") => "completion.command.<name>",
+        None => key,
+    };
+    OPTIONS.iter().find(|o| o.key == key)
+}
+
+/// A `prompt.segment.<name>=<command>` entry: the command's output, cached
+/// for `interval_secs`, shown in the prompt.
+#[derive(Debug, Clone)]
+pub struct PromptSegment {
+    pub name: String,
+    pub command: String,
+    pub interval_secs: u64,
+}
+
+/// A setting offered by the first-run wizard. Each choice is a label and the
+/// value written as `key=value`; an empty value leaves the key commented out.
+/// The comment above each key comes from `OPTIONS`.
+pub struct SettingSpec {
+    pub key: &'static str,
+    pub question: &'static str,
+    pub choices: &'static [(&'static str, &'static str)],
+}
+
+pub const WIZARD_SETTINGS: &[SettingSpec] = &[
+    SettingSpec {
+        key: "theme",
+        question: "Prompt colors",
+        choices: &[("default", ""), ("color-blind safe", "deuteranopia"), ("monochrome", "monochrome")],
+    },
+    SettingSpec {
+        key: "prompt",
+        question: "Prompt layout",
+        choices: &[("two-line powerline", ""), ("minimal", "\"%d ❯ \""), ("classic", "\"%u@%h:%d$ \"")],
+    },
+    SettingSpec {
+        key: "edit_mode",
+        question: "Key bindings",
+        choices: &[("emacs", "emacs"), ("vi", "vi")],
+    },
+    SettingSpec {
+        key: "show_timing",
+        question: "Show how long commands take",
+        choices: &[("yes", "true"), ("no", "false")],
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCorrect {
+    Off,
+    Prompt,
+    Auto,
+}
+
+/// How completion compares the typed prefix with names (`completion.case=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompletionCase {
+    Sensitive,
+    Insensitive,
+    /// Insensitive while the prefix is all lowercase
+    #[default]
+    Smart,
+}
+
+impl CompletionCase {
+    /// Whether names matching `prefix` only when case is ignored count.
+    pub fn folds(self, prefix: &str) -> bool {
+        match self {
+            CompletionCase::Sensitive => false,
+            CompletionCase::Insensitive => true,
+            CompletionCase::Smart => !prefix.chars().any(char::is_uppercase),
         }
     }
+
+    /// Whether `name` completes `prefix`.
+    pub fn matches(self, name: &str, prefix: &str) -> bool {
+        name.starts_with(prefix) || (self.folds(prefix) && name.to_lowercase().starts_with(&prefix.to_lowercase()))
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ShellConfig {
     pub prompt_format: Option<String>,
+    pub prompt_dir_depth: usize,
+    pub prompt_dir_abbrev: bool,
+    pub prompt_dir_repo_relative: bool,
+    pub prompt_segments: Vec<PromptSegment>,
+    pub prompt_clock: bool,
+    pub prompt_clock_format: String,
+    pub prompt_battery: bool,
     pub show_timing: bool,
     pub timing_threshold_ms: u64,
+    pub report_resources_over_ms: u64,
+    pub notify_over_ms: u64,
+    pub notify_ignore: Vec<String>,
     pub fancy_mode: bool,
     pub prompt_colors: PromptColors,
+    pub theme: Option<String>,
+    pub locale: Option<String>,
+    pub date_format: Option<String>,
+    pub modified_format: ModifiedFormat,
+    pub project_warnings: Vec<ProjectCheck>,
+    pub root_markers: Vec<String>,
+    pub edit_mode: EditMode,
+    pub autols: bool,
+    pub autols_command: String,
+    pub autocd: bool,
+    pub cdspell: bool,
+    pub rm_to_trash: bool,
+    pub noclobber: bool,
+    pub word_split: bool,
+    pub nohup_jobs: bool,
+    pub capture_job_output: bool,
+    pub lastout_kb: u64,
+    pub max_command_runtime: Option<Duration>,
+    pub inline_math: bool,
+    pub dirfreq_scope: DirfreqScope,
+    pub color_depth: ColorDepth,
+    pub suggest_failed: bool,
+    pub greeting: Option<String>,
+    pub greeting_command: Option<String>,
     pub autostart: Vec<String>,
+    pub precmd: Option<String>,
+    pub plugins: bool,
+    pub autocorrect: AutoCorrect,
+    pub dnf_provides: bool,
+    pub idle_hints: bool,
+    pub idle_hint_seconds: u64,
+    pub completion_preview: bool,
+    pub completion_preview_rows: u16,
+    pub completion_case: CompletionCase,
+    pub completion_dirs_only: Vec<String>,
+    /// `completion.command.<name>=<program>`, as (name, program)
+    pub completers: Vec<(String, String)>,
+    pub completion_max_candidates: usize,
+    pub ls_layout: LsLayout,
+    pub ls_grid_over: usize,
+    pub ls_columns: Vec<ListColumn>,
 }
 
 impl Default for ShellConfig {
     fn default() -> Self {
         Self {
             prompt_format: None,
+            prompt_dir_depth: 0,
+            prompt_dir_abbrev: false,
+            prompt_dir_repo_relative: false,
+            prompt_segments: Vec::new(),
+            prompt_clock: false,
+            prompt_clock_format: String::from("%H:%M"),
+            prompt_battery: false,
             show_timing: true,
             timing_threshold_ms: 50, // Only show timing if command takes > 50ms
+            report_resources_over_ms: 0, // 0 disables the resource report
+            notify_over_ms: 0, // 0 disables desktop notifications
+            notify_ignore: ["vim", "nvim", "vi", "nano", "less", "more", "man", "ssh", "top", "htop", "watch"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
             fancy_mode: true,
             prompt_colors: PromptColors::default(),
+            theme: None,
+            locale: None,
+            date_format: None,
+            modified_format: ModifiedFormat::Absolute,
+            project_warnings: Vec::new(),
+            root_markers: vec![String::from(".git")],
+            edit_mode: EditMode::Emacs,
+            autols: false,
+            autols_command: String::from("ll"),
+            autocd: false,
+            cdspell: false,
+            rm_to_trash: false,
+            noclobber: false,
+            word_split: true,
+            nohup_jobs: false,
+            capture_job_output: false,
+            lastout_kb: 0,
+            max_command_runtime: None,
+            inline_math: false,
+            dirfreq_scope: DirfreqScope::Global,
+            color_depth: ColorDepth::Auto,
+            suggest_failed: false,
+            greeting: None,
+            greeting_command: None,
             autostart: Vec::new(),
+            precmd: None,
+            plugins: true,
+            autocorrect: AutoCorrect::Prompt,
+            dnf_provides: false,
+            idle_hints: false,
+            idle_hint_seconds: 30,
+            completion_preview: false,
+            completion_preview_rows: 8,
+            completion_case: CompletionCase::Smart,
+            completion_dirs_only: ["rmdir", "pushd", "z", "mkdir -p"].iter().map(|s| s.to_string()).collect(),
+            completers: Vec::new(),
+            completion_max_candidates: 100,
+            ls_layout: LsLayout::Auto,
+            ls_grid_over: 40,
+            ls_columns: Vec::new(),
         }
     }
 }
@@ -74,56 +470,14 @@ impl ShellConfig {
                         let key = key.trim();
                         let value = value.trim();
                         
-                        match key {
-                            "prompt" => {
-                                config.prompt_format = Some(value.to_string());
-                            }
-                            "show_timing" => {
-                                config.show_timing = value.parse().unwrap_or(true);
-                            }
-                            "timing_threshold_ms" => {
-                                config.timing_threshold_ms = value.parse().unwrap_or(100);
-                            }
-                            "fancy_mode" => {
-                                config.fancy_mode = value.parse().unwrap_or(true);
-                            }
-                            // Prompt color options
-                            "prompt.distro_text" => {
-                                config.prompt_colors.distro_text = Some(value.to_string());
-                            }
-                            "prompt.distro_bg" => {
-                                config.prompt_colors.distro_bg = Some(value.to_string());
-                            }
-                            "prompt.user_host_text" => {
-                                config.prompt_colors.user_host_text = Some(value.to_string());
-                            }
-                            "prompt.user_host_bg" => {
-                                config.prompt_colors.user_host_bg = Some(value.to_string());
-                            }
-                            "prompt.dir_text" => {
-                                config.prompt_colors.dir_text = Some(value.to_string());
-                            }
-                            "prompt.dir_bg" => {
-                                config.prompt_colors.dir_bg = Some(value.to_string());
-                            }
-                            "prompt.git_text" => {
-                                config.prompt_colors.git_text = Some(value.to_string());
-                            }
-                            "prompt.git_bg" => {
-                                config.prompt_colors.git_bg = Some(value.to_string());
-                            }
-                            "prompt.arrow_success" => {
-                                config.prompt_colors.arrow_success = Some(value.to_string());
-                            }
-                            "prompt.arrow_error" => {
-                                config.prompt_colors.arrow_error = Some(value.to_string());
-                            }
-                            "autostart" => {
-                                // Support multiple autostart commands
-                                config.autostart.push(value.to_string());
+                        match option_spec(key) {
+                            None => eprintln!("squish: config: unknown option '{}'", key),
+                            Some(spec) if !spec.kind.accepts(value) => {
+                                eprintln!("squish: config: {}: expected {}, got '{}'", key, spec.kind.describe(), value)
                             }
                             _ => {}
                         }
+                        config.set(key, value);
                     } else if line.starts_with("autostart ") {
                         // Also support "autostart command" format
                         let cmd = line.trim_start_matches("autostart ").trim();
@@ -134,9 +488,233 @@ impl ShellConfig {
                 }
             }
         }
-        
+
         config
     }
-    
+
+    /// `<name>=<command>` or `<name>.interval=<secs>`, in either order.
+    fn set_segment(&mut self, key: &str, value: &str) -> bool {
+        let (name, interval) = match key.strip_suffix(".interval") {
+            Some(name) => (name, true),
+            None => (key, false),
+        };
+        if name.is_empty() {
+            return false;
+        }
+        let index = match self.prompt_segments.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.prompt_segments.push(PromptSegment { name: name.to_string(), command: String::new(), interval_secs: 60 });
+                self.prompt_segments.len() - 1
+            }
+        };
+        let segment = &mut self.prompt_segments[index];
+        if interval {
+            segment.interval_secs = value.parse().unwrap_or(60);
+        } else {
+            segment.command = value.to_string();
+        }
+        true
+    }
+
+    /// Apply one `key=value` setting. Returns false for unknown keys.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        if let Some(rest) = key.strip_prefix("prompt.segment.") {
+            return self.set_segment(rest, value);
+        }
+        if let Some(name) = key.strip_prefix("completion.command.") {
+            if name.is_empty() {
+                return false;
+            }
+            self.completers.retain(|(n, _)| n != name);
+            if !value.is_empty() {
+                self.completers.push((name.to_string(), value.to_string()));
+            }
+            return true;
+        }
+        if let Some(rest) = key.strip_prefix("prompt.")
+            && self.prompt_colors.set(rest, value)
+        {
+            return true;
+        }
+        match key {
+            "prompt" => {
+                // Quotes keep trailing spaces, which the line trimming would drop
+                let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+                self.prompt_format = Some(value.to_string());
+            }
+            "show_timing" => {
+                self.show_timing = value.parse().unwrap_or(true);
+            }
+            "timing_threshold_ms" => {
+                self.timing_threshold_ms = value.parse().unwrap_or(100);
+            }
+            "notify_over_ms" => {
+                self.notify_over_ms = value.parse().unwrap_or(0);
+            }
+            "notify_ignore" => {
+                self.notify_ignore = value.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from).collect();
+            }
+            "report_resources_over_ms" => {
+                self.report_resources_over_ms = value.parse().unwrap_or(0);
+            }
+            "fancy_mode" => {
+                self.fancy_mode = value.parse().unwrap_or(true);
+            }
+            "locale" => {
+                self.locale = Some(value.to_string()).filter(|v| v != "auto");
+            }
+            "modified_format" => {
+                self.modified_format = ModifiedFormat::from_name(value);
+            }
+            "date_format" => {
+                self.date_format = Some(value.to_string());
+            }
+            "theme" => {
+                self.theme = Some(value.to_string());
+            }
+            "prompt.clock" => {
+                self.prompt_clock = value.parse().unwrap_or(false);
+            }
+            "prompt.clock_format" => {
+                self.prompt_clock_format = value.to_string();
+            }
+            "prompt.battery" => {
+                self.prompt_battery = value.parse().unwrap_or(false);
+            }
+            "prompt.dir_depth" => {
+                self.prompt_dir_depth = value.parse().unwrap_or(0);
+            }
+            "prompt.dir_abbrev" => {
+                self.prompt_dir_abbrev = value.parse().unwrap_or(false);
+            }
+            "prompt.dir_repo_relative" => {
+                self.prompt_dir_repo_relative = value.parse().unwrap_or(false);
+            }
+            "project_warnings" => {
+                self.project_warnings = match value {
+                    "all" | "true" => ProjectCheck::ALL.to_vec(),
+                    _ => value.split(',').filter_map(|name| ProjectCheck::from_name(name.trim())).collect(),
+                };
+            }
+            "suggest_failed" => {
+                self.suggest_failed = value.parse().unwrap_or(false);
+            }
+            "color_depth" => {
+                self.color_depth = ColorDepth::from_name(value);
+            }
+            "dirfreq.scope" => {
+                self.dirfreq_scope = match value {
+                    "repo" => DirfreqScope::Repo,
+                    _ => DirfreqScope::Global,
+                };
+            }
+            "edit_mode" => {
+                self.edit_mode = if value == "vi" { EditMode::Vi } else { EditMode::Emacs };
+            }
+            "autols" => {
+                self.autols = value.parse().unwrap_or(false);
+            }
+            "autols_command" => {
+                self.autols_command = value.to_string();
+            }
+            "inline_math" => {
+                self.inline_math = value.parse().unwrap_or(false);
+            }
+            "word_split" => {
+                self.word_split = value.parse().unwrap_or(true);
+            }
+            "noclobber" => {
+                self.noclobber = value.parse().unwrap_or(false);
+            }
+            "rm_to_trash" => {
+                self.rm_to_trash = value.parse().unwrap_or(false);
+            }
+            "nohup_jobs" => {
+                self.nohup_jobs = value.parse().unwrap_or(false);
+            }
+            "lastout_kb" => {
+                self.lastout_kb = value.parse().unwrap_or(0);
+            }
+            "max_command_runtime" => {
+                self.max_command_runtime = timeout::parse_duration(value).filter(|d| !d.is_zero());
+            }
+            "capture_job_output" => {
+                self.capture_job_output = value.parse().unwrap_or(false);
+            }
+            "cdspell" => {
+                self.cdspell = value.parse().unwrap_or(false);
+            }
+            "autocd" => {
+                self.autocd = value.parse().unwrap_or(false);
+            }
+            "root_markers" => {
+                self.root_markers = value.split(',').map(str::trim).filter(|m| !m.is_empty()).map(String::from).collect();
+            }
+            "autocorrect" => {
+                self.autocorrect = match value {
+                    "off" | "false" => AutoCorrect::Off,
+                    "auto" => AutoCorrect::Auto,
+                    _ => AutoCorrect::Prompt,
+                };
+            }
+            "dnf_provides" => {
+                self.dnf_provides = value.parse().unwrap_or(false);
+            }
+            "idle_hints" => {
+                self.idle_hints = value.parse().unwrap_or(false);
+            }
+            "idle_hint_seconds" => {
+                self.idle_hint_seconds = value.parse().unwrap_or(30);
+            }
+            "completion_preview" => {
+                self.completion_preview = value.parse().unwrap_or(false);
+            }
+            "completion_preview_rows" => {
+                self.completion_preview_rows = value.parse().unwrap_or(8);
+            }
+            "ls.layout" => {
+                self.ls_layout = LsLayout::from_name(value);
+            }
+            "ls.grid_over" => {
+                self.ls_grid_over = value.parse().unwrap_or(40);
+            }
+            "ls.columns" => {
+                self.ls_columns = value.split(',').filter_map(|c| ListColumn::from_name(c.trim())).collect();
+            }
+            "completion.max_candidates" => {
+                self.completion_max_candidates = value.parse().unwrap_or(100);
+            }
+            "completion.dirs_only" => {
+                self.completion_dirs_only = value.split(',').map(str::trim).filter(|c| !c.is_empty()).map(String::from).collect();
+            }
+            "completion.case" => {
+                self.completion_case = match value {
+                    "sensitive" => CompletionCase::Sensitive,
+                    "insensitive" => CompletionCase::Insensitive,
+                    _ => CompletionCase::Smart,
+                };
+            }
+            "greeting" => {
+                let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+                self.greeting = Some(value.to_string()).filter(|v| !v.is_empty());
+            }
+            "greeting.command" => {
+                self.greeting_command = Some(value.to_string()).filter(|v| !v.is_empty());
+            }
+            "autostart" => {
+                // Support multiple autostart commands
+                self.autostart.push(value.to_string());
+            }
+            "precmd" => {
+                self.precmd = Some(value.to_string()).filter(|v| !v.is_empty());
+            }
+            "plugins" => {
+                self.plugins = value.parse().unwrap_or(true);
+            }
+            _ => return false,
+        }
+        true
+    }
 }
 
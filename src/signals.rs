@@ -0,0 +1,336 @@
+//! Ctrl-C and Ctrl-\ handling. Foreground commands share the shell's
+//! process group, so the terminal delivers SIGINT/SIGQUIT to both: the
+//! shell catches them with a handler that only records the interrupt, while
+//! the child (handlers reset to default on exec) gets the normal action.
+//! Background jobs run in their own process groups and aren't affected; so
+//! does a command under a runtime limit, but it's handed the terminal while
+//! it runs, Ctrl-C included.
+//! On Windows a console control handler plays the same part for Ctrl-C and
+//! Ctrl-Break.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::time::Duration;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_signal(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Console control handler: Ctrl-C and Ctrl-Break are recorded like
+/// SIGINT; returning TRUE keeps the shell alive, while the foreground
+/// program on the same console gets the event too.
+#[cfg(windows)]
+unsafe extern "system" fn on_console_event(event: u32) -> i32 {
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    if event == CTRL_C_EVENT || event == CTRL_BREAK_EVENT {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        return 1;
+    }
+    0
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn SetConsoleCtrlHandler(handler: Option<unsafe extern "system" fn(u32) -> i32>, add: i32) -> i32;
+}
+
+/// Write end of the self-pipe the SIGCHLD handler pokes; -1 until set up.
+#[cfg(unix)]
+static CHILD_PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+#[cfg(unix)]
+static CHILD_PIPE_READ: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(unix)]
+extern "C" fn on_child(_: libc::c_int) {
+    poke(CHILD_PIPE_WRITE.load(Ordering::SeqCst));
+}
+
+/// Write a byte to a self-pipe from a signal handler, if it's set up.
+#[cfg(unix)]
+fn poke(fd: libc::c_int) {
+    if fd >= 0 {
+        // The interrupted code may be about to read errno
+        let errno = errno_location();
+        let saved = errno.map(|errno| unsafe { *errno });
+        unsafe { libc::write(fd, [1u8].as_ptr().cast(), 1) };
+        if let (Some(errno), Some(saved)) = (errno, saved) {
+            unsafe { *errno = saved };
+        }
+    }
+}
+
+/// Write ends of the pipes handed out by `watch_resizes`; -1 for a free slot.
+#[cfg(unix)]
+static RESIZE_PIPES: [AtomicI32; 4] = [const { AtomicI32::new(-1) }; 4];
+/// The SIGWINCH action from before ours: the line editor's, which redraws.
+#[cfg(unix)]
+static PREVIOUS_RESIZE: std::sync::OnceLock<libc::sigaction> = std::sync::OnceLock::new();
+
+#[cfg(unix)]
+extern "C" fn on_resize(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    for slot in &RESIZE_PIPES {
+        poke(slot.load(Ordering::SeqCst));
+    }
+    let Some(previous) = PREVIOUS_RESIZE.get() else { return; };
+    if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+        return;
+    }
+    unsafe {
+        if previous.sa_flags & libc::SA_SIGINFO != 0 {
+            let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) = std::mem::transmute(previous.sa_sigaction);
+            handler(signal, info, context);
+        } else {
+            let handler: extern "C" fn(libc::c_int) = std::mem::transmute(previous.sa_sigaction);
+            handler(signal);
+        }
+    }
+}
+
+/// A pipe that turns readable when the terminal is resized, for a thread
+/// that polls. Stops being written to when dropped.
+#[cfg(unix)]
+pub struct Resizes {
+    slot: usize,
+    read: libc::c_int,
+    write: libc::c_int,
+}
+
+#[cfg(unix)]
+impl Resizes {
+    pub fn fd(&self) -> libc::c_int {
+        self.read
+    }
+
+    /// Empty the pipe, once the resize has been seen to.
+    pub fn drain(&self) {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.read, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Resizes {
+    fn drop(&mut self) {
+        RESIZE_PIPES[self.slot].store(-1, Ordering::SeqCst);
+        unsafe {
+            libc::close(self.read);
+            libc::close(self.write);
+        }
+    }
+}
+
+/// Be told about SIGWINCH. The handler goes in on first use and passes the
+/// signal on to whatever handled it before, so call this once the line
+/// editor is set up. None if there's no pipe or free slot to be had.
+#[cfg(unix)]
+pub fn watch_resizes() -> Option<Resizes> {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_resize as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGWINCH, &action, &mut previous) == 0 {
+            let _ = PREVIOUS_RESIZE.set(previous);
+        }
+    });
+    let [read, write] = nonblocking_pipe()?;
+    for (slot, pipe) in RESIZE_PIPES.iter().enumerate() {
+        if pipe.compare_exchange(-1, write, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+            return Some(Resizes { slot, read, write });
+        }
+    }
+    unsafe {
+        libc::close(read);
+        libc::close(write);
+    }
+    None
+}
+
+/// This thread's errno, where libc says how to reach it.
+#[cfg(unix)]
+fn errno_location() -> Option<*mut libc::c_int> {
+    #[cfg(any(target_os = "linux", target_os = "emscripten", target_os = "redox"))]
+    return Some(unsafe { libc::__errno_location() });
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+    return Some(unsafe { libc::__error() });
+    #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+    return Some(unsafe { libc::__errno() });
+    #[allow(unreachable_code)]
+    None
+}
+
+/// A pipe with both ends close-on-exec and non-blocking. Where there's no
+/// pipe2 (macOS) the flags are set after the fact, which is fine for the
+/// shell's few long-lived pipes: at worst one leaks into a program started
+/// at that moment.
+#[cfg(unix)]
+fn nonblocking_pipe() -> Option<[libc::c_int; 2]> {
+    let mut fds = [0; 2];
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+        return None;
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+    {
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        for fd in fds {
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK);
+            }
+        }
+    }
+    Some(fds)
+}
+
+/// Route SIGCHLD into a non-blocking self-pipe so the job reaper can sleep
+/// until a child actually exits. Safe to call more than once.
+fn watch_child_exits() {
+    #[cfg(unix)]
+    {
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| unsafe {
+            let Some(fds) = nonblocking_pipe() else { return; };
+            CHILD_PIPE_READ.store(fds[0], Ordering::SeqCst);
+            CHILD_PIPE_WRITE.store(fds[1], Ordering::SeqCst);
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_child as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART | libc::SA_NOCLDSTOP;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGCHLD, &action, std::ptr::null_mut());
+        });
+    }
+}
+
+/// Block until some child exits or `timeout` passes, whichever is first.
+pub fn wait_for_child_exit(timeout: Duration) {
+    watch_child_exits();
+    #[cfg(unix)]
+    {
+        let fd = CHILD_PIPE_READ.load(Ordering::SeqCst);
+        if fd >= 0 {
+            let mut pfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            unsafe { libc::poll(&mut pfd, 1, timeout.as_millis().min(i32::MAX as u128) as i32) };
+            let mut buf = [0u8; 64];
+            while unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+            return;
+        }
+    }
+    std::thread::sleep(timeout);
+}
+
+/// Install the shell's handlers. Call once, from the interactive shell.
+pub fn install() {
+    watch_child_exits();
+    #[cfg(unix)]
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        libc::sigaction(libc::SIGQUIT, &action, std::ptr::null_mut());
+    }
+    #[cfg(windows)]
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_console_event), 1);
+    }
+}
+
+/// Whether Ctrl-C arrived since the last call, clearing the flag.
+pub fn take_interrupted() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
+
+/// Whether Ctrl-C arrived since the flag was last cleared.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Make `pgid` the terminal's foreground process group, so Ctrl-C, Ctrl-Z
+/// and reads from the terminal go to it. False if stdin isn't our terminal
+/// (or the job runs in another session).
+pub fn give_terminal_to(pgid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        set_foreground_group(pgid as libc::pid_t)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pgid;
+        false
+    }
+}
+
+/// Whether the shell's process group is the terminal's foreground group,
+/// that is, whether the terminal is the shell's to hand to a command.
+pub fn holds_terminal() -> bool {
+    #[cfg(unix)]
+    unsafe {
+        libc::isatty(0) == 1 && libc::tcgetpgrp(0) == libc::getpgrp()
+    }
+    #[cfg(not(unix))]
+    false
+}
+
+/// Take the terminal back after `give_terminal_to`.
+pub fn reclaim_terminal() {
+    #[cfg(unix)]
+    set_foreground_group(unsafe { libc::getpgrp() });
+}
+
+#[cfg(unix)]
+fn set_foreground_group(pgid: libc::pid_t) -> bool {
+    unsafe {
+        if libc::isatty(0) != 1 {
+            return false;
+        }
+        // While we're in the background, tcsetpgrp raises SIGTTOU; with it
+        // blocked (just on this thread) the call goes through.
+        let mut block: libc::sigset_t = std::mem::zeroed();
+        let mut old: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut block);
+        libc::sigaddset(&mut block, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &block, &mut old);
+        let ok = libc::tcsetpgrp(0, pgid) == 0;
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+        ok
+    }
+}
+
+/// Terminal settings saved before running a command line, put back if a
+/// command was killed mid-way and left the terminal raw or without echo.
+pub struct TerminalState {
+    #[cfg(unix)]
+    saved: Option<libc::termios>,
+}
+
+impl TerminalState {
+    pub fn save() -> Self {
+        #[cfg(unix)]
+        {
+            let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+            let ok = unsafe { libc::isatty(0) == 1 && libc::tcgetattr(0, &mut termios) == 0 };
+            Self { saved: ok.then_some(termios) }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    pub fn restore(&self) {
+        #[cfg(unix)]
+        if let Some(termios) = &self.saved {
+            unsafe { libc::tcsetattr(0, libc::TCSADRAIN, termios) };
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! The one place external programs are started from. Foreground commands,
+//! background jobs, pipes and captures all describe their stdio here, so the
+//! wiring, process groups and error reporting can't drift apart.
+//!
+//! Children inherit the shell's environment, or start from an empty one
+//! for `sandbox`; only `NAME=value` overlays are set explicitly. Only a
+//! detached job installs a `pre_exec` hook, and it does nothing but
+//! `setsid`; everything else stays on std's posix_spawn (vfork-based) path
+//! on Linux rather than fork+exec. The shell itself is never forked:
+//! subshells and background pipelines run as `squish -c`, since a fork of
+//! a process with threads may only do async-signal-safe work before exec.
+
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+
+use crate::error::ShellError;
+use crate::lookup::{self, Lookup};
+use crate::stdio;
+
+/// How one of the child's standard streams is wired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Io {
+    Inherit,
+    Piped,
+    Null,
+}
+
+impl Io {
+    fn stdio(self) -> Stdio {
+        match self {
+            Io::Inherit => Stdio::inherit(),
+            Io::Piped => Stdio::piped(),
+            Io::Null => Stdio::null(),
+        }
+    }
+}
+
+/// Description of a process to start. Streams default to inherited.
+pub struct Spawn<'a> {
+    program: &'a OsStr,
+    args: &'a [String],
+    env_overlay: &'a [(String, String)],
+    stdin: Io,
+    stdout: Io,
+    stderr: Io,
+    new_group: bool,
+    new_session: bool,
+    clear_env: bool,
+    current_dir: Option<&'a Path>,
+}
+
+impl<'a> Spawn<'a> {
+    pub fn new<S: AsRef<OsStr> + ?Sized>(program: &'a S, args: &'a [String]) -> Self {
+        Spawn {
+            program: program.as_ref(),
+            args,
+            env_overlay: &[],
+            stdin: Io::Inherit,
+            stdout: Io::Inherit,
+            stderr: Io::Inherit,
+            new_group: false,
+            new_session: false,
+            clear_env: false,
+            current_dir: None,
+        }
+    }
+
+    pub fn env(mut self, env_overlay: &'a [(String, String)]) -> Self {
+        self.env_overlay = env_overlay;
+        self
+    }
+
+    pub fn stdin(mut self, io: Io) -> Self {
+        self.stdin = io;
+        self
+    }
+
+    pub fn stdout(mut self, io: Io) -> Self {
+        self.stdout = io;
+        self
+    }
+
+    pub fn stderr(mut self, io: Io) -> Self {
+        self.stderr = io;
+        self
+    }
+
+    /// Start from an empty environment: the child gets only the overlay.
+    pub fn clear_env(mut self) -> Self {
+        self.clear_env = true;
+        self
+    }
+
+    /// Run the child in `dir` instead of the shell's directory.
+    pub fn current_dir(mut self, dir: &'a Path) -> Self {
+        self.current_dir = Some(dir);
+        self
+    }
+
+    /// Start the child in its own process group so Ctrl-C at the terminal
+    /// doesn't reach it. Used for background jobs.
+    pub fn new_process_group(mut self) -> Self {
+        self.new_group = true;
+        self
+    }
+
+    /// Start the child in a session of its own, cut loose from the terminal
+    /// like `nohup`: for jobs kept running after the shell exits.
+    pub fn new_session(mut self) -> Self {
+        self.new_session = true;
+        self
+    }
+
+    /// The file to run. Bare names are looked up on `$PATH` here rather
+    /// than by the OS, so a miss and a file that can't be executed fail
+    /// differently and the error names what was found. A `PATH=` overlay
+    /// is the child's own search path, so those are left to the OS.
+    fn resolve(&self) -> Result<PathBuf, ShellError> {
+        let name = self.program.to_string_lossy();
+        if self.env_overlay.iter().any(|(k, _)| k == "PATH") {
+            return Ok(PathBuf::from(self.program));
+        }
+        match lookup::resolve(&name) {
+            Lookup::Found(path) => Ok(path),
+            Lookup::NotExecutable(path) => Err(ShellError::ExecFailed {
+                program: path.display().to_string(),
+                message: "permission denied".to_string(),
+            }),
+            Lookup::NotFound => Err(ShellError::CommandNotFound { program: name.into_owned() }),
+        }
+    }
+
+    /// Inherited stdout and stderr follow this thread's redirects; see
+    /// `stdio`.
+    fn command(&self, path: &Path) -> Result<Command, ShellError> {
+        let inherit = |io: Io, inherited: fn() -> io::Result<Stdio>| match io {
+            Io::Inherit => inherited().map_err(|e| ShellError::Other(format!("redirect: {}", e))),
+            io => Ok(io.stdio()),
+        };
+        let stdout = inherit(self.stdout, stdio::inherit_stdout)?;
+        let stderr = inherit(self.stderr, stdio::inherit_stderr)?;
+        let mut command = Command::new(path);
+        if self.clear_env {
+            command.env_clear();
+        }
+        if let Some(dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+            .args(self.args)
+            .envs(self.env_overlay.iter().map(|(k, v)| (k, v)))
+            .stdin(self.stdin.stdio())
+            .stdout(stdout)
+            .stderr(stderr);
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // The program sees the name it was called by, not the resolved path
+            command.arg0(self.program);
+            if self.new_session {
+                // setsid is async-signal-safe, so fine between fork and exec
+                unsafe {
+                    command.pre_exec(|| match libc::setsid() {
+                        -1 => Err(io::Error::last_os_error()),
+                        _ => Ok(()),
+                    });
+                }
+            } else if self.new_group {
+                // posix_spawn supports this directly (POSIX_SPAWN_SETPGROUP)
+                command.process_group(0);
+            }
+        }
+        #[cfg(windows)]
+        if self.new_group || self.new_session {
+            use std::os::windows::process::CommandExt;
+            // Keeps Ctrl-C at the console from reaching background jobs
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+            command.creation_flags(CREATE_NEW_PROCESS_GROUP);
+        }
+        Ok(command)
+    }
+
+    pub fn spawn(&self) -> Result<Child, ShellError> {
+        let path = self.resolve()?;
+        self.command(&path)?.spawn().map_err(|e| error(&path, e))
+    }
+
+    /// Run to completion, collecting whichever streams are `Io::Piped`.
+    pub fn output(&self) -> Result<Output, ShellError> {
+        let child = self.spawn()?;
+        child.wait_with_output().map_err(|e| error(Path::new(self.program), e))
+    }
+
+    /// Run to completion with `input` fed to stdin from a separate thread, so
+    /// a child that fills a piped stdout before reading everything can't
+    /// deadlock against us. A reader that exits early (`head`) is fine.
+    pub fn output_with_input(&self, input: &[u8]) -> Result<Output, ShellError> {
+        let path = self.resolve()?;
+        let mut command = self.command(&path)?;
+        command.stdin(Stdio::piped());
+        let mut child = command.spawn().map_err(|e| error(&path, e))?;
+        let stdin = child.stdin.take();
+        let write = std::thread::scope(|scope| {
+            let writer = scope.spawn(move || match stdin {
+                Some(mut stdin) => stdin.write_all(input),
+                None => Ok(()),
+            });
+            let output = child.wait_with_output();
+            (writer.join().unwrap_or(Ok(())), output)
+        });
+        match write {
+            (Err(e), _) if e.kind() != io::ErrorKind::BrokenPipe => Err(ShellError::Other(format!("pipe write error: {}", e))),
+            (_, output) => output.map_err(|e| error(&path, e)),
+        }
+    }
+}
+
+/// A child's exit status as the shell reports it: the exit code, or 128
+/// plus the signal that killed it.
+pub fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// ENOENT for a file that exists means its `#!` interpreter (or dynamic
+/// loader) is missing, which isn't the command being missing.
+fn error(path: &Path, e: io::Error) -> ShellError {
+    let program = path.to_string_lossy().to_string();
+    match e.kind() {
+        io::ErrorKind::NotFound if path.is_file() => ShellError::ExecFailed { program, message: "bad interpreter: not found".to_string() },
+        io::ErrorKind::NotFound => ShellError::CommandNotFound { program },
+        _ => ShellError::ExecFailed { program, message: e.to_string() },
+    }
+}
+
+/// Restores the shell's stdout and stderr when dropped; see `redirect_stdio`.
+#[cfg(unix)]
+pub struct StdioRestore {
+    saved: Vec<(libc::c_int, libc::c_int)>,
+}
+
+/// Point the whole process's stdout and/or stderr at files until the
+/// returned guard is dropped, for capturing a session behind a pty (`record`,
+/// `lastout`): every thread and every child writes there. Redirects on a
+/// command use `stdio::redirect`, which leaves other threads alone.
+#[cfg(unix)]
+pub fn redirect_stdio(stdout: Option<std::fs::File>, stderr: Option<std::fs::File>) -> Result<StdioRestore, ShellError> {
+    use std::os::fd::AsRawFd;
+
+    let mut restore = StdioRestore { saved: Vec::new() };
+    for (target, file) in [(1, stdout), (2, stderr)] {
+        let Some(file) = file else { continue; };
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        // Kept above the low descriptors and out of children
+        let saved = unsafe { libc::fcntl(target, libc::F_DUPFD_CLOEXEC, 10) };
+        if saved < 0 || unsafe { libc::dup2(file.as_raw_fd(), target) } < 0 {
+            let err = io::Error::last_os_error();
+            if saved >= 0 {
+                unsafe { libc::close(saved) };
+            }
+            return Err(ShellError::Other(format!("redirect: {}", err)));
+        }
+        restore.saved.push((target, saved));
+    }
+    Ok(restore)
+}
+
+#[cfg(unix)]
+impl Drop for StdioRestore {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        for &(target, saved) in self.saved.iter().rev() {
+            unsafe {
+                libc::dup2(saved, target);
+                libc::close(saved);
+            }
+        }
+    }
+}
+
+/// A pseudo terminal with the modes and size of the terminal on `fd`, as
+/// (master, slave), both close-on-exec. For standing between the shell's
+/// output and the real terminal while programs still see a terminal.
+#[cfg(unix)]
+pub fn open_pty_like(fd: libc::c_int) -> io::Result<(std::fs::File, std::fs::File)> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 || unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } != 0 {
+        return Err(io::Error::other("not a terminal"));
+    }
+    let (mut master, mut slave) = (-1, -1);
+    // Apple's openpty takes the modes and size as *mut, others as *const
+    if unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), &raw mut termios as _, &raw mut size as _) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    for end in [master, slave] {
+        unsafe { libc::fcntl(end, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    Ok((master.into(), slave.into()))
+}
+
+/// A close-on-exec duplicate of `fd`, such as the real terminal before
+/// `redirect_stdio` points stdout elsewhere.
+#[cfg(unix)]
+pub fn dup_fd(fd: libc::c_int) -> io::Result<std::fs::File> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    match unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) } {
+        -1 => Err(io::Error::last_os_error()),
+        dup => Ok(unsafe { OwnedFd::from_raw_fd(dup) }.into()),
+    }
+}
+
+/// Wait up to `timeout_ms` for a pty's `master` to have output (or hang
+/// up), keeping the pty the size of `terminal` meanwhile: `resizes` wakes
+/// the wait when the terminal is resized. Without it the size is checked
+/// each time the wait runs out.
+#[cfg(unix)]
+pub fn wait_pty_output(master: &std::fs::File, terminal: &std::fs::File, resizes: Option<&crate::signals::Resizes>, timeout_ms: libc::c_int) -> bool {
+    use std::os::fd::AsRawFd;
+    loop {
+        let mut fds = [
+            libc::pollfd { fd: master.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: resizes.map_or(-1, |r| r.fd()), events: libc::POLLIN, revents: 0 },
+        ];
+        match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            ready if ready <= 0 => {
+                if resizes.is_none() {
+                    sync_pty_size(terminal, master);
+                }
+                return false;
+            }
+            _ => {}
+        }
+        if let Some(resizes) = resizes
+            && fds[1].revents != 0
+        {
+            resizes.drain();
+            sync_pty_size(terminal, master);
+        }
+        if fds[0].revents != 0 {
+            return true;
+        }
+    }
+}
+
+/// Give the pty behind `master` the size of the terminal `terminal` is on,
+/// when it has changed, so programs started behind the pty lay out for it.
+#[cfg(unix)]
+fn sync_pty_size(terminal: &std::fs::File, master: &std::fs::File) {
+    use std::os::fd::AsRawFd;
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let mut current: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(terminal.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } == 0
+        && unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCGWINSZ, &mut current) } == 0
+        && (size.ws_col, size.ws_row) != (current.ws_col, current.ws_row)
+    {
+        unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) };
+    }
+}
+
+/// Split leading `NAME=value` words off a command line. They set the
+/// environment of that one command only.
+pub fn split_env_assignments(argv: &[String]) -> (Vec<(String, String)>, &[String]) {
+    let count = argv.iter().take_while(|word| is_assignment(word)).count();
+    let overlay = argv[..count]
+        .iter()
+        .filter_map(|word| word.split_once('='))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    (overlay, &argv[count..])
+}
+
+/// `NAME=value`, with a name a variable can have.
+pub fn is_assignment(word: &str) -> bool {
+    let Some((name, _)) = word.split_once('=') else { return false; };
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
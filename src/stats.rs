@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use colored::Colorize;
+
+use crate::config;
+use crate::shell::format_seconds;
+use crate::theme::{Paint, Role};
+use crate::outln;
+
+fn store_path() -> Option<PathBuf> { config::stats_file() }
+
+/// Invocation counts and timings for one program.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandStats {
+    pub runs: u64,
+    pub failures: u64,
+    pub total_ms: f64,
+}
+
+impl CommandStats {
+    fn add(&mut self, elapsed_ms: f64, failed: bool) {
+        self.runs = self.runs.saturating_add(1);
+        self.failures += u64::from(failed);
+        self.total_ms += elapsed_ms;
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.total_ms / self.runs as f64 }
+    }
+
+    pub fn failure_rate(&self) -> f64 {
+        if self.runs == 0 { 0.0 } else { self.failures as f64 / self.runs as f64 }
+    }
+}
+
+pub type StatsTable = HashMap<String, CommandStats>;
+
+/// All-time statistics: program -> runs, failures, total milliseconds.
+pub fn load_stats() -> StatsTable {
+    let mut map = HashMap::new();
+    let Some(path) = store_path() else { return map; };
+    let Ok(file) = OpenOptions::new().read(true).open(&path) else { return map; };
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let mut fields = line.splitn(4, '\t');
+        if let (Some(name), Some(runs), Some(failures), Some(total)) = (fields.next(), fields.next(), fields.next(), fields.next())
+            && let (Ok(runs), Ok(failures), Ok(total_ms)) = (runs.parse(), failures.parse(), total.parse())
+        {
+            map.insert(name.to_string(), CommandStats { runs, failures, total_ms });
+        }
+    }
+    map
+}
+
+/// Count one run of `program` in the session table and the persistent store.
+pub fn record(session: &mut StatsTable, program: &str, elapsed_ms: f64, status: i32) {
+    session.entry(program.to_string()).or_default().add(elapsed_ms, status != 0);
+    let mut all = load_stats();
+    all.entry(program.to_string()).or_default().add(elapsed_ms, status != 0);
+    let _ = save_stats(&all);
+}
+
+fn save_stats(map: &StatsTable) -> std::io::Result<()> {
+    if let Some(path) = store_path() {
+        let mut tmp = path.clone();
+        tmp.set_extension("tmp");
+        let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(&tmp)?;
+        for (name, s) in map {
+            writeln!(f, "{}\t{}\t{}\t{:.1}", name, s.runs, s.failures, s.total_ms)?;
+        }
+        f.flush()?;
+        fs::rename(tmp, path)?;
+    }
+    Ok(())
+}
+
+/// The program a command line is counted under: its first word, skipping
+/// `time`, `!`, grouping and `NAME=value` prefixes.
+pub fn program_of(line: &str) -> Option<&str> {
+    line.split_whitespace()
+        .map(|w| w.trim_start_matches('('))
+        .find(|w| !matches!(*w, "" | "time" | "!" | "{") && !w.contains('='))
+}
+
+/// Print the top commands, slowest commands and failure-prone commands.
+pub fn print_report(title: &str, table: &StatsTable, limit: usize) {
+    if table.is_empty() {
+        outln!("No commands recorded yet.");
+        return;
+    }
+    let mut rows: Vec<(&String, &CommandStats)> = table.iter().collect();
+    outln!("{}", title.bold());
+
+    rows.sort_by(|a, b| b.1.runs.cmp(&a.1.runs).then(a.0.cmp(b.0)));
+    print_table("Most used", &rows[..rows.len().min(limit)]);
+
+    rows.sort_by(|a, b| b.1.mean_ms().total_cmp(&a.1.mean_ms()).then(a.0.cmp(b.0)));
+    print_table("Slowest (mean)", &rows[..rows.len().min(limit)]);
+
+    rows.retain(|(_, s)| s.failures > 0);
+    rows.sort_by(|a, b| b.1.failure_rate().total_cmp(&a.1.failure_rate()).then(b.1.failures.cmp(&a.1.failures)));
+    if !rows.is_empty() {
+        print_table("Most failures", &rows[..rows.len().min(limit)]);
+    }
+}
+
+fn print_table(heading: &str, rows: &[(&String, &CommandStats)]) {
+    let headers = ["Command", "Runs", "Total", "Mean", "Failed"];
+    let cells: Vec<[String; 5]> = rows
+        .iter()
+        .map(|(name, s)| {
+            [
+                name.to_string(),
+                s.runs.to_string(),
+                format_seconds(s.total_ms / 1000.0),
+                format_seconds(s.mean_ms() / 1000.0),
+                format!("{} ({:.0}%)", s.failures, s.failure_rate() * 100.0),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| cells.iter().map(|row| row[i].chars().count()).chain([headers[i].len()]).max().unwrap_or(0))
+        .collect();
+    let border = |left: &str, mid: &str, right: &str| {
+        let parts: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}", left, parts.join(mid), right)
+    };
+    // Command names are left-aligned, numbers right-aligned
+    let pad = |i: usize, text: &str| {
+        let fill = " ".repeat(widths[i] - text.chars().count());
+        if i == 0 { format!("{}{}", text, fill) } else { format!("{}{}", fill, text) }
+    };
+
+    outln!("\n{}", heading.paint(Role::Accent).bold());
+    outln!("{}", border("┌", "┬", "┐"));
+    let header: Vec<String> = headers.iter().enumerate().map(|(i, h)| format!(" {} ", pad(i, h).bold())).collect();
+    outln!("│{}│", header.join("│"));
+    outln!("{}", border("├", "┼", "┤"));
+    for (row, (_, s)) in cells.iter().zip(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let text = pad(i, cell);
+                let text = match i {
+                    0 => text.paint(Role::Path).to_string(),
+                    4 if s.failures > 0 => text.paint(Role::Error).to_string(),
+                    _ => text,
+                };
+                format!(" {} ", text)
+            })
+            .collect();
+        outln!("│{}│", line.join("│"));
+    }
+    outln!("{}", border("└", "┴", "┘"));
+}
@@ -0,0 +1,206 @@
+//! The shell's stdout and stderr as the running command sees them. A
+//! redirect points them at a file for this thread only, rather than
+//! swapping the process's descriptors, so job notices, idle hints and
+//! plugin errors printed from other threads still reach the terminal.
+//!
+//! Builtins print with [`outln!`](crate::outln) and
+//! [`errln!`](crate::errln) instead of `println!`, and children started
+//! with [`Spawn`](crate::spawn::Spawn) are handed the redirect's file for
+//! the streams they inherit. A pipeline stage [`capture`]s what a builtin
+//! prints into a buffer.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::process::Stdio;
+
+enum Target {
+    File(File),
+    Buffer(Vec<u8>),
+}
+
+thread_local! {
+    static STDOUT: RefCell<Option<Target>> = const { RefCell::new(None) };
+    static STDERR: RefCell<Option<Target>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stream {
+    Out,
+    Err,
+}
+
+impl Stream {
+    fn with<R>(self, f: impl FnOnce(&mut Option<Target>) -> R) -> R {
+        match self {
+            Stream::Out => STDOUT.with_borrow_mut(f),
+            Stream::Err => STDERR.with_borrow_mut(f),
+        }
+    }
+}
+
+/// This thread's stdout: a redirect's file or a capture's buffer while one
+/// is in effect, the shell's own stdout otherwise.
+pub fn stdout() -> Writer {
+    Writer(Stream::Out)
+}
+
+/// This thread's stderr; see [`stdout`].
+pub fn stderr() -> Writer {
+    Writer(Stream::Err)
+}
+
+pub struct Writer(Stream);
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let stream = self.0;
+        stream.with(|target| match target {
+            Some(Target::File(file)) => file.write(buf),
+            Some(Target::Buffer(buffer)) => buffer.write(buf),
+            None => match stream {
+                Stream::Out => io::stdout().write(buf),
+                Stream::Err => io::stderr().write(buf),
+            },
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let stream = self.0;
+        stream.with(|target| match target {
+            Some(Target::File(file)) => file.flush(),
+            Some(Target::Buffer(_)) => Ok(()),
+            None => match stream {
+                Stream::Out => io::stdout().flush(),
+                Stream::Err => io::stderr().flush(),
+            },
+        })
+    }
+}
+
+// Formatted before the target is borrowed, so a `Display` impl that prints
+// can't trip over it. Errors are dropped as `println!` would panic on them:
+// a closed pipe or full disk isn't worth taking the shell down for.
+#[doc(hidden)]
+pub fn print_out(args: fmt::Arguments) {
+    let _ = stdout().write_all(fmt::format(args).as_bytes());
+}
+
+#[doc(hidden)]
+pub fn print_err(args: fmt::Arguments) {
+    let _ = stderr().write_all(fmt::format(args).as_bytes());
+}
+
+/// `print!` to this thread's stdout.
+#[macro_export]
+macro_rules! out {
+    ($($arg:tt)*) => { $crate::stdio::print_out(format_args!($($arg)*)) };
+}
+
+/// `println!` to this thread's stdout.
+#[macro_export]
+macro_rules! outln {
+    () => { $crate::stdio::print_out(format_args!("\n")) };
+    ($($arg:tt)*) => { $crate::stdio::print_out(format_args!("{}\n", format_args!($($arg)*))) };
+}
+
+/// `eprint!` to this thread's stderr.
+#[macro_export]
+macro_rules! err {
+    ($($arg:tt)*) => { $crate::stdio::print_err(format_args!($($arg)*)) };
+}
+
+/// `eprintln!` to this thread's stderr.
+#[macro_export]
+macro_rules! errln {
+    () => { $crate::stdio::print_err(format_args!("\n")) };
+    ($($arg:tt)*) => { $crate::stdio::print_err(format_args!("{}\n", format_args!($($arg)*))) };
+}
+
+/// Whether this thread's stdout is the terminal, for output that is only
+/// dressed up there.
+pub fn stdout_is_terminal() -> bool {
+    Stream::Out.with(|target| target.is_none()) && io::stdout().is_terminal()
+}
+
+/// What a child inheriting stdout should get: the redirect's file, or the
+/// shell's own stdout. A capture's buffer can't be handed to a child, so
+/// under one it gets the shell's own as well.
+pub(crate) fn inherit_stdout() -> io::Result<Stdio> {
+    inherit(Stream::Out)
+}
+
+/// What a child inheriting stderr should get; see [`inherit_stdout`].
+pub(crate) fn inherit_stderr() -> io::Result<Stdio> {
+    inherit(Stream::Err)
+}
+
+fn inherit(stream: Stream) -> io::Result<Stdio> {
+    stream.with(|target| match target {
+        Some(Target::File(file)) => Ok(Stdio::from(file.try_clone()?)),
+        _ => Ok(Stdio::inherit()),
+    })
+}
+
+/// The null device, for output that should go nowhere.
+pub fn null() -> io::Result<File> {
+    OpenOptions::new().write(true).open(if cfg!(windows) { "NUL" } else { "/dev/null" })
+}
+
+/// Restores the previous targets when dropped; see [`redirect`] and
+/// [`capture`].
+#[must_use]
+pub struct Redirected {
+    saved: Vec<(Stream, Option<Target>)>,
+}
+
+/// Point this thread's stdout and/or stderr at files until the returned
+/// guard is dropped. Redirects nest: the innermost one wins.
+pub fn redirect(stdout: Option<File>, stderr: Option<File>) -> Redirected {
+    let mut redirected = Redirected { saved: Vec::new() };
+    for (stream, file) in [(Stream::Out, stdout), (Stream::Err, stderr)] {
+        if let Some(file) = file {
+            redirected.swap(stream, Target::File(file));
+        }
+    }
+    redirected
+}
+
+/// Collect what this thread prints to stdout until [`Redirected::finish`].
+pub fn capture() -> Redirected {
+    let mut redirected = Redirected { saved: Vec::new() };
+    redirected.swap(Stream::Out, Target::Buffer(Vec::new()));
+    redirected
+}
+
+impl Redirected {
+    fn swap(&mut self, stream: Stream, target: Target) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        let previous = stream.with(|current| current.replace(target));
+        self.saved.push((stream, previous));
+    }
+
+    /// Restore the previous targets, returning what a [`capture`] collected.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> Vec<u8> {
+        let mut captured = Vec::new();
+        for (stream, previous) in self.saved.drain(..).rev() {
+            if let Some(Target::Buffer(buffer)) = stream.with(|current| std::mem::replace(current, previous)) {
+                captured = buffer;
+            }
+        }
+        captured
+    }
+}
+
+impl Drop for Redirected {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
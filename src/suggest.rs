@@ -0,0 +1,185 @@
+//! History-aware autosuggestions. Every interactive command line is logged
+//! with its time, exit status and working directory, and the hint for a
+//! typed prefix is the logged line that ranks best by how often and how
+//! recently it ran and whether it ran in the current directory.
+
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config;
+
+/// The log is compacted to its newest half once it grows past this.
+const MAX_LOG_LINES: usize = 20_000;
+
+pub type SharedLog = Arc<RwLock<CommandLog>>;
+
+fn store_path() -> Option<PathBuf> { config::command_log_file() }
+
+/// The session's log, read in the background: it can run to thousands of
+/// lines, and the first prompt shouldn't wait for it. The reader holds the
+/// write lock throughout, so a line recorded meanwhile waits its turn.
+pub fn load_shared(include_failed: bool) -> SharedLog {
+    let shared: SharedLog = Arc::new(RwLock::new(CommandLog { include_failed, ..CommandLog::default() }));
+    let loader = Arc::clone(&shared);
+    let read = move || {
+        if let Ok(mut log) = loader.write() {
+            *log = CommandLog { include_failed, ..CommandLog::load() };
+        }
+    };
+    if std::thread::Builder::new().name("squish-command-log".to_string()).spawn(read.clone()).is_err() {
+        read();
+    }
+    shared
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Default)]
+struct LineStats {
+    runs: u32,
+    last_run: u64,
+    last_status: i32,
+    /// Runs per working directory
+    dirs: HashMap<String, u32>,
+}
+
+impl LineStats {
+    fn add(&mut self, time: u64, status: i32, cwd: &str) {
+        self.runs = self.runs.saturating_add(1);
+        if time >= self.last_run {
+            self.last_run = time;
+            self.last_status = status;
+        }
+        *self.dirs.entry(cwd.to_string()).or_insert(0) += 1;
+    }
+
+    /// Frequency (log-scaled so habits don't drown out recent work), recency
+    /// (halves after a day), and a bonus for lines run in `cwd`.
+    fn score(&self, now: u64, cwd: &str) -> f64 {
+        let frequency = (1.0 + self.runs as f64).ln();
+        let age_days = now.saturating_sub(self.last_run) as f64 / 86_400.0;
+        let recency = 2.0 / (1.0 + age_days);
+        let here = match self.dirs.get(cwd) {
+            Some(&runs) => 1.0 + (runs as f64).ln_1p(),
+            None => 0.0,
+        };
+        frequency + recency + here
+    }
+
+    /// Ctrl-C (130) is the user stopping a command, not the command failing.
+    fn failed(&self) -> bool {
+        self.last_status != 0 && self.last_status != 130
+    }
+}
+
+/// Escape a line for a one-line record, as the history file does:
+/// `\\` for a backslash and `\n` for a newline.
+pub fn escape_line(line: &str) -> String {
+    line.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Undo [`escape_line`], in one pass so `\\n` stays a backslash and an `n`.
+pub fn unescape_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Default)]
+pub struct CommandLog {
+    lines: HashMap<String, LineStats>,
+    /// Suggest lines whose last run failed (`suggest_failed=`)
+    pub include_failed: bool,
+}
+
+impl CommandLog {
+    /// `time\tstatus\tcwd\tline` records, with newlines in `line` escaped the
+    /// way the history file does.
+    pub fn load() -> Self {
+        let mut log = Self::default();
+        let Some(path) = store_path() else { return log; };
+        let Ok(file) = OpenOptions::new().read(true).open(&path) else { return log; };
+        let records: Vec<String> = BufReader::new(file).lines().map_while(Result::ok).collect();
+        for record in &records {
+            let mut fields = record.splitn(4, '\t');
+            if let (Some(time), Some(status), Some(cwd), Some(line)) = (fields.next(), fields.next(), fields.next(), fields.next())
+                && let (Ok(time), Ok(status)) = (time.parse(), status.parse())
+            {
+                let line = unescape_line(line);
+                log.lines.entry(line).or_default().add(time, status, cwd);
+            }
+        }
+        if records.len() > MAX_LOG_LINES {
+            let _ = compact(&path, &records[records.len() - MAX_LOG_LINES / 2..]);
+        }
+        log
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Count a line that just finished, in memory and in the log file.
+    pub fn record(&mut self, line: &str, cwd: &str, status: i32) {
+        let time = now();
+        self.lines.entry(line.to_string()).or_default().add(time, status, cwd);
+        let Some(path) = store_path() else { return; };
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+            let escaped = escape_line(line);
+            let _ = writeln!(file, "{}\t{}\t{}\t{}", time, status, cwd, escaped);
+        }
+    }
+
+    /// The best-ranked logged line that extends `prefix`.
+    pub fn suggest(&self, prefix: &str, cwd: &str) -> Option<&str> {
+        let now = now();
+        self.lines
+            .iter()
+            .filter(|(line, stats)| line.starts_with(prefix) && line.len() > prefix.len() && (self.include_failed || !stats.failed()))
+            .max_by(|(a_line, a), (b_line, b)| {
+                a.score(now, cwd).total_cmp(&b.score(now, cwd)).then_with(|| b_line.cmp(a_line))
+            })
+            .map(|(line, _)| line.as_str())
+    }
+}
+
+fn compact(path: &PathBuf, keep: &[String]) -> std::io::Result<()> {
+    let mut tmp = path.clone();
+    tmp.set_extension("tmp");
+    let mut f = OpenOptions::new().create(true).write(true).truncate(true).open(&tmp)?;
+    for record in keep {
+        writeln!(f, "{}", record)?;
+    }
+    f.flush()?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_lines_round_trip() {
+        for line in ["printf 'a\\nb'", "echo a\nb", "a\\\\n", "trailing \\", ""] {
+            assert_eq!(unescape_line(&escape_line(line)), line);
+        }
+        assert_eq!(unescape_line("printf 'a\\\\nb'"), "printf 'a\\nb'");
+    }
+}
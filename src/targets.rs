@@ -0,0 +1,154 @@
+//! Completion values read from the build files of the current directory:
+//! make and just targets, and cargo's subcommands, binaries, examples and
+//! features. Files are read on every call; they are small, and a target
+//! added a moment ago should complete straight away.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::lookup;
+use crate::project;
+
+/// Subcommands that ship with cargo; installed `cargo-*` tools add to these.
+const CARGO_SUBCOMMANDS: &[&str] = &[
+    "add", "bench", "build", "check", "clean", "clippy", "doc", "fetch", "fix", "fmt", "generate-lockfile", "help",
+    "init", "install", "locate-project", "login", "logout", "metadata", "new", "owner", "package", "pkgid", "publish",
+    "remove", "report", "run", "rustc", "rustdoc", "search", "test", "tree", "uninstall", "update", "vendor",
+    "verify-project", "version", "yank",
+];
+
+/// Explicit targets of the Makefile in `dir`: rule names that aren't
+/// special (`.PHONY`), patterns (`%.o`) or variable assignments.
+pub fn make_targets(dir: &Path) -> Vec<String> {
+    let Some(text) = ["GNUmakefile", "makefile", "Makefile"].iter().find_map(|name| fs::read_to_string(dir.join(name)).ok()) else {
+        return Vec::new();
+    };
+    let mut targets = BTreeSet::new();
+    for line in text.lines() {
+        if line.starts_with(['\t', '#', ' ']) {
+            continue;
+        }
+        let Some((names, rest)) = line.split_once(':') else { continue; };
+        // `:=` and `::=` assign; `::` is a double-colon rule
+        if rest.starts_with('=') || rest.starts_with(":=") || names.contains('=') {
+            continue;
+        }
+        for name in names.split_whitespace() {
+            if !name.starts_with('.') && !name.contains(['%', '$']) {
+                targets.insert(name.to_string());
+            }
+        }
+    }
+    targets.into_iter().collect()
+}
+
+/// Public recipe names of the justfile in `dir`; `[private]` and `_name`
+/// recipes are left out, as `just --list` does.
+pub fn just_recipes(dir: &Path) -> Vec<String> {
+    let Some(text) = ["justfile", "Justfile", ".justfile"].iter().find_map(|name| fs::read_to_string(dir.join(name)).ok()) else {
+        return Vec::new();
+    };
+    let mut recipes = BTreeSet::new();
+    let mut private = false;
+    for line in text.lines() {
+        if line.starts_with('[') {
+            private |= line.contains("private");
+            continue;
+        }
+        if line.starts_with([' ', '\t', '#']) || line.contains(":=") {
+            continue;
+        }
+        let Some((head, _)) = line.split_once(':') else { continue; };
+        let is_private = std::mem::take(&mut private);
+        // `@name arg='x':` is a quiet recipe with parameters
+        if let Some(name) = head.split_whitespace().next().map(|n| n.trim_start_matches('@'))
+            && !is_private
+            && !name.is_empty()
+            && !name.starts_with('_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            recipes.insert(name.to_string());
+        }
+    }
+    recipes.into_iter().collect()
+}
+
+/// Built-in cargo subcommands plus `cargo-<name>` executables on PATH.
+pub fn cargo_subcommands() -> Vec<String> {
+    let mut names: BTreeSet<String> = CARGO_SUBCOMMANDS.iter().map(|s| s.to_string()).collect();
+    names.extend(lookup::commands().keys().filter_map(|name| name.strip_prefix("cargo-")).map(String::from));
+    names.into_iter().collect()
+}
+
+/// What `--bin`, `--example` and `--features` accept in the package
+/// around `dir`.
+#[derive(Debug, Default)]
+pub struct CargoTargets {
+    pub bins: Vec<String>,
+    pub examples: Vec<String>,
+    pub features: Vec<String>,
+}
+
+/// Read the nearest Cargo.toml: `[[bin]]`/`[[example]]` names, the
+/// package's own binary when `src/main.rs` exists, `src/bin` and
+/// `examples` files, and `[features]` keys. Just enough TOML for that.
+pub fn cargo_targets(dir: &Path) -> CargoTargets {
+    let Some(manifest) = project::find_upwards(dir, "Cargo.toml") else { return CargoTargets::default(); };
+    let Ok(text) = fs::read_to_string(&manifest) else { return CargoTargets::default(); };
+    let root = manifest.parent().unwrap_or(dir);
+
+    let mut bins = BTreeSet::new();
+    let mut examples = BTreeSet::new();
+    let mut features = BTreeSet::new();
+    let mut section = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line.trim_matches(['[', ']']).trim().to_string();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else { continue; };
+        let key = key.trim().trim_matches('"');
+        let value = value.trim().trim_matches('"');
+        match (section.as_str(), key) {
+            ("package", "name") if root.join("src/main.rs").is_file() => {
+                bins.insert(value.to_string());
+            }
+            ("bin", "name") => {
+                bins.insert(value.to_string());
+            }
+            ("example", "name") => {
+                examples.insert(value.to_string());
+            }
+            ("features", _) if !key.is_empty() && !key.starts_with('#') => {
+                features.insert(key.to_string());
+            }
+            _ => {}
+        }
+    }
+    bins.extend(rust_file_stems(&root.join("src/bin")));
+    examples.extend(rust_file_stems(&root.join("examples")));
+    CargoTargets {
+        bins: bins.into_iter().collect(),
+        examples: examples.into_iter().collect(),
+        features: features.into_iter().collect(),
+    }
+}
+
+/// `name` for each `name.rs` and `name/main.rs` in `dir`: cargo's target
+/// auto-discovery.
+fn rust_file_stems(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new(); };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = entry.file_name().to_str()?.to_string();
+            if path.is_dir() {
+                path.join("main.rs").is_file().then_some(name)
+            } else {
+                name.strip_suffix(".rs").map(String::from)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,469 @@
+//! Named color roles shared by highlighting, completion menus, `ll`, help
+//! pages, diagnostics and the prompt. `theme=<name>` (or the `theme` builtin)
+//! selects a built-in palette or a theme file from `~/.config/squish/themes`.
+//!
+//! A theme file holds `role=color` lines in the same syntax as the
+//! `prompt.*` options (`red`, `200,150,255`, `cyan+bold`), may set
+//! `prompt.*` colors as well, and can start from a built-in palette with
+//! `base=<name>`. Roles it leaves out keep the default colors.
+
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::sync::{OnceLock, RwLock};
+
+use colored::{Color, ColoredString, Colorize};
+
+use crate::config;
+use crate::shell_config::PromptColors;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Command names, option keys and other things you can type
+    Command,
+    Builtin,
+    /// Commands found on PATH, in completion menus
+    External,
+    Alias,
+    /// Directories and paths
+    Path,
+    String,
+    Variable,
+    /// `|`, `&&`, redirections
+    Operator,
+    Error,
+    /// The message after `error:`
+    ErrorText,
+    Warning,
+    Success,
+    Hint,
+    /// Headings, symlinks, bookmarks
+    Accent,
+    /// `ll` file kinds: source code, documents, data/archives, images
+    Source,
+    Docs,
+    Data,
+    Media,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[
+        Role::Command,
+        Role::Builtin,
+        Role::External,
+        Role::Alias,
+        Role::Path,
+        Role::String,
+        Role::Variable,
+        Role::Operator,
+        Role::Error,
+        Role::ErrorText,
+        Role::Warning,
+        Role::Success,
+        Role::Hint,
+        Role::Accent,
+        Role::Source,
+        Role::Docs,
+        Role::Data,
+        Role::Media,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Role::Command => "command",
+            Role::Builtin => "builtin",
+            Role::External => "external",
+            Role::Alias => "alias",
+            Role::Path => "path",
+            Role::String => "string",
+            Role::Variable => "variable",
+            Role::Operator => "operator",
+            Role::Error => "error",
+            Role::ErrorText => "error_text",
+            Role::Warning => "warning",
+            Role::Success => "success",
+            Role::Hint => "hint",
+            Role::Accent => "accent",
+            Role::Source => "source",
+            Role::Docs => "docs",
+            Role::Data => "data",
+            Role::Media => "media",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Role> {
+        Role::ALL.iter().copied().find(|role| role.key() == key)
+    }
+}
+
+pub const BUILTIN_THEMES: &[&str] = &["default", "deuteranopia", "monochrome"];
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    /// One color spec per role, in `Role::ALL` order (rows of the palettes
+    /// below: commands, syntax, status, hint/accent, `ll` file kinds)
+    colors: Vec<String>,
+    pub prompt: PromptColors,
+}
+
+impl Theme {
+    fn from_palette(name: &str, palette: [&str; 18], prompt: PromptColors) -> Self {
+        Self {
+            name: name.to_string(),
+            colors: palette.iter().map(|c| c.to_string()).collect(),
+            prompt,
+        }
+    }
+
+    /// A built-in palette, or `~/.config/squish/themes/<name>[.theme]`.
+    pub fn named(name: &str) -> Result<Self, String> {
+        if let Some(theme) = Self::builtin(name) {
+            return Ok(theme);
+        }
+        let dir = config::themes_dir().ok_or_else(|| String::from("no config directory"))?;
+        let path = [dir.join(name), dir.join(format!("{}.theme", name))]
+            .into_iter()
+            .find(|p| p.is_file())
+            .ok_or_else(|| format!("unknown theme '{}'", name))?;
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(name, &content)
+    }
+
+    fn builtin(name: &str) -> Option<Self> {
+        let prompt = || PromptColors::preset(name).unwrap_or_default();
+        match name {
+            "default" => Some(Self::default()),
+            // Okabe-Ito colors: distinguishable with red-green color blindness
+            "deuteranopia" | "colorblind" => Some(Self::from_palette(
+                "deuteranopia",
+                [
+                    "86,180,233", "86,180,233", "86,180,233", "0,158,115",
+                    "240,228,66", "0,158,115", "230,159,0", "204,121,167",
+                    "213,94,0", "230,159,0", "240,228,66", "0,158,115",
+                    "86,180,233", "204,121,167",
+                    "213,94,0", "204,121,167", "230,159,0", "0,158,115",
+                ],
+                prompt(),
+            )),
+            // No hues at all: roles are told apart by weight and decoration
+            "monochrome" | "mono" => Some(Self::from_palette(
+                "monochrome",
+                [
+                    "white+bold", "white+bold", "white", "white+italic",
+                    "white+bold", "white+italic", "white+underline", "white+bold",
+                    "white+bold+reversed", "white", "white+underline", "white+bold",
+                    "bright_black", "white+underline",
+                    "white", "white", "white", "white",
+                ],
+                prompt(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn parse(name: &str, content: &str) -> Result<Self, String> {
+        let mut theme = Self { name: name.to_string(), ..Self::default() };
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected role=color", number + 1));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if key == "base" {
+                let base = Self::builtin(value).ok_or_else(|| format!("line {}: unknown base theme '{}'", number + 1, value))?;
+                theme.colors = base.colors;
+                theme.prompt = base.prompt;
+            } else if let Some(role) = Role::from_key(key) {
+                theme.colors[role as usize] = value.to_string();
+            } else if !key.strip_prefix("prompt.").is_some_and(|k| theme.prompt.set(k, value)) {
+                return Err(format!("line {}: unknown role '{}'", number + 1, key));
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn color(&self, role: Role) -> &str {
+        &self.colors[role as usize]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_palette(
+            "default",
+            [
+                "200,150,255", "180,150,255", "160,140,240", "120,220,200",
+                "140,180,255", "150,255,180", "255,220,150", "255,200,150",
+                "255,120,180", "255,150,200", "255,200,120", "150,255,180",
+                "180,160,255", "200,150,255",
+                "255,150,180", "240,160,255", "255,220,150", "150,255,180",
+            ],
+            PromptColors::default(),
+        )
+    }
+}
+
+static ACTIVE: RwLock<Option<Theme>> = RwLock::new(None);
+
+/// Switch the whole UI to `theme`; takes effect at the next prompt.
+pub fn activate(theme: Theme) {
+    if let Ok(mut active) = ACTIVE.write() {
+        *active = Some(theme);
+    }
+}
+
+pub fn active_name() -> String {
+    ACTIVE.read().ok().and_then(|t| t.as_ref().map(|t| t.name.clone())).unwrap_or_else(|| String::from("default"))
+}
+
+/// Prompt colors of the active theme; explicit `prompt.*` options win over these.
+pub fn prompt_colors() -> PromptColors {
+    ACTIVE.read().ok().and_then(|t| t.as_ref().map(|t| t.prompt.clone())).unwrap_or_default()
+}
+
+/// The built-in default, for painting before any theme is applied.
+fn default_theme() -> &'static Theme {
+    static DEFAULT: OnceLock<Theme> = OnceLock::new();
+    DEFAULT.get_or_init(Theme::default)
+}
+
+/// Colors text with the active theme, like `colored`'s methods do with a
+/// fixed color: `name.paint(Role::Command).bold()`.
+pub trait Paint {
+    fn paint(&self, role: Role) -> ColoredString;
+}
+
+impl Paint for str {
+    fn paint(&self, role: Role) -> ColoredString {
+        let active = ACTIVE.read().ok();
+        match active.as_ref().and_then(|t| t.as_ref()) {
+            Some(theme) => apply_color(self, theme.color(role), false),
+            None => apply_color(self, default_theme().color(role), false),
+        }
+    }
+}
+
+/// Built-in theme names followed by the theme files on disk.
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN_THEMES.iter().map(|n| n.to_string()).collect();
+    if let Some(entries) = config::themes_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        let mut files: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| e.file_name().to_str().map(|n| n.trim_end_matches(".theme").to_string()))
+            .filter(|n| !names.contains(n))
+            .collect();
+        files.sort();
+        names.extend(files);
+    }
+    names
+}
+
+/// How many colors the terminal can show (`color_depth=`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorDepth {
+    #[default]
+    Auto,
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "truecolor" | "24bit" => ColorDepth::TrueColor,
+            "256" => ColorDepth::Ansi256,
+            "16" => ColorDepth::Ansi16,
+            _ => ColorDepth::Auto,
+        }
+    }
+
+    /// COLORTERM first, then TERM, then the terminfo entry via `tput colors`.
+    fn detect() -> Self {
+        if env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit") {
+            return ColorDepth::TrueColor;
+        }
+        let term = env::var("TERM").unwrap_or_default();
+        if term.ends_with("-direct") {
+            return ColorDepth::TrueColor;
+        }
+        if term.contains("256color") {
+            return ColorDepth::Ansi256;
+        }
+        let colors = Command::new("tput")
+            .arg("colors")
+            .output()
+            .ok()
+            .and_then(|out| String::from_utf8_lossy(&out.stdout).trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        if colors >= 256 { ColorDepth::Ansi256 } else { ColorDepth::Ansi16 }
+    }
+}
+
+static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+
+/// Apply the configured depth. Only the first call wins; `auto` is
+/// detected when a color is first reduced, since that may run tput.
+pub fn configure_depth(depth: ColorDepth) {
+    if depth != ColorDepth::Auto {
+        let _ = DEPTH.set(depth);
+    }
+}
+
+fn depth() -> ColorDepth {
+    *DEPTH.get_or_init(ColorDepth::detect)
+}
+
+/// `text` in an RGB color, reduced to the nearest one the terminal can show.
+/// `colored` only knows truecolor (and only trusts COLORTERM), so 256-color
+/// and forced truecolor escapes are written into the text itself.
+pub fn rgb(text: ColoredString, (r, g, b): (u8, u8, u8), is_bg: bool) -> ColoredString {
+    let layer = if is_bg { 48 } else { 38 };
+    let raw = match depth() {
+        ColorDepth::TrueColor if env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit") => {
+            return if is_bg { text.on_truecolor(r, g, b) } else { text.truecolor(r, g, b) };
+        }
+        ColorDepth::TrueColor => format!("\x1b[{};2;{};{};{}m", layer, r, g, b),
+        ColorDepth::Ansi256 => format!("\x1b[{};5;{}m", layer, ansi256(r, g, b)),
+        ColorDepth::Ansi16 | ColorDepth::Auto => {
+            let color = ansi16(r, g, b);
+            return if is_bg { text.on_color(color) } else { text.color(color) };
+        }
+    };
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return text;
+    }
+    let mut text = text;
+    text.input = format!("{}{}\x1b[0m", raw, text.input);
+    text
+}
+
+/// Nearest entry of the xterm 256-color palette: the 6x6x6 cube or the
+/// 24-step gray ramp, whichever is closer.
+fn ansi256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |v: u8| (0..6).min_by_key(|&i| (LEVELS[i] as i32 - v as i32).abs()).unwrap_or(0);
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = ((average.saturating_sub(8) + 5) / 10).min(23) as u8;
+    let gray = 8 + 10 * gray_index;
+    if distance((r, g, b), (gray, gray, gray)) < distance((r, g, b), cube) {
+        232 + gray_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+/// Nearest of the 16 basic colors, using xterm's default values for them.
+fn ansi16(r: u8, g: u8, b: u8) -> Color {
+    const PALETTE: [(Color, (u8, u8, u8)); 16] = [
+        (Color::Black, (0, 0, 0)),
+        (Color::Red, (205, 0, 0)),
+        (Color::Green, (0, 205, 0)),
+        (Color::Yellow, (205, 205, 0)),
+        (Color::Blue, (0, 0, 238)),
+        (Color::Magenta, (205, 0, 205)),
+        (Color::Cyan, (0, 205, 205)),
+        (Color::White, (229, 229, 229)),
+        (Color::BrightBlack, (127, 127, 127)),
+        (Color::BrightRed, (255, 0, 0)),
+        (Color::BrightGreen, (0, 255, 0)),
+        (Color::BrightYellow, (255, 255, 0)),
+        (Color::BrightBlue, (92, 92, 255)),
+        (Color::BrightMagenta, (255, 0, 255)),
+        (Color::BrightCyan, (0, 255, 255)),
+        (Color::BrightWhite, (255, 255, 255)),
+    ];
+    PALETTE.iter().min_by_key(|(_, value)| distance((r, g, b), *value)).map(|(color, _)| *color).unwrap_or(Color::White)
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Colors may carry style modifiers, e.g. `white+bold` or `black+underline`.
+pub fn apply_color(text: &str, color_str: &str, is_bg: bool) -> ColoredString {
+    let mut parts = color_str.split('+');
+    let mut colored = apply_base_color(text, parts.next().unwrap_or_default().trim(), is_bg);
+    for style in parts {
+        colored = match style.trim().to_lowercase().as_str() {
+            "bold" => colored.bold(),
+            "underline" => colored.underline(),
+            "italic" => colored.italic(),
+            "dimmed" | "dim" => colored.dimmed(),
+            "reversed" | "reverse" => colored.reversed(),
+            _ => colored,
+        };
+    }
+    colored
+}
+
+fn apply_base_color(text: &str, color_str: &str, is_bg: bool) -> ColoredString {
+    if let Some(rgb_color) = parse_rgb(color_str) {
+        rgb(text.normal(), rgb_color, is_bg)
+    } else {
+        let colored = match color_str.to_lowercase().as_str() {
+            "black" => text.black(),
+            "red" => text.red(),
+            "green" => text.green(),
+            "yellow" => text.yellow(),
+            "blue" => text.blue(),
+            "magenta" => text.magenta(),
+            "cyan" => text.cyan(),
+            "white" => text.white(),
+            "bright_black" | "brightblack" => text.bright_black(),
+            "bright_red" | "brightred" => text.bright_red(),
+            "bright_green" | "brightgreen" => text.bright_green(),
+            "bright_yellow" | "brightyellow" => text.bright_yellow(),
+            "bright_blue" | "brightblue" => text.bright_blue(),
+            "bright_magenta" | "brightmagenta" => text.bright_magenta(),
+            "bright_cyan" | "brightcyan" => text.bright_cyan(),
+            "bright_white" | "brightwhite" => text.bright_white(),
+            _ => text.normal(),
+        };
+        if is_bg {
+            match color_str.to_lowercase().as_str() {
+                "black" => colored.on_black(),
+                "red" => colored.on_red(),
+                "green" => colored.on_green(),
+                "yellow" => colored.on_yellow(),
+                "blue" => colored.on_blue(),
+                "magenta" => colored.on_magenta(),
+                "cyan" => colored.on_cyan(),
+                "white" => colored.on_white(),
+                "bright_black" | "brightblack" => colored.on_bright_black(),
+                "bright_red" | "brightred" => colored.on_bright_red(),
+                "bright_green" | "brightgreen" => colored.on_bright_green(),
+                "bright_yellow" | "brightyellow" => colored.on_bright_yellow(),
+                "bright_blue" | "brightblue" => colored.on_bright_blue(),
+                "bright_magenta" | "brightmagenta" => colored.on_bright_magenta(),
+                "bright_cyan" | "brightcyan" => colored.on_bright_cyan(),
+                "bright_white" | "brightwhite" => colored.on_bright_white(),
+                _ => colored,
+            }
+        } else {
+            colored
+        }
+    }
+}
+
+pub fn parse_rgb(color_str: &str) -> Option<(u8, u8, u8)> {
+    let parts: Vec<&str> = color_str.split([',', ' ']).collect();
+    if parts.len() == 3
+        && let (Ok(r), Ok(g), Ok(b)) = (
+            parts[0].trim().parse::<u8>(),
+            parts[1].trim().parse::<u8>(),
+            parts[2].trim().parse::<u8>(),
+        )
+    {
+        return Some((r, g, b));
+    }
+    None
+}
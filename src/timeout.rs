@@ -0,0 +1,117 @@
+//! Runtime limits for foreground commands (`max_command_runtime`, `limit`).
+//! A watchdog thread waits alongside the shell; when the limit passes it
+//! sends SIGTERM, and SIGKILL if the program is still there a few seconds
+//! later. The signals go to the command's whole process group, so a script
+//! doesn't leave what it started running. A stopped command exits with
+//! 124, as coreutils `timeout` does.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Exit status of a command stopped for running too long.
+pub const TIMED_OUT: i32 = 124;
+
+/// Time between SIGTERM and SIGKILL.
+#[cfg(unix)]
+const GRACE: Duration = Duration::from_secs(3);
+
+/// `90`, `1.5s`, `500ms`, `10m`, `2h` or combinations such as `1h30m`.
+/// A bare number is seconds.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if let Ok(secs) = text.parse::<f64>() {
+        return (secs >= 0.0 && secs.is_finite()).then(|| Duration::from_secs_f64(secs));
+    }
+    let mut total = 0.0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let value: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+        total += value
+            * match &rest[..unit_end] {
+                "ms" => 0.001,
+                "s" => 1.0,
+                "m" => 60.0,
+                "h" => 3600.0,
+                _ => return None,
+            };
+        rest = &rest[unit_end..];
+    }
+    (!text.is_empty()).then(|| Duration::from_secs_f64(total))
+}
+
+#[derive(Default)]
+struct State {
+    finished: bool,
+    /// The last signal sent
+    sent: Option<&'static str>,
+}
+
+/// Watches one foreground child until `finish`. The child must lead its
+/// own process group (`Spawn::new_process_group`): that group is what gets
+/// signalled.
+pub struct Watchdog {
+    state: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    #[cfg(unix)]
+    pub fn start(pid: u32, limit: Duration) -> Self {
+        let state = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let thread = {
+            let state = Arc::clone(&state);
+            std::thread::Builder::new()
+                .name("squish-timeout".to_string())
+                .spawn(move || {
+                    let (lock, changed) = &*state;
+                    let mut current = lock.lock().unwrap_or_else(|e| e.into_inner());
+                    for (wait, signal, name) in [(limit, libc::SIGTERM, "SIGTERM"), (GRACE, libc::SIGKILL, "SIGKILL")] {
+                        current = changed.wait_timeout_while(current, wait, |s| !s.finished).unwrap_or_else(|e| e.into_inner()).0;
+                        if current.finished {
+                            return;
+                        }
+                        unsafe { libc::killpg(pid as libc::pid_t, signal) };
+                        current.sent = Some(name);
+                    }
+                })
+                .ok()
+        };
+        Watchdog { state, thread }
+    }
+
+    #[cfg(not(unix))]
+    pub fn start(_pid: u32, _limit: Duration) -> Self {
+        Watchdog { state: Arc::default(), thread: None }
+    }
+
+    /// Stop watching, once the child has been waited for. Returns the
+    /// signal it was sent, if it ran out of time.
+    pub fn finish(mut self) -> Option<&'static str> {
+        let (lock, changed) = &*self.state;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).finished = true;
+        changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        lock.lock().unwrap_or_else(|e| e.into_inner()).sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(".5s"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("1m500ms"), Some(Duration::from_millis(60_500)));
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+}
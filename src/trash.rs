@@ -0,0 +1,178 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A trashed item as described by its `.trashinfo` file.
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// Name under `Trash/files` (and `Trash/info/<name>.trashinfo`)
+    pub name: String,
+    pub original: PathBuf,
+    pub deleted: String,
+}
+
+/// `$XDG_DATA_HOME/Trash`, defaulting to `~/.local/share/Trash`.
+pub fn trash_dir() -> Option<PathBuf> {
+    let data = match std::env::var_os("XDG_DATA_HOME").filter(|v| !v.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(data.join("Trash"))
+}
+
+/// Move `path` to the home trash following the FreeDesktop trash spec: the
+/// info file is created first (exclusively, to claim the name), then the
+/// file is renamed into `files/`. Returns the name it was stored under.
+pub fn trash_path(path: &Path) -> io::Result<String> {
+    let original = std::path::absolute(path)?;
+    fs::symlink_metadata(&original)?;
+    let root = trash_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let files = root.join("files");
+    let info = root.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let base = original
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a root directory"))?;
+    let deleted = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    for n in 1.. {
+        let name = if n == 1 { base.clone() } else { format!("{}.{}", base, n) };
+        let info_path = info.join(format!("{}.trashinfo", name));
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        if files.join(&name).exists() {
+            let _ = fs::remove_file(&info_path);
+            continue;
+        }
+        writeln!(file, "[Trash Info]\nPath={}\nDeletionDate={}", percent_encode(&original), deleted)?;
+        if let Err(e) = fs::rename(&original, files.join(&name)) {
+            let _ = fs::remove_file(&info_path);
+            // Other filesystems need their own $topdir/.Trash; refuse rather than copy
+            if e.raw_os_error() == Some(CROSS_DEVICE) {
+                return Err(io::Error::other("on a different filesystem than the trash"));
+            }
+            return Err(e);
+        }
+        return Ok(name);
+    }
+    unreachable!()
+}
+
+pub fn list() -> Vec<TrashEntry> {
+    let Some(root) = trash_dir() else { return Vec::new(); };
+    let Ok(entries) = fs::read_dir(root.join("info")) else { return Vec::new(); };
+    let mut items: Vec<TrashEntry> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = file_name.strip_suffix(".trashinfo")?.to_string();
+            let content = fs::read_to_string(entry.path()).ok()?;
+            let mut original = None;
+            let mut deleted = String::new();
+            for line in content.lines() {
+                if let Some(p) = line.strip_prefix("Path=") {
+                    original = Some(PathBuf::from(percent_decode(p)));
+                } else if let Some(d) = line.strip_prefix("DeletionDate=") {
+                    deleted = d.to_string();
+                }
+            }
+            Some(TrashEntry { name, original: original?, deleted })
+        })
+        .collect();
+    items.sort_by(|a, b| b.deleted.cmp(&a.deleted).then_with(|| b.name.cmp(&a.name)));
+    items
+}
+
+/// Restore the most recently deleted item whose trash name or original path
+/// matches `spec`. Refuses to overwrite an existing file.
+pub fn restore(spec: &str) -> io::Result<PathBuf> {
+    let root = trash_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let wanted = std::path::absolute(spec).ok();
+    let entry = list()
+        .into_iter()
+        .find(|e| e.name == spec || Some(&e.original) == wanted.as_ref())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not in the trash"))?;
+    if fs::symlink_metadata(&entry.original).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", entry.original.display())));
+    }
+    if let Some(parent) = entry.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(root.join("files").join(&entry.name), &entry.original)?;
+    fs::remove_file(root.join("info").join(format!("{}.trashinfo", entry.name)))?;
+    Ok(entry.original)
+}
+
+/// Permanently delete everything in the trash. Returns how many items went.
+pub fn empty() -> io::Result<usize> {
+    let Some(root) = trash_dir() else { return Ok(0); };
+    let mut count = 0;
+    for sub in ["files", "info", "expunged"] {
+        let Ok(entries) = fs::read_dir(root.join(sub)) else { continue; };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+            if sub == "files" {
+                count += 1;
+            }
+        }
+    }
+    let _ = fs::remove_file(root.join("directorysizes"));
+    Ok(count)
+}
+
+/// The error rename gives for a move to another filesystem.
+#[cfg(unix)]
+const CROSS_DEVICE: i32 = libc::EXDEV;
+/// ERROR_NOT_SAME_DEVICE
+#[cfg(not(unix))]
+const CROSS_DEVICE: i32 = 17;
+
+fn percent_encode(path: &Path) -> String {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy();
+    #[cfg(not(unix))]
+    let bytes = bytes.as_bytes();
+    let mut out = String::new();
+    for &b in bytes {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = s.get(i + 1..i + 3)
+            && let Ok(b) = u8::from_str_radix(hex, 16)
+        {
+            out.push(b);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,144 @@
+//! Variables the shell sets itself rather than keeping in the environment,
+//! so child processes never see them. Word expansion asks here before it
+//! looks at the environment. `PWD` and `OLDPWD` are the exception: they
+//! are exported, and kept in step with the working directory here.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Field separators when `IFS` isn't set.
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Exit status of every stage of the last foreground pipeline; a single
+/// command counts as a pipeline of one.
+static PIPESTATUS: Mutex<Vec<i32>> = Mutex::new(Vec::new());
+
+/// `IFS`, once assigned; `None` is unset, which splits like the default.
+static IFS: Mutex<Option<String>> = Mutex::new(None);
+
+static WORD_SPLIT: OnceLock<bool> = OnceLock::new();
+
+/// `SECONDS`: counts up from the value it was given at this instant;
+/// `None` until the shell starts.
+static SECONDS: Mutex<Option<(Instant, u64)>> = Mutex::new(None);
+
+/// State of the `RANDOM` generator; zero until first used or seeded.
+static RANDOM: Mutex<u64> = Mutex::new(0);
+
+/// `LINENO`: the input line the running command started on.
+static LINENO: AtomicUsize = AtomicUsize::new(0);
+
+/// Apply `word_split=` and start the `SECONDS` clock. Only the first call
+/// wins.
+pub fn configure(word_split: bool) {
+    let _ = WORD_SPLIT.set(word_split);
+    SECONDS.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert((Instant::now(), 0));
+    // An inherited PWD can be stale or missing
+    if let Ok(cwd) = std::env::current_dir()
+        && std::env::var_os("PWD").is_none_or(|pwd| Path::new(&pwd).canonicalize().ok() != cwd.canonicalize().ok())
+    {
+        unsafe { std::env::set_var("PWD", cwd) };
+    }
+}
+
+/// Change the working directory, updating `PWD` and `OLDPWD` to match.
+pub fn change_dir(target: &Path) -> std::io::Result<()> {
+    let old = std::env::var_os("PWD").or_else(|| std::env::current_dir().ok().map(Into::into));
+    std::env::set_current_dir(target)?;
+    let new = std::env::current_dir().map(Into::into).unwrap_or_else(|_| target.as_os_str().to_owned());
+    unsafe {
+        if let Some(old) = old {
+            std::env::set_var("OLDPWD", old);
+        }
+        std::env::set_var("PWD", new);
+    }
+    Ok(())
+}
+
+pub fn set_lineno(line: usize) {
+    LINENO.store(line, Ordering::Relaxed);
+}
+
+/// Next value of `RANDOM`, 0 to 32767 (xorshift64*).
+fn random() -> u64 {
+    let mut state = RANDOM.lock().unwrap_or_else(|e| e.into_inner());
+    if *state == 0 {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        *state = (nanos ^ (u64::from(std::process::id()) << 32)) | 1;
+    }
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 33) % 32768
+}
+
+/// Whether unquoted expansions are split into fields (`word_split=`).
+pub fn word_split() -> bool {
+    WORD_SPLIT.get().copied().unwrap_or(true)
+}
+
+/// The characters unquoted expansions are split on. Empty means no
+/// splitting at all.
+pub fn ifs() -> String {
+    IFS.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_else(|| DEFAULT_IFS.to_string())
+}
+
+/// `NAME=value` on its own: returns false if `name` isn't a shell
+/// variable and belongs in the environment instead.
+pub fn assign(name: &str, value: &str) -> bool {
+    match name {
+        "IFS" => *IFS.lock().unwrap_or_else(|e| e.into_inner()) = Some(value.to_string()),
+        // As in bash: assigning seeds the sequence, so it can be repeated
+        "RANDOM" => *RANDOM.lock().unwrap_or_else(|e| e.into_inner()) = value.parse::<u64>().unwrap_or(0).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        "SECONDS" => *SECONDS.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), value.parse().unwrap_or(0))),
+        _ => return false,
+    }
+    true
+}
+
+/// `unset NAME`: returns false if `name` isn't a shell variable.
+pub fn unset(name: &str) -> bool {
+    match name {
+        "IFS" => *IFS.lock().unwrap_or_else(|e| e.into_inner()) = None,
+        _ => return false,
+    }
+    true
+}
+
+pub fn set_pipestatus(statuses: Vec<i32>) {
+    *PIPESTATUS.lock().unwrap_or_else(|e| e.into_inner()) = statuses;
+}
+
+pub fn pipestatus() -> Vec<i32> {
+    PIPESTATUS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Value of a shell variable, by the name inside `${...}`:
+/// `PIPESTATUS` (the first stage, as in bash), `PIPESTATUS[N]`,
+/// `PIPESTATUS[@]`, and `pipestatus` for every stage as zsh spells it;
+/// `IFS` once assigned; `RANDOM`, `SECONDS` and `LINENO`.
+pub fn get(name: &str) -> Option<String> {
+    match name {
+        "IFS" => return IFS.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        "RANDOM" => return Some(random().to_string()),
+        "SECONDS" => {
+            let seconds = SECONDS.lock().unwrap_or_else(|e| e.into_inner());
+            return Some(seconds.map_or(0, |(since, base)| base + since.elapsed().as_secs()).to_string());
+        }
+        "LINENO" => return Some(LINENO.load(Ordering::Relaxed).to_string()),
+        _ => {}
+    }
+    let statuses = PIPESTATUS.lock().unwrap_or_else(|e| e.into_inner());
+    let all = || statuses.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ");
+    match name {
+        "pipestatus" | "PIPESTATUS[@]" | "PIPESTATUS[*]" => Some(all()),
+        "PIPESTATUS" => Some(statuses.first().map(i32::to_string).unwrap_or_default()),
+        _ => {
+            let index = name.strip_prefix("PIPESTATUS[")?.strip_suffix(']')?;
+            // An index past the end is empty, not the literal text
+            Some(index.parse::<usize>().ok().and_then(|i| statuses.get(i)).map(i32::to_string).unwrap_or_default())
+        }
+    }
+}
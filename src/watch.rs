@@ -0,0 +1,123 @@
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use colored::Colorize;
+
+use crate::locale;
+use crate::signals;
+use crate::stdio;
+use crate::{errln, out, outln};
+
+const TICK: Duration = Duration::from_millis(100);
+
+struct WatchOptions {
+    interval: Duration,
+    differences: bool,
+    title: bool,
+    command: String,
+}
+
+fn parse_args(args: &[String]) -> Result<WatchOptions, String> {
+    let mut opts = WatchOptions {
+        interval: Duration::from_secs(2),
+        differences: false,
+        title: true,
+        command: String::new(),
+    };
+    let mut iter = args.iter();
+    let mut words = Vec::new();
+    while let Some(arg) = iter.next() {
+        if !words.is_empty() {
+            words.push(arg.as_str());
+            continue;
+        }
+        match arg.as_str() {
+            "-n" | "--interval" => {
+                let secs = iter
+                    .next()
+                    .and_then(|v| v.replace(',', ".").parse::<f64>().ok())
+                    .filter(|s| s.is_finite() && *s > 0.0)
+                    .ok_or_else(|| format!("{} expects a positive number of seconds", arg))?;
+                opts.interval = Duration::from_secs_f64(secs.max(0.1));
+            }
+            "-d" | "--differences" => opts.differences = true,
+            "-t" | "--no-title" => opts.title = false,
+            _ => words.push(arg.as_str()),
+        }
+    }
+    opts.command = words.join(" ");
+    if opts.command.trim().is_empty() {
+        return Err(String::from("usage: watch [-n secs] [-d] [-t] <command>"));
+    }
+    Ok(opts)
+}
+
+/// `watch [-n secs] [-d] [-t] cmd`: re-run `cmd` through `run` until Ctrl-C,
+/// redrawing the screen each time.
+pub fn run<F>(args: &[String], mut run: F) -> i32
+where
+    F: FnMut(&str) -> String,
+{
+    let opts = match parse_args(args) {
+        Ok(opts) => opts,
+        Err(msg) => {
+            errln!("watch: {}", msg);
+            return 2;
+        }
+    };
+
+    // The shell's SIGINT handler records Ctrl-C; it stops the loop here
+    signals::take_interrupted();
+    let mut previous: Option<String> = None;
+    while !signals::interrupted() {
+        let started = Instant::now();
+        let output = run(&opts.command);
+        if signals::interrupted() {
+            break;
+        }
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        if opts.title {
+            let every = locale::localize_decimal(&format!("{:.1}s", opts.interval.as_secs_f64()));
+            screen.push_str(&format!(
+                "{} {}    {}\n\n",
+                format!("Every {}:", every).bold(),
+                opts.command,
+                locale::format_datetime(SystemTime::now()).dimmed()
+            ));
+        }
+        match (&previous, opts.differences) {
+            (Some(prev), true) => screen.push_str(&highlight_changes(prev, &output)),
+            _ => screen.push_str(&output),
+        }
+        out!("{}", screen);
+        let _ = stdio::stdout().flush();
+        previous = Some(output);
+
+        while !signals::interrupted() && started.elapsed() < opts.interval {
+            thread::sleep(TICK.min(opts.interval.saturating_sub(started.elapsed())));
+        }
+    }
+    outln!();
+    0
+}
+
+/// Reverse-video every character that differs from the same position in
+/// the previous run, like procps `watch -d`.
+fn highlight_changes(previous: &str, current: &str) -> String {
+    let old_lines: Vec<&str> = previous.lines().collect();
+    let mut out = String::with_capacity(current.len());
+    for (i, line) in current.lines().enumerate() {
+        let old: Vec<char> = old_lines.get(i).map(|l| l.chars().collect()).unwrap_or_default();
+        for (j, ch) in line.chars().enumerate() {
+            if old.get(j) == Some(&ch) {
+                out.push(ch);
+            } else {
+                out.push_str(&ch.to_string().reversed().to_string());
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
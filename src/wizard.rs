@@ -0,0 +1,72 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use colored::Colorize;
+
+use crate::config;
+use crate::shell_config::{option_spec, SettingSpec, ShellConfig, WIZARD_SETTINGS};
+
+/// Only on an interactive first launch: no config file yet, and a terminal
+/// on both ends to ask questions.
+pub fn should_run() -> bool {
+    io::stdin().is_terminal()
+        && io::stdout().is_terminal()
+        && config::config_file().is_some_and(|p| !p.exists())
+}
+
+/// Ask the `WIZARD_SETTINGS` questions and write a commented config file.
+/// End of input keeps the defaults for the remaining questions.
+pub fn run() -> io::Result<()> {
+    let Some(path) = config::config_file() else { return Ok(()); };
+
+    println!("{}", "Welcome to squish! A few quick questions to set things up.".bold());
+    println!("{}", "Press Enter to keep the default; you can edit the config file later.".dimmed());
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut answers = Vec::with_capacity(WIZARD_SETTINGS.len());
+    for spec in WIZARD_SETTINGS {
+        answers.push(ask(spec, &mut input)?);
+    }
+
+    let mut check = ShellConfig::default();
+    let mut text = String::from("# squish configuration, written by the first-run setup.\n");
+    text.push_str("# Run `option list --doc` for every available option.\n");
+    for (spec, value) in WIZARD_SETTINGS.iter().zip(answers) {
+        if let Some(option) = option_spec(spec.key) {
+            text.push_str(&format!("\n# {}\n", option.doc));
+        }
+        if value.is_empty() || !check.set(spec.key, value) {
+            text.push_str(&format!("#{}=\n", spec.key));
+        } else {
+            text.push_str(&format!("{}={}\n", spec.key, value));
+        }
+    }
+    std::fs::write(&path, text)?;
+    println!("{} {}\n", "Saved".green(), path.display());
+    Ok(())
+}
+
+fn ask(spec: &SettingSpec, input: &mut impl BufRead) -> io::Result<&'static str> {
+    println!("\n{}", spec.question.bold());
+    for (i, (label, _)) in spec.choices.iter().enumerate() {
+        let marker = if i == 0 { " (default)".dimmed().to_string() } else { String::new() };
+        println!("  {}) {}{}", i + 1, label, marker);
+    }
+    loop {
+        print!("{} ", "❯".bright_green());
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            println!();
+            return Ok(spec.choices[0].1);
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(spec.choices[0].1);
+        }
+        match line.parse::<usize>() {
+            Ok(n) if (1..=spec.choices.len()).contains(&n) => return Ok(spec.choices[n - 1].1),
+            _ => println!("Please enter a number from 1 to {}", spec.choices.len()),
+        }
+    }
+}